@@ -4,7 +4,9 @@
 #include <string.h>
 
 typedef void (*extract_jp2_fn)(const char *inputpath, const char *outputpath);
+typedef void (*extract_graphics_fn)(const char *inputpath, const char *outputpath);
 typedef void (*extract_des_fn)(const char *inputpath, const char *outputpath);
+typedef void (*extract_res_fn)(const char *inputpath, const char *outputpath);
 typedef void (*get_version_fn)(const char *inputpath);
 typedef int (*get_num_images_from_file_fn)(const char *inputpath);
 typedef int (*get_num_graphics_from_file_fn)(const char *inputpath);
@@ -28,6 +30,13 @@ int main() {
         return 1;
     }
 
+    extract_graphics_fn extract_graphics = (extract_graphics_fn)dlsym(lib_handle, "extract_graphics");
+    if (!extract_graphics) {
+        fprintf(stderr, "Failed to locate function: %s\n", dlerror());
+        dlclose(lib_handle);
+        return 1;
+    }
+
     extract_des_fn extract_des = (extract_des_fn)dlsym(lib_handle, "extract_des");
     if (!extract_des) {
         fprintf(stderr, "Failed to locate function: %s\n", dlerror());
@@ -35,6 +44,13 @@ int main() {
         return 1;
     }
 
+    extract_res_fn extract_res = (extract_res_fn)dlsym(lib_handle, "extract_res");
+    if (!extract_res) {
+        fprintf(stderr, "Failed to locate function: %s\n", dlerror());
+        dlclose(lib_handle);
+        return 1;
+    }
+
     get_version_fn get_version = (get_version_fn)dlsym(lib_handle, "get_version");
     if (!get_version) {
         fprintf(stderr, "Failed to locate function: %s\n", dlerror());
@@ -79,7 +95,9 @@ int main() {
 
     // Call the Rust function
     extract_jp2(path, out);
+    extract_graphics(path, out);
     extract_des(path, out);
+    extract_res(path, out);
     get_version(path);
     int img = get_num_images(path);
     printf("Number of images: %d\n", img);
@@ -8,6 +8,8 @@ import java.lang.reflect.Field;
 /**
  * Provides NITF file handling functionality with methods for reading, copying, 
  * and extracting specific segments from NITF files.
+ * Malformed or truncated files and out of range segment indices are reported
+ * by throwing a {@link RuntimeException}.
  */
 public class nitfgnr {
     static {
@@ -20,7 +22,7 @@ public class nitfgnr {
      * Retrieves the version of the NITF file.
      *
      * @param file the NITF file
-     * @return the version of the file as a String
+     * @return FHDR and FVER as a String, e.g. "NITF02.10", "NITF02.00" or "NSIF01.00"
      */
     public static String getVersion(File file) {
         long rawFd = rawFdFromFile(file);
@@ -103,6 +105,57 @@ public class nitfgnr {
         gnr.copyDesSegments(rawInputFd, rawOutputFd);
     }
     
+    /**
+     * Retrieves the number of Reserved Extension Segments (RES) in the NITF file.
+     *
+     * @param file the NITF file
+     * @return the number of RES segments
+     */
+    public static int getNumRes(File file) {
+        long rawFd = rawFdFromFile(file);
+        nitfgnr gnr = new nitfgnr();
+        return gnr.getNumRes(rawFd);
+    }
+
+    /**
+     * Extracts a specific Reserved Extension Segment (RES) from the NITF file.
+     *
+     * @param file the NITF file
+     * @param index the index of the RES to extract
+     * @return the extracted RES subheader and data as a byte array
+     */
+    public static byte[] extractRes(File file, int index) {
+        long rawFd = rawFdFromFile(file);
+        nitfgnr gnr = new nitfgnr();
+        return gnr.extractRes(rawFd, index);
+    }
+
+    /**
+     * Extracts the header fields (LRESH and LRE) of a specific Reserved Extension Segment (RES) from the NITF file.
+     *
+     * @param file the NITF file
+     * @param index the index of the RES to extract
+     * @return the extracted RES header fields as a byte array
+     */
+    public static byte[] extractResHeader(File file, int index) {
+        long rawFd = rawFdFromFile(file);
+        nitfgnr gnr = new nitfgnr();
+        return gnr.extractResHeader(rawFd, index);
+    }
+
+    /**
+     * Copies all Reserved Extension Segments (RES) from one NITF file to another.
+     *
+     * @param input the input NITF file
+     * @param output the output NITF file
+     */
+    public static void copyResSegments(File input, File output) {
+        long rawInputFd = rawFdFromFile(input);
+        long rawOutputFd = rawFdFromFile(output);
+        nitfgnr gnr = new nitfgnr();
+        gnr.copyResSegments(rawInputFd, rawOutputFd);
+    }
+    
     //Native functions no wrapper
     //public static native byte[] addDesBytes(byte[] nitfBytes, byte[]desHeaderBytes, byte[] desDataBytes);
     
@@ -114,6 +167,14 @@ public class nitfgnr {
      */
     public static native void copyDesSegmentsFromPaths(String input, String output);
 
+    /**
+     * Copies all Reserved Extension Segments (RES) from one NITF file to another.
+     *
+     * @param input path to the input NITF file
+     * @param output path to the output NITF file
+     */
+    public static native void copyResSegmentsFromPaths(String input, String output);
+
     /**
      * Copies all Graphic Segments from one NITF file to another.
      *
@@ -139,7 +200,8 @@ public class nitfgnr {
     public static native void copyGTDSegmentsFromPaths(String input, String output);
 
     /**
-     * Extracts all JPEG2000 (JP2) images from a NITF file.
+     * Extracts the data of every image in a NITF file, named by index with an extension
+     * depending on the compression: .j2k (JPEG 2000), .jpg (JPEG), .raw (uncompressed) or .bin.
      *
      * @param input_path path to the input NITF file
      * @param output_path path to the output directory
@@ -147,7 +209,7 @@ public class nitfgnr {
     public static native void extractAllJp2(String input_path, String output_path);
 
     /**
-     * Extracts a specific JPEG2000 (JP2) image from a NITF file.
+     * Extracts the data of a specific image from a NITF file, whatever its compression.
      *
      * @param input_path path to the input NITF file
      * @param index the index of the JP2 image to extract
@@ -155,6 +217,23 @@ public class nitfgnr {
      */
     public static native byte[] extractJp2Index(String input_path, int index);
 
+    /**
+     * Extracts the CGM of every graphic segment in a NITF file.
+     *
+     * @param input_path path to the input NITF file
+     * @param output_path path to the output directory
+     */
+    public static native void extractAllGraphics(String input_path, String output_path);
+
+    /**
+     * Extracts the CGM of a specific graphic segment from a NITF file.
+     *
+     * @param input_path path to the input NITF file
+     * @param index the index of the graphic segment to extract
+     * @return the extracted CGM as a byte array
+     */
+    public static native byte[] extractGraphicIndex(String input_path, int index);
+
 
     //Native functions
     private native String getVersion(long fd);
@@ -164,6 +243,10 @@ public class nitfgnr {
     private native int getNumDes(long fd);
     private native byte[] extractDes(long fd, int index);
     private native byte[] extractDesHeader(long fd, int index);
+    private native void copyResSegments(long inputFd, long outputFd);
+    private native int getNumRes(long fd);
+    private native byte[] extractRes(long fd, int index);
+    private native byte[] extractResHeader(long fd, int index);
 
 
     //Helper functions
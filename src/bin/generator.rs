@@ -3,6 +3,7 @@ use std::time::Instant;
 use rand::Rng;
 use clap::{Arg, ArgAction, Command};
 use chrono::{Utc, Datelike, Timelike};
+use nitf_gnr::modify::error::Result;
 use nitf_gnr::modify::parser::nitf21 as nitf;
 use rayon::prelude::*;
 
@@ -103,36 +104,40 @@ fn main() {
 
 fn generate_nitfs_seq(path: &str, o_prefix: &str, count: u32) {
     for _i in 0..count {
-        _ = alter_nitf(path, o_prefix);
+        if let Err(e) = alter_nitf(path, o_prefix) {
+            eprintln!("Failed to generate NITF: {}", e);
+        }
     };
 }
 
 fn generate_nitfs(path: &str, o_prefix: &str, count: u32) {
     (0..count).into_par_iter().for_each(|_i| {
-        _ = alter_nitf(path, o_prefix);
+        if let Err(e) = alter_nitf(path, o_prefix) {
+            eprintln!("Failed to generate NITF: {}", e);
+        }
     });
 }
 
-fn alter_nitf(path: &str, o_prefix: &str) -> std::string::String {
-    let mut buf = std::fs::read(path).unwrap();
-    let filename = change_filename(&mut buf);
-    change_originator(&mut buf);
-    change_fdt(&mut buf);
-    change_ostaid(&mut buf);
+fn alter_nitf(path: &str, o_prefix: &str) -> Result<std::string::String> {
+    let mut buf = std::fs::read(path)?;
+    let filename = change_filename(&mut buf)?;
+    change_originator(&mut buf)?;
+    change_fdt(&mut buf)?;
+    change_ostaid(&mut buf)?;
     let path = o_prefix.to_string() + &filename;
-    std::fs::write(path.as_str(), buf).expect("Unable to write file");
-    filename
+    std::fs::write(path.as_str(), buf)?;
+    Ok(filename)
 }
 
-fn change_filename(buf: &mut [u8]) -> std::string::String {
+fn change_filename(buf: &mut [u8]) -> Result<std::string::String> {
     use nitf::NitfHeader21 as N;
     let filename = generate_filename();
-    let ft_offset = N::get_offset(N::FTITLE, None);
+    let ft_offset = N::fixed_offset(N::FTITLE);
     buf[ft_offset..ft_offset + 80].copy_from_slice(filename.as_bytes());
-    filename
+    Ok(filename)
 }
 
-fn change_originator(buf: &mut [u8]) {
+fn change_originator(buf: &mut [u8]) -> Result<()> {
     use nitf::NitfHeader21 as N;
     //let user = get_current_username().unwrap();
     let user = whoami::username();
@@ -143,11 +148,12 @@ fn change_originator(buf: &mut [u8]) {
         originator = &originator[..originator.char_indices().nth(24).map_or(originator.len(), |(idx, _)| idx)];
     } 
     o_buf[..originator.len()].copy_from_slice(originator.as_bytes());
-    let originator_offset = N::get_offset(N::ONAME, None);
+    let originator_offset = N::fixed_offset(N::ONAME);
     buf[originator_offset..originator_offset + 24].copy_from_slice(&o_buf);
+    Ok(())
 }
 
-fn change_fdt(buf: &mut [u8]) {
+fn change_fdt(buf: &mut [u8]) -> Result<()> {
     use nitf::NitfHeader21 as N;
     let now = Utc::now(); 
     let fdt = format!(
@@ -159,15 +165,17 @@ fn change_fdt(buf: &mut [u8]) {
         now.minute(),
         now.second()
     );
-    let fdt_offset = N::get_offset(N::FDT, None);
+    let fdt_offset = N::fixed_offset(N::FDT);
     buf[fdt_offset..fdt_offset + 14].copy_from_slice(fdt.as_bytes());
+    Ok(())
 }
 
-fn change_ostaid(buf: &mut [u8]) {
+fn change_ostaid(buf: &mut [u8]) -> Result<()> {
     use nitf::NitfHeader21 as N;
     let ostaid = "COMPUSULT ";
-    let ostaid_offset = N::get_offset(N::OSTAID, None);
+    let ostaid_offset = N::fixed_offset(N::OSTAID);
     buf[ostaid_offset..ostaid_offset + 10].copy_from_slice(ostaid.as_bytes());
+    Ok(())
 }
 
 fn generate_filename() -> std::string::String {
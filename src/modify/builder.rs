@@ -0,0 +1,278 @@
+use crate::modify::error::{NitfError, Result};
+use crate::modify::parser::nitf21::{
+    DesSubheader, FileHeader, GraphicSubheader, ImageBand, ImageSubheader, NitfVersion,
+    SecurityGroup, TextSubheader,
+};
+use crate::modify::writer::{NitfModel, Segment};
+use chrono::{NaiveDateTime, Utc};
+use std::io::Write;
+
+/// Blocks wider or taller than this are written as a single block with NPPBH/NPPBV set to 0.
+const MAX_BLOCK_SIZE: usize = 8192;
+
+/// Creates a NITF 2.1 file from scratch.
+///
+/// The file header starts out unclassified with CLEVEL 03 and FDT set to the current time.
+/// The convenience methods (`raw_image`, `jp2_image`, `text`, `cgm`, `des`) give every segment
+/// the file's security group and FDT, and hand out display levels in the order segments are added.
+#[derive(Debug, Clone)]
+pub struct NitfBuilder {
+    header: FileHeader,
+    images: Vec<(ImageSubheader, Vec<u8>)>,
+    graphics: Vec<(GraphicSubheader, Vec<u8>)>,
+    texts: Vec<(TextSubheader, Vec<u8>)>,
+    des: Vec<(DesSubheader, Vec<u8>)>,
+    display_level: usize,
+}
+
+impl Default for NitfBuilder {
+    fn default() -> Self {
+        NitfBuilder::new()
+    }
+}
+
+impl NitfBuilder {
+    pub fn new() -> NitfBuilder {
+        let version = NitfVersion::V02_10;
+        NitfBuilder {
+            header: FileHeader {
+                fhdr: version.fhdr().to_string(),
+                fver: version.as_str().to_string(),
+                clevel: 3,
+                stype: "BF01".to_string(),
+                fdt: Some(Utc::now().naive_utc()),
+                security: unclassified(),
+                encryp: "0".to_string(),
+                ..Default::default()
+            },
+            images: Vec::new(),
+            graphics: Vec::new(),
+            texts: Vec::new(),
+            des: Vec::new(),
+            display_level: 0,
+        }
+    }
+
+    pub fn title(mut self, ftitle: &str) -> NitfBuilder {
+        self.header.ftitle = ftitle.to_string();
+        self
+    }
+
+    pub fn originating_station(mut self, ostaid: &str) -> NitfBuilder {
+        self.header.ostaid = ostaid.to_string();
+        self
+    }
+
+    pub fn originator(mut self, oname: &str, ophone: &str) -> NitfBuilder {
+        self.header.oname = oname.to_string();
+        self.header.ophone = ophone.to_string();
+        self
+    }
+
+    pub fn date_time(mut self, fdt: NaiveDateTime) -> NitfBuilder {
+        self.header.fdt = Some(fdt);
+        self
+    }
+
+    /// The CLEVEL to declare. It is not worked out from the segments.
+    pub fn complexity_level(mut self, clevel: usize) -> NitfBuilder {
+        self.header.clevel = clevel;
+        self
+    }
+
+    /// The file security group, also given to segments added after this call.
+    pub fn security(mut self, security: SecurityGroup) -> NitfBuilder {
+        self.header.security = security;
+        self
+    }
+
+    /// Direct access to the file header for anything without its own method.
+    /// FL, HL and the length tables are overwritten when the file is written.
+    pub fn file_header(&mut self) -> &mut FileHeader {
+        &mut self.header
+    }
+
+    /// Adds an uncompressed image, written as a single band sequential block (IMODE "B").
+    /// `pixels` holds every band one after the other, each `nrows * ncols` pixels of `nbpp` bits.
+    /// Fails with `MalformedField` if `pixels` is not exactly that long.
+    pub fn raw_image(mut self, nrows: usize, ncols: usize, nbands: usize, nbpp: usize, pixels: Vec<u8>) -> Result<NitfBuilder> {
+        let expected = nrows
+            .checked_mul(ncols)
+            .and_then(|n| n.checked_mul(nbands))
+            .and_then(|n| n.checked_mul(nbpp))
+            .map(|bits| bits.div_ceil(8));
+        if expected != Some(pixels.len()) {
+            return Err(NitfError::MalformedField {
+                field: "NROWS",
+                offset: 0,
+                raw: format!("{} rows, {} columns, {} bands of {} bits in {} bytes", nrows, ncols, nbands, nbpp, pixels.len()),
+            });
+        }
+        let mut subheader = self.new_image(nrows, ncols, nbands, nbpp);
+        subheader.ic = "NC".to_string();
+        Ok(self.image_segment(subheader, pixels))
+    }
+
+    /// Adds a JPEG 2000 compressed image (IC "C8"), `codestream` being the J2K codestream.
+    /// `comrat` is its COMRAT: "N" and a version ("N001") if numerically lossless, "V" and a
+    /// version if visually lossless, or the bit rate per pixel per band as "wxyz" (w.xyz).
+    pub fn jp2_image(
+        mut self,
+        nrows: usize,
+        ncols: usize,
+        nbands: usize,
+        nbpp: usize,
+        comrat: &str,
+        codestream: Vec<u8>,
+    ) -> NitfBuilder {
+        let mut subheader = self.new_image(nrows, ncols, nbands, nbpp);
+        subheader.ic = "C8".to_string();
+        subheader.comrat = Some(comrat.to_string());
+        self.image_segment(subheader, codestream)
+    }
+
+    /// Adds an image segment with a subheader built by the caller, used as is.
+    pub fn image_segment(mut self, subheader: ImageSubheader, data: Vec<u8>) -> NitfBuilder {
+        self.images.push((subheader, data));
+        self
+    }
+
+    /// Adds a CGM graphic. `extent` is the lower right corner of its bounding box as (row, column).
+    pub fn cgm(mut self, cgm: Vec<u8>, extent: (isize, isize)) -> NitfBuilder {
+        let subheader = GraphicSubheader {
+            sy: "SY".to_string(),
+            security: self.header.security.clone(),
+            encryp: "0".to_string(),
+            sfmt: "C".to_string(),
+            sdlvl: self.next_display_level(),
+            sbnd2: extent,
+            scolor: "C".to_string(),
+            ..Default::default()
+        };
+        self.graphic_segment(subheader, cgm)
+    }
+
+    pub fn graphic_segment(mut self, subheader: GraphicSubheader, data: Vec<u8>) -> NitfBuilder {
+        self.graphics.push((subheader, data));
+        self
+    }
+
+    /// Adds a plain text segment (TXTFMT "STA").
+    pub fn text(self, text: &str) -> NitfBuilder {
+        let subheader = TextSubheader {
+            te: "TE".to_string(),
+            txtdt: self.header.fdt,
+            security: self.header.security.clone(),
+            encryp: "0".to_string(),
+            txtfmt: "STA".to_string(),
+            ..Default::default()
+        };
+        self.text_segment(subheader, text.as_bytes().to_vec())
+    }
+
+    pub fn text_segment(mut self, subheader: TextSubheader, data: Vec<u8>) -> NitfBuilder {
+        self.texts.push((subheader, data));
+        self
+    }
+
+    /// Adds a version 1 DES with no user defined subheader.
+    pub fn des(self, desid: &str, data: Vec<u8>) -> NitfBuilder {
+        let subheader = DesSubheader {
+            de: "DE".to_string(),
+            desid: desid.to_string(),
+            desver: 1,
+            security: self.header.security.clone(),
+            ..Default::default()
+        };
+        self.des_segment(subheader, data)
+    }
+
+    pub fn des_segment(mut self, subheader: DesSubheader, data: Vec<u8>) -> NitfBuilder {
+        self.des.push((subheader, data));
+        self
+    }
+
+    /// Serializes the subheaders. Fails with `Overflow` if a value does not fit its field.
+    pub fn build(self) -> Result<NitfModel> {
+        Ok(NitfModel {
+            file_header: self.header,
+            images: self
+                .images
+                .into_iter()
+                .map(|(sub, data)| Ok(Segment::new(sub.to_bytes()?, data)))
+                .collect::<Result<_>>()?,
+            graphics: self
+                .graphics
+                .into_iter()
+                .map(|(sub, data)| Ok(Segment::new(sub.to_bytes()?, data)))
+                .collect::<Result<_>>()?,
+            texts: self
+                .texts
+                .into_iter()
+                .map(|(sub, data)| Ok(Segment::new(sub.to_bytes()?, data)))
+                .collect::<Result<_>>()?,
+            des: self
+                .des
+                .into_iter()
+                .map(|(sub, data)| Ok(Segment::new(sub.to_bytes()?, data)))
+                .collect::<Result<_>>()?,
+            res: Vec::new(),
+        })
+    }
+
+    pub fn write<W: Write>(self, writer: &mut W) -> Result<()> {
+        self.build()?.write(writer)
+    }
+
+    fn next_display_level(&mut self) -> usize {
+        self.display_level += 1;
+        self.display_level
+    }
+
+    fn new_image(&mut self, nrows: usize, ncols: usize, nbands: usize, nbpp: usize) -> ImageSubheader {
+        let (irep, icat, irepbands): (&str, &str, &[&str]) = match nbands {
+            1 => ("MONO", "VIS", &["M"]),
+            3 => ("RGB", "VIS", &["R", "G", "B"]),
+            _ => ("MULTI", "MS", &[]),
+        };
+        let bands = (0..nbands)
+            .map(|i| ImageBand {
+                irepband: irepbands.get(i).copied().unwrap_or_default().to_string(),
+                ifc: "N".to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let block_size = |size: usize| if size > MAX_BLOCK_SIZE { 0 } else { size };
+        let idlvl = self.next_display_level();
+        ImageSubheader {
+            im: "IM".to_string(),
+            idatim: self.header.fdt,
+            security: self.header.security.clone(),
+            encryp: "0".to_string(),
+            nrows,
+            ncols,
+            pvtype: if nbpp == 1 { "B" } else { "INT" }.to_string(),
+            irep: irep.to_string(),
+            icat: icat.to_string(),
+            abpp: nbpp,
+            pjust: "R".to_string(),
+            bands,
+            imode: "B".to_string(),
+            nbpr: 1,
+            nbpc: 1,
+            nppbh: block_size(ncols),
+            nppbv: block_size(nrows),
+            nbpp,
+            idlvl,
+            imag: "1.0".to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+fn unclassified() -> SecurityGroup {
+    SecurityGroup {
+        clas: "U".to_string(),
+        ..Default::default()
+    }
+}
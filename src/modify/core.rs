@@ -1,133 +1,565 @@
-use crate::modify::parser::file_ops::{
-    read_int_from_bytes, read_int_from_file, read_string_from_file,
+use crate::modify::error::{NitfError, Result};
+use crate::modify::parser::file_ops::{parse_int, read_bytes, Field, FieldWriter};
+use crate::modify::parser::nitf21::{
+    length_table_entry, segment_offset, DesSubheader, FileHeader, GraphicSubheader,
+    ImageCompression, ImageSubheader, Nitf, NitfDesSubheader21, NitfGraphicSubheader21,
+    NitfHeader21, NitfImageSubheader21 as I, NitfVersion, ResSubheader, SegmentLength, SegmentType,
+    TextSubheader,
 };
-use crate::modify::parser::nitf21::Nitf;
+use crate::modify::export::{export, ExportOptions};
+use crate::modify::image::{decode_uncompressed, BlockMask, DecodedImage, ImageLayout};
+use crate::modify::parser::cgm::Cgm;
+use crate::modify::parser::nitf20;
+use crate::modify::jp2::{igeolo_corners, wrap_codestream, Georeference};
+use crate::modify::parser::j2k::{Codestream, JP2_SIGNATURE};
+use crate::modify::parser::tre::{decode_tres, Tre};
+use crate::modify::parser::tre_decoders::ImageTre;
+use crate::modify::parser::xml_des::{crc16, XmlDataContent, XmlDataContentField, XML_DATA_CONTENT};
+use crate::modify::svg::render_svg;
+use crate::modify::writer::{read_segments, Segment};
 use crate::modify::parser::nitf21::{NitfHeader21 as N, NitfHeader21::*};
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
 
-pub fn get_version(file: &File) -> (String, String) {
-    let fhdr = read_string_from_file(file, N::get_offset(FHDR, None), N::get_value(FHDR));
-    let ver = read_string_from_file(file, N::get_offset(FVER, None), N::get_value(FVER));
-    (fhdr, ver)
+/// FHDR and FVER, e.g. ("NITF", "02.10") or ("NSIF", "01.00"). Anything this crate
+/// does not recognise is an `UnsupportedVersion` error.
+pub fn get_version<R: Read + Seek>(reader: &mut R) -> Result<(String, String)> {
+    let ver = Nitf::get_version(reader)?;
+    Ok((ver.fhdr().to_string(), ver.as_str().to_string()))
 }
 
-pub fn extract_jp2(mut file: &File, outpath: &str) {
-    for i in 0..get_num_images(file) {
-        let img_data_offset = N::get_image_data_field_offset(Some(file), i as u64);
-        let img_length = read_int_from_file(file, img_data_offset, N::get_value(LI));
-        let _ = file.seek(SeekFrom::Start(img_data_offset as u64));
-        let mut img_data = vec![0u8; img_length];
-        let _ = file.read_exact(&mut img_data);
-        let path = format!("{}{}.jp2", outpath, i);
-        let mut out_file = File::create(path).expect("Failed to create output file");
-        let _ = out_file.write_all(&img_data);
+/// Writes the data field of every image to `{outpath}{i}.{ext}`, the extension depending on
+/// IC: `.j2k` for JPEG 2000, `.jpg` for JPEG, `.raw` for uncompressed and `.bin` for anything else.
+/// The block mask record of masked images is left out, the file starting at IMDATOFF.
+pub fn extract_jp2<R: Read + Seek>(reader: &mut R, outpath: &str) -> Result<()> {
+    for (i, (layout, offset, length)) in image_data_fields(reader)?.into_iter().enumerate() {
+        let compression = ImageCompression::from_ic(&layout.ic);
+        let path = format!("{}{}.{}", outpath, i, compression.extension());
+        let mut out_file = File::create(path)?;
+        out_file.write_all(&image_data(reader, &layout, offset, length)?.1)?;
     }
+    Ok(())
 }
 
-pub fn extract_jp2_index(mut file: &File, i: usize) -> Option<Vec<u8>> {
-    if i >= get_num_images(file) {
-        eprint!("Index out of bounds");
-        return None;
+/// Like [`extract_jp2`], except that JPEG 2000 images are written as JP2 files to
+/// `{outpath}{i}.jp2`, see [`wrap_jp2_index`].
+pub fn extract_jp2_wrapped<R: Read + Seek>(reader: &mut R, outpath: &str, georeference: Georeference) -> Result<()> {
+    for (i, (layout, offset, length)) in image_data_fields(reader)?.into_iter().enumerate() {
+        let compression = ImageCompression::from_ic(&layout.ic);
+        let bytes = match compression {
+            ImageCompression::Jpeg2000 => wrap_jp2_index(reader, i, georeference)?,
+            _ => image_data(reader, &layout, offset, length)?.1,
+        };
+        let extension = match compression {
+            ImageCompression::Jpeg2000 => "jp2",
+            _ => compression.extension(),
+        };
+        let mut out_file = File::create(format!("{}{}.{}", outpath, i, extension))?;
+        out_file.write_all(&bytes)?;
     }
-    let img_data_offset = N::get_image_data_field_offset(Some(file), i as u64);
-    let img_length = read_int_from_file(file, img_data_offset, N::get_value(LI));
-    let _ = file.seek(SeekFrom::Start(img_data_offset as u64));
-    let mut img_data = vec![0u8; img_length];
-    let _ = file.read_exact(&mut img_data);
-    Some(img_data)
+    Ok(())
 }
 
-pub fn extract_des_header_fields_index(mut file: &File, index: usize) -> Option<Vec<u8>> {
-    if index >= get_numdes(file) {
-        eprint!("Index out of bounds");
-        return None;
+/// The codestream of JPEG 2000 image `i` (IC C8 or M8) as a JP2 file, with ihdr and colr
+/// from the image subheader and, unless `georeference` is `None`, a GMLJP2 or GeoJP2 box from
+/// IGEOLO. An image without IGEOLO, or with UTM or MGRS corners, gets no such box, and a data
+/// field that already is a JP2 file is returned as it is. Only an IGEOLO that does not parse
+/// as ICORDS says is an error.
+pub fn wrap_jp2_index<R: Read + Seek>(reader: &mut R, i: usize, georeference: Georeference) -> Result<Vec<u8>> {
+    let (layout, _, codestream) = j2k_data_field(reader, i)?;
+    if codestream.starts_with(JP2_SIGNATURE) {
+        return Ok(codestream);
     }
+    let (irep, icords, igeolo, igeolo_offset) = image_geolocation(reader, i)?;
+    //Only geographic corners can be put in a box, UTM and MGRS (ICORDS N, S and U) are left out
+    let corners = match igeolo {
+        Some(igeolo) if georeference != Georeference::None && matches!(icords.as_str(), "G" | "D") => {
+            Some(igeolo_corners(&icords, &igeolo).map_err(|e| offset_error(e, igeolo_offset))?)
+        }
+        _ => None,
+    };
+    Ok(wrap_codestream(&codestream, &layout, &irep, corners, georeference))
+}
+
+/// The data field of image `i` past any block mask, whatever its IC. See [`extract_image_index`].
+pub fn extract_jp2_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Vec<u8>> {
+    Ok(extract_image_index(reader, i)?.1)
+}
+
+/// The data field of image `i` along with what IC says it holds. For a masked IC the block
+/// mask record is left out, the bytes starting at IMDATOFF like the files [`extract_jp2`]
+/// writes, so a masked codestream is still a codestream; [`get_block_mask`] reads the mask.
+pub fn extract_image_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<(ImageCompression, Vec<u8>)> {
+    let fields = image_data_fields(reader)?;
+    let (layout, offset, length) = fields.get(i).ok_or(NitfError::IndexOutOfRange {
+        segment: "Image",
+        index: i,
+        count: fields.len(),
+    })?;
     #[cfg(all(debug_assertions, not(test)))]
-    println!("Extracting DES header fields index {}", index);
-    let mut des_header_field_offset = N::get_offset(LDSH, Some(file));
-    for _i in 0..index {
-        des_header_field_offset += N::get_value(LDSH) + N::get_value(LD);
+    println!("Extracting image index {} ({})", i, layout.ic);
+    Ok((ImageCompression::from_ic(&layout.ic), image_data(reader, layout, *offset, *length)?.1))
+}
+
+/// The block mask record of image `i`, or `None` if its IC is not a masked one.
+pub fn get_block_mask<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Option<BlockMask>> {
+    let fields = image_data_fields(reader)?;
+    let (layout, offset, _) = fields.get(i).ok_or(NitfError::IndexOutOfRange {
+        segment: "Image",
+        index: i,
+        count: fields.len(),
+    })?;
+    if !layout.is_masked() {
+        return Ok(None);
+    }
+    Ok(Some(BlockMask::read(reader, *offset, layout)?))
+}
+
+/// The stored bytes of one block of image `i`, read without reading the rest of the data
+/// field. `band` is only meaningful for IMODE S, where each band has its own blocks.
+/// Returns `None` for a block the block mask leaves out.
+///
+/// A block runs up to the next block in the file, so this works for blocks compressed
+/// one by one (M3) as well as for uncompressed ones.
+pub fn read_image_block<R: Read + Seek>(
+    reader: &mut R,
+    i: usize,
+    block_row: usize,
+    block_col: usize,
+    band: usize,
+) -> Result<Option<Vec<u8>>> {
+    let fields = image_data_fields(reader)?;
+    let (layout, offset, length) = fields.get(i).ok_or(NitfError::IndexOutOfRange {
+        segment: "Image",
+        index: i,
+        count: fields.len(),
+    })?;
+    let bands = if layout.imode == "S" { layout.nbands } else { 1 };
+    if block_row >= layout.nbpc || block_col >= layout.nbpr {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Block",
+            index: block_row * layout.nbpr + block_col,
+            count: layout.nbpr * layout.nbpc,
+        });
+    }
+    if band >= bands {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Band",
+            index: band,
+            count: bands,
+        });
+    }
+    let mask = if layout.is_masked() {
+        Some(BlockMask::read(reader, *offset, layout)?)
+    } else {
+        None
+    };
+    let offsets = layout.block_offsets(mask.as_ref());
+    let start = match offsets[(band * layout.nbpc + block_row) * layout.nbpr + block_col] {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let end = offsets.iter().flatten().filter(|&&o| o > start).min().copied().unwrap_or(*length);
+    if start >= end || end > *length {
+        return Err(NitfError::Truncated {
+            offset: offset + start,
+            length: layout.block_length(),
+        });
+    }
+    Ok(Some(read_bytes(reader, offset + start, end - start)?))
+}
+
+/// Decodes the pixels of uncompressed (IC NC or NM) image `i`, see [`decode_uncompressed`].
+pub fn decode_image_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<DecodedImage> {
+    let fields = image_data_fields(reader)?;
+    let (layout, offset, length) = fields.get(i).ok_or(NitfError::IndexOutOfRange {
+        segment: "Image",
+        index: i,
+        count: fields.len(),
+    })?;
+    let data = read_bytes(reader, *offset, *length)?;
+    decode_uncompressed(layout, &data).map_err(|e| offset_error(e, *offset))
+}
+
+/// The JPEG 2000 codestream of image `i` (IC C8 or M8), parsed down to its tile-part headers.
+/// Offsets in errors are file offsets.
+pub fn inspect_j2k_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Codestream> {
+    let (_, start, data) = j2k_data_field(reader, i)?;
+    Codestream::parse(&data).map_err(|e| offset_error(e, start))
+}
+
+/// Checks that the SIZ of image `i` (IC C8 or M8) gives the NROWS and NCOLS of its subheader.
+/// A mismatch is a `MalformedField` "SIZ" at the Ysiz or Xsiz of the codestream.
+pub fn check_j2k_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<()> {
+    let (layout, start, data) = j2k_data_field(reader, i)?;
+    let codestream = Codestream::parse(&data).map_err(|e| offset_error(e, start))?;
+    //Xsiz and Ysiz follow SOC, the SIZ marker, Lsiz and Rsiz
+    let xsiz = start + codestream.offset + 8;
+    let (width, height) = (codestream.siz.width() as usize, codestream.siz.height() as usize);
+    if height != layout.nrows {
+        return Err(NitfError::MalformedField {
+            field: "SIZ",
+            offset: xsiz + 4,
+            raw: format!("{} rows, NROWS {}", height, layout.nrows),
+        });
+    }
+    if width != layout.ncols {
+        return Err(NitfError::MalformedField {
+            field: "SIZ",
+            offset: xsiz,
+            raw: format!("{} columns, NCOLS {}", width, layout.ncols),
+        });
+    }
+    Ok(())
+}
+
+/// Decodes image `i` and writes it to `path` as a PNG, TIFF or PGM/PPM, for previewing.
+/// Only uncompressed images can be exported, see [`decode_image_index`].
+pub fn export_image<R: Read + Seek>(reader: &mut R, i: usize, options: &ExportOptions, path: &str) -> Result<()> {
+    let image = decode_image_index(reader, i)?;
+    let (layout, bands) = match Nitf::new(reader)? {
+        Nitf::V02_00(nitf) => (ImageLayout::from(&nitf.images[i]), nitf.images[i].bands.clone()),
+        Nitf::V02_10(nitf) | Nitf::NSIF01_00(nitf) => (ImageLayout::from(&nitf.images[i]), nitf.images[i].bands.clone()),
+    };
+    let bytes = export(&image, &layout, &bands, options)?;
+    File::create(path)?.write_all(&bytes)?;
+    Ok(())
+}
+
+/// The TREs of image `i` (UDID then IXSHD), with the common support data TREs decoded.
+/// TREs moved to a TRE_OVERFLOW DES are put back after the area they overflowed from.
+pub fn get_tres<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Vec<ImageTre>> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numi() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Image",
+            index: i,
+            count: file_header.numi(),
+        });
+    }
+    let subheader_offset = file_header.segment_offsets().image_segments
+        + file_header.images[..i].iter().map(SegmentLength::total).sum::<usize>();
+    let subheader = ImageSubheader::read(reader, subheader_offset, file_header.images[i].subheader)?;
+    let mut tres = decode_tres(&subheader.udid)?;
+    tres.extend(decode_tres(&read_overflow(reader, &file_header, subheader.udofl, "UDID", i + 1)?)?);
+    tres.extend(decode_tres(&subheader.ixshd)?);
+    tres.extend(decode_tres(&read_overflow(reader, &file_header, subheader.ixsofl, "IXSHD", i + 1)?)?);
+    Ok(tres)
+}
+
+/// The TREs of the file header (UDHD then XHD), including any moved to a TRE_OVERFLOW DES.
+pub fn get_header_tres<R: Read + Seek>(reader: &mut R) -> Result<Vec<Tre>> {
+    let file_header = FileHeader::read(reader)?;
+    let mut tres = decode_tres(&file_header.udhd)?;
+    tres.extend(decode_tres(&read_overflow(reader, &file_header, file_header.udhofl, "UDHD", 0)?)?);
+    tres.extend(decode_tres(&file_header.xhd)?);
+    tres.extend(decode_tres(&read_overflow(reader, &file_header, file_header.xhdlofl, "XHD", 0)?)?);
+    Ok(tres)
+}
+
+pub fn get_des_subheader<R: Read + Seek>(reader: &mut R, i: usize) -> Result<DesSubheader> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numdes() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "DES",
+            index: i,
+            count: file_header.numdes(),
+        });
+    }
+    let offset = file_header.segment_offsets().data_extension_segments
+        + file_header.des[..i].iter().map(SegmentLength::total).sum::<usize>();
+    DesSubheader::read(reader, offset, file_header.des[i].subheader)
+}
+
+pub fn extract_des_header_fields_index<R: Read + Seek>(reader: &mut R, index: usize) -> Result<Vec<u8>> {
+    let file_header = FileHeader::read(reader)?;
+    if index >= file_header.numdes() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "DES",
+            index,
+            count: file_header.numdes(),
+        });
     }
-    let mut des_header = vec![0u8; N::get_value(LDSH) + N::get_value(LD)];
-    let _ = file.seek(SeekFrom::Start(des_header_field_offset as u64));
-    let _ = file.read_exact(&mut des_header);
-    Some(des_header)
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting DES header fields index {}", index);
+    let field_length = N::get_value(LDSH) + N::get_value(LD);
+    let des_header_field_offset = file_header.offsets().ldsh + index * field_length;
+    let mut des_header = vec![0u8; field_length];
+    reader.seek(SeekFrom::Start(des_header_field_offset as u64))?;
+    reader.read_exact(&mut des_header)?;
+    Ok(des_header)
 }
 
-pub fn extract_des_index(mut file: &File, i: usize) -> Option<Vec<u8>> {
-    if i >= get_numdes(file) {
-        eprint!("Index out of bounds");
-        return None;
+pub fn extract_des_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Vec<u8>> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numdes() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "DES",
+            index: i,
+            count: file_header.numdes(),
+        });
     }
     #[cfg(all(debug_assertions, not(test)))]
     println!("Extracting DES index {}", i);
-    let des_header_offset = N::get_des_header_field_offset(Some(file), i as u64);
-    let des_header_length = read_int_from_file(file, des_header_offset, N::get_value(LDSH));
-    let des_data_offset = N::get_des_data_field_offset(Some(file), i as u64);
-    let des_length = read_int_from_file(file, des_data_offset, N::get_value(LD));
-    let _ = file.seek(SeekFrom::Start(des_header_offset as u64));
-    let mut des_header = vec![0u8; des_header_length];
-    let _ = file.read_exact(&mut des_header);
-    let _ = file.seek(SeekFrom::Start(des_data_offset as u64));
-    let mut des_data = vec![0u8; des_length];
-    let _ = file.read_exact(&mut des_data);
-    let mut des_all = vec![0u8; des_header_length + des_length];
-    des_all[..des_header_length].copy_from_slice(&des_header);
-    des_all[des_header_length..].copy_from_slice(&des_data);
-    Some(des_all)
-}
-
-pub fn extract_des(mut file: &File, outpath: &str) {
-    let num_des = get_numdes(file);
-    for i in 0..num_des {
-        let des_header_offset = N::get_des_header_field_offset(Some(file), i as u64);
-        let des_header_length = read_int_from_file(file, des_header_offset, N::get_value(LD));
-        let des_data_offset = N::get_des_data_field_offset(Some(file), i as u64);
-        let des_data_length = read_int_from_file(file, des_data_offset, N::get_value(LD));
-        let _ = file.seek(SeekFrom::Start(des_header_offset as u64));
-        let mut des_header = vec![0u8; des_header_length];
-        let _ = file.read_exact(&mut des_header);
-        let _ = file.seek(SeekFrom::Start(des_data_offset as u64));
-        let mut des_data = vec![0u8; des_data_length];
-        let _ = file.read_exact(&mut des_data);
+    read_segment(reader, &file_header, SegmentType::DataExtension, i)
+}
+
+pub fn extract_des<R: Read + Seek>(reader: &mut R, outpath: &str) -> Result<()> {
+    let file_header = FileHeader::read(reader)?;
+    for i in 0..file_header.numdes() {
+        let des_all = read_segment(reader, &file_header, SegmentType::DataExtension, i)?;
         let des = format!("{}{}.des", outpath, i);
-        let mut des_file = File::create(des).expect("Failed to create output file");
-        let _ = des_file.write_all(&des_header);
-        let _ = des_file.write_all(&des_data);
+        let mut des_file = File::create(des)?;
+        des_file.write_all(&des_all)?;
+    }
+    Ok(())
+}
+
+pub fn get_graphic_subheader<R: Read + Seek>(reader: &mut R, i: usize) -> Result<GraphicSubheader> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.nums() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Graphic",
+            index: i,
+            count: file_header.nums(),
+        });
     }
+    let offset = file_header.segment_offsets().graphic_segments
+        + file_header.graphics[..i].iter().map(SegmentLength::total).sum::<usize>();
+    GraphicSubheader::read(reader, offset, file_header.graphics[i].subheader)
 }
 
-pub fn copy_des_segments(input_file: &mut File, output_file: &mut File) {
+/// The data of graphic segment `i`, a CGM file.
+pub fn extract_graphic_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Vec<u8>> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.nums() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Graphic",
+            index: i,
+            count: file_header.nums(),
+        });
+    }
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting graphic index {}", i);
+    let segment = read_segment(reader, &file_header, SegmentType::Graphic, i)?;
+    Ok(segment[file_header.graphics[i].subheader..].to_vec())
+}
+
+/// Writes the CGM of every graphic segment to `{outpath}{i}.cgm`.
+pub fn extract_graphics<R: Read + Seek>(reader: &mut R, outpath: &str) -> Result<()> {
+    let file_header = FileHeader::read(reader)?;
+    for i in 0..file_header.nums() {
+        let segment = read_segment(reader, &file_header, SegmentType::Graphic, i)?;
+        let path = format!("{}{}.cgm", outpath, i);
+        let mut out_file = File::create(path)?;
+        out_file.write_all(&segment[file_header.graphics[i].subheader..])?;
+    }
+    Ok(())
+}
+
+/// Renders graphic segment `i` to SVG, placed in the CCS by its SLOC and the locations of the
+/// images and graphics it is attached to through SALVL.
+pub fn get_graphic_svg<R: Read + Seek>(reader: &mut R, i: usize) -> Result<String> {
+    let subheader = get_graphic_subheader(reader, i)?;
+    let cgm = Cgm::parse(&extract_graphic_index(reader, i)?)?;
+    let file_header = FileHeader::read(reader)?;
+    let salvl_offset = file_header.segment_offsets().graphic_segments
+        + file_header.graphics[..i].iter().map(SegmentLength::total).sum::<usize>()
+        + NitfGraphicSubheader21::fixed_offset(NitfGraphicSubheader21::SALVL);
+    let (row, column) = attachment_location(reader, &file_header, subheader.salvl, salvl_offset)?;
+    Ok(render_svg(&cgm, (row + subheader.sloc.0, column + subheader.sloc.1)))
+}
+
+pub fn get_text_subheader<R: Read + Seek>(reader: &mut R, i: usize) -> Result<TextSubheader> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numt() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "Text",
+            index: i,
+            count: file_header.numt(),
+        });
+    }
+    let offset = file_header.segment_offsets().text_segments
+        + file_header.texts[..i].iter().map(SegmentLength::total).sum::<usize>();
+    TextSubheader::read(reader, offset, file_header.texts[i].subheader)
+}
+
+/// The text of text segment `i`, decoded according to its TXTFMT, see [`TextSubheader::decode_text`].
+pub fn extract_text_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<String> {
+    let subheader = get_text_subheader(reader, i)?;
+    let file_header = FileHeader::read(reader)?;
+    let offset = file_header.segment_offsets().text_segments
+        + file_header.texts[..i].iter().map(SegmentLength::total).sum::<usize>();
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting text index {}", i);
+    let segment = read_segment(reader, &file_header, SegmentType::Text, i)?;
+    let data_offset = offset + file_header.texts[i].subheader;
+    subheader.decode_text(&segment[file_header.texts[i].subheader..]).map_err(|e| match e {
+        NitfError::MalformedField { field, offset: o, raw } => NitfError::MalformedField {
+            field,
+            offset: if field == "TEXT" { data_offset + o } else { offset + o },
+            raw,
+        },
+        e => e,
+    })
+}
+
+/// Indices of the XML_DATA_CONTENT DES in a file.
+pub fn get_xml_des_indices<R: Read + Seek>(reader: &mut R) -> Result<Vec<usize>> {
+    let file_header = FileHeader::read(reader)?;
+    let mut indices = Vec::new();
+    for i in 0..file_header.numdes() {
+        if get_des_subheader(reader, i)?.desid == XML_DATA_CONTENT {
+            indices.push(i);
+        }
+    }
+    Ok(indices)
+}
+
+/// The user defined subheader of DES `i`, which must be an XML_DATA_CONTENT DES.
+pub fn get_xml_des_subheader<R: Read + Seek>(reader: &mut R, i: usize) -> Result<XmlDataContent> {
+    let (xml_subheader, ..) = read_xml_des(reader, i)?;
+    Ok(xml_subheader)
+}
+
+/// The XML document held by DES `i`. Fails with `MalformedField` on DESCRC if the data
+/// does not match its CRC, or if the document is not valid UTF-8.
+pub fn extract_xml_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<String> {
+    let (xml_subheader, data, desshf_offset, data_offset) = read_xml_des(reader, i)?;
+    if !xml_subheader.crc_matches(&data) {
+        return Err(NitfError::MalformedField {
+            field: XmlDataContentField::DESCRC.as_str(),
+            offset: desshf_offset,
+            raw: xml_subheader.descrc.unwrap_or_default().to_string(),
+        });
+    }
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting XML from DES index {}", i);
+    String::from_utf8(data).map_err(|e| NitfError::MalformedField {
+        field: "DESDATA",
+        offset: data_offset + e.utf8_error().valid_up_to(),
+        raw: e.utf8_error().to_string(),
+    })
+}
+
+/// Appends an XML_DATA_CONTENT DES holding `xml` to `output_file`, with the file's security
+/// group. DESCRC is computed from `xml`, the other DESSHF fields are taken from `xml_subheader`.
+pub fn add_xml_des<W: Read + Write + Seek>(output_file: &mut W, xml: &str, xml_subheader: &XmlDataContent) -> Result<()> {
+    let file_header = FileHeader::read(output_file)?;
+    let xml_subheader = XmlDataContent {
+        descrc: Some(crc16(xml.as_bytes())),
+        ..xml_subheader.clone()
+    };
+    let subheader = DesSubheader {
+        de: "DE".to_string(),
+        desid: XML_DATA_CONTENT.to_string(),
+        desver: 1,
+        security: file_header.security.clone(),
+        desshf: xml_subheader.to_bytes()?,
+        ..Default::default()
+    };
+    let segment = Segment::new(subheader.to_bytes()?, xml.as_bytes().to_vec());
+    insert_segments(output_file, SegmentType::DataExtension, file_header.numdes(), &[segment])
+}
+
+pub fn extract_res_header_fields_index<R: Read + Seek>(reader: &mut R, index: usize) -> Result<Vec<u8>> {
+    let file_header = FileHeader::read(reader)?;
+    if index >= file_header.numres() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "RES",
+            index,
+            count: file_header.numres(),
+        });
+    }
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting RES header fields index {}", index);
+    let field_length = N::get_value(LRESH) + N::get_value(LRE);
+    read_bytes(reader, file_header.offsets().lresh + index * field_length, field_length)
+}
+
+pub fn extract_res_index<R: Read + Seek>(reader: &mut R, i: usize) -> Result<Vec<u8>> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numres() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "RES",
+            index: i,
+            count: file_header.numres(),
+        });
+    }
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting RES index {}", i);
+    read_segment(reader, &file_header, SegmentType::ReservedExtension, i)
+}
+
+pub fn extract_res<R: Read + Seek>(reader: &mut R, outpath: &str) -> Result<()> {
+    let file_header = FileHeader::read(reader)?;
+    for i in 0..file_header.numres() {
+        let res_all = read_segment(reader, &file_header, SegmentType::ReservedExtension, i)?;
+        let res = format!("{}{}.res", outpath, i);
+        let mut res_file = File::create(res)?;
+        res_file.write_all(&res_all)?;
+    }
+    Ok(())
+}
+
+pub fn get_res_subheader<R: Read + Seek>(reader: &mut R, i: usize) -> Result<ResSubheader> {
+    let file_header = FileHeader::read(reader)?;
+    if i >= file_header.numres() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: "RES",
+            index: i,
+            count: file_header.numres(),
+        });
+    }
+    let offset = file_header.segment_offsets().reserved_extension_segments
+        + file_header.res[..i].iter().map(SegmentLength::total).sum::<usize>();
+    ResSubheader::read(reader, offset, file_header.res[i].subheader)
+}
+
+/// Appends every RES of `input_file` to `output_file`.
+pub fn copy_res_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+) -> Result<()> {
+    let indices = (0..FileHeader::read(input_file)?.numres()).collect::<Vec<_>>();
+    let numres = FileHeader::read(output_file)?.numres();
+    copy_segments(input_file, output_file, SegmentType::ReservedExtension, &indices, numres)
+}
+
+pub fn copy_des_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+) -> Result<()> {
     //Retrieve offsets and lengths
-    let input_nitf = Nitf::new(input_file);
-    let input_num_des = get_numdes(input_file);
-    let input_des_length = input_nitf.segments.reserved_extension_segments - input_nitf.segments.data_extension_segments;
-    let input_des_header_length = input_nitf.get_data_extension_headers_length(input_file);
+    let input_nitf = Nitf::new(input_file)?.into_v02_10()?;
+    let input_num_des = input_nitf.file_header.numdes();
+    let input_des_length = input_nitf.get_data_extension_seg_length();
+    let input_des_header_length = input_nitf.get_data_extension_headers_length();
 
-    let output_nitf = Nitf::new(output_file);
-    let output_num_des = get_numdes(output_file);
+    let output_nitf = Nitf::new(output_file)?.into_v02_10()?;
+    let output_num_des = output_nitf.file_header.numdes();
     //End of DES segment is start of reserved extension segment, same for header
     let output_des_end = output_nitf.segments.reserved_extension_segments;
-    let output_des_field_end = N::get_offset(NUMRES, Some(output_file));
-    let output_hl = read_int_from_file(output_file, output_nitf.header.hl, N::get_value(HL));
-    let output_fl = read_int_from_file(output_file, output_nitf.header.fl, N::get_value(FL));
+    let output_des_field_end = output_nitf.header.numres;
+    let output_hl = output_nitf.file_header.hl;
+    let output_fl = output_nitf.file_header.fl;
 
     //Read input DES header
     let mut des_header = vec![0u8; input_des_header_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.header.ldsh as u64));
-    _ = input_file.read_exact(&mut des_header);
+    input_file.seek(SeekFrom::Start(input_nitf.header.ldsh as u64))?;
+    input_file.read_exact(&mut des_header)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
     println!("Successfully read DES header");
     println!("Output Des End: {}", output_des_end);
-    println!("Output Buf Length: {}", output_file.metadata().unwrap().len()); 
+    println!("Output Buf Length: {}", output_file.seek(SeekFrom::End(0))?);
     }
 
     //Read input DES data
     let mut des_data = vec![0u8; input_des_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.segments.data_extension_segments as u64));
-    _ = input_file.read_exact(&mut des_data);
+    input_file.seek(SeekFrom::Start(input_nitf.segments.data_extension_segments as u64))?;
+    input_file.read_exact(&mut des_data)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Successfully read DES data");
 
@@ -135,8 +567,8 @@ pub fn copy_des_segments(input_file: &mut File, output_file: &mut File) {
     #[cfg(all(debug_assertions, not(test)))]
     println!("Making Output Buffer");
     let out_buf = &mut Vec::new();
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.read_to_end(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.read_to_end(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Made Output Buf of length {}", out_buf.len());
     
@@ -157,29 +589,15 @@ pub fn copy_des_segments(input_file: &mut File, output_file: &mut File) {
     //Change HL, FL, and NUMDES values
     #[cfg(all(debug_assertions, not(test)))]
     println!("Changing HL, FL, and NUMDES values");
-    let total_num_des = (input_num_des + output_num_des).to_string();
+    let total_num_des = input_num_des + output_num_des;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Total Output Num Des: {}", total_num_des);
-    let mut des_buf = vec![48; N::get_value(NUMDES)];
-    let total_hl = (output_hl + input_des_header_length).to_string();
-    let mut hl_buf = vec![48; N::get_value(HL)];
-    let total_fl = (output_fl + input_des_length + input_des_header_length).to_string();
-    let mut fl_buf = vec![48; N::get_value(FL)];
-    if total_num_des.as_bytes().len() > N::get_value(NUMDES) {
-        panic!("NUMDES value too large");
-    }
-    if total_hl.as_bytes().len() > N::get_value(HL) {
-        panic!("HL value too large");
-    }
-    if total_fl.as_bytes().len() > N::get_value(FL) {
-        panic!("FL value too large");
-    }
-    des_buf[N::get_value(NUMDES) - total_num_des.len()..].copy_from_slice(total_num_des.as_bytes());
-    hl_buf[N::get_value(HL) - total_hl.len()..].copy_from_slice(total_hl.as_bytes());
-    fl_buf[N::get_value(FL) - total_fl.len()..].copy_from_slice(total_fl.as_bytes());
-    N::change_field_value(NUMDES, output_file, out_buf, &des_buf);
-    N::change_field_value(HL, output_file, out_buf, &hl_buf);
-    N::change_field_value(FL, output_file, out_buf, &fl_buf);
+    let des_buf = zero_padded(NUMDES, total_num_des)?;
+    let hl_buf = zero_padded(HL, output_hl + input_des_header_length)?;
+    let fl_buf = zero_padded(FL, output_fl + input_des_length + input_des_header_length)?;
+    N::change_field_value(NUMDES, output_file, out_buf, &des_buf)?;
+    N::change_field_value(HL, output_file, out_buf, &hl_buf)?;
+    N::change_field_value(FL, output_file, out_buf, &fl_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("Changed HL, FL, and NUMDES values");
@@ -187,52 +605,54 @@ pub fn copy_des_segments(input_file: &mut File, output_file: &mut File) {
         println!("hl_buf: {:?}", hl_buf);
         println!("fl_buf: {:?}", fl_buf);
     }
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.set_len(out_buf.len() as u64);
-    let _ = output_file.write_all(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Wrote to output file");
-    output_file.flush().expect("Failed to flush test file");
+    output_file.flush()?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("New Values: ");
-        println!("NUMDES: {}", get_numdes(output_file));
-        println!("HL: {}", get_hl(output_file));
-        println!("FL: {}", get_fl(output_file));
+        println!("NUMDES: {}", get_numdes(output_file)?);
+        println!("HL: {}", get_hl(output_file)?);
+        println!("FL: {}", get_fl(output_file)?);
     }
-
+    Ok(())
 }
 
-pub fn copy_graphic_segments(input_file: &mut File, output_file: &mut File) {
+pub fn copy_graphic_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+) -> Result<()> {
     //Retrieve offsets and lengths
-    let input_nitf = Nitf::new(input_file);
-    let input_nums = get_nums(input_file);
-    let input_graphic_length = input_nitf.segments.text_segments - input_nitf.segments.graphic_segments;
-    let input_graphic_header_length = input_nitf.get_graphic_headers_length(input_file);
+    let input_nitf = Nitf::new(input_file)?.into_v02_10()?;
+    let input_nums = input_nitf.file_header.nums();
+    let input_graphic_length = input_nitf.get_graphic_seg_length();
+    let input_graphic_header_length = input_nitf.get_graphic_headers_length();
 
-    let output_nitf = Nitf::new(output_file);
-    let output_nums = get_nums(output_file);
+    let output_nitf = Nitf::new(output_file)?.into_v02_10()?;
+    let output_nums = output_nitf.file_header.nums();
     //End of graphic segment is start of text segment, header end is numx
     let output_graphic_end = output_nitf.segments.text_segments;
     let output_graphic_field_end = output_nitf.header.numx;
-    let output_hl = get_hl(output_file);
-    let output_fl = get_fl(output_file);
+    let output_hl = output_nitf.file_header.hl;
+    let output_fl = output_nitf.file_header.fl;
 
     //Read input DES header
     let mut graphic_header = vec![0u8; input_graphic_header_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.header.lssh as u64));
-    _ = input_file.read_exact(&mut graphic_header);
+    input_file.seek(SeekFrom::Start(input_nitf.header.lssh as u64))?;
+    input_file.read_exact(&mut graphic_header)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
     println!("Successfully read Graphic header");
     println!("Output Graphic End: {}", output_graphic_end);
-    println!("Output Buf Length: {}", output_file.metadata().unwrap().len()); 
+    println!("Output Buf Length: {}", output_file.seek(SeekFrom::End(0))?);
     }
 
     //Read input graphic data
     let mut graphic_data = vec![0u8; input_graphic_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.segments.graphic_segments as u64));
-    _ = input_file.read_exact(&mut graphic_data);
+    input_file.seek(SeekFrom::Start(input_nitf.segments.graphic_segments as u64))?;
+    input_file.read_exact(&mut graphic_data)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Successfully read graphic data");
 
@@ -240,8 +660,8 @@ pub fn copy_graphic_segments(input_file: &mut File, output_file: &mut File) {
     #[cfg(all(debug_assertions, not(test)))]
     println!("Making Output Buffer");
     let out_buf = &mut Vec::new();
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.read_to_end(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.read_to_end(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Made Output Buf of length {}", out_buf.len());
     
@@ -262,29 +682,15 @@ pub fn copy_graphic_segments(input_file: &mut File, output_file: &mut File) {
     //Change HL, FL, and NUMS values
     #[cfg(all(debug_assertions, not(test)))]
     println!("Changing HL, FL, and NUMS values");
-    let total_nums = (input_nums + output_nums).to_string();
+    let total_nums = input_nums + output_nums;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Total Output Nums: {}", total_nums);
-    let mut graphic_buf = vec![48; N::get_value(NUMS)];
-    let total_hl = (output_hl + input_graphic_header_length).to_string();
-    let mut hl_buf = vec![48; N::get_value(HL)];
-    let total_fl = (output_fl + input_graphic_length + input_graphic_header_length).to_string();
-    let mut fl_buf = vec![48; N::get_value(FL)];
-    if total_nums.as_bytes().len() > N::get_value(NUMS) {
-        panic!("NUMS value too large");
-    }
-    if total_hl.as_bytes().len() > N::get_value(HL) {
-        panic!("HL value too large");
-    }
-    if total_fl.as_bytes().len() > N::get_value(FL) {
-        panic!("FL value too large");
-    }
-    graphic_buf[N::get_value(NUMS) - total_nums.len()..].copy_from_slice(total_nums.as_bytes());
-    hl_buf[N::get_value(HL) - total_hl.len()..].copy_from_slice(total_hl.as_bytes());
-    fl_buf[N::get_value(FL) - total_fl.len()..].copy_from_slice(total_fl.as_bytes());
-    N::change_field_value(NUMS, output_file, out_buf, &graphic_buf);
-    N::change_field_value(HL, output_file, out_buf, &hl_buf);
-    N::change_field_value(FL, output_file, out_buf, &fl_buf);
+    let graphic_buf = zero_padded(NUMS, total_nums)?;
+    let hl_buf = zero_padded(HL, output_hl + input_graphic_header_length)?;
+    let fl_buf = zero_padded(FL, output_fl + input_graphic_length + input_graphic_header_length)?;
+    N::change_field_value(NUMS, output_file, out_buf, &graphic_buf)?;
+    N::change_field_value(HL, output_file, out_buf, &hl_buf)?;
+    N::change_field_value(FL, output_file, out_buf, &fl_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("Changed HL, FL, and NUMS values");
@@ -292,52 +698,54 @@ pub fn copy_graphic_segments(input_file: &mut File, output_file: &mut File) {
         println!("hl_buf: {:?}", hl_buf);
         println!("fl_buf: {:?}", fl_buf);
     }
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.set_len(out_buf.len() as u64);
-    let _ = output_file.write_all(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Wrote to output file");
-    output_file.flush().expect("Failed to flush test file");
+    output_file.flush()?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("New Values: ");
-        println!("NUMS: {}", get_nums(output_file));
-        println!("HL: {}", get_hl(output_file));
-        println!("FL: {}", get_fl(output_file));
+        println!("NUMS: {}", get_nums(output_file)?);
+        println!("HL: {}", get_hl(output_file)?);
+        println!("FL: {}", get_fl(output_file)?);
     }
-
+    Ok(())
 }
 
-pub fn copy_text_segments(input_file: &mut File, output_file: &mut File) {
+pub fn copy_text_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+) -> Result<()> {
     //Retrieve offsets and lengths
-    let input_nitf = Nitf::new(input_file);
-    let input_numt = get_numt(input_file);
-    let input_t_length = input_nitf.segments.data_extension_segments - input_nitf.segments.text_segments;
-    let input_t_header_length = input_nitf.get_text_headers_length(input_file);
+    let input_nitf = Nitf::new(input_file)?.into_v02_10()?;
+    let input_numt = input_nitf.file_header.numt();
+    let input_t_length = input_nitf.get_text_seg_length();
+    let input_t_header_length = input_nitf.get_text_headers_length();
 
-    let output_nitf = Nitf::new(output_file);
-    let output_numt = get_numt(output_file);
+    let output_nitf = Nitf::new(output_file)?.into_v02_10()?;
+    let output_numt = output_nitf.file_header.numt();
     //End of text segment is start of data extension segment, same for header
     let output_t_end = output_nitf.segments.data_extension_segments;
     let output_t_field_end = output_nitf.header.numdes;
-    let output_hl = get_hl(output_file);
-    let output_fl = get_fl(output_file);
+    let output_hl = output_nitf.file_header.hl;
+    let output_fl = output_nitf.file_header.fl;
 
     //Read input text header
     let mut t_header = vec![0u8; input_t_header_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.header.ltsh as u64));
-    _ = input_file.read_exact(&mut t_header);
+    input_file.seek(SeekFrom::Start(input_nitf.header.ltsh as u64))?;
+    input_file.read_exact(&mut t_header)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
     println!("Successfully read text header");
     println!("Output text End: {}", output_t_end);
-    println!("Output Buf Length: {}", output_file.metadata().unwrap().len()); 
+    println!("Output Buf Length: {}", output_file.seek(SeekFrom::End(0))?);
     }
 
     //Read input text data
     let mut t_data = vec![0u8; input_t_length];
-    _ = input_file.seek(SeekFrom::Start(input_nitf.segments.text_segments as u64));
-    _ = input_file.read_exact(&mut t_data);
+    input_file.seek(SeekFrom::Start(input_nitf.segments.text_segments as u64))?;
+    input_file.read_exact(&mut t_data)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Successfully read text data");
 
@@ -345,8 +753,8 @@ pub fn copy_text_segments(input_file: &mut File, output_file: &mut File) {
     #[cfg(all(debug_assertions, not(test)))]
     println!("Making Output Buffer");
     let out_buf = &mut Vec::new();
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.read_to_end(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.read_to_end(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Made Output Buf of length {}", out_buf.len());
     
@@ -366,29 +774,15 @@ pub fn copy_text_segments(input_file: &mut File, output_file: &mut File) {
     //Change HL, FL, and NUMT values
     #[cfg(all(debug_assertions, not(test)))]
     println!("Changing HL, FL, and NUMT values");
-    let total_numt = (input_numt + output_numt).to_string();
+    let total_numt = input_numt + output_numt;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Total Output NUMT: {}", total_numt);
-    let mut t_buf = vec![48; N::get_value(NUMT)];
-    let total_hl = (output_hl + input_t_header_length).to_string();
-    let mut hl_buf = vec![48; N::get_value(HL)];
-    let total_fl = (output_fl + input_t_length + input_t_header_length).to_string();
-    let mut fl_buf = vec![48; N::get_value(FL)];
-    if total_numt.as_bytes().len() > N::get_value(NUMT) {
-        panic!("NUMDES value too large");
-    }
-    if total_hl.as_bytes().len() > N::get_value(HL) {
-        panic!("HL value too large");
-    }
-    if total_fl.as_bytes().len() > N::get_value(FL) {
-        panic!("FL value too large");
-    }
-    t_buf[N::get_value(NUMT) - total_numt.len()..].copy_from_slice(total_numt.as_bytes());
-    hl_buf[N::get_value(HL) - total_hl.len()..].copy_from_slice(total_hl.as_bytes());
-    fl_buf[N::get_value(FL) - total_fl.len()..].copy_from_slice(total_fl.as_bytes());
-    N::change_field_value(NUMT, output_file, out_buf, &t_buf);
-    N::change_field_value(HL, output_file, out_buf, &hl_buf);
-    N::change_field_value(FL, output_file, out_buf, &fl_buf);
+    let t_buf = zero_padded(NUMT, total_numt)?;
+    let hl_buf = zero_padded(HL, output_hl + input_t_header_length)?;
+    let fl_buf = zero_padded(FL, output_fl + input_t_length + input_t_header_length)?;
+    N::change_field_value(NUMT, output_file, out_buf, &t_buf)?;
+    N::change_field_value(HL, output_file, out_buf, &hl_buf)?;
+    N::change_field_value(FL, output_file, out_buf, &fl_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("Changed HL, FL, and NUMT values");
@@ -396,149 +790,659 @@ pub fn copy_text_segments(input_file: &mut File, output_file: &mut File) {
         println!("hl_buf: {:?}", hl_buf);
         println!("fl_buf: {:?}", fl_buf);
     }
-    let _ = output_file.seek(SeekFrom::Start(0));
-    let _ = output_file.set_len(out_buf.len() as u64);
-    let _ = output_file.write_all(out_buf);
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(out_buf)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Wrote to output file");
-    output_file.flush().expect("Failed to flush test file");
+    output_file.flush()?;
     #[cfg(all(debug_assertions, not(test)))]
     {
         println!("New Values: ");
-        println!("NUMT: {}", get_numt(output_file));
-        println!("HL: {}", get_hl(output_file));
-        println!("FL: {}", get_fl(output_file));
+        println!("NUMT: {}", get_numt(output_file)?);
+        println!("HL: {}", get_hl(output_file)?);
+        println!("FL: {}", get_fl(output_file)?);
+    }
+    Ok(())
+}
+
+
+/// Copies the segments at `indices` (in that order) from `input_file` into `output_file`,
+/// inserting them in front of the output's segment `position` of the same type.
+/// A `position` equal to the number of segments in the output appends them.
+pub fn copy_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+    segment: SegmentType,
+    indices: &[usize],
+    position: usize,
+) -> Result<()> {
+    let segments = read_segments_index(input_file, segment, indices)?;
+    insert_segments(output_file, segment, position, &segments)
+}
+
+/// Like [`copy_segments`], copying every segment of the type for which `predicate` is true.
+/// The predicate sees the raw segment; [`copy_text_segments_where`] and
+/// [`copy_des_segments_where`] hand it the parsed subheader instead.
+pub fn copy_segments_where<R, W, P>(
+    input_file: &mut R,
+    output_file: &mut W,
+    segment: SegmentType,
+    position: usize,
+    mut predicate: P,
+) -> Result<()>
+where
+    R: Read + Seek,
+    W: Read + Write + Seek,
+    P: FnMut(&Segment) -> bool,
+{
+    let file_header = FileHeader::read(input_file)?;
+    let mut segments = read_segments(input_file, &file_header, segment)?;
+    segments.retain(|s| predicate(s));
+    insert_segments(output_file, segment, position, &segments)
+}
+
+/// Like [`copy_segments_where`] for text segments, `predicate` getting the parsed subheader.
+/// A subheader that does not parse is an error rather than a segment left behind.
+pub fn copy_text_segments_where<R, W, P>(
+    input_file: &mut R,
+    output_file: &mut W,
+    position: usize,
+    mut predicate: P,
+) -> Result<()>
+where
+    R: Read + Seek,
+    W: Read + Write + Seek,
+    P: FnMut(&TextSubheader) -> bool,
+{
+    let file_header = FileHeader::read(input_file)?;
+    let mut segments = Vec::new();
+    for segment in read_segments(input_file, &file_header, SegmentType::Text)? {
+        if predicate(&segment.text_subheader()?) {
+            segments.push(segment);
+        }
+    }
+    insert_segments(output_file, SegmentType::Text, position, &segments)
+}
+
+/// Like [`copy_segments_where`] for data extension segments, `predicate` getting the parsed
+/// subheader. A subheader that does not parse is an error rather than a segment left behind.
+pub fn copy_des_segments_where<R, W, P>(
+    input_file: &mut R,
+    output_file: &mut W,
+    position: usize,
+    mut predicate: P,
+) -> Result<()>
+where
+    R: Read + Seek,
+    W: Read + Write + Seek,
+    P: FnMut(&DesSubheader) -> bool,
+{
+    let file_header = FileHeader::read(input_file)?;
+    let mut segments = Vec::new();
+    for segment in read_segments(input_file, &file_header, SegmentType::DataExtension)? {
+        if predicate(&segment.des_subheader()?) {
+            segments.push(segment);
+        }
+    }
+    insert_segments(output_file, SegmentType::DataExtension, position, &segments)
+}
+
+/// Appends every image segment of `input_file` to `output_file`, see [`copy_image_segments_index`].
+pub fn copy_image_segments<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+) -> Result<()> {
+    let indices = (0..FileHeader::read(input_file)?.numi()).collect::<Vec<_>>();
+    copy_image_segments_index(input_file, output_file, &indices)
+}
+
+/// Appends the image segments at `indices` of `input_file` to `output_file`.
+///
+/// Display levels are shared by every image and graphic in a file, so a copied image whose
+/// IDLVL is already taken is moved above the highest level in use. IALVL follows the first copy
+/// of the image it is attached to when that image is copied too, and is set to 0 (unattached)
+/// when it is not.
+pub fn copy_image_segments_index<R: Read + Seek, W: Read + Write + Seek>(
+    input_file: &mut R,
+    output_file: &mut W,
+    indices: &[usize],
+) -> Result<()> {
+    let mut segments = read_segments_index(input_file, SegmentType::Image, indices)?;
+    let subheaders = segments
+        .iter()
+        .map(|s| ImageSubheader::from_bytes(&s.subheader))
+        .collect::<Result<Vec<_>>>()?;
+
+    //New levels by position in `indices`, so copies of one image or of images sharing an
+    //IDLVL each get their own level
+    let mut used = display_levels(output_file)?;
+    let mut next_level = used.iter().max().copied().unwrap_or_default() + 1;
+    let mut new_levels = Vec::with_capacity(subheaders.len());
+    for sub in &subheaders {
+        let level = if used.contains(&sub.idlvl) { next_level } else { sub.idlvl };
+        used.insert(level);
+        next_level = next_level.max(level + 1);
+        new_levels.push(level);
     }
 
+    for (k, (segment, sub)) in segments.iter_mut().zip(&subheaders).enumerate() {
+        let idlvl = new_levels[k];
+        let parent = subheaders.iter().position(|s| sub.ialvl != 0 && s.idlvl == sub.ialvl);
+        let ialvl = parent.map_or(0, |p| new_levels[p]);
+        #[cfg(all(debug_assertions, not(test)))]
+        println!("Image IDLVL {} -> {}, IALVL {} -> {}", sub.idlvl, idlvl, sub.ialvl, ialvl);
+        let offset = ImageSubheader::idlvl_offset(&segment.subheader)?;
+        let mut levels = FieldWriter::new();
+        levels.write_int(I::IDLVL, idlvl)?;
+        levels.write_int(I::IALVL, ialvl)?;
+        let levels = levels.into_bytes();
+        segment.subheader[offset..offset + levels.len()].copy_from_slice(&levels);
+    }
+    let numi = FileHeader::read(output_file)?.numi();
+    insert_segments(output_file, SegmentType::Image, numi, &segments)
 }
 
+pub fn remove_image_segment<R: Read + Seek, W: Write>(input_file: &mut R, output_file: &mut W, index: usize) -> Result<()> {
+    remove_segment(input_file, output_file, SegmentType::Image, index)
+}
+
+pub fn remove_graphic_segment<R: Read + Seek, W: Write>(input_file: &mut R, output_file: &mut W, index: usize) -> Result<()> {
+    remove_segment(input_file, output_file, SegmentType::Graphic, index)
+}
+
+pub fn remove_text_segment<R: Read + Seek, W: Write>(input_file: &mut R, output_file: &mut W, index: usize) -> Result<()> {
+    remove_segment(input_file, output_file, SegmentType::Text, index)
+}
+
+pub fn remove_des_segment<R: Read + Seek, W: Write>(input_file: &mut R, output_file: &mut W, index: usize) -> Result<()> {
+    remove_segment(input_file, output_file, SegmentType::DataExtension, index)
+}
+
+pub fn remove_res_segment<R: Read + Seek, W: Write>(input_file: &mut R, output_file: &mut W, index: usize) -> Result<()> {
+    remove_segment(input_file, output_file, SegmentType::ReservedExtension, index)
+}
+
+/// Writes `input_file` to `output_file` without one segment. Its length table entry and its
+/// subheader and data are cut out and NUMx, HL and FL are rewritten; every other byte is kept as is.
+///
+/// Overflow fields (UDHOFL, XHDLOFL, UDOFL, IXSOFL, SXSOFL, TXSOFL) hold 1 based DES indices, so
+/// removing a DES moves down those that point past it. A DES that one of them points at holds
+/// TREs of that header or subheader and is not removed, the error naming the overflow field.
+pub fn remove_segment<R: Read + Seek, W: Write>(
+    input_file: &mut R,
+    output_file: &mut W,
+    segment: SegmentType,
+    index: usize,
+) -> Result<()> {
+    let file_header = FileHeader::read(input_file)?;
+    let lengths = file_header.lengths(segment);
+    let (count, subheader, data) = segment.fields();
+    let count_offset = file_header.offsets().count(segment);
+    let entry_offset = length_table_entry(
+        segment.as_str(),
+        count_offset + N::get_value(count),
+        lengths.len(),
+        index as u64,
+        subheader,
+        data,
+    )?;
+    let entry_length = N::get_value(subheader) + N::get_value(data);
+    let segment_start = file_header.segment_offsets().start(segment);
+    let removed_offset = segment_offset(segment.as_str(), segment_start, lengths, index as u64)?;
+    let removed_length = lengths[index].total();
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Removing {} segment {}: {} bytes at {}", segment.as_str(), index, removed_length, removed_offset);
+
+    let mut buf = Vec::new();
+    input_file.seek(SeekFrom::Start(0))?;
+    input_file.read_to_end(&mut buf)?;
+    if removed_offset + removed_length > buf.len() {
+        return Err(NitfError::Truncated {
+            offset: removed_offset,
+            length: removed_length,
+        });
+    }
+    if segment == SegmentType::DataExtension {
+        renumber_overflow(&mut buf, &file_header, index + 1)?;
+    }
+    //The segment comes after the length table, so cutting it first leaves entry_offset valid
+    buf.drain(removed_offset..removed_offset + removed_length);
+    buf.drain(entry_offset..entry_offset + entry_length);
+
+    let shorter = |field: NitfHeader21, value: usize, removed: usize| {
+        value.checked_sub(removed).ok_or_else(|| NitfError::MalformedField {
+            field: field.as_str(),
+            offset: N::fixed_offset(field),
+            raw: value.to_string(),
+        })
+    };
+    let fields = [
+        (count, count_offset, lengths.len() - 1),
+        (HL, N::fixed_offset(HL), shorter(HL, file_header.hl, entry_length)?),
+        (FL, N::fixed_offset(FL), shorter(FL, file_header.fl, entry_length + removed_length)?),
+    ];
+    for (field, offset, value) in fields {
+        buf[offset..offset + N::get_value(field)].copy_from_slice(&zero_padded(field, value)?);
+    }
+    output_file.write_all(&buf)?;
+    output_file.flush()?;
+    Ok(())
+}
 
 //Helper and Utility functions
-pub fn get_numdes(file: &File) -> usize {
-    read_int_from_file(
-        file,
-        N::get_offset(NUMDES, Some(file)),
-        N::get_value(NUMDES),
-    )
+pub fn get_numdes<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, nitf20::FileHeader::numdes, FileHeader::numdes)
+}
+
+/// NUMS, the number of graphics, or of symbols in a NITF 2.0 file.
+pub fn get_nums<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, nitf20::FileHeader::nums, FileHeader::nums)
+}
+
+pub fn get_numt<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, nitf20::FileHeader::numt, FileHeader::numt)
 }
 
-pub fn get_nums(file: &File) -> usize {
-    read_int_from_file(
-        file,
-        N::get_offset(NUMS, Some(file)),
-        N::get_value(NUMS),
-    )
+pub fn get_numres<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, nitf20::FileHeader::numres, FileHeader::numres)
 }
 
-pub fn get_numt(file: &File) -> usize {
-    read_int_from_file(
-        file,
-        N::get_offset(NUMT, Some(file)),
-        N::get_value(NUMT),
-    )
+pub fn get_num_images<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, nitf20::FileHeader::numi, FileHeader::numi)
 }
 
-pub fn get_hl(file: &File) -> usize {
-    read_int_from_file(file, N::get_offset(HL, None), N::get_value(HL))
+pub fn get_hl<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, |h| h.hl, |h| h.hl)
 }
 
-pub fn get_fl(file: &File) -> usize {
-    read_int_from_file(file, N::get_offset(FL, None), N::get_value(FL))
+pub fn get_fl<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+    header_value(reader, |h| h.fl, |h| h.fl)
 }
 
 //Private functions
-fn get_num_images(file: &File) -> usize {
-    read_int_from_file(file, N::get_offset(NUMI, None), N::get_value(NUMI))
-}
-
-fn get_hl_value_bytes(bytes: &Vec<u8>) -> usize {
-    #[cfg(all(debug_assertions, not(test)))]
-    println!("Getting HL value");
-    read_int_from_bytes(bytes, N::get_offset_bytes(HL, None), N::get_value(HL))
-}
-
-fn get_fl_value_bytes(bytes: &Vec<u8>) -> usize {
-    read_int_from_bytes(bytes, N::get_offset_bytes(FL, None), N::get_value(FL))
-}
-
-fn get_numdes_value_bytes(bytes: &Vec<u8>) -> usize {
-    read_int_from_bytes(
-        bytes,
-        N::get_offset_bytes(NUMDES, None),
-        N::get_value(NUMDES),
-    )
-}
-
-fn change_fl_value_bytes(bytes: &mut Vec<u8>, fl: usize) {
-    let fl_offset = N::get_offset_bytes(FL, None);
-    let fl_value = N::get_value(FL);
-    let fl_str = fl.to_string();
-    let fl_str_bytes = fl_str.as_bytes();
-    let fl_str_len = fl_str_bytes.len();
-    let fl_str_len = std::cmp::min(fl_str_len, fl_value);
-    bytes[fl_offset..fl_offset + fl_str_len].copy_from_slice(&fl_str_bytes[..fl_str_len]);
-}
-
-fn change_hl_value_bytes(bytes: &mut Vec<u8>, hl: usize) {
-    let hl_offset = N::get_offset_bytes(HL, None);
-    let hl_value = N::get_value(HL);
-    let hl_str = hl.to_string();
-    let hl_str_bytes = hl_str.as_bytes();
-    let hl_str_len = hl_str_bytes.len();
-    let hl_str_len = std::cmp::min(hl_str_len, hl_value);
-    bytes[hl_offset..hl_offset + hl_str_len].copy_from_slice(&hl_str_bytes[..hl_str_len]);
-}
-fn change_numdes_value_bytes(bytes: &mut Vec<u8>, numdes: usize) {
-    let numdes_offset = N::get_offset_bytes(NUMDES, None);
-    let numdes_value = N::get_value(NUMDES);
-    let numdes_str = numdes.to_string();
-    let numdes_str_bytes = numdes_str.as_bytes();
-    let numdes_str_len = numdes_str_bytes.len();
-    let numdes_str_len = std::cmp::min(numdes_str_len, numdes_value);
-    bytes[numdes_offset..numdes_offset + numdes_str_len]
-        .copy_from_slice(&numdes_str_bytes[..numdes_str_len]);
-}
-
-// pub fn add_des_bytes(nitf_bytes: &mut Vec<u8>, des_header_bytes: &Vec<u8>, des_bytes: &Vec<u8>) {
-//     //Change HL, FL, and NUMDES values
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("Getting old DES bytes");
-//     let mut hl_value = get_hl_value_bytes(nitf_bytes);
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("HL value: {}", hl_value);
-//     hl_value += des_header_bytes.len() + des_bytes.len();
-//     change_hl_value_bytes(nitf_bytes, hl_value);
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("HL value changed to {}", hl_value);
-//     let mut fl_value = get_fl_value_bytes(nitf_bytes);
-//     fl_value += des_header_bytes.len() + des_bytes.len();
-//     change_fl_value_bytes(nitf_bytes, fl_value);
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("FL value changed to {}", fl_value);
-//     let mut numdes = get_numdes_value_bytes(nitf_bytes);
-//     numdes += 1;
-//     change_numdes_value_bytes(nitf_bytes, numdes);
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("NUMDES value changed to {}", numdes);
-//     //Add DES header
-//     let des_header_offset = N::get_offset_bytes(NUMRES, Some(nitf_bytes));
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("DES header offset: {}", des_header_offset);
-//     nitf_bytes.splice(
-//         des_header_offset..des_header_offset + des_header_bytes.len(),
-//         des_header_bytes.iter().cloned(),
-//     );
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("DES header added");
-//     //Add DES data
-//     let des_data_end_offset = N::get_reserved_segment_start_bytes(nitf_bytes);
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("DES data end offset: {}", des_data_end_offset);
-//     nitf_bytes.splice(
-//         des_data_end_offset..des_data_end_offset + des_bytes.len(),
-//         des_bytes.iter().cloned(),
-//     );
-//     #[cfg(all(debug_assertions, not(test)))]
-//     println!("DES data added");
-// }
+/// Moves down every overflow field of `buf` that points past DES `removed` (1 based), and fails
+/// on one that points at it. See [`remove_segment`].
+fn renumber_overflow(buf: &mut [u8], file_header: &FileHeader, removed: usize) -> Result<()> {
+    let mut fields = overflow_fields(
+        buf,
+        file_header.hl,
+        &[("UDHDL", "UDHOFL", file_header.udhofl, &file_header.udhd), ("XHDL", "XHDLOFL", file_header.xhdlofl, &file_header.xhd)],
+    )?;
+    let subheader = |offset: usize, length: usize| {
+        buf.get(offset..offset + length).ok_or(NitfError::Truncated { offset, length })
+    };
+    let segments = file_header.segment_offsets();
+    let mut offset = segments.image_segments;
+    for length in &file_header.images {
+        let sub = ImageSubheader::from_bytes(subheader(offset, length.subheader)?)?;
+        let areas = [("UDIDL", "UDOFL", sub.udofl, &sub.udid), ("IXSHDL", "IXSOFL", sub.ixsofl, &sub.ixshd)];
+        fields.extend(overflow_fields(buf, offset + length.subheader, &areas)?);
+        offset += length.total();
+    }
+    let mut offset = segments.graphic_segments;
+    for length in &file_header.graphics {
+        let sub = GraphicSubheader::from_bytes(subheader(offset, length.subheader)?)?;
+        fields.extend(overflow_fields(buf, offset + length.subheader, &[("SXSHDL", "SXSOFL", sub.sxsofl, &sub.sxshd)])?);
+        offset += length.total();
+    }
+    let mut offset = segments.text_segments;
+    for length in &file_header.texts {
+        let sub = TextSubheader::from_bytes(subheader(offset, length.subheader)?)?;
+        fields.extend(overflow_fields(buf, offset + length.subheader, &[("TXSHDL", "TXSOFL", sub.txsofl, &sub.txshd)])?);
+        offset += length.total();
+    }
+    for (name, offset, ofl) in fields {
+        if ofl == removed {
+            return Err(NitfError::MalformedField { field: name, offset, raw: ofl.to_string() });
+        }
+        if ofl > removed {
+            buf[offset..offset + 3].copy_from_slice(format!("{:03}", ofl - 1).as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// The overflow fields of the extension areas that end a header or subheader at `end`, as
+/// (name, file offset, value), for the areas whose overflow field is there. `areas` are
+/// (length field, overflow field, overflow value, data) in file order. Each length field is
+/// checked against the data, so a field is never looked for in the wrong place.
+fn overflow_fields(
+    buf: &[u8],
+    end: usize,
+    areas: &[(&'static str, &'static str, usize, &Vec<u8>)],
+) -> Result<Vec<(&'static str, usize, usize)>> {
+    let mut fields = Vec::new();
+    let mut pos = end;
+    for &(length_name, ofl_name, ofl, data) in areas.iter().rev() {
+        let present = ofl != 0 || !data.is_empty();
+        let total = if present { data.len() + 3 } else { 0 };
+        let length_offset = pos.checked_sub(total + 5).ok_or(NitfError::Truncated { offset: 0, length: end })?;
+        if parse_int(length_name, length_offset, &buf[length_offset..length_offset + 5])? != total {
+            return Err(NitfError::MalformedField {
+                field: length_name,
+                offset: length_offset,
+                raw: String::from_utf8_lossy(&buf[length_offset..length_offset + 5]).to_string(),
+            });
+        }
+        if present {
+            fields.push((ofl_name, length_offset + 5, ofl));
+        }
+        pos = length_offset;
+    }
+    Ok(fields)
+}
 
+/// A value of the file header, read with the 2.0 layout or the 2.1 one as FHDR/FVER say. The
+/// functions that edit or copy segments only handle 2.1 and NSIF 1.0, and fail on 2.0 with
+/// `UnsupportedVersion` from [`FileHeader::read`].
+fn header_value<R: Read + Seek>(
+    reader: &mut R,
+    v20: fn(&nitf20::FileHeader) -> usize,
+    v21: fn(&FileHeader) -> usize,
+) -> Result<usize> {
+    match Nitf::get_version(reader)? {
+        NitfVersion::V02_00 => Ok(v20(&nitf20::FileHeader::read(reader)?)),
+        _ => Ok(v21(&FileHeader::read(reader)?)),
+    }
+}
+
+/// Reads a segment's subheader and data, which are contiguous in the file.
+fn read_segment<R: Read + Seek>(reader: &mut R, file_header: &FileHeader, segment: SegmentType, i: usize) -> Result<Vec<u8>> {
+    let lengths = file_header.lengths(segment);
+    let offset = file_header.segment_offsets().start(segment)
+        + lengths[..i].iter().map(SegmentLength::total).sum::<usize>();
+    read_bytes(reader, offset, lengths[i].total())
+}
+
+/// The parsed DESSHF and the data of an XML_DATA_CONTENT DES, followed by the file offsets of both.
+fn read_xml_des<R: Read + Seek>(reader: &mut R, i: usize) -> Result<(XmlDataContent, Vec<u8>, usize, usize)> {
+    let des = get_des_subheader(reader, i)?;
+    let file_header = FileHeader::read(reader)?;
+    let offset = file_header.segment_offsets().data_extension_segments
+        + file_header.des[..i].iter().map(SegmentLength::total).sum::<usize>();
+    if des.desid != XML_DATA_CONTENT {
+        return Err(NitfError::MalformedField {
+            field: NitfDesSubheader21::DESID.as_str(),
+            offset: offset + NitfDesSubheader21::fixed_offset(NitfDesSubheader21::DESID),
+            raw: des.desid,
+        });
+    }
+    let segment = read_segment(reader, &file_header, SegmentType::DataExtension, i)?;
+    let data_offset = offset + file_header.des[i].subheader;
+    Ok((
+        XmlDataContent::from_bytes(&des.desshf)?,
+        segment[file_header.des[i].subheader..].to_vec(),
+        data_offset - des.desshf.len(),
+        data_offset,
+    ))
+}
+
+/// Layout, offset and length (LI) of the data field of every image, for any version `Nitf` reads.
+fn image_data_fields<R: Read + Seek>(reader: &mut R) -> Result<Vec<(ImageLayout, usize, usize)>> {
+    let (start, lengths, layouts) = match Nitf::new(reader)? {
+        Nitf::V02_00(nitf) => (
+            nitf.segments.image_segments,
+            nitf.file_header.images,
+            nitf.images.iter().map(ImageLayout::from).collect::<Vec<_>>(),
+        ),
+        Nitf::V02_10(nitf) | Nitf::NSIF01_00(nitf) => (
+            nitf.segments.image_segments,
+            nitf.file_header.images,
+            nitf.images.iter().map(ImageLayout::from).collect(),
+        ),
+    };
+    let mut offset = start;
+    let mut fields = Vec::with_capacity(lengths.len());
+    for (length, layout) in lengths.iter().zip(layouts) {
+        fields.push((layout, offset + length.subheader, length.data));
+        offset += length.total();
+    }
+    Ok(fields)
+}
+
+/// The data field of JPEG 2000 image `i` past any block mask, and where that starts in the file.
+fn j2k_data_field<R: Read + Seek>(reader: &mut R, i: usize) -> Result<(ImageLayout, usize, Vec<u8>)> {
+    let fields = image_data_fields(reader)?;
+    let count = fields.len();
+    let (layout, offset, length) = fields.into_iter().nth(i).ok_or(NitfError::IndexOutOfRange {
+        segment: "Image",
+        index: i,
+        count,
+    })?;
+    if ImageCompression::from_ic(&layout.ic) != ImageCompression::Jpeg2000 {
+        return Err(NitfError::MalformedField { field: "IC", offset, raw: layout.ic });
+    }
+    let (start, data) = image_data(reader, &layout, offset, length)?;
+    Ok((layout, start, data))
+}
+
+/// The data field at `offset` past the block mask record of a masked image, and where that starts.
+fn image_data<R: Read + Seek>(reader: &mut R, layout: &ImageLayout, offset: usize, length: usize) -> Result<(usize, Vec<u8>)> {
+    let skip = if layout.is_masked() {
+        BlockMask::read(reader, offset, layout)?.imdatoff.min(length)
+    } else {
+        0
+    };
+    Ok((offset + skip, read_bytes(reader, offset + skip, length - skip)?))
+}
+
+/// IREP, ICORDS and IGEOLO of image `i`, with the file offset of IGEOLO.
+fn image_geolocation<R: Read + Seek>(reader: &mut R, i: usize) -> Result<(String, String, Option<String>, usize)> {
+    let (start, lengths, (irep, icords, igeolo, igeolo_offset)) = match Nitf::new(reader)? {
+        Nitf::V02_00(nitf) => {
+            let sub = &nitf.images[i];
+            let fields = (sub.irep.clone(), sub.icords.clone(), sub.igeolo.clone(), sub.igeolo_offset());
+            (nitf.segments.image_segments, nitf.file_header.images, fields)
+        }
+        Nitf::V02_10(nitf) | Nitf::NSIF01_00(nitf) => {
+            let sub = &nitf.images[i];
+            let fields = (sub.irep.clone(), sub.icords.clone(), sub.igeolo.clone(), sub.igeolo_offset());
+            (nitf.segments.image_segments, nitf.file_header.images, fields)
+        }
+    };
+    let offset = start + lengths[..i].iter().map(SegmentLength::total).sum::<usize>();
+    Ok((irep, icords, igeolo, offset + igeolo_offset))
+}
+
+/// Moves the offset of an error from parsing part of a file by `offset`, where that part starts.
+fn offset_error(e: NitfError, offset: usize) -> NitfError {
+    match e {
+        NitfError::Truncated { offset: o, length } => NitfError::Truncated { offset: offset + o, length },
+        NitfError::MalformedField { field, offset: o, raw } => NitfError::MalformedField { field, offset: offset + o, raw },
+        e => e,
+    }
+}
+
+/// The data of the TRE_OVERFLOW DES an overflow field (UDHOFL, XHDLOFL, UDOFL, IXSOFL, ...) points
+/// at. The field holds the DES's 1 based index, 0 meaning nothing overflowed. The DES must name
+/// the area that overflowed, `desoflw` (UDHD, XHD, UDID, IXSHD, ...), and `desitem`, the 1 based
+/// index of the segment it is in or 0 for the file header.
+fn read_overflow<R: Read + Seek>(
+    reader: &mut R,
+    file_header: &FileHeader,
+    ofl: usize,
+    desoflw: &str,
+    desitem: usize,
+) -> Result<Vec<u8>> {
+    use NitfDesSubheader21::{self as D, DESID, DESITEM, DESOFLW};
+    if ofl == 0 {
+        return Ok(Vec::new());
+    }
+    let i = ofl - 1;
+    let des = get_des_subheader(reader, i)?;
+    let offset = file_header.segment_offsets().data_extension_segments
+        + file_header.des[..i].iter().map(SegmentLength::total).sum::<usize>();
+    let mismatch = |field: NitfDesSubheader21, raw: String| NitfError::MalformedField {
+        field: field.as_str(),
+        offset: offset + D::fixed_offset(field),
+        raw,
+    };
+    if !des.is_overflow() {
+        return Err(mismatch(DESID, des.desid));
+    }
+    if des.desoflw.as_deref() != Some(desoflw) {
+        return Err(mismatch(DESOFLW, des.desoflw.unwrap_or_default()));
+    }
+    if des.desitem != Some(desitem) {
+        return Err(mismatch(DESITEM, des.desitem.unwrap_or_default().to_string()));
+    }
+    let segment = read_segment(reader, file_header, SegmentType::DataExtension, i)?;
+    Ok(segment[file_header.des[i].subheader..].to_vec())
+}
+
+/// The CCS location of the item at display level `alvl`, following its own attachment level
+/// down to the CCS (level 0). `offset` is the attachment level field being resolved, for errors.
+fn attachment_location<R: Read + Seek>(
+    reader: &mut R,
+    file_header: &FileHeader,
+    alvl: usize,
+    offset: usize,
+) -> Result<(isize, isize)> {
+    let mut items = HashMap::new();
+    for image in read_segments(reader, file_header, SegmentType::Image)? {
+        let subheader = ImageSubheader::from_bytes(&image.subheader)?;
+        items.insert(subheader.idlvl, (subheader.iloc, subheader.ialvl));
+    }
+    for graphic in read_segments(reader, file_header, SegmentType::Graphic)? {
+        let subheader = GraphicSubheader::from_bytes(&graphic.subheader)?;
+        items.insert(subheader.sdlvl, (subheader.sloc, subheader.salvl));
+    }
+    let mut location = (0, 0);
+    let mut level = alvl;
+    //Each item is visited at most once unless the attachments loop
+    for _ in 0..=items.len() {
+        if level == 0 {
+            return Ok(location);
+        }
+        let (loc, next) = items.get(&level).ok_or(NitfError::MalformedField {
+            field: NitfGraphicSubheader21::SALVL.as_str(),
+            offset,
+            raw: level.to_string(),
+        })?;
+        location = (location.0 + loc.0, location.1 + loc.1);
+        level = *next;
+    }
+    Err(NitfError::MalformedField {
+        field: NitfGraphicSubheader21::SALVL.as_str(),
+        offset,
+        raw: alvl.to_string(),
+    })
+}
+
+/// The display levels taken by the images (IDLVL) and graphics (SDLVL) of a file.
+fn display_levels<R: Read + Seek>(reader: &mut R) -> Result<HashSet<usize>> {
+    let file_header = FileHeader::read(reader)?;
+    let mut levels = HashSet::new();
+    for image in read_segments(reader, &file_header, SegmentType::Image)? {
+        levels.insert(ImageSubheader::from_bytes(&image.subheader)?.idlvl);
+    }
+    let sdlvl = NitfGraphicSubheader21::SDLVL;
+    let offset = NitfGraphicSubheader21::fixed_offset(sdlvl);
+    for graphic in read_segments(reader, &file_header, SegmentType::Graphic)? {
+        let bytes = graphic
+            .subheader
+            .get(offset..offset + sdlvl.length())
+            .ok_or(NitfError::Truncated { offset, length: sdlvl.length() })?;
+        levels.insert(parse_int(sdlvl.name(), offset, bytes)?);
+    }
+    Ok(levels)
+}
+
+/// Reads the segments of one type at `indices`, in that order.
+fn read_segments_index<R: Read + Seek>(input_file: &mut R, segment: SegmentType, indices: &[usize]) -> Result<Vec<Segment>> {
+    let file_header = FileHeader::read(input_file)?;
+    let lengths = file_header.lengths(segment);
+    let start = file_header.segment_offsets().start(segment);
+    let mut segments = Vec::with_capacity(indices.len());
+    for &index in indices {
+        let length = lengths.get(index).ok_or(NitfError::IndexOutOfRange {
+            segment: segment.as_str(),
+            index,
+            count: lengths.len(),
+        })?;
+        let offset = segment_offset(segment.as_str(), start, lengths, index as u64)?;
+        segments.push(Segment::new(
+            read_bytes(input_file, offset, length.subheader)?,
+            read_bytes(input_file, offset + length.subheader, length.data)?,
+        ));
+    }
+    Ok(segments)
+}
+
+/// Splices segments into a file in place: their length table entries go in front of entry
+/// `position` and their subheaders and data in front of segment `position`, then NUMx, HL and FL are updated.
+fn insert_segments<W: Read + Write + Seek>(
+    output_file: &mut W,
+    segment: SegmentType,
+    position: usize,
+    segments: &[Segment],
+) -> Result<()> {
+    let file_header = FileHeader::read(output_file)?;
+    let lengths = file_header.lengths(segment);
+    if position > lengths.len() {
+        return Err(NitfError::IndexOutOfRange {
+            segment: segment.as_str(),
+            index: position,
+            count: lengths.len(),
+        });
+    }
+    let (count, subheader, data) = segment.fields();
+    let count_offset = file_header.offsets().count(segment);
+    let entry_offset = count_offset
+        + N::get_value(count)
+        + position * (N::get_value(subheader) + N::get_value(data));
+    let data_offset = file_header.segment_offsets().start(segment)
+        + lengths[..position].iter().map(SegmentLength::total).sum::<usize>();
+
+    let mut entries = Vec::new();
+    let mut segment_bytes = Vec::new();
+    for s in segments {
+        entries.extend(zero_padded(subheader, s.subheader.len())?);
+        entries.extend(zero_padded(data, s.data.len())?);
+        segment_bytes.extend_from_slice(&s.subheader);
+        segment_bytes.extend_from_slice(&s.data);
+    }
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Inserting {} {} segments at {}", segments.len(), segment.as_str(), data_offset);
+
+    let mut buf = Vec::new();
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.read_to_end(&mut buf)?;
+    if data_offset > buf.len() {
+        return Err(NitfError::Truncated {
+            offset: data_offset,
+            length: 0,
+        });
+    }
+    //Segments first, they come after the length table so entry_offset stays valid
+    buf.splice(data_offset..data_offset, segment_bytes.iter().cloned());
+    buf.splice(entry_offset..entry_offset, entries.iter().cloned());
+
+    let fields = [
+        (count, count_offset, lengths.len() + segments.len()),
+        (HL, N::fixed_offset(HL), file_header.hl + entries.len()),
+        (FL, N::fixed_offset(FL), file_header.fl + entries.len() + segment_bytes.len()),
+    ];
+    for (field, offset, value) in fields {
+        buf[offset..offset + N::get_value(field)].copy_from_slice(&zero_padded(field, value)?);
+    }
+    output_file.seek(SeekFrom::Start(0))?;
+    output_file.write_all(&buf)?;
+    output_file.flush()?;
+    Ok(())
+}
+
+/// Formats `value` as a zero padded BCS-N field, failing if it has too many digits.
+fn zero_padded(field: NitfHeader21, value: usize) -> Result<Vec<u8>> {
+    let digits = value.to_string();
+    let width = N::get_value(field);
+    if digits.len() > width {
+        return Err(NitfError::Overflow {
+            field: field.as_str(),
+            value,
+        });
+    }
+    let mut buf = vec![b'0'; width];
+    buf[width - digits.len()..].copy_from_slice(digits.as_bytes());
+    Ok(buf)
+}
@@ -1,74 +1,87 @@
 use std::ffi::CStr;
 use std::fs::File;
 use std::os::raw::c_char;
-use crate::modify::parser::nitf21::{NitfHeader21 as N, NitfHeader21::*};
-use crate::modify::parser::file_ops::read_int_from_file;
 use super::core;
+use super::error::Result;
 
 #[no_mangle]
 pub extern "C" fn extract_jp2(path_ptr: *const c_char, out_path_ptr: *const c_char) {
-    //String Manipulation and File Handling
-    let outpath_cstr = unsafe { CStr::from_ptr(out_path_ptr as *mut c_char) };
-    let outpath = outpath_cstr
-        .to_str()
-        .expect("Failed to convert the output file path to a string");
-    let file = open_file_from_cstr(path_ptr);
-    core::extract_jp2(&file, outpath);
+    let result = cstr_path(out_path_ptr)
+        .and_then(|outpath| open_file_from_cstr(path_ptr).and_then(|mut file| core::extract_jp2(&mut file, outpath)));
+    report(result, ());
+}
+
+#[no_mangle]
+pub extern "C" fn extract_graphics(path_ptr: *const c_char, out_path_ptr: *const c_char) {
+    let result = cstr_path(out_path_ptr)
+        .and_then(|outpath| open_file_from_cstr(path_ptr).and_then(|mut file| core::extract_graphics(&mut file, outpath)));
+    report(result, ());
 }
 
 #[no_mangle]
 pub extern "C" fn extract_des(path_ptr: *const c_char, out_path_ptr: *const c_char) {
-    //String Manipulation and File Handling
-    let outpath_cstr = unsafe { CStr::from_ptr(out_path_ptr as *mut c_char) };
-    let outpath = outpath_cstr
-        .to_str()
-        .expect("Failed to convert the output file path to a string");
-    let file = open_file_from_cstr(path_ptr);
-    core::extract_des(&file, outpath);
+    let result = cstr_path(out_path_ptr)
+        .and_then(|outpath| open_file_from_cstr(path_ptr).and_then(|mut file| core::extract_des(&mut file, outpath)));
+    report(result, ());
+}
+
+#[no_mangle]
+pub extern "C" fn extract_res(path_ptr: *const c_char, out_path_ptr: *const c_char) {
+    let result = cstr_path(out_path_ptr)
+        .and_then(|outpath| open_file_from_cstr(path_ptr).and_then(|mut file| core::extract_res(&mut file, outpath)));
+    report(result, ());
 }
 
 #[no_mangle]
 pub extern "C" fn get_version(path_ptr: *const c_char) {
-    let file = open_file_from_cstr(path_ptr);
-    let (fhdr, ver) = core::get_version(&file);
-    println!("NITF Type: {}{}", fhdr, ver);
+    let version = open_file_from_cstr(path_ptr).and_then(|mut file| core::get_version(&mut file));
+    if let Some((fhdr, ver)) = report(version.map(Some), None) {
+        println!("File Type: {}{}", fhdr, ver);
+    }
 }
 
 #[no_mangle]
 pub extern "C" fn get_num_images_from_file(path_ptr: *const c_char) -> usize {
-    let file = open_file_from_cstr(path_ptr);
-    read_int_from_file(&file, N::get_offset(NUMI, None), N::get_value(NUMI))
+    report(open_file_from_cstr(path_ptr).and_then(|mut file| core::get_num_images(&mut file)), usize::MAX)
 }
 
 #[no_mangle]
 pub extern "C" fn get_num_graphics_from_file(path_ptr: *const c_char) -> usize {
-    let file = open_file_from_cstr(path_ptr);
-    read_int_from_file(&file, N::get_offset(NUMS, Some(&file)), N::get_value(NUMS))
+    report(open_file_from_cstr(path_ptr).and_then(|mut file| core::get_nums(&mut file)), usize::MAX)
 }
 
 #[no_mangle]
 pub extern "C" fn get_num_text_files_from_file(path_ptr: *const c_char) -> usize {
-    let file = open_file_from_cstr(path_ptr);
-    read_int_from_file(&file, N::get_offset(NUMT, Some(&file)), N::get_value(NUMT))
+    report(open_file_from_cstr(path_ptr).and_then(|mut file| core::get_numt(&mut file)), usize::MAX)
 }
 
 #[no_mangle]
 pub extern "C" fn get_num_des_from_file(path_ptr: *const c_char) -> usize {
-    let file = open_file_from_cstr(path_ptr);
-    read_int_from_file(&file, N::get_offset(NUMDES, Some(&file)), N::get_value(NUMDES))
+    report(open_file_from_cstr(path_ptr).and_then(|mut file| core::get_numdes(&mut file)), usize::MAX)
 }
 
 #[no_mangle]
 pub extern "C" fn get_num_res_from_file(path_ptr: *const c_char) -> usize {
-    let file = open_file_from_cstr(path_ptr);
-    read_int_from_file(&file, N::get_offset(NUMRES, Some(&file)), N::get_value(NUMRES))
+    report(open_file_from_cstr(path_ptr).and_then(|mut file| core::get_numres(&mut file)), usize::MAX)
 }
 
+fn open_file_from_cstr(path_ptr: *const c_char) -> Result<File> {
+    Ok(File::open(cstr_path(path_ptr)?)?)
+}
 
-fn open_file_from_cstr(path_ptr: *const c_char) -> File {
-    let filepath_cstr = unsafe { CStr::from_ptr(path_ptr as *mut c_char) };
-    let filepath = filepath_cstr
+//A path that isn't UTF-8 is reported like any other I/O error instead of panicking
+fn cstr_path<'a>(path_ptr: *const c_char) -> Result<&'a str> {
+    let path_cstr = unsafe { CStr::from_ptr(path_ptr as *mut c_char) };
+    path_cstr
         .to_str()
-        .expect("Failed to convert the file path to a string");
-    File::open(filepath).expect("Failed to open the file")
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e).into())
+}
+
+//Errors cannot cross the C boundary, so they are printed and a fallback value
+//(usize::MAX for the counts) is returned instead.
+fn report<T>(result: Result<T>, fallback: T) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("nitf-gnr: {}", e);
+        fallback
+    })
 }
@@ -0,0 +1,75 @@
+use std::fmt;
+
+pub type Result<T> = std::result::Result<T, NitfError>;
+
+#[derive(Debug)]
+pub enum NitfError {
+    /// Reading from or writing to the underlying file failed.
+    Io(std::io::Error),
+    /// FHDR/FVER name a format this crate cannot parse, e.g. "NITF01.10".
+    UnsupportedVersion(String),
+    /// A field could not be decoded, usually a numeric field holding non-digits.
+    MalformedField {
+        field: &'static str,
+        offset: usize,
+        raw: String,
+    },
+    /// A value does not fit in the width of the field it has to be written to.
+    Overflow { field: &'static str, value: usize },
+    /// A segment index is past the number of segments of that type in the file.
+    IndexOutOfRange {
+        segment: &'static str,
+        index: usize,
+        count: usize,
+    },
+    /// The file ends before a field of `length` bytes at `offset` could be read.
+    Truncated { offset: usize, length: usize },
+}
+
+impl fmt::Display for NitfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NitfError::Io(e) => write!(f, "I/O error: {}", e),
+            NitfError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported NITF version: {}", version)
+            }
+            NitfError::MalformedField { field, offset, raw } => write!(
+                f,
+                "Malformed {} field at offset {}: {:?}",
+                field, offset, raw
+            ),
+            NitfError::Overflow { field, value } => {
+                write!(f, "{} value {} is too large for the field", field, value)
+            }
+            NitfError::IndexOutOfRange {
+                segment,
+                index,
+                count,
+            } => write!(
+                f,
+                "{} index {} is out of range, the file has {} (they are 0 indexed)",
+                segment, index, count
+            ),
+            NitfError::Truncated { offset, length } => write!(
+                f,
+                "File is truncated, could not read {} bytes at offset {}",
+                length, offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NitfError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NitfError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for NitfError {
+    fn from(e: std::io::Error) -> Self {
+        NitfError::Io(e)
+    }
+}
@@ -0,0 +1,344 @@
+use crate::modify::error::{NitfError, Result};
+use crate::modify::image::{DecodedImage, ImageLayout, PixelData};
+use crate::modify::parser::nitf21::ImageBand;
+
+/// File format written by `core::export_image`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// Baseline TIFF, uncompressed in a single strip.
+    Tiff,
+    /// PGM for one band and PPM for three, in the binary (P5/P6) form.
+    Pnm,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Tiff => "tif",
+            ImageFormat::Pnm => "pnm",
+        }
+    }
+}
+
+/// Which bands of the image go into the exported picture.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub enum BandSelection {
+    /// The bands with IREPBAND R, G and B if there are all three, otherwise the first band.
+    #[default]
+    Auto,
+    /// One band, drawn through its LUTs if it has one (grey) or three (colour).
+    Mono(usize),
+    /// Three bands as red, green and blue, e.g. a false colour composite of a multispectral image.
+    Rgb([usize; 3]),
+}
+
+/// How pixel values are mapped to the output range. LUT output is never stretched.
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+pub enum Stretch {
+    /// The full range of ABPP bits maps to the output range, so 11 or 12 bit data comes out
+    /// dark. Floating point and complex data have no such range and are stretched `Linear`.
+    #[default]
+    None,
+    /// The lowest and highest value of the selected bands map to black and white.
+    Linear,
+    /// Like `Linear` but between two percentiles (0 to 100), clipping outliers.
+    Percentile { low: f64, high: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportOptions {
+    pub format: ImageFormat,
+    /// Write 16 bit samples instead of 8 bit.
+    pub sixteen_bit: bool,
+    pub bands: BandSelection,
+    pub stretch: Stretch,
+}
+
+impl ExportOptions {
+    /// 8 bit, automatic band selection and no stretch.
+    pub fn new(format: ImageFormat) -> ExportOptions {
+        ExportOptions {
+            format,
+            sixteen_bit: false,
+            bands: BandSelection::Auto,
+            stretch: Stretch::None,
+        }
+    }
+}
+
+/// Grey or RGB samples ready to be written, each in 0..=`max`.
+struct Raster {
+    width: usize,
+    height: usize,
+    channels: usize,
+    max: u16,
+    samples: Vec<u16>,
+}
+
+/// Renders a decoded image to the bytes of a picture file. `bands` are the image subheader's
+/// band fields, giving IREPBAND and the LUTs.
+pub fn export(image: &DecodedImage, layout: &ImageLayout, bands: &[ImageBand], options: &ExportOptions) -> Result<Vec<u8>> {
+    let raster = render(image, layout, bands, options)?;
+    Ok(match options.format {
+        ImageFormat::Png => png(&raster),
+        ImageFormat::Tiff => tiff(&raster),
+        ImageFormat::Pnm => pnm(&raster),
+    })
+}
+
+fn render(image: &DecodedImage, layout: &ImageLayout, bands: &[ImageBand], options: &ExportOptions) -> Result<Raster> {
+    let max = if options.sixteen_bit { u16::MAX } else { u8::MAX as u16 };
+    let check = |band: usize| match band < image.nbands {
+        true => Ok(band),
+        false => Err(NitfError::IndexOutOfRange { segment: "Band", index: band, count: image.nbands }),
+    };
+    let selected = match &options.bands {
+        BandSelection::Auto => {
+            let find = |rep: &str| bands.iter().position(|b| b.irepband.trim() == rep);
+            match (find("R"), find("G"), find("B")) {
+                (Some(r), Some(g), Some(b)) => vec![r, g, b],
+                _ => vec![0],
+            }
+        }
+        BandSelection::Mono(band) => vec![*band],
+        BandSelection::Rgb(rgb) => rgb.to_vec(),
+    };
+    let selected = selected.into_iter().map(check).collect::<Result<Vec<_>>>()?;
+    let values = &samples(&image.pixels);
+    let pixel_count = image.nrows * image.ncols;
+    let band_values = |band: usize| (0..pixel_count).map(move |p| values[p * image.nbands + band]);
+
+    //A single band with LUTs is drawn through them, one LUT for grey and three for colour
+    let luts = match selected[..] {
+        [band] => bands.get(band).map(|b| &b.lutd[..]).filter(|l| l.len() == 1 || l.len() == 3),
+        _ => None,
+    };
+    if let Some(luts) = luts {
+        let mut samples = Vec::with_capacity(pixel_count * luts.len());
+        for value in band_values(selected[0]) {
+            let index = value.max(0.0) as usize;
+            for lut in luts {
+                let entry = lut.get(index).ok_or(NitfError::IndexOutOfRange {
+                    segment: "LUT",
+                    index,
+                    count: lut.len(),
+                })?;
+                samples.push(*entry as u16 * (max / u8::MAX as u16));
+            }
+        }
+        return Ok(Raster { width: image.ncols, height: image.nrows, channels: luts.len(), max, samples });
+    }
+
+    let integer = matches!(layout.pvtype.as_str(), "INT" | "SI" | "B");
+    let (low, high) = match options.stretch {
+        Stretch::None if integer => {
+            let bits = if layout.pvtype == "B" { 1 } else { layout.abpp.clamp(1, 64) } as i32;
+            match layout.pvtype.as_str() {
+                "SI" => (-(2f64.powi(bits - 1)), 2f64.powi(bits - 1) - 1.0),
+                _ => (0.0, 2f64.powi(bits) - 1.0),
+            }
+        }
+        Stretch::None | Stretch::Linear => percentiles(selected.iter().flat_map(|&b| band_values(b)).collect(), 0.0, 100.0),
+        Stretch::Percentile { low, high } => percentiles(selected.iter().flat_map(|&b| band_values(b)).collect(), low, high),
+    };
+    let scale = |v: f64| match high > low {
+        true => ((v - low) / (high - low)).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+    let mut samples = Vec::with_capacity(pixel_count * selected.len());
+    for p in 0..pixel_count {
+        for &band in &selected {
+            samples.push((scale(values[p * image.nbands + band]) * max as f64).round() as u16);
+        }
+    }
+    Ok(Raster { width: image.ncols, height: image.nrows, channels: selected.len(), max, samples })
+}
+
+/// Every pixel value as f64, complex values by their magnitude.
+fn samples(pixels: &PixelData) -> Vec<f64> {
+    match pixels {
+        PixelData::U8(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::U16(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::U32(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::U64(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::I8(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::I16(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::I32(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::I64(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::F32(v) => v.iter().map(|&x| x as f64).collect(),
+        PixelData::F64(v) => v.clone(),
+        PixelData::Complex32(v) => v.iter().map(|&(re, im)| (re as f64).hypot(im as f64)).collect(),
+    }
+}
+
+/// The `low` and `high` percentiles of the finite values, nearest rank.
+fn percentiles(mut values: Vec<f64>, low: f64, high: f64) -> (f64, f64) {
+    values.retain(|v| v.is_finite());
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = |p: f64| values[((p.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f64).round()) as usize];
+    (rank(low), rank(high))
+}
+
+/// The samples as bytes, big or little endian for 16 bit.
+fn sample_bytes(raster: &Raster, big_endian: bool) -> Vec<u8> {
+    if raster.max <= u8::MAX as u16 {
+        return raster.samples.iter().map(|&s| s as u8).collect();
+    }
+    raster
+        .samples
+        .iter()
+        .flat_map(|&s| if big_endian { s.to_be_bytes() } else { s.to_le_bytes() })
+        .collect()
+}
+
+fn pnm(raster: &Raster) -> Vec<u8> {
+    let magic = if raster.channels == 1 { "P5" } else { "P6" };
+    let mut out = format!("{}\n{} {}\n{}\n", magic, raster.width, raster.height, raster.max).into_bytes();
+    out.extend(sample_bytes(raster, true));
+    out
+}
+
+fn png(raster: &Raster) -> Vec<u8> {
+    let depth = if raster.max <= u8::MAX as u16 { 8 } else { 16 };
+    let colour_type = if raster.channels == 1 { 0 } else { 2 };
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend((raster.width as u32).to_be_bytes());
+    ihdr.extend((raster.height as u32).to_be_bytes());
+    //Bit depth, colour type, then deflate compression, adaptive filtering and no interlace
+    ihdr.extend([depth, colour_type, 0, 0, 0]);
+    //Every scanline starts with its filter type, 0 being none
+    let bytes = sample_bytes(raster, true);
+    let stride = raster.width * raster.channels * depth as usize / 8;
+    let mut scanlines = Vec::with_capacity(bytes.len() + raster.height);
+    for row in bytes.chunks(stride.max(1)).take(raster.height) {
+        scanlines.push(0);
+        scanlines.extend(row);
+    }
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut out, b"IHDR", &ihdr);
+    png_chunk(&mut out, b"IDAT", &zlib_stored(&scanlines));
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend((data.len() as u32).to_be_bytes());
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.extend(kind);
+    out.extend(data);
+    out.extend(crc.finalize().to_be_bytes());
+}
+
+/// A zlib stream of uncompressed deflate blocks, which every PNG reader accepts.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        out.extend((block.len() as u16).to_le_bytes());
+        out.extend((!(block.len() as u16)).to_le_bytes());
+        out.extend(block);
+    }
+    //Adler-32 of the uncompressed data
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend(((b << 16) | a).to_be_bytes());
+    out
+}
+
+fn tiff(raster: &Raster) -> Vec<u8> {
+    let bits = if raster.max <= u8::MAX as u16 { 8u16 } else { 16 };
+    let shorts = |values: &[u16]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>();
+    let long = |value: usize| (value as u32).to_le_bytes().to_vec();
+    let fields = vec![
+        TiffField { tag: 256, kind: TiffField::LONG, values: long(raster.width) },
+        TiffField { tag: 257, kind: TiffField::LONG, values: long(raster.height) },
+        TiffField { tag: 258, kind: TiffField::SHORT, values: shorts(&vec![bits; raster.channels]) },
+        //No compression
+        TiffField { tag: 259, kind: TiffField::SHORT, values: shorts(&[1]) },
+        //BlackIsZero or RGB
+        TiffField { tag: 262, kind: TiffField::SHORT, values: shorts(&[if raster.channels == 1 { 1 } else { 2 }]) },
+        TiffField { tag: 277, kind: TiffField::SHORT, values: shorts(&[raster.channels as u16]) },
+        TiffField { tag: 278, kind: TiffField::LONG, values: long(raster.height) },
+        //X and Y resolution 1/1, with no absolute unit
+        TiffField { tag: 282, kind: TiffField::RATIONAL, values: [long(1), long(1)].concat() },
+        TiffField { tag: 283, kind: TiffField::RATIONAL, values: [long(1), long(1)].concat() },
+        TiffField { tag: 296, kind: TiffField::SHORT, values: shorts(&[1]) },
+    ];
+    tiff_file(fields, &sample_bytes(raster, false))
+}
+
+/// One field of a TIFF IFD, with its values already little endian.
+pub(crate) struct TiffField {
+    pub tag: u16,
+    pub kind: u16,
+    pub values: Vec<u8>,
+}
+
+impl TiffField {
+    pub const SHORT: u16 = 3;
+    pub const LONG: u16 = 4;
+    pub const RATIONAL: u16 = 5;
+    pub const DOUBLE: u16 = 12;
+
+    fn count(&self) -> usize {
+        let size = match self.kind {
+            TiffField::SHORT => 2,
+            TiffField::LONG => 4,
+            TiffField::RATIONAL | TiffField::DOUBLE => 8,
+            _ => 1,
+        };
+        self.values.len() / size
+    }
+}
+
+/// A little endian TIFF with one IFD and the pixels in a single strip. StripOffsets and
+/// StripByteCounts are added here, the other fields come from the caller.
+pub(crate) fn tiff_file(mut fields: Vec<TiffField>, strip: &[u8]) -> Vec<u8> {
+    fields.push(TiffField { tag: 273, kind: TiffField::LONG, values: vec![0; 4] });
+    fields.push(TiffField { tag: 279, kind: TiffField::LONG, values: (strip.len() as u32).to_le_bytes().to_vec() });
+    fields.sort_by_key(|f| f.tag);
+    //Header, the IFD, then the values that do not fit in an entry, then the strip
+    let mut extra = 8 + 2 + fields.len() * 12 + 4;
+    let strip_offset = extra + fields.iter().filter(|f| f.values.len() > 4).map(|f| f.values.len()).sum::<usize>();
+    let mut out = b"II\x2a\x00".to_vec();
+    out.extend(8u32.to_le_bytes());
+    out.extend((fields.len() as u16).to_le_bytes());
+    let mut values: Vec<u8> = Vec::new();
+    for field in &mut fields {
+        if field.tag == 273 {
+            field.values = (strip_offset as u32).to_le_bytes().to_vec();
+        }
+        out.extend(field.tag.to_le_bytes());
+        out.extend(field.kind.to_le_bytes());
+        out.extend((field.count() as u32).to_le_bytes());
+        if field.values.len() > 4 {
+            out.extend((extra as u32).to_le_bytes());
+            extra += field.values.len();
+            values.extend(&field.values);
+        } else {
+            //Values that fit sit at the start of the entry's last four bytes
+            let mut inline = field.values.clone();
+            inline.resize(4, 0);
+            out.extend(inline);
+        }
+    }
+    //No next IFD
+    out.extend(0u32.to_le_bytes());
+    out.extend(values);
+    out.extend(strip);
+    out
+}
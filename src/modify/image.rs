@@ -0,0 +1,436 @@
+use crate::modify::error::{NitfError, Result};
+use crate::modify::parser::file_ops::read_bytes;
+use crate::modify::parser::{nitf20, nitf21};
+use std::io::{Read, Seek};
+
+/// BMRnBNDm and TMRnBNDm value of a block that is not recorded / has no pad pixels.
+const NOT_RECORDED: u32 = 0xFFFFFFFF;
+
+/// The image subheader fields that say how the pixels of an image segment are laid out.
+/// Built from either a 2.0 or a 2.1 subheader, which agree on all of them.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ImageLayout {
+    pub nrows: usize,
+    pub ncols: usize,
+    pub nbands: usize,
+    pub pvtype: String,
+    pub abpp: usize,
+    pub pjust: String,
+    pub ic: String,
+    pub imode: String,
+    pub nbpr: usize,
+    pub nbpc: usize,
+    pub nppbh: usize,
+    pub nppbv: usize,
+    pub nbpp: usize,
+}
+
+impl From<&nitf21::ImageSubheader> for ImageLayout {
+    fn from(sub: &nitf21::ImageSubheader) -> ImageLayout {
+        ImageLayout {
+            nrows: sub.nrows,
+            ncols: sub.ncols,
+            nbands: sub.bands.len(),
+            pvtype: sub.pvtype.clone(),
+            abpp: sub.abpp,
+            pjust: sub.pjust.clone(),
+            ic: sub.ic.clone(),
+            imode: sub.imode.clone(),
+            nbpr: sub.nbpr,
+            nbpc: sub.nbpc,
+            nppbh: sub.nppbh,
+            nppbv: sub.nppbv,
+            nbpp: sub.nbpp,
+        }
+    }
+}
+
+impl From<&nitf20::ImageSubheader> for ImageLayout {
+    fn from(sub: &nitf20::ImageSubheader) -> ImageLayout {
+        ImageLayout {
+            nrows: sub.nrows,
+            ncols: sub.ncols,
+            nbands: sub.bands.len(),
+            pvtype: sub.pvtype.clone(),
+            abpp: sub.abpp,
+            pjust: sub.pjust.clone(),
+            ic: sub.ic.clone(),
+            imode: sub.imode.clone(),
+            nbpr: sub.nbpr,
+            nbpc: sub.nbpc,
+            nppbh: sub.nppbh,
+            nppbv: sub.nppbv,
+            nbpp: sub.nbpp,
+        }
+    }
+}
+
+impl ImageLayout {
+    /// Columns per block. NPPBH 0 means a single block as wide as the image.
+    pub fn block_width(&self) -> usize {
+        if self.nppbh == 0 { self.ncols } else { self.nppbh }
+    }
+
+    /// Rows per block. NPPBV 0 means a single block as tall as the image.
+    pub fn block_height(&self) -> usize {
+        if self.nppbv == 0 { self.nrows } else { self.nppbv }
+    }
+
+    /// Bytes taken by one block of one band (IMODE S) or of all bands (IMODE B, P and R).
+    /// Blocks start on a byte boundary, so the last byte may be part padding.
+    pub fn block_length(&self) -> usize {
+        let bands = if self.imode == "S" { 1 } else { self.nbands };
+        (self.block_width() * self.block_height() * bands * self.nbpp).div_ceil(8)
+    }
+
+    /// Number of blocks in the data field, counting each band separately for IMODE S.
+    pub fn block_count(&self) -> usize {
+        let bands = if self.imode == "S" { self.nbands } else { 1 };
+        self.nbpr * self.nbpc * bands
+    }
+
+    /// Whether the data field starts with a block mask record (IC M1, M3, M8, NM, ...).
+    pub fn is_masked(&self) -> bool {
+        self.ic.starts_with('M') || self.ic == "NM"
+    }
+
+    /// Where each block starts in the data field, in BMRnBNDm order: blocks left to right and
+    /// top to bottom, then band by band for IMODE S. `None` for blocks the mask leaves out.
+    pub fn block_offsets(&self, mask: Option<&BlockMask>) -> Vec<Option<usize>> {
+        let start = mask.map_or(0, |m| m.imdatoff);
+        match mask {
+            Some(mask) if !mask.bmr.is_empty() => mask.bmr.iter().map(|b| b.map(|b| start + b)).collect(),
+            _ => (0..self.block_count()).map(|k| Some(start + k * self.block_length())).collect(),
+        }
+    }
+}
+
+/// The block mask and pad pixel mask record at the start of a masked image's data field.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct BlockMask {
+    /// Offset of the first block from the start of the data field.
+    pub imdatoff: usize,
+    pub bmrlnth: usize,
+    pub tmrlnth: usize,
+    pub tpxcdlnth: usize,
+    /// The pad pixel value, TPXCDLNTH bits. `None` when TPXCDLNTH is 0.
+    pub tpxcd: Option<u64>,
+    /// BMRnBNDm, the offset of each block from IMDATOFF, `None` for blocks not recorded.
+    /// Empty when BMRLNTH is 0, in which case every block is present.
+    pub bmr: Vec<Option<usize>>,
+    /// TMRnBNDm, `None` for blocks without pad pixels. Empty when TMRLNTH is 0.
+    pub tmr: Vec<Option<usize>>,
+}
+
+impl BlockMask {
+    /// Reads the mask record of the data field at `offset` without reading any block data.
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, layout: &ImageLayout) -> Result<BlockMask> {
+        let fixed = read_bytes(reader, offset, 10)?;
+        let length = BlockMask::length(&fixed, layout);
+        BlockMask::from_bytes(&read_bytes(reader, offset, length)?, layout)
+    }
+
+    pub fn from_bytes(bytes: &[u8], layout: &ImageLayout) -> Result<BlockMask> {
+        let mut pos = 0;
+        let mut take = |length: usize| match bytes.get(pos..pos + length) {
+            Some(field) => {
+                pos += length;
+                Ok(field.iter().fold(0u64, |v, &b| (v << 8) | b as u64))
+            }
+            None => Err(NitfError::Truncated { offset: pos, length }),
+        };
+        let mut mask = BlockMask {
+            imdatoff: take(4)? as usize,
+            bmrlnth: take(2)? as usize,
+            tmrlnth: take(2)? as usize,
+            tpxcdlnth: take(2)? as usize,
+            ..Default::default()
+        };
+        if mask.tpxcdlnth > 0 {
+            mask.tpxcd = Some(take(mask.tpxcdlnth.div_ceil(8))?);
+        }
+        let recorded = |v: u64| if v as u32 == NOT_RECORDED { None } else { Some(v as usize) };
+        if mask.bmrlnth != 0 {
+            mask.bmr = (0..layout.block_count()).map(|_| take(4).map(recorded)).collect::<Result<_>>()?;
+        }
+        if mask.tmrlnth != 0 {
+            mask.tmr = (0..layout.block_count()).map(|_| take(4).map(recorded)).collect::<Result<_>>()?;
+        }
+        Ok(mask)
+    }
+
+    /// Length of the whole mask record, worked out from its first ten bytes.
+    fn length(fixed: &[u8], layout: &ImageLayout) -> usize {
+        let field = |at: usize| u16::from_be_bytes([fixed[at], fixed[at + 1]]) as usize;
+        let (bmrlnth, tmrlnth, tpxcdlnth) = (field(4), field(6), field(8));
+        let tables = [bmrlnth, tmrlnth].iter().filter(|&&l| l != 0).count();
+        10 + tpxcdlnth.div_ceil(8) + tables * 4 * layout.block_count()
+    }
+}
+
+/// Pixel values of a decoded image, typed by PVTYPE and NBPP.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PixelData {
+    /// INT of up to 8 bits, and B (bi-level, 0 or 1).
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+    I8(Vec<i8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    /// C with NBPP 64, as (real, imaginary).
+    Complex32(Vec<(f32, f32)>),
+}
+
+impl PixelData {
+    pub fn len(&self) -> usize {
+        match self {
+            PixelData::U8(v) => v.len(),
+            PixelData::U16(v) => v.len(),
+            PixelData::U32(v) => v.len(),
+            PixelData::U64(v) => v.len(),
+            PixelData::I8(v) => v.len(),
+            PixelData::I16(v) => v.len(),
+            PixelData::I32(v) => v.len(),
+            PixelData::I64(v) => v.len(),
+            PixelData::F32(v) => v.len(),
+            PixelData::F64(v) => v.len(),
+            PixelData::Complex32(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The pixels of one image segment, band interleaved by pixel: band `b` of the pixel at
+/// (`row`, `col`) is at `(row * ncols + col) * nbands + b`. Block padding is dropped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedImage {
+    pub nrows: usize,
+    pub ncols: usize,
+    pub nbands: usize,
+    pub pixels: PixelData,
+}
+
+impl DecodedImage {
+    pub fn index(&self, row: usize, col: usize, band: usize) -> usize {
+        (row * self.ncols + col) * self.nbands + band
+    }
+}
+
+/// Decodes the data field of an uncompressed image (IC NC or NM) into pixels.
+///
+/// Handles every IMODE, blocking with padded edge blocks, NBPP that is not a whole number of
+/// bytes, and PJUST for INT and SI values narrower than NBPP (ABPP). For NM the blocks are
+/// found through the block mask, and blocks it leaves out are filled with the pad pixel value
+/// (or 0 without one). Offsets in errors are relative to the start of `data`.
+pub fn decode_uncompressed(layout: &ImageLayout, data: &[u8]) -> Result<DecodedImage> {
+    let mask = match layout.ic.as_str() {
+        "NC" => None,
+        "NM" => Some(BlockMask::from_bytes(data, layout)?),
+        ic => return Err(malformed("IC", ic)),
+    };
+    let count = check_sizes(layout, data, mask.as_ref())?;
+    let pad = mask.as_ref().and_then(|m| m.tpxcd).unwrap_or(0);
+    let values = unpack(layout, data, &layout.block_offsets(mask.as_ref()), pad, count)?;
+    Ok(DecodedImage {
+        nrows: layout.nrows,
+        ncols: layout.ncols,
+        nbands: layout.nbands,
+        pixels: convert(layout, values)?,
+    })
+}
+
+/// Checks the sizes the subheader gives before anything is allocated from them and returns the
+/// number of values, NROWS x NCOLS x NBANDS. Every block has to be in `data` unless a block
+/// mask says which ones are, so a short data field is `Truncated` where it ends.
+fn check_sizes(layout: &ImageLayout, data: &[u8], mask: Option<&BlockMask>) -> Result<usize> {
+    let count = layout
+        .nrows
+        .checked_mul(layout.ncols)
+        .and_then(|n| n.checked_mul(layout.nbands))
+        .ok_or_else(|| {
+            let size = format!("{} rows, {} columns, {} bands", layout.nrows, layout.ncols, layout.nbands);
+            malformed("NROWS", &size)
+        })?;
+    //One pass over the blocks per band for IMODE S, a single pass holding every band otherwise
+    let (bands, passes) = if layout.imode == "S" { (1, layout.nbands) } else { (layout.nbands, 1) };
+    let start = mask.map_or(0, |m| m.imdatoff);
+    let length = layout
+        .block_width()
+        .checked_mul(layout.block_height())
+        .and_then(|n| n.checked_mul(bands))
+        .and_then(|n| n.checked_mul(layout.nbpp))
+        .map(|bits| bits.div_ceil(8))
+        .and_then(|n| n.checked_mul(layout.nbpr))
+        .and_then(|n| n.checked_mul(layout.nbpc))
+        .and_then(|n| n.checked_mul(passes))
+        .and_then(|n| n.checked_add(start))
+        .ok_or_else(|| {
+            let blocks = format!("{}x{} blocks of {}x{}", layout.nbpr, layout.nbpc, layout.nppbh, layout.nppbv);
+            malformed("NBPR", &blocks)
+        })?;
+    if mask.is_none_or(|m| m.bmr.is_empty()) && length > data.len() {
+        return Err(NitfError::Truncated {
+            offset: data.len(),
+            length: length - data.len(),
+        });
+    }
+    Ok(count)
+}
+
+fn malformed(field: &'static str, raw: &str) -> NitfError {
+    NitfError::MalformedField {
+        field,
+        offset: 0,
+        raw: raw.to_string(),
+    }
+}
+
+/// Reads MSB first values of NBPP bits.
+struct BitReader<'a> {
+    data: &'a [u8],
+    bit: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self, nbpp: usize) -> Result<u64> {
+        let offset = self.bit / 8;
+        if (self.bit + nbpp).div_ceil(8) > self.data.len() {
+            return Err(NitfError::Truncated {
+                offset,
+                length: (self.bit % 8 + nbpp).div_ceil(8),
+            });
+        }
+        let value = if nbpp.is_multiple_of(8) && self.bit.is_multiple_of(8) {
+            self.data[offset..offset + nbpp / 8]
+                .iter()
+                .fold(0u64, |v, &b| (v << 8) | b as u64)
+        } else {
+            (self.bit..self.bit + nbpp).fold(0u64, |v, i| (v << 1) | ((self.data[i / 8] >> (7 - i % 8)) & 1) as u64)
+        };
+        self.bit += nbpp;
+        Ok(value)
+    }
+}
+
+/// The raw NBPP bit values, band interleaved by pixel, with block padding dropped. `count` is
+/// the number of values from [`check_sizes`]. Blocks without an offset are filled with `pad`.
+fn unpack(layout: &ImageLayout, data: &[u8], offsets: &[Option<usize>], pad: u64, count: usize) -> Result<Vec<u64>> {
+    let (nrows, ncols, nbands, nbpp) = (layout.nrows, layout.ncols, layout.nbands, layout.nbpp);
+    if nbpp == 0 || nbpp > 64 {
+        return Err(malformed("NBPP", &nbpp.to_string()));
+    }
+    let (width, height) = (layout.block_width(), layout.block_height());
+    //A masked image may leave out most of its blocks, so the size alone can be too much to hold
+    let mut values = Vec::new();
+    values
+        .try_reserve_exact(count)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::OutOfMemory, e))?;
+    values.resize(count, 0u64);
+    //Band sequential images repeat the whole grid of blocks once per band
+    let passes = if layout.imode == "S" { nbands } else { 1 };
+    let mut offsets = offsets.iter();
+    for pass in 0..passes {
+        for block_row in 0..layout.nbpc {
+            for block_col in 0..layout.nbpr {
+                let offset = offsets.next().copied().flatten();
+                let mut bits = BitReader { data, bit: offset.unwrap_or_default() * 8 };
+                let mut read = || if offset.is_some() { bits.read(nbpp) } else { Ok(pad) };
+                let mut put = |row: usize, col: usize, band: usize, value: u64| {
+                    let (r, c) = (block_row * height + row, block_col * width + col);
+                    if r < nrows && c < ncols {
+                        values[(r * ncols + c) * nbands + band] = value;
+                    }
+                };
+                match layout.imode.as_str() {
+                    "B" => {
+                        for band in 0..nbands {
+                            for row in 0..height {
+                                for col in 0..width {
+                                    put(row, col, band, read()?);
+                                }
+                            }
+                        }
+                    }
+                    "P" => {
+                        for row in 0..height {
+                            for col in 0..width {
+                                for band in 0..nbands {
+                                    put(row, col, band, read()?);
+                                }
+                            }
+                        }
+                    }
+                    "R" => {
+                        for row in 0..height {
+                            for band in 0..nbands {
+                                for col in 0..width {
+                                    put(row, col, band, read()?);
+                                }
+                            }
+                        }
+                    }
+                    "S" => {
+                        for row in 0..height {
+                            for col in 0..width {
+                                put(row, col, pass, read()?);
+                            }
+                        }
+                    }
+                    imode => return Err(malformed("IMODE", imode)),
+                }
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Types the raw values by PVTYPE, moving the ABPP significant bits of INT and SI values into place.
+fn convert(layout: &ImageLayout, values: Vec<u64>) -> Result<PixelData> {
+    let nbpp = layout.nbpp;
+    let abpp = if layout.abpp == 0 || layout.abpp > nbpp { nbpp } else { layout.abpp };
+    let left = layout.pjust == "L";
+    let unsigned = |v: u64| {
+        if left {
+            v >> (nbpp - abpp)
+        } else if abpp == 64 {
+            v
+        } else {
+            v & ((1u64 << abpp) - 1)
+        }
+    };
+    //Sign extend from NBPP bits for left justified values, ABPP bits for right justified ones
+    let signed = |v: u64| {
+        let (bits, v) = if left { (nbpp, v) } else { (abpp, unsigned(v)) };
+        let shifted = ((v << (64 - bits)) as i64) >> (64 - bits);
+        if left { shifted >> (nbpp - abpp) } else { shifted }
+    };
+    Ok(match (layout.pvtype.as_str(), nbpp) {
+        ("B", 1) => PixelData::U8(values.into_iter().map(|v| v as u8).collect()),
+        ("INT", 1..=8) => PixelData::U8(values.into_iter().map(|v| unsigned(v) as u8).collect()),
+        ("INT", 9..=16) => PixelData::U16(values.into_iter().map(|v| unsigned(v) as u16).collect()),
+        ("INT", 17..=32) => PixelData::U32(values.into_iter().map(|v| unsigned(v) as u32).collect()),
+        ("INT", _) => PixelData::U64(values.into_iter().map(unsigned).collect()),
+        ("SI", 1..=8) => PixelData::I8(values.into_iter().map(|v| signed(v) as i8).collect()),
+        ("SI", 9..=16) => PixelData::I16(values.into_iter().map(|v| signed(v) as i16).collect()),
+        ("SI", 17..=32) => PixelData::I32(values.into_iter().map(|v| signed(v) as i32).collect()),
+        ("SI", _) => PixelData::I64(values.into_iter().map(signed).collect()),
+        ("R", 32) => PixelData::F32(values.into_iter().map(|v| f32::from_bits(v as u32)).collect()),
+        ("R", 64) => PixelData::F64(values.into_iter().map(f64::from_bits).collect()),
+        ("C", 64) => PixelData::Complex32(
+            values
+                .into_iter()
+                .map(|v| (f32::from_bits((v >> 32) as u32), f32::from_bits(v as u32)))
+                .collect(),
+        ),
+        ("B" | "R" | "C", _) => return Err(malformed("NBPP", &nbpp.to_string())),
+        (pvtype, _) => return Err(malformed("PVTYPE", pvtype)),
+    })
+}
@@ -1,6 +1,5 @@
 use super::core;
-use super::parser::file_ops::read_int_from_file;
-use crate::modify::parser::nitf21::{NitfHeader21 as N, NitfHeader21::*};
+use super::error::Result;
 use jni::objects::{JByteArray, JClass, JObject, JString};
 use jni::sys::{jbyteArray, jint, jlong, jstring};
 use jni::JNIEnv;
@@ -14,22 +13,24 @@ use std::os::windows::io::{FromRawHandle, RawHandle};
 use winapi::um::handleapi::CloseHandle;
 
 #[no_mangle]
-pub extern "system" fn Java_dutchman_mil_nitfgnr_getVersion(env: JNIEnv, _class: JClass, fd: jlong) -> jstring {
-    let file = get_java_file(fd);
-    let (fhdr, ver) = core::get_version(&file);
-    **env.new_string(fhdr+&ver).expect("Failed to create new string")
+pub extern "system" fn Java_dutchman_mil_nitfgnr_getVersion(mut env: JNIEnv, _class: JClass, fd: jlong) -> jstring {
+    let mut file = get_java_file(fd);
+    match throw_on_error(&mut env, core::get_version(&mut *file)) {
+        Some((fhdr, ver)) => **env.new_string(fhdr+&ver).expect("Failed to create new string"),
+        None => std::ptr::null_mut(),
+    }
 }
 
 #[no_mangle]
-pub extern "system" fn Java_dutchman_mil_nitfgnr_getHeaderLength(_env: JNIEnv, _class: JClass, fd: jlong) -> jint {
-    let file = get_java_file(fd);
-    read_int_from_file(&file, N::get_offset(HL, None), N::get_value(HL)) as jint
+pub extern "system" fn Java_dutchman_mil_nitfgnr_getHeaderLength(mut env: JNIEnv, _class: JClass, fd: jlong) -> jint {
+    let mut file = get_java_file(fd);
+    throw_on_error(&mut env, core::get_hl(&mut *file)).unwrap_or(0) as jint
 }
 
 #[no_mangle]
-pub extern "system" fn Java_dutchman_mil_nitfgnr_getNumImages(_env: JNIEnv, _class: JClass, fd: jlong) -> jint {
-    let file = get_java_file(fd);
-    read_int_from_file(&file, N::get_offset(NUMI, None), N::get_value(NUMI)) as jint
+pub extern "system" fn Java_dutchman_mil_nitfgnr_getNumImages(mut env: JNIEnv, _class: JClass, fd: jlong) -> jint {
+    let mut file = get_java_file(fd);
+    throw_on_error(&mut env, core::get_num_images(&mut *file)).unwrap_or(0) as jint
 }
 
 #[no_mangle]
@@ -39,13 +40,9 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_extractJp2Index(
     input_path: JString,
     index: jint,
 ) -> jbyteArray {
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    ovu8_to_jbytearray(env, core::extract_jp2_index(&input_file, index as usize))
+    let result = open_java_path(&mut env, &input_path, true)
+        .and_then(|mut input_file| core::extract_jp2_index(&mut input_file, index as usize));
+    ovu8_to_jbytearray(env, result)
 }
 
 #[no_mangle]
@@ -55,20 +52,43 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_extractAllJp2(
     input_path: JString,
     output_path: JString
 ) {
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let output_string: String = env.get_string(&output_path).expect("Couldn't get input path").into();
-    let input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    core::extract_jp2(&input_file, &output_string);
+    let result = java_path(&mut env, &output_path).and_then(|output_string| {
+        open_java_path(&mut env, &input_path, true)
+            .and_then(|mut input_file| core::extract_jp2(&mut input_file, &output_string))
+    });
+    throw_on_error(&mut env, result);
 }
 
 #[no_mangle]
-pub extern "system" fn Java_dutchman_mil_nitfgnr_getNumDes(_env: JNIEnv, _class: JClass, fd: jlong) -> jint {
-    let file = get_java_file(fd);
-    core::get_numdes(&file) as jint
+pub extern "system" fn Java_dutchman_mil_nitfgnr_extractGraphicIndex(
+    mut env: JNIEnv,
+    _class: JClass,
+    input_path: JString,
+    index: jint,
+) -> jbyteArray {
+    let result = open_java_path(&mut env, &input_path, false)
+        .and_then(|mut input_file| core::extract_graphic_index(&mut input_file, index as usize));
+    ovu8_to_jbytearray(env, result)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_extractAllGraphics(
+    mut env: JNIEnv,
+    _class: JClass,
+    input_path: JString,
+    output_path: JString
+) {
+    let result = java_path(&mut env, &output_path).and_then(|output_string| {
+        open_java_path(&mut env, &input_path, false)
+            .and_then(|mut input_file| core::extract_graphics(&mut input_file, &output_string))
+    });
+    throw_on_error(&mut env, result);
+}
+
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_getNumDes(mut env: JNIEnv, _class: JClass, fd: jlong) -> jint {
+    let mut file = get_java_file(fd);
+    throw_on_error(&mut env, core::get_numdes(&mut *file)).unwrap_or(0) as jint
 }
 
 #[no_mangle]
@@ -80,8 +100,8 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_extractDesHeader (
 ) -> jbyteArray {
     #[cfg(all(debug_assertions, not(test)))]
     println!("Extracting DES Header {}", index);
-    let file = get_java_file(fd);
-    ovu8_to_jbytearray(env, core::extract_des_header_fields_index(&file, index as usize))
+    let mut file = get_java_file(fd);
+    ovu8_to_jbytearray(env, core::extract_des_header_fields_index(&mut *file, index as usize))
 }
 
 #[no_mangle]
@@ -93,20 +113,20 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_extractDes (
 ) -> jbyteArray {
     #[cfg(all(debug_assertions, not(test)))]
     println!("Extracting DES {}", index);
-    let file = get_java_file(fd);
-    ovu8_to_jbytearray(env, core::extract_des_index(&file, index as usize))
+    let mut file = get_java_file(fd);
+    ovu8_to_jbytearray(env, core::extract_des_index(&mut *file, index as usize))
 }
 
 #[no_mangle]
 pub extern "system" fn Java_dutchman_mil_nitfgnr_copyDesSegments(
-    _env: JNIEnv,
+    mut env: JNIEnv,
     _class: JClass,
     input_fd: jlong,
     output_fd: jlong,
 ) {
     let mut input_file = get_java_file(input_fd);
     let mut output_file = get_java_file(output_fd);
-    core::copy_des_segments(&mut input_file, &mut output_file);
+    throw_on_error(&mut env, core::copy_des_segments(&mut *input_file, &mut *output_file));
 }
 
 #[no_mangle]
@@ -116,43 +136,77 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_copyDesSegmentsFromPaths(
     input_path: JString,
     output_path: JString,
 ) {
+    let result = open_java_paths(&mut env, &input_path, &output_path)
+        .and_then(|(mut input_file, mut output_file)| core::copy_des_segments(&mut input_file, &mut output_file));
+    throw_on_error(&mut env, result);
+}
 
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let output_string: String = env.get_string(&output_path).expect("Couldn't get input path").into();
-    let mut input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    let mut output_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(output_string)
-        .expect("Failed to open file");
-    core::copy_des_segments(&mut input_file, &mut output_file);
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_getNumRes(mut env: JNIEnv, _class: JClass, fd: jlong) -> jint {
+    let mut file = get_java_file(fd);
+    throw_on_error(&mut env, core::get_numres(&mut *file)).unwrap_or(0) as jint
 }
 
 #[no_mangle]
-pub extern "system" fn Java_dutchman_mil_nitfgnr_copyGraphicSegmentsFromPaths(
+pub extern "system" fn Java_dutchman_mil_nitfgnr_extractResHeader (
+    env: JNIEnv,
+    _class: JClass,
+    fd: jlong,
+    index: jint,
+) -> jbyteArray {
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting RES Header {}", index);
+    let mut file = get_java_file(fd);
+    ovu8_to_jbytearray(env, core::extract_res_header_fields_index(&mut *file, index as usize))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_extractRes (
+    env: JNIEnv,
+    _class: JClass,
+    fd: jlong,
+    index: jint,
+) -> jbyteArray {
+    #[cfg(all(debug_assertions, not(test)))]
+    println!("Extracting RES {}", index);
+    let mut file = get_java_file(fd);
+    ovu8_to_jbytearray(env, core::extract_res_index(&mut *file, index as usize))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_copyResSegments(
+    mut env: JNIEnv,
+    _class: JClass,
+    input_fd: jlong,
+    output_fd: jlong,
+) {
+    let mut input_file = get_java_file(input_fd);
+    let mut output_file = get_java_file(output_fd);
+    throw_on_error(&mut env, core::copy_res_segments(&mut *input_file, &mut *output_file));
+}
+
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_copyResSegmentsFromPaths(
     mut env: JNIEnv,
     _class: JClass,
     input_path: JString,
     output_path: JString,
 ) {
+    let result = open_java_paths(&mut env, &input_path, &output_path)
+        .and_then(|(mut input_file, mut output_file)| core::copy_res_segments(&mut input_file, &mut output_file));
+    throw_on_error(&mut env, result);
+}
 
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let output_string: String = env.get_string(&output_path).expect("Couldn't get input path").into();
-    let mut input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    let mut output_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(output_string)
-        .expect("Failed to open file");
-    core::copy_graphic_segments(&mut input_file, &mut output_file);
+#[no_mangle]
+pub extern "system" fn Java_dutchman_mil_nitfgnr_copyGraphicSegmentsFromPaths(
+    mut env: JNIEnv,
+    _class: JClass,
+    input_path: JString,
+    output_path: JString,
+) {
+    let result = open_java_paths(&mut env, &input_path, &output_path)
+        .and_then(|(mut input_file, mut output_file)| core::copy_graphic_segments(&mut input_file, &mut output_file));
+    throw_on_error(&mut env, result);
 }
 
 #[no_mangle]
@@ -162,22 +216,12 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_copyGTDSegmentsFromPaths(
     input_path: JString,
     output_path: JString,
 ) {
-
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let output_string: String = env.get_string(&output_path).expect("Couldn't get input path").into();
-    let mut input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    let mut output_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(output_string)
-        .expect("Failed to open file");
-    core::copy_graphic_segments(&mut input_file, &mut output_file);
-    core::copy_text_segments(&mut input_file, &mut output_file);
-    core::copy_des_segments(&mut input_file, &mut output_file);
+    let result = open_java_paths(&mut env, &input_path, &output_path).and_then(|(mut input_file, mut output_file)| {
+        core::copy_graphic_segments(&mut input_file, &mut output_file)
+            .and_then(|_| core::copy_text_segments(&mut input_file, &mut output_file))
+            .and_then(|_| core::copy_des_segments(&mut input_file, &mut output_file))
+    });
+    throw_on_error(&mut env, result);
 }
 
 #[no_mangle]
@@ -187,20 +231,9 @@ pub extern "system" fn Java_dutchman_mil_nitfgnr_copyTextSegmentsFromPaths(
     input_path: JString,
     output_path: JString,
 ) {
-
-    let input_string: String = env.get_string(&input_path).expect("Couldn't get input path").into();
-    let output_string: String = env.get_string(&output_path).expect("Couldn't get input path").into();
-    let mut input_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(input_string)
-        .expect("Failed to open file");
-    let mut output_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(output_string)
-        .expect("Failed to open file");
-    core::copy_text_segments(&mut input_file, &mut output_file);
+    let result = open_java_paths(&mut env, &input_path, &output_path)
+        .and_then(|(mut input_file, mut output_file)| core::copy_text_segments(&mut input_file, &mut output_file));
+    throw_on_error(&mut env, result);
 }
 
 // #[no_mangle]
@@ -237,6 +270,7 @@ fn get_java_file(fd: jlong) -> ManuallyDrop<File> {
     ManuallyDrop::new(file)
 }
 
+#[allow(dead_code)]
 fn get_java_file_owned(fd: jlong) -> File {
     #[cfg(unix)]
     unsafe { File::from_raw_fd(fd as RawFd) }
@@ -244,21 +278,42 @@ fn get_java_file_owned(fd: jlong) -> File {
     unsafe { File::from_raw_handle(fd as RawHandle) }
 }
 
-fn get_java_file_from_path(env: &mut JNIEnv, path: jstring) -> File {
-    let path = jstring_to_string(env, path);
+/// Reads a path passed from Java, failing with an I/O error rather than panicking.
+fn java_path(env: &mut JNIEnv, path: &JString) -> Result<String> {
+    env.get_string(path)
+        .map(String::from)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()).into())
+}
+
+/// Opens the file at a path passed from Java, for writing too if `write` is set.
+fn open_java_path(env: &mut JNIEnv, path: &JString, write: bool) -> Result<File> {
+    let path = java_path(env, path)?;
     #[cfg(all(debug_assertions, not(test)))]
     println!("Opening file: {}", path);
-    let file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(path)
-        .expect("Failed to open file");
-    file
+    Ok(std::fs::OpenOptions::new().read(true).write(write).open(path)?)
+}
+
+/// Opens the input and output files of the copy functions, both read-write.
+fn open_java_paths(env: &mut JNIEnv, input_path: &JString, output_path: &JString) -> Result<(File, File)> {
+    Ok((open_java_path(env, input_path, true)?, open_java_path(env, output_path, true)?))
+}
+
+/// Throws a `RuntimeException` with the error message if `result` is an error.
+/// The caller still has to return, with `None` telling it to return a null/zero value.
+fn throw_on_error<T>(env: &mut JNIEnv, result: Result<T>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            env.throw_new("java/lang/RuntimeException", e.to_string())
+                .expect("Failed to throw exception");
+            None
+        }
+    }
 }
 
-/// Converts an `Option<Vec<u8>>` to a `Vec<i8>`, panics if None.
-fn ovu8_to_vi8(vec: Option<Vec<u8>>) -> Vec<i8> {
-    vec.expect("Vec was None!").iter().map(|&b| b as i8).collect()
+/// Converts a `Vec<u8>` to a `Vec<i8>`.
+fn vu8_to_vi8(vec: Vec<u8>) -> Vec<i8> {
+    vec.iter().map(|&b| b as i8).collect()
 }
 
 /// Converts a `Vec<i8>` to a `jbyteArray`.
@@ -271,11 +326,15 @@ fn vi8_to_jbytearray(env: JNIEnv, vec: Vec<i8>) -> jbyteArray {
     **byte_array
 }
 
-/// Converts an Option<Vec<u8>> to a jbyteArray, panics if None.
-fn ovu8_to_jbytearray(env: JNIEnv, vec: Option<Vec<u8>>) -> jbyteArray {
-    vi8_to_jbytearray(env, ovu8_to_vi8(vec))
+/// Converts a Result<Vec<u8>> to a jbyteArray, throwing and returning null on an error.
+fn ovu8_to_jbytearray(mut env: JNIEnv, vec: Result<Vec<u8>>) -> jbyteArray {
+    match throw_on_error(&mut env, vec) {
+        Some(vec) => vi8_to_jbytearray(env, vu8_to_vi8(vec)),
+        None => std::ptr::null_mut(),
+    }
 }
 
+#[allow(dead_code)]
 /// Converts a `jbyteArray` to a `Vec<u8>`.
 fn jbytearray_to_vec(env: &JNIEnv, array: jbyteArray) -> Vec<u8> {
     let obj: JObject = unsafe { JObject::from_raw(array) };
@@ -283,12 +342,3 @@ fn jbytearray_to_vec(env: &JNIEnv, array: jbyteArray) -> Vec<u8> {
     env.convert_byte_array(byte_array)
         .expect("Failed to convert byte array")
 }
-
-fn jstring_to_string(env:&mut JNIEnv, jstr: jstring) -> String {
-    let jstr = unsafe {
-        JString::from_raw(jstr)
-    };
-    let path = env.get_string(&jstr).expect("Failed to get string");
-    path.to_str().expect("Failed to convert to str").to_string()
-}
-
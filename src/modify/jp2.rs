@@ -0,0 +1,206 @@
+use crate::modify::error::{NitfError, Result};
+use crate::modify::export::{tiff_file, TiffField};
+use crate::modify::image::ImageLayout;
+use crate::modify::parser::j2k::JP2_SIGNATURE;
+
+/// UUID of a GeoJP2 box, which holds a degenerate GeoTIFF.
+const GEOJP2_UUID: [u8; 16] = [
+    0xB1, 0x4B, 0xF8, 0xBD, 0x08, 0x3D, 0x4B, 0x43, 0xA5, 0xAE, 0x8C, 0xD7, 0xD5, 0xA6, 0xCE, 0x03,
+];
+
+/// How an extracted JP2 file is georeferenced from IGEOLO.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Georeference {
+    None,
+    /// A GML in JPEG 2000 (GMLJP2 1.0) association box with a RectifiedGrid.
+    GmlJp2,
+    /// A GeoJP2 UUID box, a 1x1 GeoTIFF with a ModelTransformation.
+    GeoJp2,
+}
+
+/// Image corners from IGEOLO as (latitude, longitude) in degrees, in IGEOLO order: first row
+/// first column, first row last column, last row last column, last row first column.
+///
+/// Only geographic ICORDS are read, G (ddmmssXdddmmssY) and D (decimal degrees). Offsets in
+/// errors are within IGEOLO, except for an unsupported ICORDS which is reported at offset 0.
+pub fn igeolo_corners(icords: &str, igeolo: &str) -> Result<[(f64, f64); 4]> {
+    let malformed = |offset: usize| NitfError::MalformedField {
+        field: "IGEOLO",
+        offset,
+        raw: igeolo.to_string(),
+    };
+    if !igeolo.is_ascii() || igeolo.len() != 60 {
+        return Err(malformed(0));
+    }
+    let number = |offset: usize, length: usize| igeolo[offset..offset + length].trim().parse::<f64>().map_err(|_| malformed(offset));
+    let mut corners = [(0.0, 0.0); 4];
+    for (k, corner) in corners.iter_mut().enumerate() {
+        let o = k * 15;
+        *corner = match icords {
+            "G" => {
+                let lat = number(o, 2)? + number(o + 2, 2)? / 60.0 + number(o + 4, 2)? / 3600.0;
+                let lon = number(o + 7, 3)? + number(o + 10, 2)? / 60.0 + number(o + 12, 2)? / 3600.0;
+                let lat = match &igeolo[o + 6..o + 7] {
+                    "N" => lat,
+                    "S" => -lat,
+                    _ => return Err(malformed(o + 6)),
+                };
+                let lon = match &igeolo[o + 14..o + 15] {
+                    "E" => lon,
+                    "W" => -lon,
+                    _ => return Err(malformed(o + 14)),
+                };
+                (lat, lon)
+            }
+            "D" => (number(o, 7)?, number(o + 7, 8)?),
+            _ => {
+                return Err(NitfError::MalformedField {
+                    field: "ICORDS",
+                    offset: 0,
+                    raw: icords.to_string(),
+                })
+            }
+        };
+    }
+    Ok(corners)
+}
+
+/// Wraps a raw J2K codestream in a JP2 file: signature, ftyp, jp2h (ihdr and colr from the
+/// image subheader), an optional georeferencing box from `corners`, then jp2c.
+///
+/// The colour space is sRGB for IREP RGB, sYCC for YCbCr601 and greyscale otherwise, extra
+/// bands being left to the codestream.
+pub fn wrap_codestream(
+    codestream: &[u8],
+    layout: &ImageLayout,
+    irep: &str,
+    corners: Option<[(f64, f64); 4]>,
+    georeference: Georeference,
+) -> Vec<u8> {
+    let mut ihdr = Vec::with_capacity(14);
+    ihdr.extend((layout.nrows as u32).to_be_bytes());
+    ihdr.extend((layout.ncols as u32).to_be_bytes());
+    ihdr.extend((layout.nbands as u16).to_be_bytes());
+    //BPC is bits less one, the top bit set for signed samples
+    let bits = if layout.abpp == 0 { layout.nbpp } else { layout.abpp };
+    let signed = if layout.pvtype == "SI" { 0x80 } else { 0 };
+    ihdr.push((bits.clamp(1, 38) as u8 - 1) | signed);
+    //Compression type 7 (JPEG 2000), colour space known, no IPR box
+    ihdr.extend([7, 0, 0]);
+    let colour_space: u32 = match irep.trim() {
+        "RGB" => 16,
+        "YCbCr601" => 18,
+        _ => 17,
+    };
+    //Enumerated method, precedence and approximation 0
+    let mut colr = vec![1, 0, 0];
+    colr.extend(colour_space.to_be_bytes());
+    let jp2h = [jp2_box(b"ihdr", &ihdr), jp2_box(b"colr", &colr)].concat();
+
+    let mut out = JP2_SIGNATURE.to_vec();
+    out.extend(jp2_box(b"ftyp", b"jp2 \x00\x00\x00\x00jp2 "));
+    out.extend(jp2_box(b"jp2h", &jp2h));
+    if let Some(corners) = corners {
+        let transform = grid_transform(&corners, layout.nrows, layout.ncols);
+        match georeference {
+            Georeference::None => {}
+            Georeference::GmlJp2 => out.extend(gml_box(&transform, layout.nrows, layout.ncols)),
+            Georeference::GeoJp2 => out.extend(jp2_box(b"uuid", &[&GEOJP2_UUID[..], &geotiff(&transform)].concat())),
+        }
+    }
+    out.extend(jp2_box(b"jp2c", codestream));
+    out
+}
+
+fn jp2_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut out = box_header(kind, content.len());
+    out.extend(content);
+    out
+}
+
+/// LBox and TBox for `length` bytes of content. A box too long for a 32 bit LBox has LBox 1 and
+/// its length in the 8 byte XLBox that follows TBox.
+fn box_header(kind: &[u8; 4], length: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(16);
+    match u32::try_from(length + 8) {
+        Ok(lbox) => {
+            out.extend(lbox.to_be_bytes());
+            out.extend(kind);
+        }
+        Err(_) => {
+            out.extend(1u32.to_be_bytes());
+            out.extend(kind);
+            out.extend((length as u64 + 16).to_be_bytes());
+        }
+    }
+    out
+}
+
+/// Latitude and longitude of a pixel centre, (lat0, lon0, dlat/dcol, dlon/dcol, dlat/drow,
+/// dlon/drow), from the first row's corners and the last row's first column.
+fn grid_transform(corners: &[(f64, f64); 4], nrows: usize, ncols: usize) -> [f64; 6] {
+    let step = |a: f64, b: f64, n: usize| if n > 1 { (b - a) / (n - 1) as f64 } else { 0.0 };
+    let (first, right, below) = (corners[0], corners[1], corners[3]);
+    [
+        first.0,
+        first.1,
+        step(first.0, right.0, ncols),
+        step(first.1, right.1, ncols),
+        step(first.0, below.0, nrows),
+        step(first.1, below.1, nrows),
+    ]
+}
+
+/// A GMLJP2 association box: a "gml.data" label and, under "gml.root-instance", a
+/// FeatureCollection whose RectifiedGrid is in EPSG:4326 (latitude first).
+fn gml_box(t: &[f64; 6], nrows: usize, ncols: usize) -> Vec<u8> {
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<gml:FeatureCollection xmlns:gml="http://www.opengis.net/gml">
+<gml:featureMember><gml:FeatureCollection><gml:featureMember><gml:RectifiedGridCoverage dimension="2" gml:id="RGC0001">
+<gml:rectifiedGridDomain><gml:RectifiedGrid dimension="2">
+<gml:limits><gml:GridEnvelope><gml:low>0 0</gml:low><gml:high>{} {}</gml:high></gml:GridEnvelope></gml:limits>
+<gml:axisName>x</gml:axisName><gml:axisName>y</gml:axisName>
+<gml:origin><gml:Point gml:id="P0001" srsName="urn:ogc:def:crs:EPSG::4326"><gml:pos>{} {}</gml:pos></gml:Point></gml:origin>
+<gml:offsetVector srsName="urn:ogc:def:crs:EPSG::4326">{} {}</gml:offsetVector>
+<gml:offsetVector srsName="urn:ogc:def:crs:EPSG::4326">{} {}</gml:offsetVector>
+</gml:RectifiedGrid></gml:rectifiedGridDomain>
+<gml:rangeSet><gml:File><gml:fileName>gmljp2://codestream/0</gml:fileName><gml:fileStructure>Record Interleaved</gml:fileStructure></gml:File></gml:rangeSet>
+</gml:RectifiedGridCoverage></gml:featureMember></gml:FeatureCollection></gml:featureMember>
+</gml:FeatureCollection>
+"#,
+        ncols.saturating_sub(1),
+        nrows.saturating_sub(1),
+        t[0],
+        t[1],
+        t[2],
+        t[3],
+        t[4],
+        t[5]
+    );
+    let instance = [jp2_box(b"lbl ", b"gml.root-instance"), jp2_box(b"xml ", xml.as_bytes())].concat();
+    let content = [jp2_box(b"lbl ", b"gml.data"), jp2_box(b"asoc", &instance)].concat();
+    jp2_box(b"asoc", &content)
+}
+
+/// The degenerate GeoTIFF of a GeoJP2 box: one pixel, a ModelTransformation from (column, row)
+/// to (longitude, latitude), and GeoKeys for WGS 84 with pixels as points, since IGEOLO gives
+/// pixel centres.
+fn geotiff(t: &[f64; 6]) -> Vec<u8> {
+    let shorts = |values: &[u16]| values.iter().flat_map(|v| v.to_le_bytes()).collect::<Vec<_>>();
+    let transform = [t[3], t[5], 0.0, t[1], t[2], t[4], 0.0, t[0], 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0];
+    //Version 1.1.0, then GTModelType geographic, GTRasterType point and GeographicType WGS 84
+    let keys = [1, 1, 0, 3, 1024, 0, 1, 2, 1025, 0, 1, 2, 2048, 0, 1, 4326];
+    let fields = vec![
+        TiffField { tag: 256, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 257, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 258, kind: TiffField::SHORT, values: shorts(&[8]) },
+        TiffField { tag: 259, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 262, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 277, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 278, kind: TiffField::SHORT, values: shorts(&[1]) },
+        TiffField { tag: 34264, kind: TiffField::DOUBLE, values: transform.iter().flat_map(|v| v.to_le_bytes()).collect() },
+        TiffField { tag: 34735, kind: TiffField::SHORT, values: shorts(&keys) },
+    ];
+    tiff_file(fields, &[0])
+}
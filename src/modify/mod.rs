@@ -1,4 +1,11 @@
 pub mod core;
+pub mod error;
 pub mod cwrapper;
 pub mod javawrapper;
 pub mod parser;
+pub mod writer;
+pub mod builder;
+pub mod svg;
+pub mod image;
+pub mod export;
+pub mod jp2;
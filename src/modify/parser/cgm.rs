@@ -0,0 +1,282 @@
+use crate::modify::error::{NitfError, Result};
+
+/// A point in VDC space. With the NITF CGM profile x grows to the right and y grows upward,
+/// so points below the graphic's origin have a negative y.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub x: isize,
+    pub y: isize,
+}
+
+/// A direct colour, the only colour selection mode the NITF profile allows.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InteriorStyle {
+    Hollow,
+    Solid,
+    Pattern,
+    Hatch,
+    Empty,
+}
+
+/// How a closed arc is joined up: back through the centre, or straight between its ends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArcClosure {
+    Pie,
+    Chord,
+}
+
+/// One CGM element. Elements outside the subset below are kept as `Unknown` with their raw
+/// parameters, so a graphic using them still parses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CgmElement {
+    BeginMetafile(String),
+    EndMetafile,
+    BeginPicture(String),
+    BeginPictureBody,
+    EndPicture,
+    VdcExtent(Point, Point),
+    BackgroundColour(Colour),
+    Polyline(Vec<Point>),
+    /// Unconnected lines, each pair of points being one line.
+    DisjointPolyline(Vec<Point>),
+    Text { position: Point, text: String },
+    Polygon(Vec<Point>),
+    Rectangle(Point, Point),
+    Circle { centre: Point, radius: isize },
+    /// An ellipse given by its centre and the end points of two conjugate diameters.
+    Ellipse { centre: Point, first: Point, second: Point },
+    /// CIRCULAR ARC CENTRE, or with `close` CIRCULAR ARC CENTRE CLOSE. The arc runs
+    /// counterclockwise from the ray along `start` to the ray along `end`, both vectors from
+    /// the centre; rays that coincide give the whole circle.
+    CircularArc { centre: Point, start: Point, end: Point, radius: isize, close: Option<ArcClosure> },
+    /// ELLIPTICAL ARC, or with `close` ELLIPTICAL ARC CLOSE. The ellipse is given as for
+    /// `Ellipse` and the arc runs from the ray along `start` to the ray along `end` in the
+    /// direction from `first` to `second`.
+    EllipticalArc {
+        centre: Point,
+        first: Point,
+        second: Point,
+        start: Point,
+        end: Point,
+        close: Option<ArcClosure>,
+    },
+    LineType(isize),
+    LineWidth(isize),
+    LineColour(Colour),
+    TextColour(Colour),
+    CharacterHeight(isize),
+    InteriorStyle(InteriorStyle),
+    FillColour(Colour),
+    EdgeType(isize),
+    EdgeWidth(isize),
+    EdgeColour(Colour),
+    EdgeVisibility(bool),
+    Unknown { class: u8, id: u8, params: Vec<u8> },
+}
+
+/// A binary encoded CGM as carried by a NITF graphic segment.
+///
+/// Only the MIL-STD-2301A profile is understood: 16 bit integer VDC, 8 bit direct colour and
+/// absolute line and edge widths. The precision and mode elements that would change those are
+/// kept as `Unknown` and not applied.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Cgm {
+    pub elements: Vec<CgmElement>,
+}
+
+impl Cgm {
+    pub fn parse(bytes: &[u8]) -> Result<Cgm> {
+        let mut elements = Vec::new();
+        let mut offset = 0;
+        //Anything after END METAFILE, usually padding, is ignored
+        while offset + 2 <= bytes.len() {
+            let header = read_u16(bytes, offset)?;
+            let class = (header >> 12) as u8;
+            let id = ((header >> 5) & 0x7F) as u8;
+            let length = (header & 0x1F) as usize;
+            let (params, next) = read_params(bytes, offset + 2, length)?;
+            //Long form parameters start after the length word
+            let params_offset = if length == 31 { offset + 4 } else { offset + 2 };
+            let element = decode_element(class, id, &params, params_offset)?;
+            offset = next;
+            match element {
+                //No-op
+                CgmElement::Unknown { class: 0, id: 0, .. } => {}
+                CgmElement::EndMetafile => {
+                    elements.push(element);
+                    break;
+                }
+                _ => elements.push(element),
+            }
+        }
+        Ok(Cgm { elements })
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    match bytes.get(offset..offset + 2) {
+        Some(word) => Ok(u16::from_be_bytes([word[0], word[1]])),
+        None => Err(NitfError::Truncated { offset, length: 2 }),
+    }
+}
+
+/// Reads the parameter list of a command, joining the partitions of a long form one.
+/// Returns the parameters and the offset of the next command.
+fn read_params(bytes: &[u8], mut offset: usize, length: usize) -> Result<(Vec<u8>, usize)> {
+    let take = |offset: usize, length: usize| match bytes.get(offset..offset + length) {
+        Some(params) => Ok(params.to_vec()),
+        None => Err(NitfError::Truncated { offset, length }),
+    };
+    //Parameter lists are padded to an even number of bytes
+    if length != 31 {
+        return Ok((take(offset, length)?, offset + length + length % 2));
+    }
+    let mut params = Vec::new();
+    loop {
+        let word = read_u16(bytes, offset)?;
+        let length = (word & 0x7FFF) as usize;
+        params.extend(take(offset + 2, length)?);
+        offset += 2 + length + length % 2;
+        if word & 0x8000 == 0 {
+            return Ok((params, offset));
+        }
+    }
+}
+
+fn decode_element(class: u8, id: u8, params: &[u8], offset: usize) -> Result<CgmElement> {
+    use CgmElement::*;
+    let mut p = Params { bytes: params, pos: 0, offset };
+    Ok(match (class, id) {
+        (0, 1) => BeginMetafile(p.read_string()?),
+        (0, 2) => EndMetafile,
+        (0, 3) => BeginPicture(p.read_string()?),
+        (0, 4) => BeginPictureBody,
+        (0, 5) => EndPicture,
+        (2, 6) => VdcExtent(p.read_point()?, p.read_point()?),
+        (2, 7) => BackgroundColour(p.read_colour()?),
+        (4, 1) => Polyline(p.read_points()?),
+        (4, 2) => DisjointPolyline(p.read_points()?),
+        (4, 4) => {
+            let position = p.read_point()?;
+            //Final/not final flag, text is never appended to in the profile
+            p.read_int()?;
+            Text { position, text: p.read_string()? }
+        }
+        (4, 7) => Polygon(p.read_points()?),
+        (4, 11) => Rectangle(p.read_point()?, p.read_point()?),
+        (4, 12) => Circle { centre: p.read_point()?, radius: p.read_int()? },
+        (4, 15) | (4, 16) => CircularArc {
+            centre: p.read_point()?,
+            start: p.read_point()?,
+            end: p.read_point()?,
+            radius: p.read_int()?,
+            close: if id == 16 { Some(p.read_closure()?) } else { None },
+        },
+        (4, 17) => Ellipse { centre: p.read_point()?, first: p.read_point()?, second: p.read_point()? },
+        (4, 18) | (4, 19) => EllipticalArc {
+            centre: p.read_point()?,
+            first: p.read_point()?,
+            second: p.read_point()?,
+            start: p.read_point()?,
+            end: p.read_point()?,
+            close: if id == 19 { Some(p.read_closure()?) } else { None },
+        },
+        (5, 2) => LineType(p.read_int()?),
+        (5, 3) => LineWidth(p.read_int()?),
+        (5, 4) => LineColour(p.read_colour()?),
+        (5, 14) => TextColour(p.read_colour()?),
+        (5, 15) => CharacterHeight(p.read_int()?),
+        (5, 22) => {
+            let pos = p.offset + p.pos;
+            CgmElement::InteriorStyle(match p.read_int()? {
+                0 => self::InteriorStyle::Hollow,
+                1 => self::InteriorStyle::Solid,
+                2 => self::InteriorStyle::Pattern,
+                3 => self::InteriorStyle::Hatch,
+                4 => self::InteriorStyle::Empty,
+                style => {
+                    return Err(NitfError::MalformedField {
+                        field: "INTERIOR STYLE",
+                        offset: pos,
+                        raw: style.to_string(),
+                    })
+                }
+            })
+        }
+        (5, 23) => FillColour(p.read_colour()?),
+        (5, 27) => EdgeType(p.read_int()?),
+        (5, 28) => EdgeWidth(p.read_int()?),
+        (5, 29) => EdgeColour(p.read_colour()?),
+        (5, 30) => EdgeVisibility(p.read_int()? == 1),
+        _ => Unknown { class, id, params: params.to_vec() },
+    })
+}
+
+/// Reads the parameters of one command. `offset` is where they start in the CGM, for errors.
+struct Params<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    offset: usize,
+}
+
+impl Params<'_> {
+    fn read_bytes(&mut self, length: usize) -> Result<&[u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + length).ok_or(NitfError::Truncated {
+            offset: self.offset + self.pos,
+            length,
+        })?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    /// A 16 bit signed integer, which VDC, index and enumerated values all are in the profile.
+    fn read_int(&mut self) -> Result<isize> {
+        let bytes = self.read_bytes(2)?;
+        Ok(i16::from_be_bytes([bytes[0], bytes[1]]) as isize)
+    }
+
+    fn read_point(&mut self) -> Result<Point> {
+        Ok(Point { x: self.read_int()?, y: self.read_int()? })
+    }
+
+    /// Every point left in the parameter list.
+    fn read_points(&mut self) -> Result<Vec<Point>> {
+        let mut points = Vec::new();
+        while self.pos + 4 <= self.bytes.len() {
+            points.push(self.read_point()?);
+        }
+        Ok(points)
+    }
+
+    fn read_closure(&mut self) -> Result<ArcClosure> {
+        let pos = self.offset + self.pos;
+        match self.read_int()? {
+            0 => Ok(ArcClosure::Pie),
+            1 => Ok(ArcClosure::Chord),
+            close => Err(NitfError::MalformedField { field: "CLOSE TYPE", offset: pos, raw: close.to_string() }),
+        }
+    }
+
+    fn read_colour(&mut self) -> Result<Colour> {
+        let rgb = self.read_bytes(3)?;
+        Ok(Colour { r: rgb[0], g: rgb[1], b: rgb[2] })
+    }
+
+    /// A length prefixed string, read as ISO-8859-1. A length of 255 means the real length
+    /// follows in the next 16 bits.
+    fn read_string(&mut self) -> Result<String> {
+        let mut length = self.read_bytes(1)?[0] as usize;
+        if length == 255 {
+            let word = self.read_bytes(2)?;
+            length = (u16::from_be_bytes([word[0], word[1]]) & 0x7FFF) as usize;
+        }
+        Ok(self.read_bytes(length)?.iter().map(|&b| b as char).collect())
+    }
+}
@@ -1,41 +1,258 @@
-use std::io::{Read, Seek, SeekFrom};
-use std::fs::File;
+use crate::modify::error::{NitfError, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
 
-pub fn read_string_from_file(mut file: &File, offset: usize, length: usize) -> String {
-    let mut file_slice_bytes = vec![0u8; length];
-    file.seek(SeekFrom::Start(offset as u64))
-        .expect("Failed to seek to offset.");
-    let _ = file.read_exact(&mut file_slice_bytes);
-    let file_slice_str = String::from_utf8_lossy(&file_slice_bytes).to_string();
-    file_slice_str
+/// A fixed width field from one of the header or subheader layouts.
+pub trait Field: Copy {
+    fn name(&self) -> &'static str;
+    fn length(&self) -> usize;
 }
 
-pub fn read_int_from_file(mut file: &File, offset: usize, length: usize) -> usize {
+pub fn read_bytes<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<Vec<u8>> {
     let mut file_slice_bytes = vec![0u8; length];
-    file.seek(SeekFrom::Start(offset as u64))
-        .expect("Failed to seek to offset.");
-    let _ = file.read_exact(&mut file_slice_bytes);
-    let file_slice_str = String::from_utf8_lossy(&file_slice_bytes);
-    match file_slice_str.parse::<usize>() {
-        Ok(num) => num,
-        Err(e) => {
-            eprintln!("Error: {}:\n String: {}", e, file_slice_str);
-            panic!("File Slice String cannot be coerced to a number.");
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    reader.read_exact(&mut file_slice_bytes).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => NitfError::Truncated { offset, length },
+        _ => NitfError::Io(e),
+    })?;
+    Ok(file_slice_bytes)
+}
+
+pub fn read_string<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<String> {
+    let file_slice_bytes = read_bytes(reader, offset, length)?;
+    Ok(String::from_utf8_lossy(&file_slice_bytes).to_string())
+}
+
+pub fn read_int<R: Read + Seek, F: Field>(reader: &mut R, offset: usize, field: F) -> Result<usize> {
+    let file_slice_bytes = read_bytes(reader, offset, field.length())?;
+    parse_int(field.name(), offset, &file_slice_bytes)
+}
+
+fn slice_bytes(data: &[u8], offset: usize, length: usize) -> Result<&[u8]> {
+    match offset.checked_add(length) {
+        Some(end) if end <= data.len() => Ok(&data[offset..end]),
+        _ => Err(NitfError::Truncated { offset, length }),
+    }
+}
+
+/// Parses a BCS-N field. Space padding is tolerated, anything else that is not a digit is an error.
+pub fn parse_int(field: &'static str, offset: usize, bytes: &[u8]) -> Result<usize> {
+    let field_str = String::from_utf8_lossy(bytes);
+    field_str
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| NitfError::MalformedField {
+            field,
+            offset,
+            raw: field_str.to_string(),
+        })
+}
+
+/// Parses a signed numeric field such as one half of ILOC, where a leading '-' is allowed.
+pub fn parse_signed(field: &'static str, offset: usize, bytes: &[u8]) -> Result<isize> {
+    let field_str = String::from_utf8_lossy(bytes);
+    field_str
+        .trim()
+        .parse::<isize>()
+        .map_err(|_| NitfError::MalformedField {
+            field,
+            offset,
+            raw: field_str.to_string(),
+        })
+}
+
+/// Reads consecutive fixed width fields out of an in-memory subheader.
+///
+/// Subheaders contain conditional and repeating fields, so their field offsets
+/// cannot be computed up front the way the file header offsets are. The cursor
+/// walks the buffer front to back and keeps track of the current offset instead.
+pub struct FieldCursor<'a> {
+    bytes: &'a [u8],
+    pub offset: usize,
+}
+
+impl<'a> FieldCursor<'a> {
+    pub fn new(bytes: &'a [u8]) -> FieldCursor<'a> {
+        FieldCursor { bytes, offset: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len().saturating_sub(self.offset)
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8]> {
+        let field = slice_bytes(self.bytes, self.offset, length)?;
+        self.offset += length;
+        Ok(field)
+    }
+
+    /// Reads a BCS-A field, dropping the space padding.
+    pub fn read_string<F: Field>(&mut self, field: F) -> Result<String> {
+        Ok(String::from_utf8_lossy(self.read_bytes(field.length())?)
+            .trim()
+            .to_string())
+    }
+
+    pub fn read_int<F: Field>(&mut self, field: F) -> Result<usize> {
+        let offset = self.offset;
+        parse_int(field.name(), offset, self.read_bytes(field.length())?)
+    }
+
+    /// Reads a decimal field such as "+1.234567E+0" or "0012.50".
+    pub fn read_float<F: Field>(&mut self, field: F) -> Result<f64> {
+        let offset = self.offset;
+        let field_str = String::from_utf8_lossy(self.read_bytes(field.length())?);
+        field_str
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| NitfError::MalformedField {
+                field: field.name(),
+                offset,
+                raw: field_str.to_string(),
+            })
+    }
+
+    /// Reads a user defined or extended data area: the length field, then the overflow field and
+    /// the data when the length is not zero. The length includes the overflow field, so anything
+    /// shorter than it is malformed. Returns the overflow value and the data.
+    pub fn read_extension<F: Field>(&mut self, length: F, overflow: F) -> Result<(usize, Vec<u8>)> {
+        let offset = self.offset;
+        let total = self.read_int(length)?;
+        if total == 0 {
+            return Ok((0, Vec::new()));
         }
+        let data_length =
+            total
+                .checked_sub(overflow.length())
+                .ok_or_else(|| NitfError::MalformedField {
+                    field: length.name(),
+                    offset,
+                    raw: total.to_string(),
+                })?;
+        let ofl = self.read_int(overflow)?;
+        Ok((ofl, self.read_bytes(data_length)?.to_vec()))
+    }
+
+    /// Reads a location field such as ILOC or SLOC, two halves of the field width holding
+    /// (row, column). Either half may be negative.
+    pub fn read_location<F: Field>(&mut self, field: F) -> Result<(isize, isize)> {
+        let offset = self.offset;
+        let half = field.length() / 2;
+        let location = self.read_bytes(field.length())?;
+        Ok((
+            parse_signed(field.name(), offset, &location[..half])?,
+            parse_signed(field.name(), offset, &location[half..])?,
+        ))
+    }
+
+    /// Reads a CCYYMMDDhhmmss field. Blank fields and fields with unknown
+    /// components (filled with '-') give `None`.
+    pub fn read_date_time<F: Field>(&mut self, field: F) -> Result<Option<NaiveDateTime>> {
+        Ok(NaiveDateTime::parse_from_str(&self.read_string(field)?, "%Y%m%d%H%M%S").ok())
+    }
+
+    /// Reads a CCYYMMDD field. Blank fields give `None`.
+    pub fn read_date<F: Field>(&mut self, field: F) -> Result<Option<NaiveDate>> {
+        Ok(NaiveDate::parse_from_str(&self.read_string(field)?, "%Y%m%d").ok())
     }
 }
 
-pub fn read_string_from_bytes(data: &Vec<u8>, offset: usize, length: usize) -> String {
-    let end = std::cmp::min(offset + length, data.len());
-    let file_slice_bytes = &data[offset..end];
-    String::from_utf8_lossy(file_slice_bytes).to_string()
+/// Builds a header or subheader out of fixed width fields, the inverse of [`FieldCursor`].
+/// Values that do not fit in their field are an `Overflow` error rather than being cut short.
+#[derive(Default)]
+pub struct FieldWriter {
+    bytes: Vec<u8>,
 }
 
-pub fn read_int_from_bytes(data: &Vec<u8>, offset: usize, length: usize) -> usize {
-    let end = std::cmp::min(offset + length, data.len());
-    let file_slice_bytes = &data[offset..end];
-    let file_slice_str = String::from_utf8_lossy(file_slice_bytes);
-    file_slice_str
-        .parse::<usize>()
-        .expect("File Slice String cannot be coerced to a number.")
+impl FieldWriter {
+    pub fn new() -> FieldWriter {
+        FieldWriter { bytes: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Writes a BCS-A field, left justified and padded with spaces. BCS is ASCII, so anything
+    /// else is malformed rather than taking more bytes than the field has.
+    pub fn write_string<F: Field>(&mut self, field: F, value: &str) -> Result<()> {
+        if !value.is_ascii() {
+            return Err(NitfError::MalformedField {
+                field: field.name(),
+                offset: self.bytes.len(),
+                raw: value.to_string(),
+            });
+        }
+        if value.len() > field.length() {
+            return Err(NitfError::Overflow {
+                field: field.name(),
+                value: value.len(),
+            });
+        }
+        self.write_bytes(format!("{:<width$}", value, width = field.length()).as_bytes());
+        Ok(())
+    }
+
+    /// Writes a BCS-N field, right justified and padded with zeros.
+    pub fn write_int<F: Field>(&mut self, field: F, value: usize) -> Result<()> {
+        let digits = format!("{:0width$}", value, width = field.length());
+        if digits.len() > field.length() {
+            return Err(NitfError::Overflow {
+                field: field.name(),
+                value,
+            });
+        }
+        self.write_bytes(digits.as_bytes());
+        Ok(())
+    }
+
+    /// Writes a location field, the inverse of [`FieldCursor::read_location`].
+    pub fn write_location<F: Field>(&mut self, field: F, value: (isize, isize)) -> Result<()> {
+        let half = field.length() / 2;
+        let location = format!("{:0half$}{:0half$}", value.0, value.1, half = half);
+        if location.len() != field.length() {
+            return Err(NitfError::Overflow {
+                field: field.name(),
+                value: value.0.unsigned_abs().max(value.1.unsigned_abs()),
+            });
+        }
+        self.write_bytes(location.as_bytes());
+        Ok(())
+    }
+
+    /// Writes a CCYYMMDDhhmmss field, blank for `None`.
+    pub fn write_date_time<F: Field>(&mut self, field: F, value: Option<NaiveDateTime>) -> Result<()> {
+        let value = value.map(|v| v.format("%Y%m%d%H%M%S").to_string()).unwrap_or_default();
+        self.write_string(field, &value)
+    }
+
+    /// Writes a CCYYMMDD field, blank for `None`.
+    pub fn write_date<F: Field>(&mut self, field: F, value: Option<NaiveDate>) -> Result<()> {
+        let value = value.map(|v| v.format("%Y%m%d").to_string()).unwrap_or_default();
+        self.write_string(field, &value)
+    }
+
+    /// Writes a user defined or extended data area, the inverse of [`FieldCursor::read_extension`].
+    /// An area with no data is left out unless its TREs overflowed (`ofl` is not 0).
+    pub fn write_extension<F: Field>(&mut self, length: F, overflow: F, ofl: usize, data: &[u8]) -> Result<()> {
+        if data.is_empty() && ofl == 0 {
+            return self.write_int(length, 0);
+        }
+        self.write_int(length, data.len() + overflow.length())?;
+        self.write_int(overflow, ofl)?;
+        self.write_bytes(data);
+        Ok(())
+    }
 }
@@ -0,0 +1,451 @@
+use crate::modify::error::{NitfError, Result};
+
+const SOC: u16 = 0xFF4F;
+const SIZ: u16 = 0xFF51;
+const COD: u16 = 0xFF52;
+const TLM: u16 = 0xFF55;
+const PLT: u16 = 0xFF58;
+const QCD: u16 = 0xFF5C;
+const COM: u16 = 0xFF64;
+const SOT: u16 = 0xFF90;
+const SOD: u16 = 0xFF93;
+const EOC: u16 = 0xFFD9;
+//Isot numbers tiles from 0 to 65534
+const MAX_TILES: u64 = 65535;
+
+/// The first 12 bytes of a JP2 file, its signature box.
+pub const JP2_SIGNATURE: &[u8] = b"\x00\x00\x00\x0cjP  \r\n\x87\n";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ProgressionOrder {
+    Lrcp,
+    Rlcp,
+    Rpcl,
+    Pcrl,
+    Cprl,
+}
+
+impl ProgressionOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProgressionOrder::Lrcp => "LRCP",
+            ProgressionOrder::Rlcp => "RLCP",
+            ProgressionOrder::Rpcl => "RPCL",
+            ProgressionOrder::Pcrl => "PCRL",
+            ProgressionOrder::Cprl => "CPRL",
+        }
+    }
+}
+
+/// One component (band) of the SIZ marker.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// Bits per sample.
+    pub precision: u8,
+    pub signed: bool,
+    pub xrsiz: u8,
+    pub yrsiz: u8,
+}
+
+/// Image and tile size (SIZ).
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Siz {
+    pub rsiz: u16,
+    pub xsiz: u32,
+    pub ysiz: u32,
+    pub xosiz: u32,
+    pub yosiz: u32,
+    pub xtsiz: u32,
+    pub ytsiz: u32,
+    pub xtosiz: u32,
+    pub ytosiz: u32,
+    pub components: Vec<Component>,
+}
+
+impl Siz {
+    /// Columns of the image, the NCOLS it should be carried with.
+    pub fn width(&self) -> u32 {
+        self.xsiz.saturating_sub(self.xosiz)
+    }
+
+    /// Rows of the image, the NROWS it should be carried with.
+    pub fn height(&self) -> u32 {
+        self.ysiz.saturating_sub(self.yosiz)
+    }
+
+    pub fn tiles_across(&self) -> u32 {
+        self.xsiz.saturating_sub(self.xtosiz).div_ceil(self.xtsiz.max(1))
+    }
+
+    pub fn tiles_down(&self) -> u32 {
+        self.ysiz.saturating_sub(self.ytosiz).div_ceil(self.ytsiz.max(1))
+    }
+
+    /// In u64, as a SIZ can describe more tiles than fit in a u32 or than Isot can number.
+    pub fn tile_count(&self) -> u64 {
+        u64::from(self.tiles_across()) * u64::from(self.tiles_down())
+    }
+}
+
+/// Default coding style (COD).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cod {
+    pub progression: ProgressionOrder,
+    pub layers: u16,
+    /// Whether the multiple component (colour) transform is used.
+    pub mct: bool,
+    /// Decomposition levels, one less than the number of resolution levels.
+    pub levels: u8,
+    /// Code-block width and height in samples.
+    pub code_block: (usize, usize),
+    /// The 5-3 reversible wavelet rather than the 9-7 irreversible one.
+    pub reversible: bool,
+    /// Whether precinct sizes are given rather than the default maximum precincts.
+    pub precincts: bool,
+}
+
+impl Cod {
+    pub fn resolution_levels(&self) -> usize {
+        self.levels as usize + 1
+    }
+}
+
+/// Default quantization (QCD). Step sizes are not kept.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Qcd {
+    /// 0 no quantization, 1 scalar derived, 2 scalar expounded.
+    pub style: u8,
+    pub guard_bits: u8,
+}
+
+/// One tile-part, from its SOT marker.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TilePart {
+    /// Where the SOT marker is in the bytes parsed.
+    pub offset: usize,
+    pub tile: u16,
+    /// Psot, the length from the SOT marker to the end of the tile-part.
+    pub length: usize,
+    pub index: u8,
+    /// TNsot, 0 when the encoder did not say.
+    pub count: u8,
+    /// Whether the tile-part header has packet lengths (PLT).
+    pub plt: bool,
+}
+
+/// The JPEG 2000 profiles of BPJ2K01 (ISO/IEC BIIF Profile for JPEG 2000).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Profile {
+    /// NSIF Preferred JPEG 2000 Encoding.
+    Npje,
+    /// Exploitation Preferred JPEG 2000 Encoding.
+    Epje,
+}
+
+/// The main header and tile-part headers of a JPEG 2000 codestream. Packet data is skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Codestream {
+    /// Where SOC is in the bytes parsed, past the boxes of a JP2 file.
+    pub offset: usize,
+    pub siz: Siz,
+    pub cod: Cod,
+    pub qcd: Qcd,
+    /// Whether the main header has tile-part lengths (TLM).
+    pub tlm: bool,
+    /// COM markers, binary ones (Rcom 0) read as ISO-8859-1 like the text ones.
+    pub comments: Vec<String>,
+    pub tile_parts: Vec<TilePart>,
+}
+
+impl Codestream {
+    /// Parses a raw codestream, or the contiguous codestream box of a JP2 file.
+    pub fn parse(bytes: &[u8]) -> Result<Codestream> {
+        let start = if bytes.starts_with(JP2_SIGNATURE) { jp2c_offset(bytes)? } else { 0 };
+        let mut m = Markers { bytes, pos: start };
+        m.expect(SOC, "SOC")?;
+        m.expect(SIZ, "SIZ")?;
+        let siz = read_siz(&mut m.segment()?)?;
+        let (mut cod, mut qcd, mut tlm, mut comments) = (None, None, false, Vec::new());
+        loop {
+            let (marker, offset) = (m.marker()?, m.pos - 2);
+            match marker {
+                SOT => {
+                    m.pos = offset;
+                    break;
+                }
+                COD => cod = Some(read_cod(&mut m.segment()?)?),
+                QCD => {
+                    let mut p = m.segment()?;
+                    let sqcd = p.u8()?;
+                    qcd = Some(Qcd { style: sqcd & 0x1F, guard_bits: sqcd >> 5 });
+                }
+                TLM => {
+                    m.segment()?;
+                    tlm = true;
+                }
+                COM => {
+                    let mut p = m.segment()?;
+                    p.u16()?;
+                    comments.push(p.bytes[p.pos..].iter().map(|&b| b as char).collect());
+                }
+                _ => {
+                    m.segment()?;
+                }
+            }
+        }
+        let missing = |field| NitfError::MalformedField { field, offset: m.pos, raw: String::new() };
+        let cod = cod.ok_or_else(|| missing("COD"))?;
+        let qcd = qcd.ok_or_else(|| missing("QCD"))?;
+
+        let mut tile_parts = Vec::new();
+        loop {
+            let offset = m.pos;
+            match m.marker()? {
+                EOC => break,
+                SOT => {}
+                marker => return Err(malformed("SOT", offset, marker)),
+            }
+            let mut p = m.segment()?;
+            let mut part = TilePart {
+                offset,
+                tile: p.u16()?,
+                length: p.u32()? as usize,
+                index: p.u8()?,
+                count: p.u8()?,
+                plt: false,
+            };
+            //A tile-part holds at least SOT (12 bytes) and SOD (2 bytes)
+            if part.length != 0 && part.length < 14 {
+                return Err(NitfError::MalformedField { field: "SOT", offset: offset + 6, raw: part.length.to_string() });
+            }
+            loop {
+                match m.marker()? {
+                    SOD => break,
+                    PLT => {
+                        m.segment()?;
+                        part.plt = true;
+                    }
+                    _ => {
+                        m.segment()?;
+                    }
+                }
+            }
+            //Psot 0 is the last tile-part, running up to EOC
+            if part.length == 0 {
+                part.length = bytes.len().saturating_sub(2).max(m.pos) - offset;
+            }
+            if offset + part.length > bytes.len() {
+                return Err(NitfError::Truncated { offset, length: part.length });
+            }
+            m.pos = offset + part.length;
+            tile_parts.push(part);
+        }
+        Ok(Codestream { offset: start, siz, cod, qcd, tlm, comments, tile_parts })
+    }
+
+    /// The ways the codestream departs from `profile`, empty if it conforms.
+    ///
+    /// Only the structure is checked: 1024x1024 tiles (or one tile for a smaller image) with no
+    /// offsets, 64x64 code-blocks, 5 decomposition levels, TLM in the main header and PLT in
+    /// every tile-part, LRCP progression for NPJE, and RLCP with one tile-part per resolution
+    /// level for EPJE. Layer and bit rate targets are not.
+    pub fn violations(&self, profile: Profile) -> Vec<String> {
+        let (siz, cod) = (&self.siz, &self.cod);
+        let mut found = Vec::new();
+        let single_tile = siz.tile_count() == 1 && siz.xtsiz >= siz.width() && siz.ytsiz >= siz.height();
+        if (siz.xtsiz, siz.ytsiz) != (1024, 1024) && !single_tile {
+            found.push(format!("Tiles are {}x{}, not 1024x1024", siz.xtsiz, siz.ytsiz));
+        }
+        if siz.tile_count() > MAX_TILES {
+            found.push(format!("{} tiles, more than {}", siz.tile_count(), MAX_TILES));
+        }
+        if [siz.xosiz, siz.yosiz, siz.xtosiz, siz.ytosiz].iter().any(|&o| o != 0) {
+            found.push("Image or tile offset is not 0".to_string());
+        }
+        if cod.code_block != (64, 64) {
+            found.push(format!("Code-blocks are {}x{}, not 64x64", cod.code_block.0, cod.code_block.1));
+        }
+        if cod.levels != 5 {
+            found.push(format!("{} decomposition levels, not 5", cod.levels));
+        }
+        if !self.tlm {
+            found.push("No TLM marker".to_string());
+        }
+        if self.tile_parts.iter().any(|t| !t.plt) {
+            found.push("Tile-part without a PLT marker".to_string());
+        }
+        let progression = match profile {
+            Profile::Npje => ProgressionOrder::Lrcp,
+            Profile::Epje => ProgressionOrder::Rlcp,
+        };
+        if cod.progression != progression {
+            found.push(format!("{} progression, not {}", cod.progression.as_str(), progression.as_str()));
+        }
+        if profile == Profile::Epje {
+            let resolutions = cod.resolution_levels();
+            for tile in 0..siz.tile_count().min(MAX_TILES) {
+                let parts = self.tile_parts.iter().filter(|t| u64::from(t.tile) == tile).count();
+                if parts != resolutions {
+                    found.push(format!("Tile {} has {} tile-parts, not {}", tile, parts, resolutions));
+                }
+            }
+        }
+        found
+    }
+
+    pub fn conforms(&self, profile: Profile) -> bool {
+        self.violations(profile).is_empty()
+    }
+}
+
+/// Where the content of the contiguous codestream box (jp2c) of a JP2 file starts.
+fn jp2c_offset(bytes: &[u8]) -> Result<usize> {
+    let mut pos = 0;
+    while pos + 8 <= bytes.len() {
+        let mut p = Params { bytes, pos, offset: 0 };
+        let (length, kind) = (p.u32()? as usize, p.take(4)?);
+        let (header, length) = match length {
+            //XLBox follows
+            1 => (16, p.u64()? as usize),
+            //Up to the end of the file
+            0 => (8, bytes.len() - pos),
+            length => (8, length),
+        };
+        if kind == b"jp2c" {
+            return Ok(pos + header);
+        }
+        if length < header {
+            return Err(NitfError::MalformedField { field: "LBox", offset: pos, raw: length.to_string() });
+        }
+        pos += length;
+    }
+    Err(NitfError::MalformedField { field: "jp2c", offset: pos, raw: String::new() })
+}
+
+fn read_siz(p: &mut Params) -> Result<Siz> {
+    let mut siz = Siz {
+        rsiz: p.u16()?,
+        xsiz: p.u32()?,
+        ysiz: p.u32()?,
+        xosiz: p.u32()?,
+        yosiz: p.u32()?,
+        xtsiz: p.u32()?,
+        ytsiz: p.u32()?,
+        xtosiz: p.u32()?,
+        ytosiz: p.u32()?,
+        components: Vec::new(),
+    };
+    for _ in 0..p.u16()? {
+        let ssiz = p.u8()?;
+        siz.components.push(Component {
+            precision: (ssiz & 0x7F) + 1,
+            signed: ssiz & 0x80 != 0,
+            xrsiz: p.u8()?,
+            yrsiz: p.u8()?,
+        });
+    }
+    Ok(siz)
+}
+
+fn read_cod(p: &mut Params) -> Result<Cod> {
+    let scod = p.u8()?;
+    let offset = p.offset + p.pos;
+    let progression = match p.u8()? {
+        0 => ProgressionOrder::Lrcp,
+        1 => ProgressionOrder::Rlcp,
+        2 => ProgressionOrder::Rpcl,
+        3 => ProgressionOrder::Pcrl,
+        4 => ProgressionOrder::Cprl,
+        order => return Err(NitfError::MalformedField { field: "COD", offset, raw: order.to_string() }),
+    };
+    let layers = p.u16()?;
+    let mct = p.u8()? == 1;
+    let levels = p.u8()?;
+    //Code-block size exponents are stored less 2 and go up to 10 (1024 samples)
+    let mut exponent = || {
+        let offset = p.offset + p.pos;
+        match p.u8()? {
+            e @ 0..=8 => Ok(1 << (e + 2)),
+            e => Err(NitfError::MalformedField { field: "COD", offset, raw: e.to_string() }),
+        }
+    };
+    let code_block = (exponent()?, exponent()?);
+    p.u8()?;
+    let reversible = p.u8()? == 1;
+    Ok(Cod { progression, layers, mct, levels, code_block, reversible, precincts: scod & 1 != 0 })
+}
+
+fn malformed(field: &'static str, offset: usize, marker: u16) -> NitfError {
+    NitfError::MalformedField { field, offset, raw: format!("{:04X}", marker) }
+}
+
+/// Walks the markers of a codestream.
+struct Markers<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Markers<'a> {
+    fn marker(&mut self) -> Result<u16> {
+        let marker = Params { bytes: self.bytes, pos: self.pos, offset: 0 }.u16()?;
+        if marker >> 8 != 0xFF {
+            return Err(malformed("MARKER", self.pos, marker));
+        }
+        self.pos += 2;
+        Ok(marker)
+    }
+
+    fn expect(&mut self, expected: u16, field: &'static str) -> Result<()> {
+        let offset = self.pos;
+        match self.marker()? {
+            marker if marker == expected => Ok(()),
+            marker => Err(malformed(field, offset, marker)),
+        }
+    }
+
+    /// The parameters of the marker segment just read, its length field included in the skip.
+    fn segment(&mut self) -> Result<Params<'a>> {
+        let length = Params { bytes: self.bytes, pos: self.pos, offset: 0 }.u16()? as usize;
+        let params = self.bytes.get(self.pos + 2..self.pos + length.max(2)).ok_or(NitfError::Truncated {
+            offset: self.pos,
+            length,
+        })?;
+        let params = Params { bytes: params, pos: 0, offset: self.pos + 2 };
+        self.pos += length.max(2);
+        Ok(params)
+    }
+}
+
+/// Big endian reads from a marker segment. `offset` is where it starts in the codestream, for errors.
+struct Params<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    offset: usize,
+}
+
+impl<'a> Params<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + length).ok_or(NitfError::Truncated {
+            offset: self.offset + self.pos,
+            length,
+        })?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
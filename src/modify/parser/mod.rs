@@ -1,3 +1,8 @@
 pub mod nitf21;
 pub mod nitf20;
 pub mod file_ops;
+pub mod tre;
+pub mod tre_decoders;
+pub mod xml_des;
+pub mod cgm;
+pub mod j2k;
@@ -1,12 +1,17 @@
-use crate::modify::parser::file_ops::read_string_from_file;
-use crate::modify::parser::file_ops::read_int_from_file;
-use std::fs::File;
+use crate::modify::error::{NitfError, Result};
+use crate::modify::parser::file_ops::{parse_signed, read_bytes, read_int, Field, FieldCursor};
+use crate::modify::parser::nitf21::{
+    length_table_entry, read_length_table, segment_offset, ImageBand, SegmentLength,
+};
+use crate::modify::parser::tre::{Tre, TreIter};
+use chrono::NaiveDateTime;
+use std::io::{Read, Seek};
 
-const FHDR: usize = 4;
-const FVER: usize = 5;
+//FSDWNG/ISDWNG value that means the downgrade is tied to an event, described in FSDEVT/ISDEVT
+const DOWNGRADE_EVENT: &str = "999998";
 
-#[derive(Default, Debug)]
-pub struct Nitf {
+#[derive(Default, Debug, Clone)]
+pub struct Header {
     pub fhdr: usize,
     pub fver: usize,
     pub clevel: usize,
@@ -15,89 +20,329 @@ pub struct Nitf {
     pub fdt: usize,
     pub ftitle: usize,
     pub fsclas: usize,
-    pub fsclsy: usize,
     pub fscode: usize,
     pub fsctlh: usize,
     pub fsrel: usize,
-    pub fsdctp: usize,
-    pub fsdcdt: usize,
-    pub fsdcxm: usize,
-    pub fsdg: usize,
-    pub fsdgdt: usize,
-    pub fscltx: usize,
-    pub fscatp: usize,
     pub fscaut: usize,
-    pub fscrsn: usize,
-    pub fssrdt: usize,
     pub fsctln: usize,
     pub fsdwng: usize,
-    pub fsdevt: usize,
+    pub fsdevt: Option<usize>,
     pub fscop: usize,
     pub fscpys: usize,
     pub encryp: usize,
-    pub fbkgc: usize,
     pub oname: usize,
     pub ophone: usize,
     pub fl: usize,
     pub hl: usize,
     pub numi: usize,
-    pub lish_nnn: usize,
-    pub li_nnn: usize,
-    // non-header values
-    pub file_profile_name: String,
-    pub file_version: String,
-    pub header_length: usize,
-    pub num_images: usize,
-    pub image_header_lengths: Vec<usize>,
-    pub image_data_lengths: Vec<usize>,
-}
-
-impl Nitf {
-    pub fn get_file_profile_name_and_version(self, file: &File) -> Nitf {
-        println!("Reading file profile name from offset: 0");
-        let file_profile_name = read_string_from_file(file, 0, FHDR);
-        println!("Reading file version from offset: 4");
-        let file_version = read_string_from_file(file, FHDR, FVER);
-
-        Nitf {
-            file_profile_name,
-            file_version,
-            ..self
+    pub lish: usize,
+    pub li: usize,
+    pub nums: usize,
+    pub lssh: usize,
+    pub ls: usize,
+    pub numl: usize,
+    pub llsh: usize,
+    pub ll: usize,
+    pub numt: usize,
+    pub ltsh: usize,
+    pub lt: usize,
+    pub numdes: usize,
+    pub ldsh: usize,
+    pub ldnnn: usize,
+    pub numres: usize,
+    pub lresh: usize,
+    pub lre: usize,
+    pub udhdl: usize,
+    pub udhofl: usize,
+    pub udhd: usize,
+    pub xhdl: usize,
+    pub xhdlofl: usize,
+    pub xhd: usize,
+}
+
+/// Byte offsets of the start of each group of segments. NITF 2.0 has symbol and
+/// label segments where 2.1 has graphic segments.
+#[derive(Default, Debug, Clone)]
+pub struct Segments {
+    pub image_segments: usize,
+    pub symbol_segments: usize,
+    pub label_segments: usize,
+    pub text_segments: usize,
+    pub data_extension_segments: usize,
+    pub reserved_extension_segments: usize,
+}
+
+/// Decoded NITF 2.0 file header values.
+///
+/// The segment counts (NUMI, NUMS, NUML, ...) are the lengths of the segment length
+/// tables, and UDHDL/XHDL are implied by the length of `udhd`/`xhd`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FileHeader {
+    pub fhdr: String,
+    pub fver: String,
+    pub clevel: usize,
+    pub stype: String,
+    pub ostaid: String,
+    pub fdt: Option<NaiveDateTime>,
+    pub ftitle: String,
+    pub security: SecurityGroup,
+    pub fscop: usize,
+    pub fscpys: usize,
+    pub encryp: String,
+    pub oname: String,
+    pub ophone: String,
+    pub fl: usize,
+    pub hl: usize,
+    pub images: Vec<SegmentLength>,
+    pub symbols: Vec<SegmentLength>,
+    pub labels: Vec<SegmentLength>,
+    pub texts: Vec<SegmentLength>,
+    pub des: Vec<SegmentLength>,
+    pub res: Vec<SegmentLength>,
+    pub udhofl: usize,
+    pub udhd: Vec<u8>,
+    pub xhdlofl: usize,
+    pub xhd: Vec<u8>,
+}
+
+impl FileHeader {
+    /// Reads the whole file header in one go, HL bytes from the start of the file.
+    /// HL moves with FSDEVT, so FSDWNG is read first to find it.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<FileHeader> {
+        use NitfHeader20::{self as N, *};
+        let fsdwng = read_bytes(reader, N::fixed_offset(FSDWNG), N::get_value(FSDWNG))?;
+        let mut hl_offset = N::fixed_offset(HL);
+        if fsdwng != DOWNGRADE_EVENT.as_bytes() {
+            hl_offset -= N::get_value(FSDEVT);
+        }
+        let hl = read_int(reader, hl_offset, HL)?;
+        FileHeader::from_bytes(&read_bytes(reader, 0, hl)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<FileHeader> {
+        use NitfHeader20::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut header = FileHeader {
+            fhdr: c.read_string(FHDR)?,
+            fver: c.read_string(FVER)?,
+            clevel: c.read_int(CLEVEL)?,
+            stype: c.read_string(STYPE)?,
+            ostaid: c.read_string(OSTAID)?,
+            fdt: read_date_time(&mut c, FDT)?,
+            ftitle: c.read_string(FTITLE)?,
+            security: SecurityGroup::read(&mut c)?,
+            fscop: c.read_int(FSCOP)?,
+            fscpys: c.read_int(FSCPYS)?,
+            encryp: c.read_string(ENCRYP)?,
+            oname: c.read_string(ONAME)?,
+            ophone: c.read_string(OPHONE)?,
+            fl: c.read_int(FL)?,
+            hl: c.read_int(HL)?,
+            ..Default::default()
+        };
+        header.images = read_length_table(&mut c, NUMI, LISHNNN, LINNN)?;
+        header.symbols = read_length_table(&mut c, NUMS, LSSHNNN, LSNNN)?;
+        header.labels = read_length_table(&mut c, NUML, LLSHNNN, LLNNN)?;
+        header.texts = read_length_table(&mut c, NUMT, LTSHNNN, LTNNN)?;
+        header.des = read_length_table(&mut c, NUMDES, LDSHNNN, LDNNN)?;
+        header.res = read_length_table(&mut c, NUMRES, LRESHNNN, LRENNN)?;
+        (header.udhofl, header.udhd) = c.read_extension(UDHDL, UDHOFL)?;
+        (header.xhdlofl, header.xhd) = c.read_extension(XHDL, XHDLOFL)?;
+        Ok(header)
+    }
+
+    pub fn numi(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn nums(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn numl(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn numt(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn numdes(&self) -> usize {
+        self.des.len()
+    }
+
+    pub fn numres(&self) -> usize {
+        self.res.len()
+    }
+
+    /// The TREs of UDHD followed by those of XHD.
+    pub fn tres(&self) -> impl Iterator<Item = Result<Tre>> + '_ {
+        TreIter::new(&self.udhd).chain(TreIter::new(&self.xhd))
+    }
+
+    /// Byte offsets of every header field, worked out from FSDWNG and the segment counts.
+    pub fn offsets(&self) -> Header {
+        use NitfHeader20::{self as N, *};
+        let table_length = |count: usize, sh: NitfHeader20, data: NitfHeader20| {
+            count * (N::get_value(sh) + N::get_value(data))
+        };
+        //Everything from FSCOP to NUMI sits FSDEVT bytes further along when FSDEVT is present
+        let fsdevt = self.security.devt.as_ref().map(|_| N::fixed_offset(FSDEVT));
+        let devt_length = if fsdevt.is_some() { N::get_value(FSDEVT) } else { 0 };
+        let moved = |target: NitfHeader20| N::fixed_offset(target) - N::get_value(FSDEVT) + devt_length;
+        let numi = moved(NUMI);
+        let nums = numi + N::get_value(NUMI) + table_length(self.numi(), LISHNNN, LINNN);
+        let numl = nums + N::get_value(NUMS) + table_length(self.nums(), LSSHNNN, LSNNN);
+        let numt = numl + N::get_value(NUML) + table_length(self.numl(), LLSHNNN, LLNNN);
+        let numdes = numt + N::get_value(NUMT) + table_length(self.numt(), LTSHNNN, LTNNN);
+        let numres = numdes + N::get_value(NUMDES) + table_length(self.numdes(), LDSHNNN, LDNNN);
+        let udhdl = numres + N::get_value(NUMRES) + table_length(self.numres(), LRESHNNN, LRENNN);
+        let udhd_length = if self.udhd.is_empty() { 0 } else { N::get_value(UDHOFL) + self.udhd.len() };
+        let xhdl = udhdl + N::get_value(UDHDL) + udhd_length;
+        Header {
+            fhdr: N::fixed_offset(FHDR),
+            fver: N::fixed_offset(FVER),
+            clevel: N::fixed_offset(CLEVEL),
+            stype: N::fixed_offset(STYPE),
+            ostaid: N::fixed_offset(OSTAID),
+            fdt: N::fixed_offset(FDT),
+            ftitle: N::fixed_offset(FTITLE),
+            fsclas: N::fixed_offset(FSCLAS),
+            fscode: N::fixed_offset(FSCODE),
+            fsctlh: N::fixed_offset(FSCTLH),
+            fsrel: N::fixed_offset(FSREL),
+            fscaut: N::fixed_offset(FSCAUT),
+            fsctln: N::fixed_offset(FSCTLN),
+            fsdwng: N::fixed_offset(FSDWNG),
+            fsdevt,
+            fscop: moved(FSCOP),
+            fscpys: moved(FSCPYS),
+            encryp: moved(ENCRYP),
+            oname: moved(ONAME),
+            ophone: moved(OPHONE),
+            fl: moved(FL),
+            hl: moved(HL),
+            numi,
+            lish: numi + N::get_value(NUMI),
+            li: numi + N::get_value(NUMI) + N::get_value(LISHNNN),
+            nums,
+            lssh: nums + N::get_value(NUMS),
+            ls: nums + N::get_value(NUMS) + N::get_value(LSSHNNN),
+            numl,
+            llsh: numl + N::get_value(NUML),
+            ll: numl + N::get_value(NUML) + N::get_value(LLSHNNN),
+            numt,
+            ltsh: numt + N::get_value(NUMT),
+            lt: numt + N::get_value(NUMT) + N::get_value(LTSHNNN),
+            numdes,
+            ldsh: numdes + N::get_value(NUMDES),
+            ldnnn: numdes + N::get_value(NUMDES) + N::get_value(LDSHNNN),
+            numres,
+            lresh: numres + N::get_value(NUMRES),
+            lre: numres + N::get_value(NUMRES) + N::get_value(LRESHNNN),
+            udhdl,
+            udhofl: udhdl + N::get_value(UDHDL),
+            udhd: udhdl + N::get_value(UDHDL) + N::get_value(UDHOFL),
+            xhdl,
+            xhdlofl: xhdl + N::get_value(XHDL),
+            xhd: xhdl + N::get_value(XHDL) + N::get_value(XHDLOFL),
         }
     }
 
-    pub fn load_v02_00(self) -> Nitf {
-        Nitf {
-            fhdr: 9,
-            clevel: 2,
-            stype: 4,
-            ostaid: 10,
-            fdt: 14,
-            ftitle: 80,
-            fsclas: 1,
-            fscode: 40,
-            fsctlh: 40,
-            fsrel: 40,
-            fscaut: 20,
-            fsctln: 20,
-            fsdwng: 6,
-            fsdevt: 40,
-            fscop: 5,
-            fscpys: 5,
-            encryp: 1,
-            oname: 27,
-            ophone: 18,
-            fl: 12,
-            hl: 6,
-            numi: 3,
-            lish_nnn: 6,
-            li_nnn: 10,
-            ..self
+    /// Byte offsets of the start of each group of segments, worked out from the length tables.
+    pub fn segment_offsets(&self) -> Segments {
+        let total = |table: &[SegmentLength]| table.iter().map(SegmentLength::total).sum::<usize>();
+        let image_segments = self.hl;
+        let symbol_segments = image_segments + total(&self.images);
+        let label_segments = symbol_segments + total(&self.symbols);
+        let text_segments = label_segments + total(&self.labels);
+        let data_extension_segments = text_segments + total(&self.texts);
+        let reserved_extension_segments = data_extension_segments + total(&self.des);
+        Segments {
+            image_segments,
+            symbol_segments,
+            label_segments,
+            text_segments,
+            data_extension_segments,
+            reserved_extension_segments,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Default, Debug)]
+pub struct Nitf20 {
+    pub header: Header,
+    pub file_header: FileHeader,
+    pub segments: Segments,
+    pub images: Vec<ImageSubheader>,
+    pub symbols: Vec<SymbolSubheader>,
+    pub labels: Vec<LabelSubheader>,
+    pub texts: Vec<TextSubheader>,
+    pub des: Vec<DesSubheader>,
+}
+
+impl Nitf20 {
+    pub fn load_v02_00<R: Read + Seek>(reader: &mut R) -> Result<Nitf20> {
+        #[cfg(all(debug_assertions, not(test)))]
+        println!("Loading NITF 02.00 file.");
+        let file_header = FileHeader::read(reader)?;
+        let mut nitf = Nitf20 {
+            header: file_header.offsets(),
+            segments: file_header.segment_offsets(),
+            file_header,
+            ..Default::default()
+        };
+        #[cfg(all(debug_assertions, not(test)))]
+        println!("Succesfully retrieved header offsets.");
+        nitf.images = nitf.read_image_subheaders(reader)?;
+        let (segments, header) = (&nitf.segments, &nitf.file_header);
+        nitf.symbols = read_subheaders(reader, segments.symbol_segments, &header.symbols, SymbolSubheader::read)?;
+        nitf.labels = read_subheaders(reader, segments.label_segments, &header.labels, LabelSubheader::read)?;
+        nitf.texts = read_subheaders(reader, segments.text_segments, &header.texts, TextSubheader::read)?;
+        nitf.des = read_subheaders(reader, segments.data_extension_segments, &header.des, DesSubheader::read)?;
+        #[cfg(all(debug_assertions, not(test)))]
+        {
+            println!("************************************************");
+            println!("Image Segments: {}", nitf.segments.image_segments);
+            println!("Symbol Segments: {}", nitf.segments.symbol_segments);
+            println!("Label Segments: {}", nitf.segments.label_segments);
+            println!("Text Segments: {}", nitf.segments.text_segments);
+            println!(
+                "Data Extension Segments: {}",
+                nitf.segments.data_extension_segments
+            );
+            println!(
+                "Reserved Extension Segments: {}",
+                nitf.segments.reserved_extension_segments
+            );
+            println!("nitf FL: {}", nitf.file_header.fl);
+            println!("************************************************");
+        }
+        Ok(nitf)
+    }
+
+    pub fn read_image_subheaders<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<ImageSubheader>> {
+        read_subheaders(reader, self.segments.image_segments, &self.file_header.images, ImageSubheader::read)
+    }
+}
+
+/// Reads the subheader of each segment of one type, the first starting at `offset`.
+fn read_subheaders<R: Read + Seek, T>(
+    reader: &mut R,
+    offset: usize,
+    lengths: &[SegmentLength],
+    read: fn(&mut R, usize, usize) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut subheader_offset = offset;
+    let mut subheaders = Vec::with_capacity(lengths.len());
+    for length in lengths {
+        subheaders.push(read(reader, subheader_offset, length.subheader)?);
+        subheader_offset += length.total();
+    }
+    Ok(subheaders)
+}
+
+#[derive(Debug, Copy, Clone)]
 pub enum NitfHeader20 {
     FHDR,
     FVER,
@@ -107,25 +352,16 @@ pub enum NitfHeader20 {
     FDT,
     FTITLE,
     FSCLAS,
-    FSCLSY,
     FSCODE,
     FSCTLH,
     FSREL,
-    FSDCTP,
-    FSDCDT,
-    FSDCXM,
-    FSDG,
-    FSDGDT,
-    FSCLTX,
-    FSCATP,
     FSCAUT,
-    FSCRSN,
-    FSSRDT,
     FSCTLN,
+    FSDWNG,
+    FSDEVT,
     FSCOP,
     FSCPYS,
     ENCRYP,
-    FBKGC,
     ONAME,
     OPHONE,
     FL,
@@ -136,193 +372,331 @@ pub enum NitfHeader20 {
     NUMS,
     LSSHNNN,
     LSNNN,
-    NUMX,
+    NUML,
+    LLSHNNN,
+    LLNNN,
     NUMT,
     LTSHNNN,
     LTNNN,
     NUMDES,
+    LDSHNNN,
+    LDNNN,
     NUMRES,
+    LRESHNNN,
+    LRENNN,
     UDHDL,
+    UDHOFL,
+    UDHD,
     XHDL,
+    XHDLOFL,
+    XHD,
 }
 
 impl NitfHeader20 {
     pub fn values() -> &'static [usize] {
         &[
-            4, 5, 2, 4, 10, 14, 80, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 5, 5, 1,
-            3, 24, 18, 12, 6, 3, 6, 10, 3, 4, 6, 3, 3, 4, 5, 3, 3, 5, 5,
+            4, 5, 2, 4, 10, 14, 80, 1, 40, 40, 40, 20, 20, 6, 40, 5, 5, 1, 27, 18, 12, 6, 3, 6, 10,
+            3, 4, 6, 3, 4, 3, 3, 4, 5, 3, 4, 9, 3, 4, 7, 5, 3, 0, 5, 3, 0,
         ]
     }
+    pub fn as_str(&self) -> &'static str {
+        use NitfHeader20::*;
+        match self {
+            FHDR => "FHDR",
+            FVER => "FVER",
+            CLEVEL => "CLEVEL",
+            STYPE => "STYPE",
+            OSTAID => "OSTAID",
+            FDT => "FDT",
+            FTITLE => "FTITLE",
+            FSCLAS => "FSCLAS",
+            FSCODE => "FSCODE",
+            FSCTLH => "FSCTLH",
+            FSREL => "FSREL",
+            FSCAUT => "FSCAUT",
+            FSCTLN => "FSCTLN",
+            FSDWNG => "FSDWNG",
+            FSDEVT => "FSDEVT",
+            FSCOP => "FSCOP",
+            FSCPYS => "FSCPYS",
+            ENCRYP => "ENCRYP",
+            ONAME => "ONAME",
+            OPHONE => "OPHONE",
+            FL => "FL",
+            HL => "HL",
+            NUMI => "NUMI",
+            LISHNNN => "LISHNNN",
+            LINNN => "LINNN",
+            NUMS => "NUMS",
+            LSSHNNN => "LSSHNNN",
+            LSNNN => "LSNNN",
+            NUML => "NUML",
+            LLSHNNN => "LLSHNNN",
+            LLNNN => "LLNNN",
+            NUMT => "NUMT",
+            LTSHNNN => "LTSHNNN",
+            LTNNN => "LTNNN",
+            NUMDES => "NUMDES",
+            LDSHNNN => "LDSHNNN",
+            LDNNN => "LDNNN",
+            NUMRES => "NUMRES",
+            LRESHNNN => "LRESHNNN",
+            LRENNN => "LRENNN",
+            UDHDL => "UDHDL",
+            UDHOFL => "UDHOFL",
+            UDHD => "UDHD",
+            XHDL => "XHDL",
+            XHDLOFL => "XHDLOFL",
+            XHD => "XHD",
+        }
+    }
     pub fn get_value(target: NitfHeader20) -> usize {
         let index = target as usize;
         NitfHeader20::values()[index]
     }
-    pub fn get_offset(target: NitfHeader20, nitf_file: Option<&File>) -> usize {
-        use NitfHeader20::{self as N, *};
-        match target {
-            //Graphic Segment offsets
-            NUMS => {
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                let base_offset = N::get_offset(NUMI, None);
-                let num_images = read_int_from_file(nitf_file.expect("Dev Error, this should not be called with no file."), base_offset, N::get_value(NUMI));
-                base_offset
-                    + N::get_value(NUMI)
-                    + (num_images * (N::get_value(LISHNNN) + N::get_value(LINNN)))
-            }
-            LSSHNNN  => {
-                println!("Warning: This will only return the offset of the FIRST segment. Use get_<segment>_offset() to get the offset of a specific segment.");
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                N::get_offset(NUMS, nitf_file) + N::get_value(NUMS)
-            }
-            LSNNN => {
-                println!("Warning: This will only return the offset of the FIRST segment. Use get_<segment>_offset() to get the offset of a specific segment.");
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                N::get_offset(LSSHNNN, nitf_file) + N::get_value(LSSHNNN)
-            }
-            //NUMX IS RESERVED FOR FUTURE USE
-            NUMX => {
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                let lsshnnn_offset = N::get_offset(LSSHNNN, nitf_file);
-                let num_segments = read_int_from_file(nitf_file.expect("You must provide a file to get the offset of this header field."), N::get_offset(NUMS, nitf_file), N::get_value(NUMS));
-                lsshnnn_offset + (num_segments * (N::get_value(LSSHNNN) + N::get_value(LSNNN)))
-            }
-            //Text Segment offsets
-            NUMT => {
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                let numx_offset = N::get_offset(NUMX, nitf_file);
-                numx_offset + N::get_value(NUMX)
-            }
-            LTSHNNN => {
-                println!("Warning: This will only return the offset of the FIRST segment. Use get_<segment>_offset() to get the offset of a specific segment.");
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                N::get_offset(NUMT, nitf_file) + N::get_value(NUMT)
-            }
-            LTNNN => {
-                println!("Warning: This will only return the offset of the FIRST segment. Use get_<segment>_offset() to get the offset of a specific segment.");
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                N::get_offset(LTSHNNN, nitf_file) + N::get_value(LTSHNNN)
-            }
-            //Anything after the dynamic segments
-            XHDL | UDHDL | NUMRES | NUMDES => {
-                if nitf_file.is_none() {panic!("You must provide a file to get the offset of this header field.");}
-                let hl_offset = N::get_offset(HL, None);
-                let header_length = read_int_from_file(
-                    nitf_file.expect("You must provide a file to get the offset of this header field."),
-                    hl_offset,
-                    N::get_value(HL),
-                );
-                let index = target as usize;
-                header_length - N::values()[index..].iter().sum::<usize>()
-            }
-            //Anything before NUMS
-            _ => {
-                let index = target as usize;
-                N::values()[..index].iter().sum()
-            }
+    /// Byte offset of a header field. Fields after FSDWNG move with FSDEVT and the
+    /// segment counts, so the file header is read from `reader` to work them out.
+    /// FSDEVT itself is reported where it would be, even when the file leaves it out.
+    pub fn get_offset<R: Read + Seek>(target: NitfHeader20, reader: &mut R) -> Result<usize> {
+        use NitfHeader20::*;
+        if target as usize <= FSDEVT as usize {
+            return Ok(NitfHeader20::fixed_offset(target));
         }
+        let offsets = FileHeader::read(reader)?.offsets();
+        let offset = match target {
+            FSCOP => offsets.fscop,
+            FSCPYS => offsets.fscpys,
+            ENCRYP => offsets.encryp,
+            ONAME => offsets.oname,
+            OPHONE => offsets.ophone,
+            FL => offsets.fl,
+            HL => offsets.hl,
+            //Image Segment offsets
+            NUMI => offsets.numi,
+            LISHNNN => offsets.lish,
+            LINNN => offsets.li,
+            //Symbol Segment offsets
+            NUMS => offsets.nums,
+            LSSHNNN => offsets.lssh,
+            LSNNN => offsets.ls,
+            //Label Segment offsets
+            NUML => offsets.numl,
+            LLSHNNN => offsets.llsh,
+            LLNNN => offsets.ll,
+            //Text Segment offsets
+            NUMT => offsets.numt,
+            LTSHNNN => offsets.ltsh,
+            LTNNN => offsets.lt,
+            //Data Extension Segment offsets
+            NUMDES => offsets.numdes,
+            LDSHNNN => offsets.ldsh,
+            LDNNN => offsets.ldnnn,
+            //Reserved Extension Segment offsets
+            NUMRES => offsets.numres,
+            LRESHNNN => offsets.lresh,
+            LRENNN => offsets.lre,
+            //User Defined Header offsets
+            UDHDL => offsets.udhdl,
+            UDHOFL => offsets.udhofl,
+            UDHD => offsets.udhd,
+            //Extended Header Data offsets
+            XHDL => offsets.xhdl,
+            XHDLOFL => offsets.xhdlofl,
+            XHD => offsets.xhd,
+            //Anything up to FSDEVT
+            _ => NitfHeader20::fixed_offset(target),
+        };
+        Ok(offset)
     }
 
-    pub fn get_image_header_field_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");}
-        let lish_nnn_offset = N::get_offset(LISHNNN, nitf_file);
-        lish_nnn_offset + (image_num as usize * (N::get_value(LISHNNN) + N::get_value(LINNN)))
+    /// Offset of a field as if FSDEVT were present. Only fields up to FSDEVT are
+    /// guaranteed to be there, use `get_offset` for anything after it.
+    pub fn fixed_offset(target: NitfHeader20) -> usize {
+        NitfHeader20::values()[..target as usize].iter().sum()
     }
 
-    pub fn get_image_data_field_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");}
-        let lish_nnn_offset = N::get_image_header_field_offset(nitf_file, image_num);
-        lish_nnn_offset + N::get_value(LISHNNN)
+    pub fn get_image_header_field_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        use NitfHeader20::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Image", header.offsets().lish, header.numi(), image_num, LISHNNN, LINNN)
     }
 
-    pub fn get_graphic_header_field_offset(nitf_file: Option<&File>, graphic_num: u64) -> usize {
+    pub fn get_image_data_field_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
         use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics = read_int_from_file(file, N::get_offset(NUMS, nitf_file), N::get_value(NUMS));
-        if graphic_num + 1 > num_graphics as u64 {panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");}
-        let lssh_nnn_offset = N::get_offset(LSSHNNN, nitf_file);
-        lssh_nnn_offset + (graphic_num as usize * (N::get_value(LSSHNNN) + N::get_value(LSNNN)))
+        Ok(N::get_image_header_field_offset(reader, image_num)? + N::get_value(LISHNNN))
+    }
+
+    pub fn get_symbol_header_field_offset<R: Read + Seek>(reader: &mut R, symbol_num: u64) -> Result<usize> {
+        use NitfHeader20::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Symbol", header.offsets().lssh, header.nums(), symbol_num, LSSHNNN, LSNNN)
     }
 
-    pub fn get_graphic_data_field_offset(nitf_file: Option<&File>, graphic_num: u64) -> usize {
+    pub fn get_symbol_data_field_offset<R: Read + Seek>(reader: &mut R, symbol_num: u64) -> Result<usize> {
         use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics = read_int_from_file(file, N::get_offset(NUMS, nitf_file), N::get_value(NUMS));
-        if graphic_num + 1 > num_graphics as u64 {panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");}
-        let lssh_nnn_offset = N::get_graphic_header_field_offset(nitf_file, graphic_num);
-        lssh_nnn_offset + N::get_value(LSSHNNN) 
+        Ok(N::get_symbol_header_field_offset(reader, symbol_num)? + N::get_value(LSSHNNN))
+    }
+
+    pub fn get_label_header_field_offset<R: Read + Seek>(reader: &mut R, label_num: u64) -> Result<usize> {
+        use NitfHeader20::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Label", header.offsets().llsh, header.numl(), label_num, LLSHNNN, LLNNN)
     }
 
-    pub fn get_text_header_field_offset(nitf_file: Option<&File>, text_num: u64) -> usize {
+    pub fn get_label_data_field_offset<R: Read + Seek>(reader: &mut R, label_num: u64) -> Result<usize> {
         use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_file(file, N::get_offset(NUMT, nitf_file), N::get_value(NUMT));
-        if text_num + 1 > num_text as u64 {panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");}
-        let ltsh_nnn_offset = N::get_offset(LTSHNNN, nitf_file);
-        ltsh_nnn_offset + (text_num as usize * (N::get_value(LTSHNNN) + N::get_value(LTNNN)))
+        Ok(N::get_label_header_field_offset(reader, label_num)? + N::get_value(LLSHNNN))
     }
 
-    pub fn get_text_data_field_offset(nitf_file: Option<&File>, text_num: u64) -> usize {
+    pub fn get_text_header_field_offset<R: Read + Seek>(reader: &mut R, text_num: u64) -> Result<usize> {
+        use NitfHeader20::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Text", header.offsets().ltsh, header.numt(), text_num, LTSHNNN, LTNNN)
+    }
+
+    pub fn get_text_data_field_offset<R: Read + Seek>(reader: &mut R, text_num: u64) -> Result<usize> {
         use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_file(file, N::get_offset(NUMT, nitf_file), N::get_value(NUMT));
-        if text_num + 1 > num_text as u64 {panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");}
-        let ltsh_nnn_offset = N::get_text_header_field_offset(nitf_file, text_num);
-        ltsh_nnn_offset + N::get_value(LTSHNNN) 
+        Ok(N::get_text_header_field_offset(reader, text_num)? + N::get_value(LTSHNNN))
+    }
+
+    pub fn get_des_header_field_offset<R: Read + Seek>(reader: &mut R, des_num: u64) -> Result<usize> {
+        use NitfHeader20::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("DES", header.offsets().ldsh, header.numdes(), des_num, LDSHNNN, LDNNN)
     }
 
-    pub fn get_image_subheader_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
+    pub fn get_des_data_field_offset<R: Read + Seek>(reader: &mut R, des_num: u64) -> Result<usize> {
         use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");}
-        let header_length = read_int_from_file(file, N::get_offset(HL, nitf_file), N::get_value(HL));
-        if image_num == 0 { return header_length }
-        let mut offset = header_length;
-        for i in 0..image_num {
-            offset += read_int_from_file(file, N::get_image_header_field_offset(nitf_file, i), N::get_value(LISHNNN))
-                + read_int_from_file(file, N::get_image_data_field_offset(nitf_file, i), N::get_value(LINNN));
+        Ok(N::get_des_header_field_offset(reader, des_num)? + N::get_value(LDSHNNN))
+    }
+
+    pub fn get_image_subheader_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        if image_num >= header.numi() as u64 {
+            return Err(NitfError::IndexOutOfRange {
+                segment: "Image",
+                index: image_num as usize,
+                count: header.numi(),
+            });
         }
-        offset
+        segment_offset("Image", header.hl, &header.images, image_num)
     }
 
-    pub fn get_image_data_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader20::{self as N, *};
-        let file = nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");}
-        let image_subheader_length = read_int_from_file(file, N::get_image_subheader_offset(nitf_file, image_num), N::get_value(LISHNNN));
-        N::get_image_subheader_offset(nitf_file, image_num) + image_subheader_length
+    pub fn get_image_data_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let subheader_offset = NitfHeader20::get_image_subheader_offset(reader, image_num)?;
+        Ok(subheader_offset + header.images[image_num as usize].subheader)
+    }
+}
+
+impl Field for NitfHeader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfHeader20::get_value(*self)
+    }
+}
+
+/// Reads a DDHHMMSSZMONYY field, the date format used by NITF 2.0 (FDT, IDATIM).
+/// Blank or unparseable fields give `None`.
+fn read_date_time<F: Field>(cursor: &mut FieldCursor, field: F) -> Result<Option<NaiveDateTime>> {
+    Ok(NaiveDateTime::parse_from_str(&cursor.read_string(field)?, "%d%H%M%SZ%b%y").ok())
+}
+
+/// The security fields shared by the file header and every NITF 2.0 segment subheader.
+/// DEVT is only present when DWNG is 999998 (downgrade on an event).
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SecurityGroup {
+    pub clas: String,
+    pub code: String,
+    pub ctlh: String,
+    pub rel: String,
+    pub caut: String,
+    pub ctln: String,
+    pub dwng: String,
+    pub devt: Option<String>,
+}
+
+impl SecurityGroup {
+    pub fn values() -> &'static [usize] {
+        &[1, 40, 40, 40, 20, 20, 6, 40]
+    }
+
+    pub fn read(cursor: &mut FieldCursor) -> Result<SecurityGroup> {
+        use SecurityField::*;
+        let mut security = SecurityGroup {
+            clas: cursor.read_string(CLAS)?,
+            code: cursor.read_string(CODE)?,
+            ctlh: cursor.read_string(CTLH)?,
+            rel: cursor.read_string(REL)?,
+            caut: cursor.read_string(CAUT)?,
+            ctln: cursor.read_string(CTLN)?,
+            dwng: cursor.read_string(DWNG)?,
+            devt: None,
+        };
+        if security.dwng == DOWNGRADE_EVENT {
+            security.devt = Some(cursor.read_string(DEVT)?);
+        }
+        Ok(security)
+    }
+}
+
+/// The fields of a [`SecurityGroup`], without the two letter prefix (FS, IS, SS, LS, ...) of the segment they are in.
+#[derive(Debug, Copy, Clone)]
+pub enum SecurityField {
+    CLAS,
+    CODE,
+    CTLH,
+    REL,
+    CAUT,
+    CTLN,
+    DWNG,
+    DEVT,
+}
+
+impl SecurityField {
+    pub fn as_str(&self) -> &'static str {
+        use SecurityField::*;
+        match self {
+            CLAS => "CLAS",
+            CODE => "CODE",
+            CTLH => "CTLH",
+            REL => "REL",
+            CAUT => "CAUT",
+            CTLN => "CTLN",
+            DWNG => "DWNG",
+            DEVT => "DEVT",
+        }
     }
 }
 
+impl Field for SecurityField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        SecurityGroup::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
 pub enum NitfImageSubheader20 {
     IM,
-    IID1,
-    IIDATIM,
+    IID,
+    IDATIM,
     TGTID,
-    IID2,
+    ITITLE,
     ISCLAS,
-    ISCLSY,
     ISCODE,
     ISCTLH,
     ISREL,
-    ISDCTP,
-    ISDCDT,
-    ISDCXM,
-    ISDG,
-    ISDGDT,
-    ISCLTX,
-    ISCATP,
     ISCAUT,
-    ISCRSN,
-    ISSRDT,
     ISCTLN,
+    ISDWNG,
+    ISDEVT,
     ENCRYP,
     ISORCE,
     NROWS,
@@ -333,15 +707,19 @@ pub enum NitfImageSubheader20 {
     ABPP,
     PJUST,
     ICORDS,
+    IGEOLO,
     NICOM,
     ICOMNNN,
     IC,
+    COMRAT,
     NBANDS,
     IREPBANDNNN,
     ISUBCATNNN,
     IFCNNN,
     IMFLTNNN,
     NLUTSNNN,
+    NELUTNNN,
+    LUTDNNNM,
     ISYNC,
     IMODE,
     NBPR,
@@ -354,162 +732,748 @@ pub enum NitfImageSubheader20 {
     ILOC,
     IMAG,
     UDIDL,
+    UDOFL,
+    UDID,
     IXSHDL,
+    IXSOFL,
+    IXSHD,
+}
+
+impl NitfImageSubheader20 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 10, 14, 17, 80, 1, 40, 40, 40, 20, 20, 6, 40, 1, 42, 8, 8, 3, 8, 8, 2, 1, 1, 60, 1,
+            80, 2, 4, 1, 2, 6, 1, 3, 1, 5, 0, 1, 1, 4, 4, 4, 4, 2, 3, 3, 10, 4, 5, 3, 0, 5, 3, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfImageSubheader20::*;
+        match self {
+            IM => "IM",
+            IID => "IID",
+            IDATIM => "IDATIM",
+            TGTID => "TGTID",
+            ITITLE => "ITITLE",
+            ISCLAS => "ISCLAS",
+            ISCODE => "ISCODE",
+            ISCTLH => "ISCTLH",
+            ISREL => "ISREL",
+            ISCAUT => "ISCAUT",
+            ISCTLN => "ISCTLN",
+            ISDWNG => "ISDWNG",
+            ISDEVT => "ISDEVT",
+            ENCRYP => "ENCRYP",
+            ISORCE => "ISORCE",
+            NROWS => "NROWS",
+            NCOLS => "NCOLS",
+            PVTYPE => "PVTYPE",
+            IREP => "IREP",
+            ICAT => "ICAT",
+            ABPP => "ABPP",
+            PJUST => "PJUST",
+            ICORDS => "ICORDS",
+            IGEOLO => "IGEOLO",
+            NICOM => "NICOM",
+            ICOMNNN => "ICOMNNN",
+            IC => "IC",
+            COMRAT => "COMRAT",
+            NBANDS => "NBANDS",
+            IREPBANDNNN => "IREPBANDNNN",
+            ISUBCATNNN => "ISUBCATNNN",
+            IFCNNN => "IFCNNN",
+            IMFLTNNN => "IMFLTNNN",
+            NLUTSNNN => "NLUTSNNN",
+            NELUTNNN => "NELUTNNN",
+            LUTDNNNM => "LUTDNNNM",
+            ISYNC => "ISYNC",
+            IMODE => "IMODE",
+            NBPR => "NBPR",
+            NBPC => "NBPC",
+            NPPBH => "NPPBH",
+            NPPBV => "NPPBV",
+            NBPP => "NBPP",
+            IDLVL => "IDLVL",
+            IALVL => "IALVL",
+            ILOC => "ILOC",
+            IMAG => "IMAG",
+            UDIDL => "UDIDL",
+            UDOFL => "UDOFL",
+            UDID => "UDID",
+            IXSHDL => "IXSHDL",
+            IXSOFL => "IXSOFL",
+            IXSHD => "IXSHD",
+        }
+    }
+    pub fn get_value(target: NitfImageSubheader20) -> usize {
+        let index = target as usize;
+        NitfImageSubheader20::values()[index]
+    }
+}
+
+impl Field for NitfImageSubheader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfImageSubheader20::get_value(*self)
+    }
+}
+
+/// A parsed NITF 2.0 image subheader.
+///
+/// Laid out like the 2.1 subheader, except for the single IID and ITITLE fields, the
+/// shorter security group, the DDHHMMSSZMONYY date and NBANDS having no XBANDS escape.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ImageSubheader {
+    pub im: String,
+    pub iid: String,
+    pub idatim: Option<NaiveDateTime>,
+    pub tgtid: String,
+    pub ititle: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    pub isorce: String,
+    pub nrows: usize,
+    pub ncols: usize,
+    pub pvtype: String,
+    pub irep: String,
+    pub icat: String,
+    pub abpp: usize,
+    pub pjust: String,
+    pub icords: String,
+    pub igeolo: Option<String>,
+    pub icom: Vec<String>,
+    pub ic: String,
+    pub comrat: Option<String>,
+    pub bands: Vec<ImageBand>,
+    pub isync: usize,
+    pub imode: String,
+    pub nbpr: usize,
+    pub nbpc: usize,
+    pub nppbh: usize,
+    pub nppbv: usize,
+    pub nbpp: usize,
+    pub idlvl: usize,
+    pub ialvl: usize,
+    /// ILOC as (row, column); either may be negative.
+    pub iloc: (isize, isize),
+    pub imag: String,
+    pub udofl: usize,
+    pub udid: Vec<u8>,
+    pub ixsofl: usize,
+    pub ixshd: Vec<u8>,
 }
 
-// impl NitfImageSubheader20 {
-//     pub fn values() -> &'static [usize] {
-//         &[2, 10, 14, 17, 80, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 1, 42, 8, 8, 3, 4, 8, 2, 1, 1, 1, 80, 2, 1, 2, 6, 1, 3, 1, 1, 1, 4, 4, 4, 4, 2, 3, 3, 10, 4, 5, 5
-//         ]
-//     }
-//     pub fn get_value(target: NitfImageSubheader20) -> usize {
-//         let index = target as usize;
-//         NitfImageSubheader20::values()[index]
-//     }
-// pub fn get_offset(target: NitfImageSubheader20, nitf_file: Option<&File>) -> usize {
-//     match target {
-//         NitfImageSubheader20::IC => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of X segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader20::get_offset(NitfImageSubheader20::NICOM, None);
-//             let num_comments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::NICOM),
-//             );
-//             base_offset
-//                 + NitfImageSubheader20::get_value(NitfImageSubheader20::NICOM)
-//                 + (num_comments
-//                     * (NitfImageSubheader20::get_value(NitfImageSubheader20::ICOMNNN)))
-//         }
-//         NitfImageSubheader20::NUMX => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of Graphic segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader20::get_offset(NitfImageSubheader20::NUMS, Some(file));
-//             let num_segments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::NUMS),
-//             );
-//             base_offset
-//                 + NitfImageSubheader20::get_value(NitfImageSubheader20::NUMS)
-//                 + (num_segments
-//                     * (NitfImageSubheader20::get_value(NitfImageSubheader20::LSSHNNN)
-//                         + NitfImageSubheader20::get_value(NitfImageSubheader20::LSNNN)))
-//         }
-//         NitfImageSubheader20::NUMT => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of Text segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader20::get_offset(NitfImageSubheader20::NUMX, Some(file));
-//             let num_text_segments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::NUMX),
-//             );
-//             base_offset
-//                 + NitfImageSubheader20::get_value(NitfImageSubheader20::NUMX)
-//                 + (num_text_segments
-//                     * (NitfImageSubheader20::get_value(NitfImageSubheader20::LTSHNNN)
-//                         + NitfImageSubheader20::get_value(NitfImageSubheader20::LTNNN)))
-//         }
-//         NitfImageSubheader20::NUMDES => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of DESegments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader20::get_offset(NitfImageSubheader20::HL, None),
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader20::get_value(NitfImageSubheader20::XHDL)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::UDHDL)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::NUMRES)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::NUMDES))
-//         }
-//         NitfImageSubheader20::NUMRES => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader20::get_offset(NitfImageSubheader20::HL, None),
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader20::get_value(NitfImageSubheader20::XHDL)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::UDHDL)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::NUMRES))
-//         }
-//         NitfImageSubheader20::UDHDL => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader20::get_offset(NitfImageSubheader20::HL, None),
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader20::get_value(NitfImageSubheader20::XHDL)
-//                     + NitfImageSubheader20::get_value(NitfImageSubheader20::UDHDL))
-//         }
-//         NitfImageSubheader20::XHDL => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader20::get_offset(NitfImageSubheader20::HL, None),
-//                 NitfImageSubheader20::get_value(NitfImageSubheader20::HL),
-//             );
-//             header_length - NitfImageSubheader20::get_value(NitfImageSubheader20::XHDL)
-//         }
-//         NitfImageSubheader20::LISHNNN
-//         | NitfImageSubheader20::LINNN
-//         | NitfImageSubheader20::LSSHNNN
-//         | NitfImageSubheader20::LSNNN
-//         | NitfImageSubheader20::LTSHNNN
-//         | NitfImageSubheader20::LTNNN => {
-//             eprint!("You cannot get the single offset of N number of segments. Consider finding the offset of the number of segments and adding the size of the Subheader and Data length fields you are looking as many times as you need to get to the header offset you are looking for.");
-//             0
-//         }
-//         _ => {
-//             let index = target as usize;
-//             NitfImageSubheader20::values()[..index].iter().sum()
-//         }
-//     }
-//}
-//}
+impl ImageSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<ImageSubheader> {
+        ImageSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    /// Offset of IGEOLO within the subheader, which moves with ISDEVT.
+    pub fn igeolo_offset(&self) -> usize {
+        use NitfImageSubheader20::*;
+        let offset: usize = NitfImageSubheader20::values()[..IGEOLO as usize].iter().sum();
+        match self.security.devt {
+            Some(_) => offset,
+            None => offset - NitfImageSubheader20::values()[ISDEVT as usize],
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ImageSubheader> {
+        use NitfImageSubheader20::{self as I, *};
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = ImageSubheader {
+            im: c.read_string(IM)?,
+            iid: c.read_string(IID)?,
+            idatim: read_date_time(&mut c, IDATIM)?,
+            tgtid: c.read_string(TGTID)?,
+            ititle: c.read_string(ITITLE)?,
+            security: SecurityGroup::read(&mut c)?,
+            encryp: c.read_string(ENCRYP)?,
+            isorce: c.read_string(ISORCE)?,
+            nrows: c.read_int(NROWS)?,
+            ncols: c.read_int(NCOLS)?,
+            pvtype: c.read_string(PVTYPE)?,
+            irep: c.read_string(IREP)?,
+            icat: c.read_string(ICAT)?,
+            abpp: c.read_int(ABPP)?,
+            pjust: c.read_string(PJUST)?,
+            icords: c.read_string(ICORDS)?,
+            ..Default::default()
+        };
+        //IGEOLO is left out when ICORDS is N (no coordinates)
+        if sub.icords != "N" {
+            sub.igeolo = Some(c.read_string(IGEOLO)?);
+        }
+        let nicom = c.read_int(NICOM)?;
+        for _ in 0..nicom {
+            sub.icom.push(c.read_string(ICOMNNN)?);
+        }
+        sub.ic = c.read_string(IC)?;
+        //COMRAT is only present for compressed images
+        if sub.ic != "NC" && sub.ic != "NM" {
+            sub.comrat = Some(c.read_string(COMRAT)?);
+        }
+        let nbands = c.read_int(NBANDS)?;
+        for _ in 0..nbands {
+            let mut band = ImageBand {
+                irepband: c.read_string(IREPBANDNNN)?,
+                isubcat: c.read_string(ISUBCATNNN)?,
+                ifc: c.read_string(IFCNNN)?,
+                imflt: c.read_string(IMFLTNNN)?,
+                ..Default::default()
+            };
+            let nluts = c.read_int(NLUTSNNN)?;
+            if nluts > 0 {
+                band.nelut = c.read_int(NELUTNNN)?;
+                for _ in 0..nluts {
+                    band.lutd.push(c.read_bytes(band.nelut)?.to_vec());
+                }
+            }
+            sub.bands.push(band);
+        }
+        sub.isync = c.read_int(ISYNC)?;
+        sub.imode = c.read_string(IMODE)?;
+        sub.nbpr = c.read_int(NBPR)?;
+        sub.nbpc = c.read_int(NBPC)?;
+        sub.nppbh = c.read_int(NPPBH)?;
+        sub.nppbv = c.read_int(NPPBV)?;
+        sub.nbpp = c.read_int(NBPP)?;
+        sub.idlvl = c.read_int(IDLVL)?;
+        sub.ialvl = c.read_int(IALVL)?;
+        let iloc_offset = c.offset;
+        let iloc = c.read_bytes(I::get_value(ILOC))?;
+        sub.iloc = (
+            parse_signed(ILOC.as_str(), iloc_offset, &iloc[..5])?,
+            parse_signed(ILOC.as_str(), iloc_offset, &iloc[5..])?,
+        );
+        sub.imag = c.read_string(IMAG)?;
+        (sub.udofl, sub.udid) = c.read_extension(UDIDL, UDOFL)?;
+        (sub.ixsofl, sub.ixshd) = c.read_extension(IXSHDL, IXSOFL)?;
+        Ok(sub)
+    }
+
+    pub fn nbands(&self) -> usize {
+        self.bands.len()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.comrat.is_some()
+    }
+
+    /// The TREs of UDID followed by those of IXSHD.
+    pub fn tres(&self) -> impl Iterator<Item = Result<Tre>> + '_ {
+        TreIter::new(&self.udid).chain(TreIter::new(&self.ixshd))
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfSymbolSubheader20 {
+    SY,
+    SID,
+    SNAME,
+    SSCLAS,
+    SSCODE,
+    SSCTLH,
+    SSREL,
+    SSCAUT,
+    SSCTLN,
+    SSDWNG,
+    SSDEVT,
+    ENCRYP,
+    STYPE,
+    NLIPS,
+    NPIXPL,
+    NWDTH,
+    NBPP,
+    SDLVL,
+    SALVL,
+    SLOC,
+    SLOC2,
+    SCOLOR,
+    SNUM,
+    SROT,
+    NELUT,
+    DLUT,
+    SXSHDL,
+    SXSOFL,
+    SXSHD,
+}
+
+impl NitfSymbolSubheader20 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 10, 20, 1, 40, 40, 40, 20, 20, 6, 40, 1, 1, 4, 4, 4, 1, 3, 3, 10, 10, 1, 6, 3, 3,
+            0, 5, 3, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfSymbolSubheader20::*;
+        match self {
+            SY => "SY",
+            SID => "SID",
+            SNAME => "SNAME",
+            SSCLAS => "SSCLAS",
+            SSCODE => "SSCODE",
+            SSCTLH => "SSCTLH",
+            SSREL => "SSREL",
+            SSCAUT => "SSCAUT",
+            SSCTLN => "SSCTLN",
+            SSDWNG => "SSDWNG",
+            SSDEVT => "SSDEVT",
+            ENCRYP => "ENCRYP",
+            STYPE => "STYPE",
+            NLIPS => "NLIPS",
+            NPIXPL => "NPIXPL",
+            NWDTH => "NWDTH",
+            NBPP => "NBPP",
+            SDLVL => "SDLVL",
+            SALVL => "SALVL",
+            SLOC => "SLOC",
+            SLOC2 => "SLOC2",
+            SCOLOR => "SCOLOR",
+            SNUM => "SNUM",
+            SROT => "SROT",
+            NELUT => "NELUT",
+            DLUT => "DLUT",
+            SXSHDL => "SXSHDL",
+            SXSOFL => "SXSOFL",
+            SXSHD => "SXSHD",
+        }
+    }
+    pub fn get_value(target: NitfSymbolSubheader20) -> usize {
+        let index = target as usize;
+        NitfSymbolSubheader20::values()[index]
+    }
+}
+
+impl Field for NitfSymbolSubheader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfSymbolSubheader20::get_value(*self)
+    }
+}
+
+/// A parsed NITF 2.0 symbol subheader, the forerunner of the 2.1 graphic subheader.
+///
+/// STYPE is B (bitmap), C (CGM) or O (object). NLIPS, NPIXPL, NWDTH, NBPP and the LUT only
+/// describe bitmaps and are zero otherwise.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SymbolSubheader {
+    pub sy: String,
+    pub sid: String,
+    pub sname: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    pub stype: String,
+    pub nlips: usize,
+    pub npixpl: usize,
+    pub nwdth: usize,
+    pub nbpp: usize,
+    pub sdlvl: usize,
+    pub salvl: usize,
+    /// SLOC as (row, column), relative to the attachment level.
+    pub sloc: (isize, isize),
+    /// SLOC2 as (row, column), the second point of line and arrow objects.
+    pub sloc2: (isize, isize),
+    pub scolor: String,
+    pub snum: String,
+    /// Rotation in degrees counterclockwise.
+    pub srot: usize,
+    /// DLUT as RGB entries. NELUT is its length.
+    pub dlut: Vec<[u8; 3]>,
+    pub sxsofl: usize,
+    pub sxshd: Vec<u8>,
+}
+
+impl SymbolSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<SymbolSubheader> {
+        SymbolSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SymbolSubheader> {
+        use NitfSymbolSubheader20::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = SymbolSubheader {
+            sy: c.read_string(SY)?,
+            sid: c.read_string(SID)?,
+            sname: c.read_string(SNAME)?,
+            security: SecurityGroup::read(&mut c)?,
+            encryp: c.read_string(ENCRYP)?,
+            stype: c.read_string(STYPE)?,
+            nlips: c.read_int(NLIPS)?,
+            npixpl: c.read_int(NPIXPL)?,
+            nwdth: c.read_int(NWDTH)?,
+            nbpp: c.read_int(NBPP)?,
+            sdlvl: c.read_int(SDLVL)?,
+            salvl: c.read_int(SALVL)?,
+            sloc: c.read_location(SLOC)?,
+            sloc2: c.read_location(SLOC2)?,
+            scolor: c.read_string(SCOLOR)?,
+            snum: c.read_string(SNUM)?,
+            srot: c.read_int(SROT)?,
+            ..Default::default()
+        };
+        let nelut = c.read_int(NELUT)?;
+        sub.dlut = c.read_bytes(nelut * 3)?.chunks(3).map(|e| [e[0], e[1], e[2]]).collect();
+        (sub.sxsofl, sub.sxshd) = c.read_extension(SXSHDL, SXSOFL)?;
+        Ok(sub)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfLabelSubheader20 {
+    LA,
+    LID,
+    LSCLAS,
+    LSCODE,
+    LSCTLH,
+    LSREL,
+    LSCAUT,
+    LSCTLN,
+    LSDWNG,
+    LSDEVT,
+    ENCRYP,
+    LFS,
+    LCW,
+    LCH,
+    LDLVL,
+    LALVL,
+    LLOC,
+    LTC,
+    LBC,
+    LXSHDL,
+    LXSOFL,
+    LXSHD,
+}
+
+impl NitfLabelSubheader20 {
+    pub fn values() -> &'static [usize] {
+        &[2, 10, 1, 40, 40, 40, 20, 20, 6, 40, 1, 1, 2, 2, 3, 3, 10, 3, 3, 5, 3, 0]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfLabelSubheader20::*;
+        match self {
+            LA => "LA",
+            LID => "LID",
+            LSCLAS => "LSCLAS",
+            LSCODE => "LSCODE",
+            LSCTLH => "LSCTLH",
+            LSREL => "LSREL",
+            LSCAUT => "LSCAUT",
+            LSCTLN => "LSCTLN",
+            LSDWNG => "LSDWNG",
+            LSDEVT => "LSDEVT",
+            ENCRYP => "ENCRYP",
+            LFS => "LFS",
+            LCW => "LCW",
+            LCH => "LCH",
+            LDLVL => "LDLVL",
+            LALVL => "LALVL",
+            LLOC => "LLOC",
+            LTC => "LTC",
+            LBC => "LBC",
+            LXSHDL => "LXSHDL",
+            LXSOFL => "LXSOFL",
+            LXSHD => "LXSHD",
+        }
+    }
+    pub fn get_value(target: NitfLabelSubheader20) -> usize {
+        let index = target as usize;
+        NitfLabelSubheader20::values()[index]
+    }
+}
+
+impl Field for NitfLabelSubheader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
 
+    fn length(&self) -> usize {
+        NitfLabelSubheader20::get_value(*self)
+    }
+}
+
+/// A parsed NITF 2.0 label subheader. Labels were dropped in 2.1; the data is the label text.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct LabelSubheader {
+    pub la: String,
+    pub lid: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    /// Font style, blank for the default.
+    pub lfs: String,
+    /// Character width in pixels.
+    pub lcw: usize,
+    /// Character height in pixels.
+    pub lch: usize,
+    pub ldlvl: usize,
+    pub lalvl: usize,
+    /// LLOC as (row, column), relative to the attachment level.
+    pub lloc: (isize, isize),
+    /// Text colour as binary RGB.
+    pub ltc: [u8; 3],
+    /// Background colour as binary RGB.
+    pub lbc: [u8; 3],
+    pub lxsofl: usize,
+    pub lxshd: Vec<u8>,
+}
+
+impl LabelSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<LabelSubheader> {
+        LabelSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<LabelSubheader> {
+        use NitfLabelSubheader20::*;
+        let mut c = FieldCursor::new(bytes);
+        let la = c.read_string(LA)?;
+        let lid = c.read_string(LID)?;
+        let security = SecurityGroup::read(&mut c)?;
+        let encryp = c.read_string(ENCRYP)?;
+        let lfs = c.read_string(LFS)?;
+        let lcw = c.read_int(LCW)?;
+        let lch = c.read_int(LCH)?;
+        let ldlvl = c.read_int(LDLVL)?;
+        let lalvl = c.read_int(LALVL)?;
+        let lloc = c.read_location(LLOC)?;
+        let ltc = c.read_bytes(LTC.length())?;
+        let lbc = c.read_bytes(LBC.length())?;
+        let (lxsofl, lxshd) = c.read_extension(LXSHDL, LXSOFL)?;
+        Ok(LabelSubheader {
+            la,
+            lid,
+            security,
+            encryp,
+            lfs,
+            lcw,
+            lch,
+            ldlvl,
+            lalvl,
+            lloc,
+            ltc: [ltc[0], ltc[1], ltc[2]],
+            lbc: [lbc[0], lbc[1], lbc[2]],
+            lxsofl,
+            lxshd,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfTextSubheader20 {
+    TE,
+    TEXTID,
+    TXTDT,
+    TXTITL,
+    TSCLAS,
+    TSCODE,
+    TSCTLH,
+    TSREL,
+    TSCAUT,
+    TSCTLN,
+    TSDWNG,
+    TSDEVT,
+    ENCRYP,
+    TXTFMT,
+    TXSHDL,
+    TXSOFL,
+    TXSHD,
+}
+
+impl NitfTextSubheader20 {
+    pub fn values() -> &'static [usize] {
+        &[2, 10, 14, 80, 1, 40, 40, 40, 20, 20, 6, 40, 1, 3, 5, 3, 0]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfTextSubheader20::*;
+        match self {
+            TE => "TE",
+            TEXTID => "TEXTID",
+            TXTDT => "TXTDT",
+            TXTITL => "TXTITL",
+            TSCLAS => "TSCLAS",
+            TSCODE => "TSCODE",
+            TSCTLH => "TSCTLH",
+            TSREL => "TSREL",
+            TSCAUT => "TSCAUT",
+            TSCTLN => "TSCTLN",
+            TSDWNG => "TSDWNG",
+            TSDEVT => "TSDEVT",
+            ENCRYP => "ENCRYP",
+            TXTFMT => "TXTFMT",
+            TXSHDL => "TXSHDL",
+            TXSOFL => "TXSOFL",
+            TXSHD => "TXSHD",
+        }
+    }
+    pub fn get_value(target: NitfTextSubheader20) -> usize {
+        let index = target as usize;
+        NitfTextSubheader20::values()[index]
+    }
+}
+
+impl Field for NitfTextSubheader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfTextSubheader20::get_value(*self)
+    }
+}
+
+/// A parsed NITF 2.0 text subheader. Unlike 2.1 it has no TXTALVL, a 10 character TEXTID and
+/// the DDHHMMSSZMONYY date.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TextSubheader {
+    pub te: String,
+    pub textid: String,
+    pub txtdt: Option<NaiveDateTime>,
+    pub txtitl: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    pub txtfmt: String,
+    pub txsofl: usize,
+    pub txshd: Vec<u8>,
+}
+
+impl TextSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<TextSubheader> {
+        TextSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TextSubheader> {
+        use NitfTextSubheader20::*;
+        let mut c = FieldCursor::new(bytes);
+        let te = c.read_string(TE)?;
+        let textid = c.read_string(TEXTID)?;
+        let txtdt = read_date_time(&mut c, TXTDT)?;
+        let txtitl = c.read_string(TXTITL)?;
+        let security = SecurityGroup::read(&mut c)?;
+        let encryp = c.read_string(ENCRYP)?;
+        let txtfmt = c.read_string(TXTFMT)?;
+        let (txsofl, txshd) = c.read_extension(TXSHDL, TXSOFL)?;
+        Ok(TextSubheader {
+            te,
+            textid,
+            txtdt,
+            txtitl,
+            security,
+            encryp,
+            txtfmt,
+            txsofl,
+            txshd,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfDesSubheader20 {
+    DE,
+    DESTAG,
+    DESVER,
+    DESCLAS,
+    DESCODE,
+    DESCTLH,
+    DESREL,
+    DESCAUT,
+    DESCTLN,
+    DESDWNG,
+    DESDEVT,
+    DESOFLW,
+    DESITEM,
+    DESSHL,
+    DESSHF,
+}
+
+impl NitfDesSubheader20 {
+    pub fn values() -> &'static [usize] {
+        &[2, 25, 2, 1, 40, 40, 40, 20, 20, 6, 40, 6, 3, 4, 0]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfDesSubheader20::*;
+        match self {
+            DE => "DE",
+            DESTAG => "DESTAG",
+            DESVER => "DESVER",
+            DESCLAS => "DESCLAS",
+            DESCODE => "DESCODE",
+            DESCTLH => "DESCTLH",
+            DESREL => "DESREL",
+            DESCAUT => "DESCAUT",
+            DESCTLN => "DESCTLN",
+            DESDWNG => "DESDWNG",
+            DESDEVT => "DESDEVT",
+            DESOFLW => "DESOFLW",
+            DESITEM => "DESITEM",
+            DESSHL => "DESSHL",
+            DESSHF => "DESSHF",
+        }
+    }
+    pub fn get_value(target: NitfDesSubheader20) -> usize {
+        let index = target as usize;
+        NitfDesSubheader20::values()[index]
+    }
+}
+
+impl Field for NitfDesSubheader20 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfDesSubheader20::get_value(*self)
+    }
+}
+
+/// DESTAGs of the NITF 2.0 DES that carry TREs which did not fit in a header or subheader,
+/// the 2.0 counterparts of TRE_OVERFLOW.
+pub const OVERFLOW_DESTAGS: [&str; 2] = ["Registered Extensions", "Controlled Extensions"];
+
+/// A parsed NITF 2.0 data extension subheader. DESSHL is the length of `desshf`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct DesSubheader {
+    pub de: String,
+    pub destag: String,
+    pub desver: usize,
+    pub security: SecurityGroup,
+    /// Only present for the overflow DESTAGs.
+    pub desoflw: Option<String>,
+    /// Only present for the overflow DESTAGs.
+    pub desitem: Option<usize>,
+    pub desshf: Vec<u8>,
+}
+
+impl DesSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<DesSubheader> {
+        DesSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DesSubheader> {
+        use NitfDesSubheader20::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = DesSubheader {
+            de: c.read_string(DE)?,
+            destag: c.read_string(DESTAG)?,
+            desver: c.read_int(DESVER)?,
+            security: SecurityGroup::read(&mut c)?,
+            ..Default::default()
+        };
+        if sub.is_overflow() {
+            sub.desoflw = Some(c.read_string(DESOFLW)?);
+            sub.desitem = Some(c.read_int(DESITEM)?);
+        }
+        let desshl = c.read_int(DESSHL)?;
+        sub.desshf = c.read_bytes(desshl)?.to_vec();
+        Ok(sub)
+    }
+
+    /// Whether the DES holds TREs that overflowed a header or subheader.
+    pub fn is_overflow(&self) -> bool {
+        OVERFLOW_DESTAGS.contains(&self.destag.as_str())
+    }
+}
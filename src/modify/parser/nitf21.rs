@@ -1,42 +1,62 @@
+use crate::modify::error::{NitfError, Result};
 use crate::modify::parser::file_ops::{
-    read_int_from_bytes, read_int_from_file, read_string_from_file,
+    read_bytes, read_int, read_string, Field, FieldCursor, FieldWriter,
 };
-use std::fs::File;
+use crate::modify::parser::nitf20::Nitf20;
+use crate::modify::parser::tre::{decode_tres, Tre, TreIter};
+use crate::modify::parser::tre_decoders::ImageTre;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::io::{Read, Seek};
 use std::str::FromStr;
 
 const FHDR: usize = 4;
 const FVER: usize = 5;
 
+/// FHDR and FVER together. NSIF 1.0 is the NATO profile of NITF 2.1 and shares its layout.
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum NitfVersion {
     V01_10,
     V02_00,
     V02_10,
+    NSIF01_00,
 }
 
 impl NitfVersion {
+    /// The FVER value.
     pub fn as_str(&self) -> &'static str {
         match self {
             NitfVersion::V01_10 => "01.10",
             NitfVersion::V02_00 => "02.00",
             NitfVersion::V02_10 => "02.10",
+            NitfVersion::NSIF01_00 => "01.00",
+        }
+    }
+
+    /// The FHDR value.
+    pub fn fhdr(&self) -> &'static str {
+        match self {
+            NitfVersion::NSIF01_00 => "NSIF",
+            _ => "NITF",
         }
     }
 }
 
 impl FromStr for NitfVersion {
-    type Err = String; // Define the error type (you can also use a custom error type here)
+    type Err = NitfError;
 
-    fn from_str(version: &str) -> Result<Self, Self::Err> {
+    /// Parses FHDR followed by FVER, e.g. "NITF02.10" or "NSIF01.00".
+    fn from_str(version: &str) -> Result<Self> {
         match version {
-            "01.10" => Ok(NitfVersion::V01_10),
-            "02.00" => Ok(NitfVersion::V02_00),
-            "02.10" => Ok(NitfVersion::V02_10),
-            _ => Err(format!("Invalid NITF version: {}", version)), // Return an error if the string doesn’t match
+            "NITF01.10" => Ok(NitfVersion::V01_10),
+            "NITF02.00" => Ok(NitfVersion::V02_00),
+            "NITF02.10" => Ok(NitfVersion::V02_10),
+            "NSIF01.00" => Ok(NitfVersion::NSIF01_00),
+            _ => Err(NitfError::UnsupportedVersion(version.to_string())),
         }
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Header {
     pub fhdr: usize,
     pub fver: usize,
@@ -93,7 +113,7 @@ pub struct Header {
     pub xhd: usize,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Segments {
     pub image_segments: usize,
     pub graphic_segments: usize,
@@ -103,113 +123,435 @@ pub struct Segments {
     pub reserved_extension_segments: usize,
 }
 
-#[derive(Default, Debug)]
-pub struct Nitf {
-    pub header: Header,
-    pub segments: Segments,
+/// Subheader and data length of one segment, as listed in the file header length tables.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct SegmentLength {
+    pub subheader: usize,
+    pub data: usize,
+}
+
+impl SegmentLength {
+    pub fn total(&self) -> usize {
+        self.subheader + self.data
+    }
+}
+
+/// The kinds of segment listed in the 2.1 file header length tables, in file order.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SegmentType {
+    Image,
+    Graphic,
+    Text,
+    DataExtension,
+    ReservedExtension,
+}
+
+impl SegmentType {
+    /// The segment name used in errors such as `IndexOutOfRange`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SegmentType::Image => "Image",
+            SegmentType::Graphic => "Graphic",
+            SegmentType::Text => "Text",
+            SegmentType::DataExtension => "DES",
+            SegmentType::ReservedExtension => "RES",
+        }
+    }
+
+    /// The count, subheader length and data length fields of its length table.
+    pub fn fields(&self) -> (NitfHeader21, NitfHeader21, NitfHeader21) {
+        use NitfHeader21::*;
+        match self {
+            SegmentType::Image => (NUMI, LISH, LI),
+            SegmentType::Graphic => (NUMS, LSSH, LS),
+            SegmentType::Text => (NUMT, LTSH, LT),
+            SegmentType::DataExtension => (NUMDES, LDSH, LD),
+            SegmentType::ReservedExtension => (NUMRES, LRESH, LRE),
+        }
+    }
+}
+
+impl Header {
+    /// Offset of the count field (NUMI, NUMS, ...) in front of a length table.
+    pub fn count(&self, segment: SegmentType) -> usize {
+        match segment {
+            SegmentType::Image => self.numi,
+            SegmentType::Graphic => self.nums,
+            SegmentType::Text => self.numt,
+            SegmentType::DataExtension => self.numdes,
+            SegmentType::ReservedExtension => self.numres,
+        }
+    }
+}
+
+impl Segments {
+    pub fn start(&self, segment: SegmentType) -> usize {
+        match segment {
+            SegmentType::Image => self.image_segments,
+            SegmentType::Graphic => self.graphic_segments,
+            SegmentType::Text => self.text_segments,
+            SegmentType::DataExtension => self.data_extension_segments,
+            SegmentType::ReservedExtension => self.reserved_extension_segments,
+        }
+    }
+}
+
+/// Decoded NITF 2.1 file header values.
+///
+/// The segment counts (NUMI, NUMS, ...) are the lengths of the segment length tables,
+/// and UDHDL/XHDL are implied by the length of `udhd`/`xhd`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FileHeader {
+    pub fhdr: String,
+    pub fver: String,
+    pub clevel: usize,
+    pub stype: String,
+    pub ostaid: String,
+    pub fdt: Option<NaiveDateTime>,
+    pub ftitle: String,
+    pub security: SecurityGroup,
+    pub fscop: usize,
+    pub fscpys: usize,
+    pub encryp: String,
+    pub fbkgc: [u8; 3],
+    pub oname: String,
+    pub ophone: String,
+    pub fl: usize,
+    pub hl: usize,
+    pub images: Vec<SegmentLength>,
+    pub graphics: Vec<SegmentLength>,
+    pub numx: usize,
+    pub texts: Vec<SegmentLength>,
+    pub des: Vec<SegmentLength>,
+    pub res: Vec<SegmentLength>,
+    pub udhofl: usize,
+    pub udhd: Vec<u8>,
+    pub xhdlofl: usize,
+    pub xhd: Vec<u8>,
+}
+
+impl FileHeader {
+    /// Reads the whole file header in one go, HL bytes from the start of the file. Only NITF 2.1
+    /// and NSIF 1.0 are laid out this way, any other FHDR/FVER is `UnsupportedVersion`.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<FileHeader> {
+        use NitfHeader21::{self as N, *};
+        match Nitf::get_version(reader)? {
+            NitfVersion::V02_10 | NitfVersion::NSIF01_00 => {}
+            ver => return Err(NitfError::UnsupportedVersion(format!("{}{}", ver.fhdr(), ver.as_str()))),
+        }
+        let hl = read_int(reader, N::fixed_offset(HL), HL)?;
+        FileHeader::from_bytes(&read_bytes(reader, 0, hl)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<FileHeader> {
+        use NitfHeader21::{self as N, *};
+        let mut c = FieldCursor::new(bytes);
+        let mut header = FileHeader {
+            fhdr: c.read_string(FHDR)?,
+            fver: c.read_string(FVER)?,
+            clevel: c.read_int(CLEVEL)?,
+            stype: c.read_string(STYPE)?,
+            ostaid: c.read_string(OSTAID)?,
+            fdt: c.read_date_time(FDT)?,
+            ftitle: c.read_string(FTITLE)?,
+            security: SecurityGroup::read(&mut c)?,
+            fscop: c.read_int(FSCOP)?,
+            fscpys: c.read_int(FSCPYS)?,
+            encryp: c.read_string(ENCRYP)?,
+            ..Default::default()
+        };
+        header
+            .fbkgc
+            .copy_from_slice(c.read_bytes(N::get_value(FBKGC))?);
+        header.oname = c.read_string(ONAME)?;
+        header.ophone = c.read_string(OPHONE)?;
+        header.fl = c.read_int(FL)?;
+        header.hl = c.read_int(HL)?;
+        header.images = read_length_table(&mut c, NUMI, LISH, LI)?;
+        header.graphics = read_length_table(&mut c, NUMS, LSSH, LS)?;
+        header.numx = c.read_int(NUMX)?;
+        header.texts = read_length_table(&mut c, NUMT, LTSH, LT)?;
+        header.des = read_length_table(&mut c, NUMDES, LDSH, LD)?;
+        header.res = read_length_table(&mut c, NUMRES, LRESH, LRE)?;
+        (header.udhofl, header.udhd) = c.read_extension(UDHDL, UDHOFL)?;
+        (header.xhdlofl, header.xhd) = c.read_extension(XHDL, XHDLOFL)?;
+        Ok(header)
+    }
+
+    /// Serializes the header exactly as the fields say, FL, HL and the length tables included.
+    /// [`NitfModel`](crate::modify::writer::NitfModel) keeps those in step with the segments.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfHeader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(FHDR, &self.fhdr)?;
+        w.write_string(FVER, &self.fver)?;
+        w.write_int(CLEVEL, self.clevel)?;
+        w.write_string(STYPE, &self.stype)?;
+        w.write_string(OSTAID, &self.ostaid)?;
+        w.write_date_time(FDT, self.fdt)?;
+        w.write_string(FTITLE, &self.ftitle)?;
+        self.security.write(&mut w)?;
+        w.write_int(FSCOP, self.fscop)?;
+        w.write_int(FSCPYS, self.fscpys)?;
+        w.write_string(ENCRYP, &self.encryp)?;
+        w.write_bytes(&self.fbkgc);
+        w.write_string(ONAME, &self.oname)?;
+        w.write_string(OPHONE, &self.ophone)?;
+        w.write_int(FL, self.fl)?;
+        w.write_int(HL, self.hl)?;
+        write_length_table(&mut w, &self.images, NUMI, LISH, LI)?;
+        write_length_table(&mut w, &self.graphics, NUMS, LSSH, LS)?;
+        w.write_int(NUMX, self.numx)?;
+        write_length_table(&mut w, &self.texts, NUMT, LTSH, LT)?;
+        write_length_table(&mut w, &self.des, NUMDES, LDSH, LD)?;
+        write_length_table(&mut w, &self.res, NUMRES, LRESH, LRE)?;
+        w.write_extension(UDHDL, UDHOFL, self.udhofl, &self.udhd)?;
+        w.write_extension(XHDL, XHDLOFL, self.xhdlofl, &self.xhd)?;
+        Ok(w.into_bytes())
+    }
+
+    pub fn numi(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn nums(&self) -> usize {
+        self.graphics.len()
+    }
+
+    pub fn numt(&self) -> usize {
+        self.texts.len()
+    }
+
+    pub fn numdes(&self) -> usize {
+        self.des.len()
+    }
+
+    pub fn numres(&self) -> usize {
+        self.res.len()
+    }
+
+    pub fn lengths(&self, segment: SegmentType) -> &[SegmentLength] {
+        match segment {
+            SegmentType::Image => &self.images,
+            SegmentType::Graphic => &self.graphics,
+            SegmentType::Text => &self.texts,
+            SegmentType::DataExtension => &self.des,
+            SegmentType::ReservedExtension => &self.res,
+        }
+    }
+
+    /// The TREs of UDHD followed by those of XHD.
+    pub fn tres(&self) -> impl Iterator<Item = Result<Tre>> + '_ {
+        TreIter::new(&self.udhd).chain(TreIter::new(&self.xhd))
+    }
+
+    /// Byte offsets of every header field, worked out from the segment counts.
+    pub fn offsets(&self) -> Header {
+        use NitfHeader21::{self as N, *};
+        let table_length = |count: usize, sh: NitfHeader21, data: NitfHeader21| {
+            count * (N::get_value(sh) + N::get_value(data))
+        };
+        let numi = N::fixed_offset(NUMI);
+        let nums = numi + N::get_value(NUMI) + table_length(self.numi(), LISH, LI);
+        let numx = nums + N::get_value(NUMS) + table_length(self.nums(), LSSH, LS);
+        let numt = numx + N::get_value(NUMX);
+        let numdes = numt + N::get_value(NUMT) + table_length(self.numt(), LTSH, LT);
+        let numres = numdes + N::get_value(NUMDES) + table_length(self.numdes(), LDSH, LD);
+        let udhdl = numres + N::get_value(NUMRES) + table_length(self.numres(), LRESH, LRE);
+        let udhd_length = if self.udhd.is_empty() { 0 } else { N::get_value(UDHOFL) + self.udhd.len() };
+        let xhdl = udhdl + N::get_value(UDHDL) + udhd_length;
+        Header {
+            fhdr: N::fixed_offset(FHDR),
+            fver: N::fixed_offset(FVER),
+            clevel: N::fixed_offset(CLEVEL),
+            stype: N::fixed_offset(STYPE),
+            ostaid: N::fixed_offset(OSTAID),
+            fdt: N::fixed_offset(FDT),
+            ftitle: N::fixed_offset(FTITLE),
+            fsclas: N::fixed_offset(FSCLAS),
+            fsclsy: N::fixed_offset(FSCLSY),
+            fscode: N::fixed_offset(FSCODE),
+            fsctlh: N::fixed_offset(FSCTLH),
+            fsrel: N::fixed_offset(FSREL),
+            fsdctp: N::fixed_offset(FSDCTP),
+            fsdcdt: N::fixed_offset(FSDCDT),
+            fsdcxm: N::fixed_offset(FSDCXM),
+            fsdg: N::fixed_offset(FSDG),
+            fsdgdt: N::fixed_offset(FSDGDT),
+            fscltx: N::fixed_offset(FSCLTX),
+            fscatp: N::fixed_offset(FSCATP),
+            fscaut: N::fixed_offset(FSCAUT),
+            fscrsn: N::fixed_offset(FSCRSN),
+            fssrdt: N::fixed_offset(FSSRDT),
+            fsctln: N::fixed_offset(FSCTLN),
+            fscop: N::fixed_offset(FSCOP),
+            fscpys: N::fixed_offset(FSCPYS),
+            encryp: N::fixed_offset(ENCRYP),
+            fbkgc: N::fixed_offset(FBKGC),
+            oname: N::fixed_offset(ONAME),
+            ophone: N::fixed_offset(OPHONE),
+            fl: N::fixed_offset(FL),
+            hl: N::fixed_offset(HL),
+            numi,
+            lish: numi + N::get_value(NUMI),
+            li: numi + N::get_value(NUMI) + N::get_value(LISH),
+            nums,
+            lssh: nums + N::get_value(NUMS),
+            ls: nums + N::get_value(NUMS) + N::get_value(LSSH),
+            numx,
+            numt,
+            ltsh: numt + N::get_value(NUMT),
+            lt: numt + N::get_value(NUMT) + N::get_value(LTSH),
+            numdes,
+            ldsh: numdes + N::get_value(NUMDES),
+            ldnnn: numdes + N::get_value(NUMDES) + N::get_value(LDSH),
+            numres,
+            lresh: numres + N::get_value(NUMRES),
+            lre: numres + N::get_value(NUMRES) + N::get_value(LRESH),
+            udhdl,
+            udhofl: udhdl + N::get_value(UDHDL),
+            udhd: udhdl + N::get_value(UDHDL) + N::get_value(UDHOFL),
+            xhdl,
+            xhdlofl: xhdl + N::get_value(XHDL),
+            xhd: xhdl + N::get_value(XHDL) + N::get_value(XHDLOFL),
+        }
+    }
+
+    /// Byte offsets of the start of each group of segments, worked out from the length tables.
+    pub fn segment_offsets(&self) -> Segments {
+        let total = |table: &[SegmentLength]| table.iter().map(SegmentLength::total).sum::<usize>();
+        let image_segments = self.hl;
+        let graphic_segments = image_segments + total(&self.images);
+        //Reserved (NUMX) segments have no length table, so they take up no space
+        let reserved_segments = graphic_segments + total(&self.graphics);
+        let text_segments = reserved_segments;
+        let data_extension_segments = text_segments + total(&self.texts);
+        let reserved_extension_segments = data_extension_segments + total(&self.des);
+        Segments {
+            image_segments,
+            graphic_segments,
+            reserved_segments,
+            text_segments,
+            data_extension_segments,
+            reserved_extension_segments,
+        }
+    }
+}
+
+pub(crate) fn read_length_table<F: Field>(
+    cursor: &mut FieldCursor,
+    count: F,
+    subheader: F,
+    data: F,
+) -> Result<Vec<SegmentLength>> {
+    let num = cursor.read_int(count)?;
+    let mut table = Vec::with_capacity(num);
+    for _ in 0..num {
+        table.push(SegmentLength {
+            subheader: cursor.read_int(subheader)?,
+            data: cursor.read_int(data)?,
+        });
+    }
+    Ok(table)
+}
+
+pub(crate) fn write_length_table<F: Field>(
+    writer: &mut FieldWriter,
+    table: &[SegmentLength],
+    count: F,
+    subheader: F,
+    data: F,
+) -> Result<()> {
+    writer.write_int(count, table.len())?;
+    for length in table {
+        writer.write_int(subheader, length.subheader)?;
+        writer.write_int(data, length.data)?;
+    }
+    Ok(())
+}
+
+/// A parsed file, tagged with the version of the standard it was written to.
+#[derive(Debug)]
+pub enum Nitf {
+    V02_00(Nitf20),
+    V02_10(Nitf21),
+    /// Laid out exactly like 2.1, only FHDR and FVER differ.
+    NSIF01_00(Nitf21),
 }
 
 impl Nitf {
-    pub fn get_version(file: &File) -> NitfVersion {
-        NitfVersion::from_str(&read_string_from_file(file, FHDR, FVER)).unwrap()
+    pub fn get_version<R: Read + Seek>(reader: &mut R) -> Result<NitfVersion> {
+        NitfVersion::from_str(&read_string(reader, 0, FHDR + FVER)?)
     }
 
-    pub fn new(file: &std::fs::File) -> Nitf {
+    pub fn new<R: Read + Seek>(reader: &mut R) -> Result<Nitf> {
         #[cfg(all(debug_assertions, not(test)))]
         println!("Loading NITF file.");
-        let ver = Nitf::get_version(file);
+        let ver = Nitf::get_version(reader)?;
         #[cfg(all(debug_assertions, not(test)))]
-        println!("NITF Version: {}", ver.as_str());
+        println!("NITF Version: {}{}", ver.fhdr(), ver.as_str());
         match ver {
-            NitfVersion::V01_10 => panic!("Version 01.10 is not supported."),
-            NitfVersion::V02_00 => panic!("Version 02.00 is not supported."),
-            NitfVersion::V02_10 => Nitf::load_v02_10(file),
+            NitfVersion::V01_10 => Err(NitfError::UnsupportedVersion(format!("{}{}", ver.fhdr(), ver.as_str()))),
+            NitfVersion::V02_00 => Ok(Nitf::V02_00(Nitf20::load_v02_00(reader)?)),
+            NitfVersion::V02_10 => Ok(Nitf::V02_10(Nitf21::load_v02_10(reader)?)),
+            NitfVersion::NSIF01_00 => Ok(Nitf::NSIF01_00(Nitf21::load_v02_10(reader)?)),
         }
     }
 
-    pub fn load_v02_10(file: &std::fs::File) -> Nitf {
-        use NitfHeader21::*;
+    pub fn version(&self) -> NitfVersion {
+        match self {
+            Nitf::V02_00(_) => NitfVersion::V02_00,
+            Nitf::V02_10(_) => NitfVersion::V02_10,
+            Nitf::NSIF01_00(_) => NitfVersion::NSIF01_00,
+        }
+    }
+
+    /// The 2.0 contents, or `UnsupportedVersion` for any other version.
+    pub fn into_v02_00(self) -> Result<Nitf20> {
+        match self {
+            Nitf::V02_00(nitf) => Ok(nitf),
+            other => {
+                let ver = other.version();
+                Err(NitfError::UnsupportedVersion(format!("{}{}", ver.fhdr(), ver.as_str())))
+            }
+        }
+    }
+
+    /// The 2.1 (or NSIF 1.0) contents, or `UnsupportedVersion` for any other version. Used by
+    /// operations that only know how to edit 2.1 files.
+    pub fn into_v02_10(self) -> Result<Nitf21> {
+        match self {
+            Nitf::V02_10(nitf) | Nitf::NSIF01_00(nitf) => Ok(nitf),
+            other => {
+                let ver = other.version();
+                Err(NitfError::UnsupportedVersion(format!("{}{}", ver.fhdr(), ver.as_str())))
+            }
+        }
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Nitf21 {
+    pub header: Header,
+    pub file_header: FileHeader,
+    pub segments: Segments,
+    pub images: Vec<ImageSubheader>,
+}
+
+impl Nitf21 {
+    pub fn load_v02_10<R: Read + Seek>(reader: &mut R) -> Result<Nitf21> {
         #[cfg(all(debug_assertions, not(test)))]
         println!("Loading NITF 02.10 file.");
-        let mut nitf = Nitf {
-            header: Header {
-                fhdr: NitfHeader21::get_offset(FHDR, None),
-                fver: NitfHeader21::get_offset(FVER, None),
-                clevel: NitfHeader21::get_offset(CLEVEL, None),
-                stype: NitfHeader21::get_offset(STYPE, None),
-                ostaid: NitfHeader21::get_offset(OSTAID, None),
-                fdt: NitfHeader21::get_offset(FDT, None),
-                ftitle: NitfHeader21::get_offset(FTITLE, None),
-                fsclas: NitfHeader21::get_offset(FSCLAS, None),
-                fsclsy: NitfHeader21::get_offset(FSCLSY, None),
-                fscode: NitfHeader21::get_offset(FSCODE, None),
-                fsctlh: NitfHeader21::get_offset(FSCTLH, None),
-                fsrel: NitfHeader21::get_offset(FSREL, None),
-                fsdctp: NitfHeader21::get_offset(FSDCTP, None),
-                fsdcdt: NitfHeader21::get_offset(FSDCDT, None),
-                fsdcxm: NitfHeader21::get_offset(FSDCXM, None),
-                fsdg: NitfHeader21::get_offset(FSDG, None),
-                fsdgdt: NitfHeader21::get_offset(FSDGDT, None),
-                fscltx: NitfHeader21::get_offset(FSCLTX, None),
-                fscatp: NitfHeader21::get_offset(FSCATP, None),
-                fscaut: NitfHeader21::get_offset(FSCAUT, None),
-                fscrsn: NitfHeader21::get_offset(FSCRSN, None),
-                fssrdt: NitfHeader21::get_offset(FSSRDT, None),
-                fsctln: NitfHeader21::get_offset(FSCTLN, None),
-                fscop: NitfHeader21::get_offset(FSCOP, None),
-                fscpys: NitfHeader21::get_offset(FSCPYS, None),
-                encryp: NitfHeader21::get_offset(ENCRYP, None),
-                fbkgc: NitfHeader21::get_offset(FBKGC, None),
-                oname: NitfHeader21::get_offset(ONAME, None),
-                ophone: NitfHeader21::get_offset(OPHONE, None),
-                fl: NitfHeader21::get_offset(FL, None),
-                hl: NitfHeader21::get_offset(HL, None),
-                numi: NitfHeader21::get_offset(NUMI, None),
-                lish: NitfHeader21::get_offset(LISH, Some(file)),
-                li: NitfHeader21::get_offset(LI, Some(file)),
-                nums: NitfHeader21::get_offset(NUMS, Some(file)),
-                lssh: NitfHeader21::get_offset(LSSH, Some(file)),
-                ls: NitfHeader21::get_offset(LS, Some(file)),
-                numx: NitfHeader21::get_offset(NUMX, Some(file)),
-                numt: NitfHeader21::get_offset(NUMT, Some(file)),
-                ltsh: NitfHeader21::get_offset(LTSH, Some(file)),
-                lt: NitfHeader21::get_offset(LT, Some(file)),
-                numdes: NitfHeader21::get_offset(NUMDES, Some(file)),
-                ldsh: NitfHeader21::get_offset(LDSH, Some(file)),
-                ldnnn: NitfHeader21::get_offset(LD, Some(file)),
-                numres: NitfHeader21::get_offset(NUMRES, Some(file)),
-                lresh: NitfHeader21::get_offset(LRESH, Some(file)),
-                lre: NitfHeader21::get_offset(LRE, Some(file)),
-                udhdl: 0,
-                // udhdl: NitfHeader21::get_offset(UDHDL, Some(file)),
-                udhofl: 0,
-                //udhofl: NitfHeader21::get_offset(UDHOFL, Some(file)),
-                udhd: 0,
-                //udhd: NitfHeader21::get_offset(UDHD, Some(file)),
-                xhdl: 0,
-                // xhdl: NitfHeader21::get_offset(XHDL, Some(file)),
-                xhdlofl: 0,
-                // xhdlofl: NitfHeader21::get_offset(XHDLOFL, Some(file)),
-                xhd: 0, //xhd: NitfHeader21::get_offset(XHD, Some(file)),
-            },
-            segments: Segments {
-                image_segments: 0,
-                graphic_segments: 0,
-                reserved_segments: 0,
-                text_segments: 0,
-                data_extension_segments: 0,
-                reserved_extension_segments: 0,
-            },
+        let file_header = FileHeader::read(reader)?;
+        let mut nitf = Nitf21 {
+            header: file_header.offsets(),
+            segments: file_header.segment_offsets(),
+            file_header,
+            images: Vec::new(),
         };
         #[cfg(all(debug_assertions, not(test)))]
         println!("Succesfully retrieved header offsets.");
-        nitf.segments.image_segments = nitf.get_image_segments_offset(file);
-        nitf.segments.graphic_segments = nitf.get_graphics_segments_offset(file);
-        nitf.segments.reserved_segments = nitf.get_reserved_extension_segments_offset(file);
-        nitf.segments.text_segments = nitf.get_text_segments_offset(file);
-        nitf.segments.data_extension_segments = nitf.get_data_extension_segments_offset(file);
-        nitf.segments.reserved_extension_segments =
-            nitf.get_reserved_extension_segments_offset(file);
+        nitf.images = nitf.read_image_subheaders(reader)?;
         #[cfg(all(debug_assertions, not(test)))]
         {
             println!("************************************************");
@@ -225,142 +567,65 @@ impl Nitf {
                 "Reserved Extension Segments: {}",
                 nitf.segments.reserved_extension_segments
             );
-            println!(
-                "nitf FL: {}",
-                read_int_from_file(file, nitf.header.fl, NitfHeader21::get_value(FL))
-            );
+            println!("nitf FL: {}", nitf.file_header.fl);
             println!("************************************************");
         }
-        nitf
-    }
-
-    pub fn get_image_headers_length(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let numi = read_int_from_file(file, self.header.numi, N::get_value(NUMI));
-        numi * (N::get_value(LISH) + N::get_value(LI))
-    }
-
-    pub fn get_graphic_headers_length(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let nums = read_int_from_file(file, self.header.nums, N::get_value(NUMS));
-        nums * (N::get_value(LSSH) + N::get_value(LS))
-    }
-
-    pub fn get_text_headers_length(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let numt = read_int_from_file(file, self.header.numt, N::get_value(NUMT));
-        numt * (N::get_value(LTSH) + N::get_value(LT))
-    }
-
-    pub fn get_data_extension_headers_length(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let numdes = read_int_from_file(file, self.header.numdes, N::get_value(NUMDES));
-        numdes * (N::get_value(LDSH) + N::get_value(LD))
+        Ok(nitf)
     }
 
-    pub fn get_reserved_extension_headers_length(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let numres = read_int_from_file(file, self.header.numres, N::get_value(NUMRES));
-        numres * (N::get_value(LRESH) + N::get_value(LRE))
+    pub fn read_image_subheaders<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<ImageSubheader>> {
+        let mut subheader_offset = self.segments.image_segments;
+        let mut images = Vec::with_capacity(self.file_header.numi());
+        for length in &self.file_header.images {
+            images.push(ImageSubheader::read(reader, subheader_offset, length.subheader)?);
+            subheader_offset += length.total();
+        }
+        Ok(images)
     }
 
-    pub fn get_image_seg_length(&self, file: &File) -> usize {
+    pub fn get_image_headers_length(&self) -> usize {
         use NitfHeader21::{self as N, *};
-        let mut image_segment_length = 0;
-        let mut header_cursor = self.header.lish;
-        for _ in 0..read_int_from_file(file, self.header.numi, N::get_value(NUMI)) {
-            image_segment_length += read_int_from_file(file, header_cursor, N::get_value(LISH));
-            header_cursor += N::get_value(LISH);
-            image_segment_length += read_int_from_file(file, header_cursor, N::get_value(LI));
-            header_cursor += N::get_value(LI);
-        }
-        image_segment_length
+        self.file_header.numi() * (N::get_value(LISH) + N::get_value(LI))
     }
 
-    pub fn get_graphic_seg_length(&self, file: &File) -> usize {
+    pub fn get_graphic_headers_length(&self) -> usize {
         use NitfHeader21::{self as N, *};
-        let mut graphic_segment_length = 0;
-        let mut header_cursor = self.header.lssh;
-        for _ in 0..read_int_from_file(file, self.header.nums, N::get_value(NUMS)) {
-            graphic_segment_length +=
-                read_int_from_file(file, header_cursor, N::get_value(LSSH));
-            header_cursor += N::get_value(LSSH);
-            graphic_segment_length += read_int_from_file(file, header_cursor, N::get_value(LS));
-            header_cursor += N::get_value(LS);
-        }
-        graphic_segment_length
+        self.file_header.nums() * (N::get_value(LSSH) + N::get_value(LS))
     }
 
-    pub fn get_text_seg_length(&self, file: &File) -> usize {
+    pub fn get_text_headers_length(&self) -> usize {
         use NitfHeader21::{self as N, *};
-        let mut text_segment_length = 0;
-        let mut header_cursor = N::get_offset(LTSH, Some(file));
-        for _ in 0..read_int_from_file(file, self.header.numt, N::get_value(NUMT)) {
-            text_segment_length += read_int_from_file(file, header_cursor, N::get_value(LTSH));
-            header_cursor += N::get_value(LTSH);
-            text_segment_length += read_int_from_file(file, header_cursor, N::get_value(LT));
-            header_cursor += N::get_value(LT);
-        }
-        text_segment_length
+        self.file_header.numt() * (N::get_value(LTSH) + N::get_value(LT))
     }
 
-    pub fn get_data_extension_seg_length(&self, file: &File) -> usize {
+    pub fn get_data_extension_headers_length(&self) -> usize {
         use NitfHeader21::{self as N, *};
-        let mut data_extension_segment_length = 0;
-        let mut header_cursor = self.header.ldsh;
-        for _ in 0..read_int_from_file(file, self.header.numdes, N::get_value(NUMDES)) {
-            data_extension_segment_length +=
-                read_int_from_file(file, header_cursor, N::get_value(LDSH));
-            header_cursor += N::get_value(LDSH);
-            data_extension_segment_length +=
-                read_int_from_file(file, header_cursor, N::get_value(LD));
-            header_cursor += N::get_value(LD);
-        }
-        data_extension_segment_length
+        self.file_header.numdes() * (N::get_value(LDSH) + N::get_value(LD))
     }
 
-    pub fn get_reserved_extension_seg_length(&self, file: &File) -> usize {
+    pub fn get_reserved_extension_headers_length(&self) -> usize {
         use NitfHeader21::{self as N, *};
-        let mut reserved_extension_segment_length = 0;
-        let mut header_cursor = self.header.lresh;
-        for _ in 0..read_int_from_file(file, self.header.numres, N::get_value(NUMRES)) {
-            reserved_extension_segment_length +=
-                read_int_from_file(file, header_cursor, N::get_value(LRESH));
-            header_cursor += N::get_value(LRESH);
-            reserved_extension_segment_length +=
-                read_int_from_file(file, header_cursor, N::get_value(LRE));
-            header_cursor += N::get_value(LRE);
-        }
-        reserved_extension_segment_length
+        self.file_header.numres() * (N::get_value(LRESH) + N::get_value(LRE))
     }
 
-    pub fn get_image_segments_offset(&self, file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        read_int_from_file(file, N::get_offset(HL, Some(file)), N::get_value(HL))
+    pub fn get_image_seg_length(&self) -> usize {
+        self.file_header.images.iter().map(SegmentLength::total).sum()
     }
 
-    pub fn get_graphics_segments_offset(&self, file: &File) -> usize {
-        let image_seg_offset = self.get_image_segments_offset(file);
-        let image_segment_length = self.get_image_seg_length(file);
-        image_seg_offset + image_segment_length
+    pub fn get_graphic_seg_length(&self) -> usize {
+        self.file_header.graphics.iter().map(SegmentLength::total).sum()
     }
 
-    pub fn get_text_segments_offset(&self, file: &File) -> usize {
-        let graphic_seg_offset = self.get_graphics_segments_offset(file);
-        let graphic_segment_length = self.get_graphic_seg_length(file);
-        graphic_seg_offset + graphic_segment_length
+    pub fn get_text_seg_length(&self) -> usize {
+        self.file_header.texts.iter().map(SegmentLength::total).sum()
     }
 
-    pub fn get_data_extension_segments_offset(&self, file: &File) -> usize {
-        let text_seg_offset = self.get_text_segments_offset(file);
-        let text_segment_length = self.get_text_seg_length(file);
-        text_seg_offset + text_segment_length
+    pub fn get_data_extension_seg_length(&self) -> usize {
+        self.file_header.des.iter().map(SegmentLength::total).sum()
     }
 
-    pub fn get_reserved_extension_segments_offset(&self, file: &File) -> usize {
-        let data_ext_seg_offset = self.get_data_extension_segments_offset(file);
-        let data_ext_segment_length = self.get_data_extension_seg_length(file);
-        data_ext_seg_offset + data_ext_segment_length
+    pub fn get_reserved_extension_seg_length(&self) -> usize {
+        self.file_header.res.iter().map(SegmentLength::total).sum()
     }
 }
 
@@ -490,1249 +755,253 @@ impl NitfHeader21 {
         let index = target as usize;
         NitfHeader21::values()[index]
     }
-    pub fn get_offset(target: NitfHeader21, nitf_file: Option<&File>) -> usize {
-        use NitfHeader21::{self as N, *};
-        match target {
+    /// Byte offset of a header field. Fields after NUMI move with the segment counts,
+    /// so the file header is read from `reader` to work them out.
+    pub fn get_offset<R: Read + Seek>(target: NitfHeader21, reader: &mut R) -> Result<usize> {
+        use NitfHeader21::*;
+        if target as usize <= NUMI as usize {
+            return Ok(NitfHeader21::fixed_offset(target));
+        }
+        let offsets = FileHeader::read(reader)?.offsets();
+        let offset = match target {
             //Graphic Segment offsets
-            NUMS => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of NUMS header field.");
-                }
-                let base_offset = N::get_offset(NUMI, None);
-                let num_images = read_int_from_file(
-                    nitf_file.expect("Dev Error, this should not be called with no file."),
-                    base_offset,
-                    N::get_value(NUMI),
-                );
-                base_offset
-                    + N::get_value(NUMI)
-                    + (num_images * (N::get_value(LISH) + N::get_value(LI)))
-            }
-            LSSH => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LSSHNNN header field.");
-                }
-                N::get_offset(NUMS, nitf_file) + N::get_value(NUMS)
-            }
-            LS => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LSNNN header field.");
-                }
-                N::get_offset(LSSH, nitf_file) + N::get_value(LSSH)
-            }
+            LISH => offsets.lish,
+            LI => offsets.li,
+            NUMS => offsets.nums,
+            LSSH => offsets.lssh,
+            LS => offsets.ls,
             //NUMX IS RESERVED FOR FUTURE USE
-            NUMX => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of NUMX header field.");
-                }
-                let lsshnnn_offset = N::get_offset(LSSH, nitf_file);
-                let num_segments = read_int_from_file(
-                    nitf_file
-                        .expect("You must provide a file to get the offset of NUMX header field."),
-                    N::get_offset(NUMS, nitf_file),
-                    N::get_value(NUMS),
-                );
-                lsshnnn_offset + (num_segments * (N::get_value(LSSH) + N::get_value(LS)))
-            }
+            NUMX => offsets.numx,
             //Text Segment offsets
-            NUMT => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of NUMT header field.");
-                }
-                let numx_offset = N::get_offset(NUMX, nitf_file);
-                numx_offset + N::get_value(NUMX)
-            }
-            LTSH => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LTSHNNN header field.");
-                }
-                N::get_offset(NUMT, nitf_file) + N::get_value(NUMT)
-            }
-            LT => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LTNNN header field.");
-                }
-                N::get_offset(LTSH, nitf_file) + N::get_value(LTSH)
-            }
+            NUMT => offsets.numt,
+            LTSH => offsets.ltsh,
+            LT => offsets.lt,
             //Data Extension Segment offsets
-            NUMDES => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of NUMDES header field.");
-                }
-                let num_text = read_int_from_file(
-                    nitf_file.expect(
-                        "You must provide a file to get the offset of NUMDES header field.",
-                    ),
-                    N::get_offset(NUMT, nitf_file),
-                    N::get_value(NUMT),
-                );
-                N::get_offset(LTSH, nitf_file)
-                    + (num_text * (N::get_value(LTSH) + N::get_value(LT)))
-            }
-            LDSH => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LDSHNNN header field.");
-                }
-                N::get_offset(NUMDES, nitf_file) + N::get_value(NUMDES)
-            }
-            LD => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LDNNN header field.");
-                }
-                N::get_offset(LDSH, nitf_file) + N::get_value(LDSH)
-            }
+            NUMDES => offsets.numdes,
+            LDSH => offsets.ldsh,
+            LD => offsets.ldnnn,
             //Reserved Extension Segment offsets
-            NUMRES => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of NUMRES header field.");
-                }
-                let num_des = read_int_from_file(
-                    nitf_file.expect(
-                        "You must provide a file to get the offset of NUMRES header field.",
-                    ),
-                    N::get_offset(NUMDES, nitf_file),
-                    N::get_value(NUMDES),
-                );
-                N::get_offset(LDSH, nitf_file)
-                    + (num_des * (N::get_value(LDSH) + N::get_value(LD)))
-            }
-            LRESH => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LRESHNNN header field.");
-                }
-                N::get_offset(NUMRES, nitf_file) + N::get_value(NUMRES)
-            }
-            LRE => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of LRENNN header field.");
-                }
-                N::get_offset(LRESH, nitf_file) + N::get_value(LRESH)
-            }
+            NUMRES => offsets.numres,
+            LRESH => offsets.lresh,
+            LRE => offsets.lre,
             //User Defined Header offsets
-            UDHDL => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let num_res = read_int_from_file(
-                    nitf_file
-                        .expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset(NUMRES, nitf_file),
-                    N::get_value(NUMRES),
-                );
-                N::get_offset(LRESH, nitf_file)
-                    + (num_res * (N::get_value(LRESH) + N::get_value(LRE)))
-            }
-            UDHOFL => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset(UDHDL, nitf_file) + N::get_value(UDHDL)
-            }
-            UDHD => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset(UDHOFL, nitf_file) + N::get_value(UDHOFL)
-            }
+            UDHDL => offsets.udhdl,
+            UDHOFL => offsets.udhofl,
+            UDHD => offsets.udhd,
             //Extended Header Data Length Segment offsets
-            XHDL => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let udhd_len = read_int_from_file(
-                    nitf_file
-                        .expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset(UDHDL, nitf_file),
-                    N::get_value(UDHDL),
-                );
-                N::get_offset(UDHOFL, nitf_file) + udhd_len
-            }
-            XHDLOFL => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset(XHDL, nitf_file) + N::get_value(XHDL)
-            }
-            XHD => {
-                if nitf_file.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let xhd_len = read_int_from_file(
-                    nitf_file
-                        .expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset(XHDL, nitf_file),
-                    N::get_value(XHDL),
-                );
-                N::get_offset(XHDLOFL, nitf_file) + xhd_len
-            }
+            XHDL => offsets.xhdl,
+            XHDLOFL => offsets.xhdlofl,
+            XHD => offsets.xhd,
             //Anything before NUMS
-            _ => {
-                let index = target as usize;
-                N::values()[..index].iter().sum()
-            }
-        }
+            _ => NitfHeader21::fixed_offset(target),
+        };
+        Ok(offset)
     }
 
-    pub fn change_field_value(
+    pub fn change_field_value<R: Read + Seek>(
         target: NitfHeader21,
-        file: &File,
+        reader: &mut R,
         file_buf: &mut [u8],
         new_value: &[u8],
-    ) {
-        let off = NitfHeader21::get_offset(target, Some(file));
+    ) -> Result<()> {
+        let off = NitfHeader21::get_offset(target, reader)?;
         let len = NitfHeader21::get_value(target);
         if new_value.len() != len {
             eprint!("New value not the same length as field... Are you sure you want to do this?");
         }
-        file_buf[off..off + len].copy_from_slice(new_value);
+        file_buf
+            .get_mut(off..off + len)
+            .ok_or(NitfError::Truncated { offset: off, length: len })?
+            .copy_from_slice(new_value);
+        Ok(())
     }
 
-    pub fn get_offset_bytes(target: NitfHeader21, bytes: Option<&Vec<u8>>) -> usize {
-        use NitfHeader21::{self as N, *};
-        match target {
-            //Graphic Segment offsets
-            NUMS => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let base_offset = N::get_offset_bytes(NUMI, None);
-                let num_images = read_int_from_bytes(
-                    bytes.expect("Dev Error, this should not be called with no file."),
-                    base_offset,
-                    N::get_value(NUMI),
-                );
-                base_offset
-                    + N::get_value(NUMI)
-                    + (num_images * (N::get_value(LISH) + N::get_value(LI)))
-            }
-            LSSH => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(NUMS, bytes) + N::get_value(NUMS)
-            }
-            LS => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(LSSH, bytes) + N::get_value(LSSH)
-            }
-            //NUMX IS RESERVED FOR FUTURE USE
-            NUMX => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let lsshnnn_offset = N::get_offset_bytes(LSSH, bytes);
-                let num_segments = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(NUMS, bytes),
-                    N::get_value(NUMS),
-                );
-                lsshnnn_offset + (num_segments * (N::get_value(LSSH) + N::get_value(LS)))
-            }
-            //Text Segment offsets
-            NUMT => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let numx_offset = N::get_offset_bytes(NUMX, bytes);
-                numx_offset + N::get_value(NUMX)
-            }
-            LTSH => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(NUMT, bytes) + N::get_value(NUMT)
-            }
-            LT => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(LTSH, bytes) + N::get_value(LTSH)
-            }
-            //Data Extension Segment offsets
-            NUMDES => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let num_text = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(NUMT, bytes),
-                    N::get_value(NUMT),
-                );
-                N::get_offset_bytes(LTSH, bytes)
-                    + (num_text * (N::get_value(LTSH) + N::get_value(LT)))
-            }
-            LDSH => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(NUMDES, bytes) + N::get_value(NUMDES)
-            }
-            LD => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(LDSH, bytes) + N::get_value(LDSH)
-            }
-            //Reserved Extension Segment offsets
-            NUMRES => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let num_des = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(NUMDES, bytes),
-                    N::get_value(NUMDES),
-                );
-                N::get_offset_bytes(NUMDES, bytes)
-                    + (num_des * (N::get_value(LDSH) + N::get_value(LD)))
-            }
-            LRESH => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(NUMRES, bytes) + N::get_value(NUMRES)
-            }
-            LRE => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(LRESH, bytes) + N::get_value(LRESH)
-            }
-            //User Defined Header offsets
-            UDHDL => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let num_res = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(NUMRES, bytes),
-                    N::get_value(NUMRES),
-                );
-                N::get_offset_bytes(NUMRES, bytes)
-                    + (num_res * (N::get_value(LRESH) + N::get_value(LRE)))
-            }
-            UDHOFL => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(UDHDL, bytes) + N::get_value(UDHDL)
-            }
-            UDHD => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(UDHOFL, bytes) + N::get_value(UDHOFL)
-            }
-            //Extended Header Data Length Segment offsets
-            XHDL => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let udhd_len = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(UDHDL, bytes),
-                    N::get_value(UDHDL),
-                );
-                N::get_offset_bytes(UDHOFL, bytes) + udhd_len
-            }
-            XHDLOFL => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                N::get_offset_bytes(XHDL, bytes) + N::get_value(XHDL)
-            }
-            XHD => {
-                if bytes.is_none() {
-                    panic!("You must provide a file to get the offset of this header field.");
-                }
-                let xhd_len = read_int_from_bytes(
-                    bytes.expect("You must provide a file to get the offset of this header field."),
-                    N::get_offset_bytes(XHDL, bytes),
-                    N::get_value(XHDL),
-                );
-                N::get_offset_bytes(XHDLOFL, bytes) + xhd_len
-            }
-            //Anything before NUMS
-            _ => {
-                let index = target as usize;
-                let val = N::values()[..index].iter().sum();
-                val
-            }
-        }
+    /// Offset of a field at or before NUMI. These never move, so no file is needed.
+    pub fn fixed_offset(target: NitfHeader21) -> usize {
+        NitfHeader21::values()[..target as usize].iter().sum()
     }
 
-    pub fn get_image_header_field_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images =
-            read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lish_nnn_offset = N::get_offset(LISH, nitf_file);
-        lish_nnn_offset + (image_num as usize * (N::get_value(LISH) + N::get_value(LI)))
+    pub fn get_image_header_field_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        use NitfHeader21::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Image", header.offsets().lish, header.numi(), image_num, LISH, LI)
     }
 
-    pub fn get_image_header_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        image_num: u64,
-    ) -> usize {
+    pub fn get_image_data_field_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
         use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMI, nitf_bytes),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lish_nnn_offset = N::get_offset_bytes(LISH, nitf_bytes);
-        lish_nnn_offset + (image_num as usize * (N::get_value(LISH) + N::get_value(LI)))
+        Ok(N::get_image_header_field_offset(reader, image_num)? + N::get_value(LISH))
     }
 
-    pub fn get_image_data_field_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images =
-            read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lish_nnn_offset = N::get_image_header_field_offset(nitf_file, image_num);
-        lish_nnn_offset + N::get_value(LISH)
+    pub fn get_graphic_header_field_offset<R: Read + Seek>(reader: &mut R, graphic_num: u64) -> Result<usize> {
+        use NitfHeader21::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Graphic", header.offsets().lssh, header.nums(), graphic_num, LSSH, LS)
     }
 
-    pub fn get_image_data_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        image_num: u64,
-    ) -> usize {
+    pub fn get_graphic_data_field_offset<R: Read + Seek>(reader: &mut R, graphic_num: u64) -> Result<usize> {
         use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMI, nitf_bytes),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lish_nnn_offset = N::get_image_header_field_offset_bytes(nitf_bytes, image_num);
-        lish_nnn_offset + N::get_value(LISH)
+        Ok(N::get_graphic_header_field_offset(reader, graphic_num)? + N::get_value(LSSH))
     }
 
-    pub fn get_graphic_header_field_offset(nitf_file: Option<&File>, graphic_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics =
-            read_int_from_file(file, N::get_offset(NUMS, nitf_file), N::get_value(NUMS));
-        if graphic_num + 1 > num_graphics as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lssh_nnn_offset = N::get_offset(LSSH, nitf_file);
-        lssh_nnn_offset + (graphic_num as usize * (N::get_value(LSSH) + N::get_value(LS)))
+    pub fn get_text_header_field_offset<R: Read + Seek>(reader: &mut R, text_num: u64) -> Result<usize> {
+        use NitfHeader21::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("Text", header.offsets().ltsh, header.numt(), text_num, LTSH, LT)
     }
 
-    pub fn get_graphic_header_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        graphic_num: u64,
-    ) -> usize {
+    pub fn get_text_data_field_offset<R: Read + Seek>(reader: &mut R, text_num: u64) -> Result<usize> {
         use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMS, nitf_bytes),
-            N::get_value(NUMS),
-        );
-        if graphic_num + 1 > num_graphics as u64 {
-            panic!("Graphic number is greater than the number of graphics in the bytes, they are 0 indexed, are you off by 1?");
-        }
-        let lssh_nnn_offset = N::get_offset_bytes(LSSH, nitf_bytes);
-        lssh_nnn_offset + (graphic_num as usize * (N::get_value(LSSH) + N::get_value(LS)))
+        Ok(N::get_text_header_field_offset(reader, text_num)? + N::get_value(LTSH))
     }
 
-    pub fn get_graphic_data_field_offset(nitf_file: Option<&File>, graphic_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics =
-            read_int_from_file(file, N::get_offset(NUMS, nitf_file), N::get_value(NUMS));
-        if graphic_num + 1 > num_graphics as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lssh_nnn_offset = N::get_graphic_header_field_offset(nitf_file, graphic_num);
-        lssh_nnn_offset + N::get_value(LSSH)
+    pub fn get_des_header_field_offset<R: Read + Seek>(reader: &mut R, des_num: u64) -> Result<usize> {
+        use NitfHeader21::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("DES", header.offsets().ldsh, header.numdes(), des_num, LDSH, LD)
     }
 
-    pub fn get_graphic_data_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        graphic_num: u64,
-    ) -> usize {
+    pub fn get_des_data_field_offset<R: Read + Seek>(reader: &mut R, des_num: u64) -> Result<usize> {
         use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_graphics = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMS, nitf_bytes),
-            N::get_value(NUMS),
-        );
-        if graphic_num + 1 > num_graphics as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lssh_nnn_offset = N::get_graphic_header_field_offset_bytes(nitf_bytes, graphic_num);
-        lssh_nnn_offset + N::get_value(LSSH)
+        Ok(N::get_des_header_field_offset(reader, des_num)? + N::get_value(LDSH))
     }
 
-    pub fn get_text_header_field_offset(nitf_file: Option<&File>, text_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_file(file, N::get_offset(NUMT, nitf_file), N::get_value(NUMT));
-        if text_num + 1 > num_text as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ltsh_nnn_offset = N::get_offset(LTSH, nitf_file);
-        ltsh_nnn_offset + (text_num as usize * (N::get_value(LTSH) + N::get_value(LT)))
+    pub fn get_reserved_header_field_offset<R: Read + Seek>(reader: &mut R, res_num: u64) -> Result<usize> {
+        use NitfHeader21::*;
+        let header = FileHeader::read(reader)?;
+        length_table_entry("RES", header.offsets().lresh, header.numres(), res_num, LRESH, LRE)
     }
 
-    pub fn get_text_header_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        text_num: u64,
-    ) -> usize {
+    pub fn get_reserved_data_field_offset<R: Read + Seek>(reader: &mut R, res_num: u64) -> Result<usize> {
         use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMT, nitf_bytes),
-            N::get_value(NUMT),
-        );
-        if text_num + 1 > num_text as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ltsh_nnn_offset = N::get_offset_bytes(LTSH, nitf_bytes);
-        ltsh_nnn_offset + (text_num as usize * (N::get_value(LTSH) + N::get_value(LT)))
+        Ok(N::get_reserved_header_field_offset(reader, res_num)? + N::get_value(LRESH))
     }
 
-    pub fn get_text_data_field_offset(nitf_file: Option<&File>, text_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_file(file, N::get_offset(NUMT, nitf_file), N::get_value(NUMT));
-        if text_num + 1 > num_text as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
+    pub fn get_image_subheader_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        if image_num >= header.numi() as u64 {
+            return Err(NitfError::IndexOutOfRange {
+                segment: "Image",
+                index: image_num as usize,
+                count: header.numi(),
+            });
         }
-        let ltsh_nnn_offset = N::get_text_header_field_offset(nitf_file, text_num);
-        ltsh_nnn_offset + N::get_value(LTSH)
+        segment_offset("Image", header.hl, &header.images, image_num)
     }
 
-    pub fn get_text_data_field_offset_bytes(nitf_bytes: Option<&Vec<u8>>, text_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_text = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMT, nitf_bytes),
-            N::get_value(NUMT),
-        );
-        if text_num + 1 > num_text as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ltsh_nnn_offset = N::get_text_header_field_offset_bytes(nitf_bytes, text_num);
-        ltsh_nnn_offset + N::get_value(LTSH)
+    pub fn get_image_data_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let subheader_offset = NitfHeader21::get_image_subheader_offset(reader, image_num)?;
+        Ok(subheader_offset + header.images[image_num as usize].subheader)
     }
 
-    /// Get the offset of the Data Extension Segment header length header field
-    pub fn get_des_header_field_offset(nitf_file: Option<&File>, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_des =
-            read_int_from_file(file, N::get_offset(NUMDES, nitf_file), N::get_value(NUMDES));
-        if des_num + 1 > num_des as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ldsh_nnn_offset = N::get_offset(LDSH, nitf_file);
-        ldsh_nnn_offset + (des_num as usize * (N::get_value(LDSH) + N::get_value(LD)))
+    pub fn get_images_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        Ok(FileHeader::read(reader)?.segment_offsets().image_segments)
     }
 
-    pub fn get_des_header_field_offset_bytes(nitf_bytes: Option<&Vec<u8>>, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_des = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMDES, nitf_bytes),
-            N::get_value(NUMDES),
-        );
-        if des_num + 1 > num_des as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ldsh_nnn_offset = N::get_offset_bytes(LDSH, nitf_bytes);
-        ldsh_nnn_offset + (des_num as usize * (N::get_value(LDSH) + N::get_value(LD)))
+    pub fn get_graphic_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        Ok(FileHeader::read(reader)?.segment_offsets().graphic_segments)
     }
 
-    /// Get the offset of the Data Extension Segment data length header field
-    pub fn get_des_data_field_offset(nitf_file: Option<&File>, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_des =
-            read_int_from_file(file, N::get_offset(NUMDES, nitf_file), N::get_value(NUMDES));
-        if des_num + 1 > num_des as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ldsh_nnn_offset = N::get_des_header_field_offset(nitf_file, des_num);
-        ldsh_nnn_offset + N::get_value(LDSH)
+    pub fn get_reserved_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        println!("This segment is reserved for a future standard. This method currently returns the same value as get_text_segment_start.");
+        Ok(FileHeader::read(reader)?.segment_offsets().reserved_segments)
     }
 
-    pub fn get_des_data_field_offset_bytes(nitf_bytes: Option<&Vec<u8>>, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_des = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMDES, nitf_bytes),
-            N::get_value(NUMDES),
-        );
-        if des_num + 1 > num_des as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let ldsh_nnn_offset = N::get_des_header_field_offset_bytes(nitf_bytes, des_num);
-        ldsh_nnn_offset + N::get_value(LDSH)
+    pub fn get_text_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        Ok(FileHeader::read(reader)?.segment_offsets().text_segments)
     }
 
-    pub fn get_reserved_header_field_offset(nitf_file: Option<&File>, res_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_res =
-            read_int_from_file(file, N::get_offset(NUMRES, nitf_file), N::get_value(NUMRES));
-        if res_num + 1 > num_res as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lresh_nnn_offset = N::get_offset(LRESH, nitf_file);
-        lresh_nnn_offset + (res_num as usize * (N::get_value(LRESH) + N::get_value(LRE)))
+    pub fn get_des_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        Ok(FileHeader::read(reader)?.segment_offsets().data_extension_segments)
     }
 
-    pub fn get_reserved_header_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        res_num: u64,
-    ) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_res = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMRES, nitf_bytes),
-            N::get_value(NUMRES),
-        );
-        if res_num + 1 > num_res as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lresh_nnn_offset = N::get_offset_bytes(LRESH, nitf_bytes);
-        lresh_nnn_offset + (res_num as usize * (N::get_value(LRESH) + N::get_value(LRE)))
+    pub fn get_reserved_extension_segment_start<R: Read + Seek>(reader: &mut R) -> Result<usize> {
+        Ok(FileHeader::read(reader)?.segment_offsets().reserved_extension_segments)
     }
 
-    pub fn get_reserved_data_field_offset(nitf_file: Option<&File>, res_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_res =
-            read_int_from_file(file, N::get_offset(NUMRES, nitf_file), N::get_value(NUMRES));
-        if res_num + 1 > num_res as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lresh_nnn_offset = N::get_reserved_header_field_offset(nitf_file, res_num);
-        lresh_nnn_offset + N::get_value(LRESH)
+    pub fn get_image_segments_offset<R: Read + Seek>(reader: &mut R, image_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let start = header.segment_offsets().image_segments;
+        segment_offset("Image", start, &header.images, image_num)
     }
 
-    pub fn get_reserved_data_field_offset_bytes(
-        nitf_bytes: Option<&Vec<u8>>,
-        res_num: u64,
-    ) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_res = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMRES, nitf_bytes),
-            N::get_value(NUMRES),
-        );
-        if res_num + 1 > num_res as u64 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let lresh_nnn_offset = N::get_reserved_header_field_offset_bytes(nitf_bytes, res_num);
-        lresh_nnn_offset + N::get_value(LRESH)
+    pub fn get_graphic_segments_offset<R: Read + Seek>(reader: &mut R, graphic_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let start = header.segment_offsets().graphic_segments;
+        segment_offset("Graphic", start, &header.graphics, graphic_num)
     }
 
-    pub fn get_image_subheader_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images =
-            read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let header_length =
-            read_int_from_file(file, N::get_offset(HL, nitf_file), N::get_value(HL));
-        if image_num == 0 {
-            return header_length;
-        }
-        let mut offset = header_length;
-        for i in 0..image_num {
-            offset += read_int_from_file(
-                file,
-                N::get_image_header_field_offset(nitf_file, i),
-                N::get_value(LISH),
-            ) + read_int_from_file(
-                file,
-                N::get_image_data_field_offset(nitf_file, i),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_image_subheader_offset_bytes(nitf_bytes: Option<&Vec<u8>>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMI, nitf_bytes),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let header_length =
-            read_int_from_bytes(bytes, N::get_offset_bytes(HL, nitf_bytes), N::get_value(HL));
-        if image_num == 0 {
-            return header_length;
-        }
-        let mut offset = header_length;
-        for i in 0..image_num {
-            offset += read_int_from_bytes(
-                bytes,
-                N::get_image_header_field_offset_bytes(nitf_bytes, i),
-                N::get_value(LISH),
-            ) + read_int_from_bytes(
-                bytes,
-                N::get_image_data_field_offset_bytes(nitf_bytes, i),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_image_data_offset(nitf_file: Option<&File>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let file =
-            nitf_file.expect("You must provide a file to get the offset of this header field.");
-        let num_images =
-            read_int_from_file(file, N::get_offset(NUMI, nitf_file), N::get_value(NUMI));
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let image_subheader_length = read_int_from_file(
-            file,
-            N::get_image_subheader_offset(nitf_file, image_num),
-            N::get_value(LISH),
-        );
-        N::get_image_subheader_offset(nitf_file, image_num) + image_subheader_length
-    }
-
-    pub fn get_image_data_offset_bytes(nitf_bytes: Option<&Vec<u8>>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let bytes =
-            nitf_bytes.expect("You must provide a file to get the offset of this header field.");
-        let num_images = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMI, nitf_bytes),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let image_subheader_length = read_int_from_bytes(
-            bytes,
-            N::get_image_subheader_offset_bytes(nitf_bytes, image_num),
-            N::get_value(LISH),
-        );
-        N::get_image_subheader_offset_bytes(nitf_bytes, image_num) + image_subheader_length
-    }
-
-    pub fn get_images_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        read_int_from_file(
-            nitf_file,
-            N::get_offset(HL, Some(nitf_file)),
-            N::get_value(HL),
-        )
-    }
-
-    pub fn get_images_segment_start_bytes(bytes: &Vec<u8>) -> usize {
-        use NitfHeader21::{self as N, *};
-        read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(HL, Some(bytes)),
-            N::get_value(HL),
-        )
-    }
-
-    pub fn get_graphic_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_images = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMI, Some(nitf_file)),
-            N::get_value(NUMI),
-        );
-        let mut offset = N::get_images_segment_start(nitf_file);
-        for i in 0..num_images {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_image_header_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LISH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_image_data_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_graphic_segment_start_bytes(bytes: &Vec<u8>) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_images = read_int_from_bytes(
-            bytes,
-            N::get_offset_bytes(NUMI, Some(bytes)),
-            N::get_value(NUMI),
-        );
-        let mut offset = N::get_images_segment_start_bytes(bytes);
-        for i in 0..num_images {
-            offset += read_int_from_bytes(
-                bytes,
-                N::get_image_header_field_offset_bytes(Some(bytes), i as u64),
-                N::get_value(LISH),
-            ) + read_int_from_bytes(
-                bytes,
-                N::get_image_data_field_offset_bytes(Some(bytes), i as u64),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_reserved_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21 as N;
-        println!("This segment is reserved for a future standard. This method currently returns the same value as get_text_segment_start.");
-        N::get_text_segment_start(nitf_file)
+    pub fn get_text_segments_offset<R: Read + Seek>(reader: &mut R, text_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let start = header.segment_offsets().text_segments;
+        segment_offset("Text", start, &header.texts, text_num)
     }
 
-    pub fn get_reserved_segment_start_bytes(nitf_bytes: &Vec<u8>) -> usize {
-        use NitfHeader21 as N;
-        println!("This segment is reserved for a future standard. This method currently returns the same value as get_text_segment_start.");
-        N::get_text_segment_start_bytes(nitf_bytes)
-    }
-
-    pub fn get_text_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_graphics = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMS, Some(nitf_file)),
-            N::get_value(NUMS),
-        );
-        let mut offset = N::get_graphic_segment_start(nitf_file);
-        for i in 0..num_graphics {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_image_header_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LISH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_image_data_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_text_segment_start_bytes(nitf_bytes: &Vec<u8>) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_graphics = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMS, Some(nitf_bytes)),
-            N::get_value(NUMS),
-        );
-        let mut offset = N::get_graphic_segment_start_bytes(nitf_bytes);
-        for i in 0..num_graphics {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_image_header_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LISH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_image_data_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_des_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_text = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMT, Some(nitf_file)),
-            N::get_value(NUMT),
-        );
-        let mut offset = N::get_text_segment_start(nitf_file);
-        for i in 0..num_text {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_text_header_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LTSH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_text_data_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LT),
-            );
-        }
-        offset
-    }
-
-    pub fn get_des_segment_start_bytes(nitf_bytes: &Vec<u8>) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_text = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMT, Some(nitf_bytes)),
-            N::get_value(NUMT),
-        );
-        let mut offset = N::get_text_segment_start_bytes(nitf_bytes);
-        for i in 0..num_text {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_text_header_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LTSH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_text_data_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LT),
-            );
-        }
-        offset
-    }
-
-    pub fn get_reserved_extension_segment_start(nitf_file: &File) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_des = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMDES, Some(nitf_file)),
-            N::get_value(NUMDES),
-        );
-        let mut offset = N::get_des_segment_start(nitf_file);
-        for i in 0..num_des {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_des_header_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LDSH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_des_data_field_offset(Some(nitf_file), i as u64),
-                N::get_value(LD),
-            );
-        }
-        offset
+    pub fn get_des_segments_offset<R: Read + Seek>(reader: &mut R, des_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let start = header.segment_offsets().data_extension_segments;
+        segment_offset("DES", start, &header.des, des_num)
     }
 
-    pub fn get_reserved_extension_segment_start_bytes(nitf_bytes: &Vec<u8>) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_des = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMDES, Some(nitf_bytes)),
-            N::get_value(NUMDES),
-        );
-        let mut offset = N::get_des_segment_start_bytes(nitf_bytes);
-        for i in 0..num_des {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_des_header_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LDSH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_des_data_field_offset_bytes(Some(nitf_bytes), i as u64),
-                N::get_value(LD),
-            );
-        }
-        offset
-    }
-
-    pub fn get_image_segments_offset(nitf_file: &File, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_images = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMI, Some(nitf_file)),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 && image_num != 0 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_images_segment_start(nitf_file);
-        for i in 0..image_num {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_image_header_field_offset(Some(nitf_file), i),
-                N::get_value(LISH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_image_data_field_offset(Some(nitf_file), i),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_image_segments_offset_bytes(nitf_bytes: &Vec<u8>, image_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_images = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMI, Some(nitf_bytes)),
-            N::get_value(NUMI),
-        );
-        if image_num + 1 > num_images as u64 && image_num != 0 {
-            panic!("Image number is greater than the number of images in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_images_segment_start_bytes(nitf_bytes);
-        for i in 0..image_num {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_image_header_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LISH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_image_data_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LI),
-            );
-        }
-        offset
-    }
-
-    pub fn get_graphic_segments_offset(nitf_file: &File, graphic_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_graphics = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMS, Some(nitf_file)),
-            N::get_value(NUMS),
-        );
-        if graphic_num + 1 > num_graphics as u64 && graphic_num != 0 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_graphic_segment_start(nitf_file);
-        for i in 0..graphic_num {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_graphic_header_field_offset(Some(nitf_file), i),
-                N::get_value(LSSH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_graphic_data_field_offset(Some(nitf_file), i),
-                N::get_value(LS),
-            );
-        }
-        offset
-    }
-
-    pub fn get_graphic_segments_offset_bytes(nitf_bytes: &Vec<u8>, graphic_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_graphics = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMS, Some(nitf_bytes)),
-            N::get_value(NUMS),
-        );
-        if graphic_num + 1 > num_graphics as u64 && graphic_num != 0 {
-            panic!("Graphic number is greater than the number of graphics in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_graphic_segment_start_bytes(nitf_bytes);
-        for i in 0..graphic_num {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_graphic_header_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LSSH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_graphic_data_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LS),
-            );
-        }
-        offset
-    }
-
-    pub fn get_text_segments_offset(nitf_file: &File, text_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_text = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMT, Some(nitf_file)),
-            N::get_value(NUMT),
-        );
-        if text_num + 1 > num_text as u64 && text_num != 0 {
-            panic!("Text number is greater than the number of text segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_offset(NUMT, Some(nitf_file)) + N::get_value(NUMT);
-        for i in 0..text_num {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_text_header_field_offset(Some(nitf_file), i),
-                N::get_value(LTSH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_text_data_field_offset(Some(nitf_file), i),
-                N::get_value(LT),
-            );
-        }
-        offset
+    pub fn get_reserved_segments_offset<R: Read + Seek>(reader: &mut R, res_num: u64) -> Result<usize> {
+        let header = FileHeader::read(reader)?;
+        let start = header.segment_offsets().reserved_extension_segments;
+        segment_offset("RES", start, &header.res, res_num)
     }
+}
 
-    pub fn get_text_segments_offset_bytes(nitf_bytes: &Vec<u8>, text_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_text = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMT, Some(nitf_bytes)),
-            N::get_value(NUMT),
-        );
-        if text_num + 1 > num_text as u64 && text_num != 0 {
-            panic!("Text number is greater than the number of text segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_offset_bytes(NUMT, Some(nitf_bytes)) + N::get_value(NUMT);
-        for i in 0..text_num {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_text_header_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LTSH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_text_data_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LT),
-            );
-        }
-        offset
+impl Field for NitfHeader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
     }
 
-    pub fn get_des_segments_offset(nitf_file: &File, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_des = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMDES, Some(nitf_file)),
-            N::get_value(NUMDES),
-        );
-        if des_num + 1 > num_des as u64 && des_num != 0 {
-            panic!("Data Extension number is greater than the number of data extension segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_des_segment_start(nitf_file);
-        for i in 0..des_num {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_des_header_field_offset(Some(nitf_file), i),
-                N::get_value(LDSH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_des_data_field_offset(Some(nitf_file), i),
-                N::get_value(LD),
-            );
-        }
-        offset
-    }
-
-    pub fn get_des_segments_offset_bytes(nitf_bytes: &Vec<u8>, des_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_des = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMDES, Some(nitf_bytes)),
-            N::get_value(NUMDES),
-        );
-        if des_num + 1 > num_des as u64 && des_num != 0 {
-            panic!("Data Extension number is greater than the number of data extension segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_des_segment_start_bytes(nitf_bytes);
-        for i in 0..des_num {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_des_header_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LDSH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_des_data_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LD),
-            );
-        }
-        offset
+    fn length(&self) -> usize {
+        NitfHeader21::get_value(*self)
     }
+}
 
-    pub fn get_reserved_segments_offset(nitf_file: &File, res_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_res = read_int_from_file(
-            nitf_file,
-            N::get_offset(NUMRES, Some(nitf_file)),
-            N::get_value(NUMRES),
-        );
-        if res_num + 1 > num_res as u64 && res_num != 0 {
-            panic!("Reserved Extension number is greater than the number of reserved extension segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_reserved_extension_segment_start(nitf_file);
-        for i in 0..res_num {
-            offset += read_int_from_file(
-                nitf_file,
-                N::get_reserved_header_field_offset(Some(nitf_file), i),
-                N::get_value(LRESH),
-            ) + read_int_from_file(
-                nitf_file,
-                N::get_reserved_data_field_offset(Some(nitf_file), i),
-                N::get_value(LRE),
-            );
-        }
-        offset
-    }
+/// Offset of the subheader length field of one entry in a segment length table.
+pub(crate) fn length_table_entry<F: Field>(
+    segment: &'static str,
+    table_offset: usize,
+    count: usize,
+    index: u64,
+    subheader: F,
+    data: F,
+) -> Result<usize> {
+    if index >= count as u64 {
+        return Err(NitfError::IndexOutOfRange {
+            segment,
+            index: index as usize,
+            count,
+        });
+    }
+    Ok(table_offset + index as usize * (subheader.length() + data.length()))
+}
 
-    pub fn get_reserved_segments_offset_bytes(nitf_bytes: &Vec<u8>, res_num: u64) -> usize {
-        use NitfHeader21::{self as N, *};
-        let num_res = read_int_from_bytes(
-            nitf_bytes,
-            N::get_offset_bytes(NUMRES, Some(nitf_bytes)),
-            N::get_value(NUMRES),
-        );
-        if res_num + 1 > num_res as u64 && res_num != 0 {
-            panic!("Reserved Extension number is greater than the number of reserved extension segments in the file, they are 0 indexed, are you off by 1?");
-        }
-        let mut offset = N::get_reserved_extension_segment_start_bytes(nitf_bytes);
-        for i in 0..res_num {
-            offset += read_int_from_bytes(
-                nitf_bytes,
-                N::get_reserved_header_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LRESH),
-            ) + read_int_from_bytes(
-                nitf_bytes,
-                N::get_reserved_data_field_offset_bytes(Some(nitf_bytes), i),
-                N::get_value(LRE),
-            );
-        }
-        offset
-    }
+/// Offset of a segment, found by adding the lengths of the segments before it to the group start.
+pub(crate) fn segment_offset(
+    segment: &'static str,
+    start: usize,
+    lengths: &[SegmentLength],
+    index: u64,
+) -> Result<usize> {
+    //Index 0 is allowed when there are no segments, it gives the start of the empty group
+    if index >= lengths.len() as u64 && index != 0 {
+        return Err(NitfError::IndexOutOfRange {
+            segment,
+            index: index as usize,
+            count: lengths.len(),
+        });
+    }
+    Ok(start + lengths[..index as usize].iter().map(SegmentLength::total).sum::<usize>())
 }
 
+#[derive(Debug, Copy, Clone)]
 pub enum NitfImageSubheader21 {
     IM,
     IID1,
-    IIDATIM,
+    IDATIM,
     TGTID,
     IID2,
     ISCLAS,
@@ -1761,15 +1030,20 @@ pub enum NitfImageSubheader21 {
     ABPP,
     PJUST,
     ICORDS,
+    IGEOLO,
     NICOM,
     ICOMNNN,
     IC,
+    COMRAT,
     NBANDS,
+    XBANDS,
     IREPBANDNNN,
     ISUBCATNNN,
     IFCNNN,
     IMFLTNNN,
     NLUTSNNN,
+    NELUTNNN,
+    LUTDNNNM,
     ISYNC,
     IMODE,
     NBPR,
@@ -1782,161 +1056,1133 @@ pub enum NitfImageSubheader21 {
     ILOC,
     IMAG,
     UDIDL,
+    UDOFL,
+    UDID,
     IXSHDL,
+    IXSOFL,
+    IXSHD,
+}
+
+impl NitfImageSubheader21 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 10, 14, 17, 80, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 1, 42, 8, 8,
+            3, 8, 8, 2, 1, 1, 60, 1, 80, 2, 4, 1, 5, 2, 6, 1, 3, 1, 5, 0, 1, 1, 4, 4, 4, 4, 2, 3,
+            3, 10, 4, 5, 3, 0, 5, 3, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfImageSubheader21::*;
+        match self {
+            IM => "IM",
+            IID1 => "IID1",
+            IDATIM => "IDATIM",
+            TGTID => "TGTID",
+            IID2 => "IID2",
+            ISCLAS => "ISCLAS",
+            ISCLSY => "ISCLSY",
+            ISCODE => "ISCODE",
+            ISCTLH => "ISCTLH",
+            ISREL => "ISREL",
+            ISDCTP => "ISDCTP",
+            ISDCDT => "ISDCDT",
+            ISDCXM => "ISDCXM",
+            ISDG => "ISDG",
+            ISDGDT => "ISDGDT",
+            ISCLTX => "ISCLTX",
+            ISCATP => "ISCATP",
+            ISCAUT => "ISCAUT",
+            ISCRSN => "ISCRSN",
+            ISSRDT => "ISSRDT",
+            ISCTLN => "ISCTLN",
+            ENCRYP => "ENCRYP",
+            ISORCE => "ISORCE",
+            NROWS => "NROWS",
+            NCOLS => "NCOLS",
+            PVTYPE => "PVTYPE",
+            IREP => "IREP",
+            ICAT => "ICAT",
+            ABPP => "ABPP",
+            PJUST => "PJUST",
+            ICORDS => "ICORDS",
+            IGEOLO => "IGEOLO",
+            NICOM => "NICOM",
+            ICOMNNN => "ICOMNNN",
+            IC => "IC",
+            COMRAT => "COMRAT",
+            NBANDS => "NBANDS",
+            XBANDS => "XBANDS",
+            IREPBANDNNN => "IREPBANDNNN",
+            ISUBCATNNN => "ISUBCATNNN",
+            IFCNNN => "IFCNNN",
+            IMFLTNNN => "IMFLTNNN",
+            NLUTSNNN => "NLUTSNNN",
+            NELUTNNN => "NELUTNNN",
+            LUTDNNNM => "LUTDNNNM",
+            ISYNC => "ISYNC",
+            IMODE => "IMODE",
+            NBPR => "NBPR",
+            NBPC => "NBPC",
+            NPPBH => "NPPBH",
+            NPPBV => "NPPBV",
+            NBPP => "NBPP",
+            IDLVL => "IDLVL",
+            IALVL => "IALVL",
+            ILOC => "ILOC",
+            IMAG => "IMAG",
+            UDIDL => "UDIDL",
+            UDOFL => "UDOFL",
+            UDID => "UDID",
+            IXSHDL => "IXSHDL",
+            IXSOFL => "IXSOFL",
+            IXSHD => "IXSHD",
+        }
+    }
+    pub fn get_value(target: NitfImageSubheader21) -> usize {
+        let index = target as usize;
+        NitfImageSubheader21::values()[index]
+    }
+}
+
+impl Field for NitfImageSubheader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfImageSubheader21::get_value(*self)
+    }
+}
+
+/// The sixteen security fields shared by the file header and every NITF 2.1 segment subheader.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SecurityGroup {
+    pub clas: String,
+    pub clsy: String,
+    pub code: String,
+    pub ctlh: String,
+    pub rel: String,
+    pub dctp: String,
+    pub dcdt: Option<NaiveDate>,
+    pub dcxm: String,
+    pub dg: String,
+    pub dgdt: Option<NaiveDate>,
+    pub cltx: String,
+    pub catp: String,
+    pub caut: String,
+    pub crsn: String,
+    pub srdt: Option<NaiveDate>,
+    pub ctln: String,
+}
+
+impl SecurityGroup {
+    pub fn values() -> &'static [usize] {
+        &[1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15]
+    }
+
+    pub fn length() -> usize {
+        SecurityGroup::values().iter().sum()
+    }
+
+    pub fn read(cursor: &mut FieldCursor) -> Result<SecurityGroup> {
+        use SecurityField::*;
+        Ok(SecurityGroup {
+            clas: cursor.read_string(CLAS)?,
+            clsy: cursor.read_string(CLSY)?,
+            code: cursor.read_string(CODE)?,
+            ctlh: cursor.read_string(CTLH)?,
+            rel: cursor.read_string(REL)?,
+            dctp: cursor.read_string(DCTP)?,
+            dcdt: cursor.read_date(DCDT)?,
+            dcxm: cursor.read_string(DCXM)?,
+            dg: cursor.read_string(DG)?,
+            dgdt: cursor.read_date(DGDT)?,
+            cltx: cursor.read_string(CLTX)?,
+            catp: cursor.read_string(CATP)?,
+            caut: cursor.read_string(CAUT)?,
+            crsn: cursor.read_string(CRSN)?,
+            srdt: cursor.read_date(SRDT)?,
+            ctln: cursor.read_string(CTLN)?,
+        })
+    }
+
+    pub fn write(&self, writer: &mut FieldWriter) -> Result<()> {
+        use SecurityField::*;
+        writer.write_string(CLAS, &self.clas)?;
+        writer.write_string(CLSY, &self.clsy)?;
+        writer.write_string(CODE, &self.code)?;
+        writer.write_string(CTLH, &self.ctlh)?;
+        writer.write_string(REL, &self.rel)?;
+        writer.write_string(DCTP, &self.dctp)?;
+        writer.write_date(DCDT, self.dcdt)?;
+        writer.write_string(DCXM, &self.dcxm)?;
+        writer.write_string(DG, &self.dg)?;
+        writer.write_date(DGDT, self.dgdt)?;
+        writer.write_string(CLTX, &self.cltx)?;
+        writer.write_string(CATP, &self.catp)?;
+        writer.write_string(CAUT, &self.caut)?;
+        writer.write_string(CRSN, &self.crsn)?;
+        writer.write_date(SRDT, self.srdt)?;
+        writer.write_string(CTLN, &self.ctln)
+    }
+}
+
+/// The fields of a [`SecurityGroup`], without the two letter prefix (FS, IS, SS, ...) of the segment they are in.
+#[derive(Debug, Copy, Clone)]
+pub enum SecurityField {
+    CLAS,
+    CLSY,
+    CODE,
+    CTLH,
+    REL,
+    DCTP,
+    DCDT,
+    DCXM,
+    DG,
+    DGDT,
+    CLTX,
+    CATP,
+    CAUT,
+    CRSN,
+    SRDT,
+    CTLN,
+}
+
+impl SecurityField {
+    pub fn as_str(&self) -> &'static str {
+        use SecurityField::*;
+        match self {
+            CLAS => "CLAS",
+            CLSY => "CLSY",
+            CODE => "CODE",
+            CTLH => "CTLH",
+            REL => "REL",
+            DCTP => "DCTP",
+            DCDT => "DCDT",
+            DCXM => "DCXM",
+            DG => "DG",
+            DGDT => "DGDT",
+            CLTX => "CLTX",
+            CATP => "CATP",
+            CAUT => "CAUT",
+            CRSN => "CRSN",
+            SRDT => "SRDT",
+            CTLN => "CTLN",
+        }
+    }
+}
+
+impl Field for SecurityField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        SecurityGroup::values()[*self as usize]
+    }
+}
+
+/// Per band fields of the image subheader, repeated NBANDS (or XBANDS) times.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ImageBand {
+    pub irepband: String,
+    pub isubcat: String,
+    pub ifc: String,
+    pub imflt: String,
+    pub nelut: usize,
+    /// One entry per LUT (NLUTS of them), each NELUT bytes long.
+    pub lutd: Vec<Vec<u8>>,
+}
+
+impl ImageBand {
+    pub fn nluts(&self) -> usize {
+        self.lutd.len()
+    }
+}
+
+/// What the data field of an image segment holds, going by IC. The masked variants (M3, M8,
+/// NM, ...) hold the same data behind a block mask table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageCompression {
+    /// C8, M8: a JPEG 2000 codestream.
+    Jpeg2000,
+    /// C3, M3, C5, M5, I1: a JPEG stream.
+    Jpeg,
+    /// NC, NM: raw pixels.
+    Uncompressed,
+    /// Any other IC, kept as is.
+    Other(String),
+}
+
+impl ImageCompression {
+    pub fn from_ic(ic: &str) -> ImageCompression {
+        match ic {
+            "C8" | "M8" => ImageCompression::Jpeg2000,
+            "C3" | "M3" | "C5" | "M5" | "I1" => ImageCompression::Jpeg,
+            "NC" | "NM" => ImageCompression::Uncompressed,
+            other => ImageCompression::Other(other.to_string()),
+        }
+    }
+
+    /// File extension for the extracted data field.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageCompression::Jpeg2000 => "j2k",
+            ImageCompression::Jpeg => "jpg",
+            ImageCompression::Uncompressed => "raw",
+            ImageCompression::Other(_) => "bin",
+        }
+    }
+}
+
+/// A parsed NITF 2.1 image subheader.
+///
+/// Repeating fields are stored as vectors, so NICOM, NBANDS/XBANDS and NLUTS are
+/// the lengths of `icom`, `bands` and `ImageBand::lutd`. Conditional fields that are
+/// absent from the subheader are `None` or empty.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ImageSubheader {
+    pub im: String,
+    pub iid1: String,
+    pub idatim: Option<NaiveDateTime>,
+    pub tgtid: String,
+    pub iid2: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    pub isorce: String,
+    pub nrows: usize,
+    pub ncols: usize,
+    pub pvtype: String,
+    pub irep: String,
+    pub icat: String,
+    pub abpp: usize,
+    pub pjust: String,
+    pub icords: String,
+    pub igeolo: Option<String>,
+    pub icom: Vec<String>,
+    pub ic: String,
+    pub comrat: Option<String>,
+    pub bands: Vec<ImageBand>,
+    pub isync: usize,
+    pub imode: String,
+    pub nbpr: usize,
+    pub nbpc: usize,
+    pub nppbh: usize,
+    pub nppbv: usize,
+    pub nbpp: usize,
+    pub idlvl: usize,
+    pub ialvl: usize,
+    /// ILOC as (row, column); either may be negative.
+    pub iloc: (isize, isize),
+    pub imag: String,
+    pub udofl: usize,
+    pub udid: Vec<u8>,
+    pub ixsofl: usize,
+    pub ixshd: Vec<u8>,
+}
+
+impl ImageSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<ImageSubheader> {
+        ImageSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    /// Offset of IGEOLO within the subheader. None of the fields before it are conditional.
+    pub fn igeolo_offset(&self) -> usize {
+        NitfImageSubheader21::values()[..NitfImageSubheader21::IGEOLO as usize].iter().sum()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ImageSubheader> {
+        Ok(ImageSubheader::parse(bytes)?.0)
+    }
+
+    /// Offset of IDLVL within the subheader `bytes`, as found while parsing them, since
+    /// everything in front of it can vary in length. IALVL, ILOC and IMAG follow it.
+    pub fn idlvl_offset(bytes: &[u8]) -> Result<usize> {
+        Ok(ImageSubheader::parse(bytes)?.1)
+    }
+
+    /// The subheader and the offset of IDLVL.
+    fn parse(bytes: &[u8]) -> Result<(ImageSubheader, usize)> {
+        use NitfImageSubheader21::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = ImageSubheader {
+            im: c.read_string(IM)?,
+            iid1: c.read_string(IID1)?,
+            idatim: c.read_date_time(IDATIM)?,
+            tgtid: c.read_string(TGTID)?,
+            iid2: c.read_string(IID2)?,
+            security: SecurityGroup::read(&mut c)?,
+            encryp: c.read_string(ENCRYP)?,
+            isorce: c.read_string(ISORCE)?,
+            nrows: c.read_int(NROWS)?,
+            ncols: c.read_int(NCOLS)?,
+            pvtype: c.read_string(PVTYPE)?,
+            irep: c.read_string(IREP)?,
+            icat: c.read_string(ICAT)?,
+            abpp: c.read_int(ABPP)?,
+            pjust: c.read_string(PJUST)?,
+            icords: c.read_string(ICORDS)?,
+            ..Default::default()
+        };
+        //IGEOLO is only present when ICORDS is not blank
+        if !sub.icords.is_empty() {
+            sub.igeolo = Some(c.read_string(IGEOLO)?);
+        }
+        let nicom = c.read_int(NICOM)?;
+        for _ in 0..nicom {
+            sub.icom.push(c.read_string(ICOMNNN)?);
+        }
+        sub.ic = c.read_string(IC)?;
+        //COMRAT is only present for compressed images
+        if sub.ic != "NC" && sub.ic != "NM" {
+            sub.comrat = Some(c.read_string(COMRAT)?);
+        }
+        let mut nbands = c.read_int(NBANDS)?;
+        if nbands == 0 {
+            nbands = c.read_int(XBANDS)?;
+        }
+        for _ in 0..nbands {
+            let mut band = ImageBand {
+                irepband: c.read_string(IREPBANDNNN)?,
+                isubcat: c.read_string(ISUBCATNNN)?,
+                ifc: c.read_string(IFCNNN)?,
+                imflt: c.read_string(IMFLTNNN)?,
+                ..Default::default()
+            };
+            let nluts = c.read_int(NLUTSNNN)?;
+            if nluts > 0 {
+                band.nelut = c.read_int(NELUTNNN)?;
+                for _ in 0..nluts {
+                    band.lutd.push(c.read_bytes(band.nelut)?.to_vec());
+                }
+            }
+            sub.bands.push(band);
+        }
+        sub.isync = c.read_int(ISYNC)?;
+        sub.imode = c.read_string(IMODE)?;
+        sub.nbpr = c.read_int(NBPR)?;
+        sub.nbpc = c.read_int(NBPC)?;
+        sub.nppbh = c.read_int(NPPBH)?;
+        sub.nppbv = c.read_int(NPPBV)?;
+        sub.nbpp = c.read_int(NBPP)?;
+        let idlvl_offset = c.offset;
+        sub.idlvl = c.read_int(IDLVL)?;
+        sub.ialvl = c.read_int(IALVL)?;
+        sub.iloc = c.read_location(ILOC)?;
+        sub.imag = c.read_string(IMAG)?;
+        (sub.udofl, sub.udid) = c.read_extension(UDIDL, UDOFL)?;
+        (sub.ixsofl, sub.ixshd) = c.read_extension(IXSHDL, IXSOFL)?;
+        Ok((sub, idlvl_offset))
+    }
+
+    /// Serializes the subheader, the inverse of [`ImageSubheader::from_bytes`]. The counts
+    /// (NICOM, NBANDS/XBANDS, NLUTS) come from the vector lengths.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfImageSubheader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(IM, &self.im)?;
+        w.write_string(IID1, &self.iid1)?;
+        w.write_date_time(IDATIM, self.idatim)?;
+        w.write_string(TGTID, &self.tgtid)?;
+        w.write_string(IID2, &self.iid2)?;
+        self.security.write(&mut w)?;
+        w.write_string(ENCRYP, &self.encryp)?;
+        w.write_string(ISORCE, &self.isorce)?;
+        w.write_int(NROWS, self.nrows)?;
+        w.write_int(NCOLS, self.ncols)?;
+        w.write_string(PVTYPE, &self.pvtype)?;
+        w.write_string(IREP, &self.irep)?;
+        w.write_string(ICAT, &self.icat)?;
+        w.write_int(ABPP, self.abpp)?;
+        w.write_string(PJUST, &self.pjust)?;
+        w.write_string(ICORDS, &self.icords)?;
+        if let Some(igeolo) = &self.igeolo {
+            w.write_string(IGEOLO, igeolo)?;
+        }
+        w.write_int(NICOM, self.icom.len())?;
+        for icom in &self.icom {
+            w.write_string(ICOMNNN, icom)?;
+        }
+        w.write_string(IC, &self.ic)?;
+        if let Some(comrat) = &self.comrat {
+            w.write_string(COMRAT, comrat)?;
+        }
+        //More than nine bands do not fit in NBANDS, they go in XBANDS with NBANDS set to 0
+        if !(1..=9).contains(&self.nbands()) {
+            w.write_int(NBANDS, 0)?;
+            w.write_int(XBANDS, self.nbands())?;
+        } else {
+            w.write_int(NBANDS, self.nbands())?;
+        }
+        for band in &self.bands {
+            w.write_string(IREPBANDNNN, &band.irepband)?;
+            w.write_string(ISUBCATNNN, &band.isubcat)?;
+            w.write_string(IFCNNN, &band.ifc)?;
+            w.write_string(IMFLTNNN, &band.imflt)?;
+            w.write_int(NLUTSNNN, band.nluts())?;
+            if band.nluts() > 0 {
+                w.write_int(NELUTNNN, band.nelut)?;
+                for lut in &band.lutd {
+                    w.write_bytes(lut);
+                }
+            }
+        }
+        w.write_int(ISYNC, self.isync)?;
+        w.write_string(IMODE, &self.imode)?;
+        w.write_int(NBPR, self.nbpr)?;
+        w.write_int(NBPC, self.nbpc)?;
+        w.write_int(NPPBH, self.nppbh)?;
+        w.write_int(NPPBV, self.nppbv)?;
+        w.write_int(NBPP, self.nbpp)?;
+        w.write_int(IDLVL, self.idlvl)?;
+        w.write_int(IALVL, self.ialvl)?;
+        w.write_location(ILOC, self.iloc)?;
+        w.write_string(IMAG, &self.imag)?;
+        w.write_extension(UDIDL, UDOFL, self.udofl, &self.udid)?;
+        w.write_extension(IXSHDL, IXSOFL, self.ixsofl, &self.ixshd)?;
+        Ok(w.into_bytes())
+    }
+
+    pub fn nbands(&self) -> usize {
+        self.bands.len()
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        self.comrat.is_some()
+    }
+
+    /// The TREs of UDID followed by those of IXSHD.
+    pub fn tres(&self) -> impl Iterator<Item = Result<Tre>> + '_ {
+        TreIter::new(&self.udid).chain(TreIter::new(&self.ixshd))
+    }
+
+    /// The TREs of UDID and IXSHD, with the support data TREs in [`ImageTre`] decoded.
+    pub fn decode_tres(&self) -> Result<Vec<ImageTre>> {
+        let mut tres = decode_tres(&self.udid)?;
+        tres.extend(decode_tres(&self.ixshd)?);
+        Ok(tres)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfGraphicSubheader21 {
+    SY,
+    SID,
+    SNAME,
+    SSCLAS,
+    SSCLSY,
+    SSCODE,
+    SSCTLH,
+    SSREL,
+    SSDCTP,
+    SSDCDT,
+    SSDCXM,
+    SSDG,
+    SSDGDT,
+    SSCLTX,
+    SSCATP,
+    SSCAUT,
+    SSCRSN,
+    SSSRDT,
+    SSCTLN,
+    ENCRYP,
+    SFMT,
+    SSTRUCT,
+    SDLVL,
+    SALVL,
+    SLOC,
+    SBND1,
+    SCOLOR,
+    SBND2,
+    SRES2,
+    SXSHDL,
+    SXSOFL,
+    SXSHD,
+}
+
+impl NitfGraphicSubheader21 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 10, 20, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 1, 1, 13, 3, 3, 10,
+            10, 1, 10, 2, 5, 3, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfGraphicSubheader21::*;
+        match self {
+            SY => "SY",
+            SID => "SID",
+            SNAME => "SNAME",
+            SSCLAS => "SSCLAS",
+            SSCLSY => "SSCLSY",
+            SSCODE => "SSCODE",
+            SSCTLH => "SSCTLH",
+            SSREL => "SSREL",
+            SSDCTP => "SSDCTP",
+            SSDCDT => "SSDCDT",
+            SSDCXM => "SSDCXM",
+            SSDG => "SSDG",
+            SSDGDT => "SSDGDT",
+            SSCLTX => "SSCLTX",
+            SSCATP => "SSCATP",
+            SSCAUT => "SSCAUT",
+            SSCRSN => "SSCRSN",
+            SSSRDT => "SSSRDT",
+            SSCTLN => "SSCTLN",
+            ENCRYP => "ENCRYP",
+            SFMT => "SFMT",
+            SSTRUCT => "SSTRUCT",
+            SDLVL => "SDLVL",
+            SALVL => "SALVL",
+            SLOC => "SLOC",
+            SBND1 => "SBND1",
+            SCOLOR => "SCOLOR",
+            SBND2 => "SBND2",
+            SRES2 => "SRES2",
+            SXSHDL => "SXSHDL",
+            SXSOFL => "SXSOFL",
+            SXSHD => "SXSHD",
+        }
+    }
+    pub fn get_value(target: NitfGraphicSubheader21) -> usize {
+        let index = target as usize;
+        NitfGraphicSubheader21::values()[index]
+    }
+    /// Offset of a field up to SXSHDL within the subheader. None of the fields before it are conditional.
+    pub fn fixed_offset(target: NitfGraphicSubheader21) -> usize {
+        NitfGraphicSubheader21::values()[..target as usize].iter().sum()
+    }
+}
+
+impl Field for NitfGraphicSubheader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfGraphicSubheader21::get_value(*self)
+    }
+}
+
+/// A NITF 2.1 graphic subheader. CGM (SFMT "C") is the only graphic format 2.1 defines.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct GraphicSubheader {
+    pub sy: String,
+    pub sid: String,
+    pub sname: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    pub sfmt: String,
+    /// Reserved, always zero.
+    pub sstruct: usize,
+    pub sdlvl: usize,
+    pub salvl: usize,
+    /// SLOC as (row, column), relative to the attachment level.
+    pub sloc: (isize, isize),
+    /// Upper left corner of the graphic's bounding box as (row, column).
+    pub sbnd1: (isize, isize),
+    pub scolor: String,
+    /// Lower right corner of the graphic's bounding box as (row, column).
+    pub sbnd2: (isize, isize),
+    /// Reserved, always zero.
+    pub sres2: usize,
+    pub sxsofl: usize,
+    pub sxshd: Vec<u8>,
 }
 
-// impl NitfImageSubheader21 {
-//     pub fn values() -> &'static [usize] {
-//         &[2, 10, 14, 17, 80, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 1, 42, 8, 8, 3, 4, 8, 2, 1, 1, 1, 80, 2, 1, 2, 6, 1, 3, 1, 1, 1, 4, 4, 4, 4, 2, 3, 3, 10, 4, 5, 5
-//         ]
-//     }
-//     pub fn get_value(target: NitfImageSubheader21) -> usize {
-//         let index = target as usize;
-//         NitfImageSubheader21::values()[index]
-//     }
-// pub fn get_offset(target: NitfImageSubheader21, nitf_file: Option<&File>) -> usize {
-//     match target {
-//         NitfImageSubheader21::IC => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of X segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader21::get_offset(NitfImageSubheader21::NICOM, None);
-//             let num_comments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::NICOM),
-//             );
-//             base_offset
-//                 + NitfImageSubheader21::get_value(NitfImageSubheader21::NICOM)
-//                 + (num_comments
-//                     * (NitfImageSubheader21::get_value(NitfImageSubheader21::ICOMNNN)))
-//         }
-//         NitfImageSubheader21::NUMX => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of Graphic segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader21::get_offset(NitfImageSubheader21::NUMS, Some(file));
-//             let num_segments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::NUMS),
-//             );
-//             base_offset
-//                 + NitfImageSubheader21::get_value(NitfImageSubheader21::NUMS)
-//                 + (num_segments
-//                     * (NitfImageSubheader21::get_value(NitfImageSubheader21::LSSHNNN)
-//                         + NitfImageSubheader21::get_value(NitfImageSubheader21::LSNNN)))
-//         }
-//         NitfImageSubheader21::NUMT => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of Text segments."
-//                 ),
-//             }
-//             let base_offset = NitfImageSubheader21::get_offset(NitfImageSubheader21::NUMX, Some(file));
-//             let num_text_segments = read_int_from_file(
-//                 &file,
-//                 base_offset,
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::NUMX),
-//             );
-//             base_offset
-//                 + NitfImageSubheader21::get_value(NitfImageSubheader21::NUMX)
-//                 + (num_text_segments
-//                     * (NitfImageSubheader21::get_value(NitfImageSubheader21::LTSHNNN)
-//                         + NitfImageSubheader21::get_value(NitfImageSubheader21::LTNNN)))
-//         }
-//         NitfImageSubheader21::NUMDES => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of DESegments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader21::get_offset(NitfImageSubheader21::HL, None),
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader21::get_value(NitfImageSubheader21::XHDL)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::UDHDL)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::NUMRES)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::NUMDES))
-//         }
-//         NitfImageSubheader21::NUMRES => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader21::get_offset(NitfImageSubheader21::HL, None),
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader21::get_value(NitfImageSubheader21::XHDL)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::UDHDL)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::NUMRES))
-//         }
-//         NitfImageSubheader21::UDHDL => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader21::get_offset(NitfImageSubheader21::HL, None),
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::HL),
-//             );
-//             header_length
-//                 - (NitfImageSubheader21::get_value(NitfImageSubheader21::XHDL)
-//                     + NitfImageSubheader21::get_value(NitfImageSubheader21::UDHDL))
-//         }
-//         NitfImageSubheader21::XHDL => {
-//             let file;
-//             match nitf_file {
-//                 Some(f) => file = f,
-//                 None => panic!(
-//                     "You must provide a file to get the offset of the number of segments."
-//                 ),
-//             }
-//             let header_length = read_int_from_file(
-//                 &file,
-//                 NitfImageSubheader21::get_offset(NitfImageSubheader21::HL, None),
-//                 NitfImageSubheader21::get_value(NitfImageSubheader21::HL),
-//             );
-//             header_length - NitfImageSubheader21::get_value(NitfImageSubheader21::XHDL)
-//         }
-//         NitfImageSubheader21::LISHNNN
-//         | NitfImageSubheader21::LINNN
-//         | NitfImageSubheader21::LSSHNNN
-//         | NitfImageSubheader21::LSNNN
-//         | NitfImageSubheader21::LTSHNNN
-//         | NitfImageSubheader21::LTNNN => {
-//             eprint!("You cannot get the single offset of N number of segments. Consider finding the offset of the number of segments and adding the size of the Subheader and Data length fields you are looking as many times as you need to get to the header offset you are looking for.");
-//             0
-//         }
-//         _ => {
-//             let index = target as usize;
-//             NitfImageSubheader21::values()[..index].iter().sum()
-//         }
-//     }
-//}
-//}
+impl GraphicSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<GraphicSubheader> {
+        GraphicSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<GraphicSubheader> {
+        use NitfGraphicSubheader21::*;
+        let mut c = FieldCursor::new(bytes);
+        let sy = c.read_string(SY)?;
+        let sid = c.read_string(SID)?;
+        let sname = c.read_string(SNAME)?;
+        let security = SecurityGroup::read(&mut c)?;
+        let encryp = c.read_string(ENCRYP)?;
+        let sfmt = c.read_string(SFMT)?;
+        let sstruct = c.read_int(SSTRUCT)?;
+        let sdlvl = c.read_int(SDLVL)?;
+        let salvl = c.read_int(SALVL)?;
+        let sloc = c.read_location(SLOC)?;
+        let sbnd1 = c.read_location(SBND1)?;
+        let scolor = c.read_string(SCOLOR)?;
+        let sbnd2 = c.read_location(SBND2)?;
+        let sres2 = c.read_int(SRES2)?;
+        let (sxsofl, sxshd) = c.read_extension(SXSHDL, SXSOFL)?;
+        Ok(GraphicSubheader {
+            sy,
+            sid,
+            sname,
+            security,
+            encryp,
+            sfmt,
+            sstruct,
+            sdlvl,
+            salvl,
+            sloc,
+            sbnd1,
+            scolor,
+            sbnd2,
+            sres2,
+            sxsofl,
+            sxshd,
+        })
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfGraphicSubheader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(SY, &self.sy)?;
+        w.write_string(SID, &self.sid)?;
+        w.write_string(SNAME, &self.sname)?;
+        self.security.write(&mut w)?;
+        w.write_string(ENCRYP, &self.encryp)?;
+        w.write_string(SFMT, &self.sfmt)?;
+        w.write_int(SSTRUCT, self.sstruct)?;
+        w.write_int(SDLVL, self.sdlvl)?;
+        w.write_int(SALVL, self.salvl)?;
+        w.write_location(SLOC, self.sloc)?;
+        w.write_location(SBND1, self.sbnd1)?;
+        w.write_string(SCOLOR, &self.scolor)?;
+        w.write_location(SBND2, self.sbnd2)?;
+        w.write_int(SRES2, self.sres2)?;
+        w.write_extension(SXSHDL, SXSOFL, self.sxsofl, &self.sxshd)?;
+        Ok(w.into_bytes())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfTextSubheader21 {
+    TE,
+    TEXTID,
+    TXTALVL,
+    TXTDT,
+    TXTITL,
+    TSCLAS,
+    TSCLSY,
+    TSCODE,
+    TSCTLH,
+    TSREL,
+    TSDCTP,
+    TSDCDT,
+    TSDCXM,
+    TSDG,
+    TSDGDT,
+    TSCLTX,
+    TSCATP,
+    TSCAUT,
+    TSCRSN,
+    TSSRDT,
+    TSCTLN,
+    ENCRYP,
+    TXTFMT,
+    TXSHDL,
+    TXSOFL,
+    TXSHD,
+}
+
+impl NitfTextSubheader21 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 7, 3, 14, 80, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 1, 3, 5, 3, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfTextSubheader21::*;
+        match self {
+            TE => "TE",
+            TEXTID => "TEXTID",
+            TXTALVL => "TXTALVL",
+            TXTDT => "TXTDT",
+            TXTITL => "TXTITL",
+            TSCLAS => "TSCLAS",
+            TSCLSY => "TSCLSY",
+            TSCODE => "TSCODE",
+            TSCTLH => "TSCTLH",
+            TSREL => "TSREL",
+            TSDCTP => "TSDCTP",
+            TSDCDT => "TSDCDT",
+            TSDCXM => "TSDCXM",
+            TSDG => "TSDG",
+            TSDGDT => "TSDGDT",
+            TSCLTX => "TSCLTX",
+            TSCATP => "TSCATP",
+            TSCAUT => "TSCAUT",
+            TSCRSN => "TSCRSN",
+            TSSRDT => "TSSRDT",
+            TSCTLN => "TSCTLN",
+            ENCRYP => "ENCRYP",
+            TXTFMT => "TXTFMT",
+            TXSHDL => "TXSHDL",
+            TXSOFL => "TXSOFL",
+            TXSHD => "TXSHD",
+        }
+    }
+    pub fn get_value(target: NitfTextSubheader21) -> usize {
+        let index = target as usize;
+        NitfTextSubheader21::values()[index]
+    }
+    /// Offset of a field up to TXSHDL within the subheader. None of the fields before it are conditional.
+    pub fn fixed_offset(target: NitfTextSubheader21) -> usize {
+        NitfTextSubheader21::values()[..target as usize].iter().sum()
+    }
+}
+
+impl Field for NitfTextSubheader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfTextSubheader21::get_value(*self)
+    }
+}
+
+/// A NITF 2.1 text subheader.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TextSubheader {
+    pub te: String,
+    pub textid: String,
+    pub txtalvl: usize,
+    pub txtdt: Option<NaiveDateTime>,
+    pub txtitl: String,
+    pub security: SecurityGroup,
+    pub encryp: String,
+    /// STA, UT1, U8S or MTF.
+    pub txtfmt: String,
+    pub txsofl: usize,
+    pub txshd: Vec<u8>,
+}
+
+impl TextSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<TextSubheader> {
+        TextSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TextSubheader> {
+        use NitfTextSubheader21::*;
+        let mut c = FieldCursor::new(bytes);
+        let te = c.read_string(TE)?;
+        let textid = c.read_string(TEXTID)?;
+        let txtalvl = c.read_int(TXTALVL)?;
+        let txtdt = c.read_date_time(TXTDT)?;
+        let txtitl = c.read_string(TXTITL)?;
+        let security = SecurityGroup::read(&mut c)?;
+        let encryp = c.read_string(ENCRYP)?;
+        let txtfmt = c.read_string(TXTFMT)?;
+        let (txsofl, txshd) = c.read_extension(TXSHDL, TXSOFL)?;
+        Ok(TextSubheader {
+            te,
+            textid,
+            txtalvl,
+            txtdt,
+            txtitl,
+            security,
+            encryp,
+            txtfmt,
+            txsofl,
+            txshd,
+        })
+    }
+
+    /// Decodes the text data of the segment according to TXTFMT. U8S is UTF-8, while STA (BCS),
+    /// UT1 (ECS) and MTF (USMTF, BCS or ECS) are single byte and read as ISO-8859-1, which BCS and
+    /// ECS are subsets of. Offsets in errors are relative to the subheader for TXTFMT and to the
+    /// start of `data` for the text itself.
+    pub fn decode_text(&self, data: &[u8]) -> Result<String> {
+        match self.txtfmt.as_str() {
+            "U8S" => String::from_utf8(data.to_vec()).map_err(|e| NitfError::MalformedField {
+                field: "TEXT",
+                offset: e.utf8_error().valid_up_to(),
+                raw: e.utf8_error().to_string(),
+            }),
+            //ISO-8859-1 maps each byte to the code point of the same value
+            "STA" | "UT1" | "MTF" => Ok(data.iter().map(|&b| b as char).collect()),
+            _ => Err(NitfError::MalformedField {
+                field: NitfTextSubheader21::TXTFMT.as_str(),
+                offset: NitfTextSubheader21::fixed_offset(NitfTextSubheader21::TXTFMT),
+                raw: self.txtfmt.clone(),
+            }),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfTextSubheader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(TE, &self.te)?;
+        w.write_string(TEXTID, &self.textid)?;
+        w.write_int(TXTALVL, self.txtalvl)?;
+        w.write_date_time(TXTDT, self.txtdt)?;
+        w.write_string(TXTITL, &self.txtitl)?;
+        self.security.write(&mut w)?;
+        w.write_string(ENCRYP, &self.encryp)?;
+        w.write_string(TXTFMT, &self.txtfmt)?;
+        w.write_extension(TXSHDL, TXSOFL, self.txsofl, &self.txshd)?;
+        Ok(w.into_bytes())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfDesSubheader21 {
+    DE,
+    DESID,
+    DESVER,
+    DESCLAS,
+    DESCLSY,
+    DESCODE,
+    DESCTLH,
+    DESREL,
+    DESDCTP,
+    DESDCDT,
+    DESDCXM,
+    DESDG,
+    DESDGDT,
+    DESCLTX,
+    DESCATP,
+    DESCAUT,
+    DESCRSN,
+    DESSRDT,
+    DESCTLN,
+    DESOFLW,
+    DESITEM,
+    DESSHL,
+    DESSHF,
+}
+
+impl NitfDesSubheader21 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 25, 2, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 6, 3, 4, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfDesSubheader21::*;
+        match self {
+            DE => "DE",
+            DESID => "DESID",
+            DESVER => "DESVER",
+            DESCLAS => "DESCLAS",
+            DESCLSY => "DESCLSY",
+            DESCODE => "DESCODE",
+            DESCTLH => "DESCTLH",
+            DESREL => "DESREL",
+            DESDCTP => "DESDCTP",
+            DESDCDT => "DESDCDT",
+            DESDCXM => "DESDCXM",
+            DESDG => "DESDG",
+            DESDGDT => "DESDGDT",
+            DESCLTX => "DESCLTX",
+            DESCATP => "DESCATP",
+            DESCAUT => "DESCAUT",
+            DESCRSN => "DESCRSN",
+            DESSRDT => "DESSRDT",
+            DESCTLN => "DESCTLN",
+            DESOFLW => "DESOFLW",
+            DESITEM => "DESITEM",
+            DESSHL => "DESSHL",
+            DESSHF => "DESSHF",
+        }
+    }
+    pub fn get_value(target: NitfDesSubheader21) -> usize {
+        let index = target as usize;
+        NitfDesSubheader21::values()[index]
+    }
+    /// Offset of a field up to DESOFLW within the subheader. None of the fields before it are conditional.
+    pub fn fixed_offset(target: NitfDesSubheader21) -> usize {
+        NitfDesSubheader21::values()[..target as usize].iter().sum()
+    }
+}
+
+impl Field for NitfDesSubheader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfDesSubheader21::get_value(*self)
+    }
+}
+
+/// DESID of the DES that carries TREs which did not fit in a header or subheader.
+pub const TRE_OVERFLOW: &str = "TRE_OVERFLOW";
+
+/// A NITF 2.1 data extension subheader. DESSHL is the length of `desshf`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct DesSubheader {
+    pub de: String,
+    pub desid: String,
+    pub desver: usize,
+    pub security: SecurityGroup,
+    /// Only present when DESID is TRE_OVERFLOW.
+    pub desoflw: Option<String>,
+    /// Only present when DESID is TRE_OVERFLOW.
+    pub desitem: Option<usize>,
+    pub desshf: Vec<u8>,
+}
+
+impl DesSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<DesSubheader> {
+        DesSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DesSubheader> {
+        use NitfDesSubheader21::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = DesSubheader {
+            de: c.read_string(DE)?,
+            desid: c.read_string(DESID)?,
+            desver: c.read_int(DESVER)?,
+            security: SecurityGroup::read(&mut c)?,
+            ..Default::default()
+        };
+        if sub.is_overflow() {
+            sub.desoflw = Some(c.read_string(DESOFLW)?);
+            sub.desitem = Some(c.read_int(DESITEM)?);
+        }
+        let desshl = c.read_int(DESSHL)?;
+        sub.desshf = c.read_bytes(desshl)?.to_vec();
+        Ok(sub)
+    }
+
+    /// Whether the DES holds TREs that overflowed a header or subheader.
+    pub fn is_overflow(&self) -> bool {
+        self.desid == TRE_OVERFLOW
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfDesSubheader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(DE, &self.de)?;
+        w.write_string(DESID, &self.desid)?;
+        w.write_int(DESVER, self.desver)?;
+        self.security.write(&mut w)?;
+        //DESOFLW and DESITEM are there whenever DESID says so, whether or not they were set
+        if self.is_overflow() {
+            w.write_string(DESOFLW, self.desoflw.as_deref().unwrap_or_default())?;
+            w.write_int(DESITEM, self.desitem.unwrap_or_default())?;
+        }
+        w.write_int(DESSHL, self.desshf.len())?;
+        w.write_bytes(&self.desshf);
+        Ok(w.into_bytes())
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum NitfResSubheader21 {
+    RE,
+    RESID,
+    RESVER,
+    RESCLAS,
+    RESCLSY,
+    RESCODE,
+    RESCTLH,
+    RESREL,
+    RESDCTP,
+    RESDCDT,
+    RESDCXM,
+    RESDG,
+    RESDGDT,
+    RESCLTX,
+    RESCATP,
+    RESCAUT,
+    RESCRSN,
+    RESSRDT,
+    RESCTLN,
+    RESSHL,
+    RESSHF,
+}
+
+impl NitfResSubheader21 {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 25, 2, 1, 2, 11, 2, 20, 2, 8, 4, 1, 8, 43, 1, 40, 1, 8, 15, 4, 0,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use NitfResSubheader21::*;
+        match self {
+            RE => "RE",
+            RESID => "RESID",
+            RESVER => "RESVER",
+            RESCLAS => "RESCLAS",
+            RESCLSY => "RESCLSY",
+            RESCODE => "RESCODE",
+            RESCTLH => "RESCTLH",
+            RESREL => "RESREL",
+            RESDCTP => "RESDCTP",
+            RESDCDT => "RESDCDT",
+            RESDCXM => "RESDCXM",
+            RESDG => "RESDG",
+            RESDGDT => "RESDGDT",
+            RESCLTX => "RESCLTX",
+            RESCATP => "RESCATP",
+            RESCAUT => "RESCAUT",
+            RESCRSN => "RESCRSN",
+            RESSRDT => "RESSRDT",
+            RESCTLN => "RESCTLN",
+            RESSHL => "RESSHL",
+            RESSHF => "RESSHF",
+        }
+    }
+    pub fn get_value(target: NitfResSubheader21) -> usize {
+        let index = target as usize;
+        NitfResSubheader21::values()[index]
+    }
+}
+
+impl Field for NitfResSubheader21 {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        NitfResSubheader21::get_value(*self)
+    }
+}
+
+/// A NITF 2.1 reserved extension subheader. RESSHL is the length of `resshf`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ResSubheader {
+    pub re: String,
+    pub resid: String,
+    pub resver: usize,
+    pub security: SecurityGroup,
+    pub resshf: Vec<u8>,
+}
+
+impl ResSubheader {
+    pub fn read<R: Read + Seek>(reader: &mut R, offset: usize, length: usize) -> Result<ResSubheader> {
+        ResSubheader::from_bytes(&read_bytes(reader, offset, length)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ResSubheader> {
+        use NitfResSubheader21::*;
+        let mut c = FieldCursor::new(bytes);
+        let mut sub = ResSubheader {
+            re: c.read_string(RE)?,
+            resid: c.read_string(RESID)?,
+            resver: c.read_int(RESVER)?,
+            security: SecurityGroup::read(&mut c)?,
+            ..Default::default()
+        };
+        let resshl = c.read_int(RESSHL)?;
+        sub.resshf = c.read_bytes(resshl)?.to_vec();
+        Ok(sub)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use NitfResSubheader21::*;
+        let mut w = FieldWriter::new();
+        w.write_string(RE, &self.re)?;
+        w.write_string(RESID, &self.resid)?;
+        w.write_int(RESVER, self.resver)?;
+        self.security.write(&mut w)?;
+        w.write_int(RESSHL, self.resshf.len())?;
+        w.write_bytes(&self.resshf);
+        Ok(w.into_bytes())
+    }
+}
@@ -0,0 +1,125 @@
+use crate::modify::error::Result;
+use crate::modify::parser::file_ops::{Field, FieldCursor};
+
+/// One Tagged Record Extension out of a UDHD, XHD, UDID or IXSHD area.
+/// CEL is not stored, it is the length of `cedata`.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Tre {
+    pub cetag: String,
+    pub cedata: Vec<u8>,
+}
+
+impl Tre {
+    pub fn read(cursor: &mut FieldCursor) -> Result<Tre> {
+        use TreField::*;
+        let cetag = cursor.read_string(CETAG)?;
+        let cel = cursor.read_int(CEL)?;
+        Ok(Tre {
+            cetag,
+            cedata: cursor.read_bytes(cel)?.to_vec(),
+        })
+    }
+
+    pub fn cel(&self) -> usize {
+        self.cedata.len()
+    }
+
+    /// The TRE as it is laid out in an extension area, CETAG padded to six characters.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        use TreField::*;
+        let mut bytes = format!(
+            "{:<cetag$}{:0cel$}",
+            self.cetag,
+            self.cel(),
+            cetag = CETAG.length(),
+            cel = CEL.length()
+        )
+        .into_bytes();
+        bytes.extend_from_slice(&self.cedata);
+        bytes
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum TreField {
+    CETAG,
+    CEL,
+}
+
+impl TreField {
+    pub fn values() -> &'static [usize] {
+        &[6, 5]
+    }
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TreField::CETAG => "CETAG",
+            TreField::CEL => "CEL",
+        }
+    }
+}
+
+impl Field for TreField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        TreField::values()[*self as usize]
+    }
+}
+
+/// Walks the TREs of one extension area front to back.
+///
+/// Error offsets are relative to the start of the area. A TRE that cannot be read
+/// ends the iteration, since the boundaries of anything after it are unknown.
+pub struct TreIter<'a> {
+    cursor: FieldCursor<'a>,
+    done: bool,
+}
+
+impl<'a> TreIter<'a> {
+    pub fn new(bytes: &'a [u8]) -> TreIter<'a> {
+        TreIter {
+            cursor: FieldCursor::new(bytes),
+            done: false,
+        }
+    }
+}
+
+impl Iterator for TreIter<'_> {
+    type Item = Result<Tre>;
+
+    fn next(&mut self) -> Option<Result<Tre>> {
+        if self.done || self.cursor.remaining() == 0 {
+            return None;
+        }
+        let tre = Tre::read(&mut self.cursor);
+        self.done = tre.is_err();
+        Some(tre)
+    }
+}
+
+/// A typed decoder for the CEDATA of one kind of TRE.
+pub trait TreDecoder: Sized {
+    const CETAG: &'static str;
+
+    fn decode(cedata: &[u8]) -> Result<Self>;
+}
+
+/// A set of TREs that can be decoded, usually an enum with one variant per [`TreDecoder`]
+/// and one holding the raw [`Tre`] for tags it does not know.
+pub trait TreRegistry: Sized {
+    fn decode(tre: Tre) -> Result<Self>;
+}
+
+/// The empty registry, every TRE is kept as raw bytes.
+impl TreRegistry for Tre {
+    fn decode(tre: Tre) -> Result<Tre> {
+        Ok(tre)
+    }
+}
+
+/// Splits an extension area into TREs and decodes each one with the registry `T`.
+pub fn decode_tres<T: TreRegistry>(bytes: &[u8]) -> Result<Vec<T>> {
+    TreIter::new(bytes).map(|tre| T::decode(tre?)).collect()
+}
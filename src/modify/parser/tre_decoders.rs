@@ -0,0 +1,810 @@
+use crate::modify::error::Result;
+use crate::modify::parser::file_ops::{Field, FieldCursor};
+use crate::modify::parser::tre::{Tre, TreDecoder, TreRegistry};
+use chrono::NaiveDateTime;
+
+/// The image support data TREs this crate decodes, anything else is kept as a raw [`Tre`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImageTre {
+    Rpc00b(Rpc00b),
+    Blocka(Blocka),
+    Geolob(Geolob),
+    Acftb(Acftb),
+    Aimidb(Aimidb),
+    Stdidc(Stdidc),
+    Piaimc(Piaimc),
+    Unknown(Tre),
+}
+
+impl TreRegistry for ImageTre {
+    fn decode(tre: Tre) -> Result<ImageTre> {
+        let data = &tre.cedata;
+        Ok(match tre.cetag.as_str() {
+            Rpc00b::CETAG => ImageTre::Rpc00b(Rpc00b::decode(data)?),
+            Blocka::CETAG => ImageTre::Blocka(Blocka::decode(data)?),
+            Geolob::CETAG => ImageTre::Geolob(Geolob::decode(data)?),
+            Acftb::CETAG => ImageTre::Acftb(Acftb::decode(data)?),
+            Aimidb::CETAG => ImageTre::Aimidb(Aimidb::decode(data)?),
+            Stdidc::CETAG => ImageTre::Stdidc(Stdidc::decode(data)?),
+            Piaimc::CETAG => ImageTre::Piaimc(Piaimc::decode(data)?),
+            _ => ImageTre::Unknown(tre),
+        })
+    }
+}
+
+/// Rational polynomial coefficients mapping ground coordinates to image line/sample.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Rpc00b {
+    pub success: bool,
+    pub err_bias: f64,
+    pub err_rand: f64,
+    pub line_off: f64,
+    pub samp_off: f64,
+    pub lat_off: f64,
+    pub long_off: f64,
+    pub height_off: f64,
+    pub line_scale: f64,
+    pub samp_scale: f64,
+    pub lat_scale: f64,
+    pub long_scale: f64,
+    pub height_scale: f64,
+    pub line_num_coeff: [f64; 20],
+    pub line_den_coeff: [f64; 20],
+    pub samp_num_coeff: [f64; 20],
+    pub samp_den_coeff: [f64; 20],
+}
+
+impl TreDecoder for Rpc00b {
+    const CETAG: &'static str = "RPC00B";
+
+    fn decode(cedata: &[u8]) -> Result<Rpc00b> {
+        use Rpc00bField::*;
+        let mut c = FieldCursor::new(cedata);
+        let mut rpc = Rpc00b {
+            success: c.read_int(SUCCESS)? == 1,
+            err_bias: c.read_float(ERRBIAS)?,
+            err_rand: c.read_float(ERRRAND)?,
+            line_off: c.read_float(LINEOFF)?,
+            samp_off: c.read_float(SAMPOFF)?,
+            lat_off: c.read_float(LATOFF)?,
+            long_off: c.read_float(LONGOFF)?,
+            height_off: c.read_float(HEIGHTOFF)?,
+            line_scale: c.read_float(LINESCALE)?,
+            samp_scale: c.read_float(SAMPSCALE)?,
+            lat_scale: c.read_float(LATSCALE)?,
+            long_scale: c.read_float(LONGSCALE)?,
+            height_scale: c.read_float(HEIGHTSCALE)?,
+            ..Default::default()
+        };
+        for coefficients in [
+            &mut rpc.line_num_coeff,
+            &mut rpc.line_den_coeff,
+            &mut rpc.samp_num_coeff,
+            &mut rpc.samp_den_coeff,
+        ] {
+            for coefficient in coefficients.iter_mut() {
+                *coefficient = c.read_float(COEFF)?;
+            }
+        }
+        Ok(rpc)
+    }
+}
+
+/// Image block information: the ground location of the four corners of the image block.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Blocka {
+    pub block_instance: usize,
+    pub n_gray: usize,
+    pub l_lines: usize,
+    pub layover_angle: String,
+    pub shadow_angle: String,
+    /// First row, last column.
+    pub frlc_loc: String,
+    /// Last row, last column.
+    pub lrlc_loc: String,
+    /// Last row, first column.
+    pub lrfc_loc: String,
+    /// First row, first column.
+    pub frfc_loc: String,
+}
+
+impl TreDecoder for Blocka {
+    const CETAG: &'static str = "BLOCKA";
+
+    fn decode(cedata: &[u8]) -> Result<Blocka> {
+        use BlockaField::*;
+        let mut c = FieldCursor::new(cedata);
+        let blocka = Blocka {
+            block_instance: c.read_int(BLOCKINSTANCE)?,
+            n_gray: c.read_int(NGRAY)?,
+            l_lines: c.read_int(LLINES)?,
+            layover_angle: c.read_string(LAYOVERANGLE)?,
+            shadow_angle: c.read_string(SHADOWANGLE)?,
+            ..Default::default()
+        };
+        c.read_bytes(BLANKS.length())?;
+        Ok(Blocka {
+            frlc_loc: c.read_string(FRLCLOC)?,
+            lrlc_loc: c.read_string(LRLCLOC)?,
+            lrfc_loc: c.read_string(LRFCLOC)?,
+            frfc_loc: c.read_string(FRFCLOC)?,
+            ..blocka
+        })
+    }
+}
+
+/// Local geographic (lat/long) coordinate system: pixel density and the origin of the image.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Geolob {
+    /// Longitude density, pixels per 360 degrees.
+    pub arv: usize,
+    /// Latitude density, pixels per 360 degrees.
+    pub brv: usize,
+    /// Longitude of the origin in degrees.
+    pub lso: f64,
+    /// Latitude of the origin in degrees.
+    pub pso: f64,
+}
+
+impl TreDecoder for Geolob {
+    const CETAG: &'static str = "GEOLOB";
+
+    fn decode(cedata: &[u8]) -> Result<Geolob> {
+        use GeolobField::*;
+        let mut c = FieldCursor::new(cedata);
+        Ok(Geolob {
+            arv: c.read_int(ARV)?,
+            brv: c.read_int(BRV)?,
+            lso: c.read_float(LSO)?,
+            pso: c.read_float(PSO)?,
+        })
+    }
+}
+
+/// Aircraft information. Most fields allow unknown/default fill values, so they are kept as text.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Acftb {
+    pub ac_msn_id: String,
+    pub ac_tail_no: String,
+    pub ac_to: String,
+    pub sensor_id_type: String,
+    pub sensor_id: String,
+    pub scene_source: String,
+    pub scnum: String,
+    pub pdate: String,
+    pub imhostno: String,
+    pub imreqid: String,
+    pub mplan: String,
+    pub entloc: String,
+    pub loc_accy: String,
+    pub entelv: String,
+    pub elv_unit: String,
+    pub exitloc: String,
+    pub exitelv: String,
+    pub tmap: String,
+    pub row_spacing: String,
+    pub row_spacing_units: String,
+    pub col_spacing: String,
+    pub col_spacing_units: String,
+    pub focal_length: String,
+    pub senserial: String,
+    pub abswver: String,
+    pub cal_date: String,
+    pub patch_tot: usize,
+    pub mti_tot: usize,
+}
+
+impl TreDecoder for Acftb {
+    const CETAG: &'static str = "ACFTB";
+
+    fn decode(cedata: &[u8]) -> Result<Acftb> {
+        use AcftbField::*;
+        let mut c = FieldCursor::new(cedata);
+        Ok(Acftb {
+            ac_msn_id: c.read_string(ACMSNID)?,
+            ac_tail_no: c.read_string(ACTAILNO)?,
+            ac_to: c.read_string(ACTO)?,
+            sensor_id_type: c.read_string(SENSORIDTYPE)?,
+            sensor_id: c.read_string(SENSORID)?,
+            scene_source: c.read_string(SCENESOURCE)?,
+            scnum: c.read_string(SCNUM)?,
+            pdate: c.read_string(PDATE)?,
+            imhostno: c.read_string(IMHOSTNO)?,
+            imreqid: c.read_string(IMREQID)?,
+            mplan: c.read_string(MPLAN)?,
+            entloc: c.read_string(ENTLOC)?,
+            loc_accy: c.read_string(LOCACCY)?,
+            entelv: c.read_string(ENTELV)?,
+            elv_unit: c.read_string(ELVUNIT)?,
+            exitloc: c.read_string(EXITLOC)?,
+            exitelv: c.read_string(EXITELV)?,
+            tmap: c.read_string(TMAP)?,
+            row_spacing: c.read_string(ROWSPACING)?,
+            row_spacing_units: c.read_string(ROWSPACINGUNITS)?,
+            col_spacing: c.read_string(COLSPACING)?,
+            col_spacing_units: c.read_string(COLSPACINGUNITS)?,
+            focal_length: c.read_string(FOCALLENGTH)?,
+            senserial: c.read_string(SENSERIAL)?,
+            abswver: c.read_string(ABSWVER)?,
+            cal_date: c.read_string(CALDATE)?,
+            patch_tot: c.read_int(PATCHTOT)?,
+            mti_tot: c.read_int(MTITOT)?,
+        })
+    }
+}
+
+/// Additional image identification (mission, tiling and location).
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Aimidb {
+    pub acquisition_date: Option<NaiveDateTime>,
+    pub mission_no: String,
+    pub mission_identification: String,
+    pub flight_no: String,
+    pub op_num: usize,
+    pub current_segment: String,
+    pub repro_num: usize,
+    pub replay: String,
+    pub start_tile_column: usize,
+    pub start_tile_row: usize,
+    pub end_segment: String,
+    pub end_tile_column: usize,
+    pub end_tile_row: usize,
+    pub country: String,
+    pub location: String,
+}
+
+impl TreDecoder for Aimidb {
+    const CETAG: &'static str = "AIMIDB";
+
+    fn decode(cedata: &[u8]) -> Result<Aimidb> {
+        use AimidbField::*;
+        let mut c = FieldCursor::new(cedata);
+        let aimidb = Aimidb {
+            acquisition_date: c.read_date_time(ACQUISITIONDATE)?,
+            mission_no: c.read_string(MISSIONNO)?,
+            mission_identification: c.read_string(MISSIONIDENTIFICATION)?,
+            flight_no: c.read_string(FLIGHTNO)?,
+            op_num: c.read_int(OPNUM)?,
+            current_segment: c.read_string(CURRENTSEGMENT)?,
+            repro_num: c.read_int(REPRONUM)?,
+            replay: c.read_string(REPLAY)?,
+            ..Default::default()
+        };
+        c.read_bytes(RESERVED1.length())?;
+        let aimidb = Aimidb {
+            start_tile_column: c.read_int(STARTTILECOLUMN)?,
+            start_tile_row: c.read_int(STARTTILEROW)?,
+            end_segment: c.read_string(ENDSEGMENT)?,
+            end_tile_column: c.read_int(ENDTILECOLUMN)?,
+            end_tile_row: c.read_int(ENDTILEROW)?,
+            country: c.read_string(COUNTRY)?,
+            ..aimidb
+        };
+        c.read_bytes(RESERVED2.length())?;
+        Ok(Aimidb {
+            location: c.read_string(LOCATION)?,
+            ..aimidb
+        })
+    }
+}
+
+/// Standard ID: acquisition metadata of the mission and the image's place in it.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Stdidc {
+    pub acquisition_date: Option<NaiveDateTime>,
+    pub mission: String,
+    pub pass: String,
+    pub op_num: usize,
+    pub start_segment: String,
+    pub repro_num: usize,
+    pub replay_regen: String,
+    pub start_column: usize,
+    pub start_row: usize,
+    pub end_segment: String,
+    pub end_column: usize,
+    pub end_row: usize,
+    pub country: String,
+    pub wac: String,
+    pub location: String,
+}
+
+impl TreDecoder for Stdidc {
+    const CETAG: &'static str = "STDIDC";
+
+    fn decode(cedata: &[u8]) -> Result<Stdidc> {
+        use StdidcField::*;
+        let mut c = FieldCursor::new(cedata);
+        let stdidc = Stdidc {
+            acquisition_date: c.read_date_time(ACQUISITIONDATE)?,
+            mission: c.read_string(MISSION)?,
+            pass: c.read_string(PASS)?,
+            op_num: c.read_int(OPNUM)?,
+            start_segment: c.read_string(STARTSEGMENT)?,
+            repro_num: c.read_int(REPRONUM)?,
+            replay_regen: c.read_string(REPLAYREGEN)?,
+            ..Default::default()
+        };
+        c.read_bytes(BLANKFILL.length())?;
+        Ok(Stdidc {
+            start_column: c.read_int(STARTCOLUMN)?,
+            start_row: c.read_int(STARTROW)?,
+            end_segment: c.read_string(ENDSEGMENT)?,
+            end_column: c.read_int(ENDCOLUMN)?,
+            end_row: c.read_int(ENDROW)?,
+            country: c.read_string(COUNTRY)?,
+            wac: c.read_string(WAC)?,
+            location: c.read_string(LOCATION)?,
+            ..stdidc
+        })
+    }
+}
+
+/// Profile for Imagery Access Image Support Extensions.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Piaimc {
+    /// Cloud cover percentage, 999 when unknown.
+    pub cloudcvr: usize,
+    pub srp: String,
+    pub sensmode: String,
+    pub sensname: String,
+    pub source: String,
+    pub comgen: usize,
+    pub subqual: String,
+    pub piamsnnum: String,
+    pub camspecs: String,
+    pub projid: String,
+    pub generation: usize,
+    pub esd: String,
+    pub othercond: String,
+    pub meangsd: f64,
+    pub idatum: String,
+    pub iellip: String,
+    pub preproc: String,
+    pub iproj: String,
+    pub sattrack: String,
+}
+
+impl TreDecoder for Piaimc {
+    const CETAG: &'static str = "PIAIMC";
+
+    fn decode(cedata: &[u8]) -> Result<Piaimc> {
+        use PiaimcField::*;
+        let mut c = FieldCursor::new(cedata);
+        Ok(Piaimc {
+            cloudcvr: c.read_int(CLOUDCVR)?,
+            srp: c.read_string(SRP)?,
+            sensmode: c.read_string(SENSMODE)?,
+            sensname: c.read_string(SENSNAME)?,
+            source: c.read_string(SOURCE)?,
+            comgen: c.read_int(COMGEN)?,
+            subqual: c.read_string(SUBQUAL)?,
+            piamsnnum: c.read_string(PIAMSNNUM)?,
+            camspecs: c.read_string(CAMSPECS)?,
+            projid: c.read_string(PROJID)?,
+            generation: c.read_int(GENERATION)?,
+            esd: c.read_string(ESD)?,
+            othercond: c.read_string(OTHERCOND)?,
+            meangsd: c.read_float(MEANGSD)?,
+            idatum: c.read_string(IDATUM)?,
+            iellip: c.read_string(IELLIP)?,
+            preproc: c.read_string(PREPROC)?,
+            iproj: c.read_string(IPROJ)?,
+            sattrack: c.read_string(SATTRACK)?,
+        })
+    }
+}
+
+//Field layouts, in the order the fields appear in CEDATA
+
+#[derive(Debug, Copy, Clone)]
+pub enum Rpc00bField {
+    SUCCESS,
+    ERRBIAS,
+    ERRRAND,
+    LINEOFF,
+    SAMPOFF,
+    LATOFF,
+    LONGOFF,
+    HEIGHTOFF,
+    LINESCALE,
+    SAMPSCALE,
+    LATSCALE,
+    LONGSCALE,
+    HEIGHTSCALE,
+    COEFF,
+}
+
+impl Rpc00bField {
+    pub fn values() -> &'static [usize] {
+        &[
+            1, 7, 7, 6, 5, 8, 9, 5, 6, 5, 8, 9, 5, 12,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use Rpc00bField::*;
+        match self {
+            SUCCESS => "SUCCESS",
+            ERRBIAS => "ERR_BIAS",
+            ERRRAND => "ERR_RAND",
+            LINEOFF => "LINE_OFF",
+            SAMPOFF => "SAMP_OFF",
+            LATOFF => "LAT_OFF",
+            LONGOFF => "LONG_OFF",
+            HEIGHTOFF => "HEIGHT_OFF",
+            LINESCALE => "LINE_SCALE",
+            SAMPSCALE => "SAMP_SCALE",
+            LATSCALE => "LAT_SCALE",
+            LONGSCALE => "LONG_SCALE",
+            HEIGHTSCALE => "HEIGHT_SCALE",
+            COEFF => "COEFF",
+        }
+    }
+}
+
+impl Field for Rpc00bField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        Rpc00bField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum BlockaField {
+    BLOCKINSTANCE,
+    NGRAY,
+    LLINES,
+    LAYOVERANGLE,
+    SHADOWANGLE,
+    BLANKS,
+    FRLCLOC,
+    LRLCLOC,
+    LRFCLOC,
+    FRFCLOC,
+    RESERVED,
+}
+
+impl BlockaField {
+    pub fn values() -> &'static [usize] {
+        &[
+            2, 5, 5, 3, 3, 16, 21, 21, 21, 21, 5,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use BlockaField::*;
+        match self {
+            BLOCKINSTANCE => "BLOCK_INSTANCE",
+            NGRAY => "N_GRAY",
+            LLINES => "L_LINES",
+            LAYOVERANGLE => "LAYOVER_ANGLE",
+            SHADOWANGLE => "SHADOW_ANGLE",
+            BLANKS => "BLANKS",
+            FRLCLOC => "FRLC_LOC",
+            LRLCLOC => "LRLC_LOC",
+            LRFCLOC => "LRFC_LOC",
+            FRFCLOC => "FRFC_LOC",
+            RESERVED => "RESERVED",
+        }
+    }
+}
+
+impl Field for BlockaField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        BlockaField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum GeolobField {
+    ARV,
+    BRV,
+    LSO,
+    PSO,
+}
+
+impl GeolobField {
+    pub fn values() -> &'static [usize] {
+        &[
+            9, 9, 15, 15,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use GeolobField::*;
+        match self {
+            ARV => "ARV",
+            BRV => "BRV",
+            LSO => "LSO",
+            PSO => "PSO",
+        }
+    }
+}
+
+impl Field for GeolobField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        GeolobField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AcftbField {
+    ACMSNID,
+    ACTAILNO,
+    ACTO,
+    SENSORIDTYPE,
+    SENSORID,
+    SCENESOURCE,
+    SCNUM,
+    PDATE,
+    IMHOSTNO,
+    IMREQID,
+    MPLAN,
+    ENTLOC,
+    LOCACCY,
+    ENTELV,
+    ELVUNIT,
+    EXITLOC,
+    EXITELV,
+    TMAP,
+    ROWSPACING,
+    ROWSPACINGUNITS,
+    COLSPACING,
+    COLSPACINGUNITS,
+    FOCALLENGTH,
+    SENSERIAL,
+    ABSWVER,
+    CALDATE,
+    PATCHTOT,
+    MTITOT,
+}
+
+impl AcftbField {
+    pub fn values() -> &'static [usize] {
+        &[
+            20, 10, 12, 4, 6, 1, 6, 8, 6, 5, 3, 25, 6, 6, 1, 25, 6, 7, 7, 1, 7, 1, 6, 6, 7, 8, 4, 3,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use AcftbField::*;
+        match self {
+            ACMSNID => "AC_MSN_ID",
+            ACTAILNO => "AC_TAIL_NO",
+            ACTO => "AC_TO",
+            SENSORIDTYPE => "SENSOR_ID_TYPE",
+            SENSORID => "SENSOR_ID",
+            SCENESOURCE => "SCENE_SOURCE",
+            SCNUM => "SCNUM",
+            PDATE => "PDATE",
+            IMHOSTNO => "IMHOSTNO",
+            IMREQID => "IMREQID",
+            MPLAN => "MPLAN",
+            ENTLOC => "ENTLOC",
+            LOCACCY => "LOC_ACCY",
+            ENTELV => "ENTELV",
+            ELVUNIT => "ELV_UNIT",
+            EXITLOC => "EXITLOC",
+            EXITELV => "EXITELV",
+            TMAP => "TMAP",
+            ROWSPACING => "ROW_SPACING",
+            ROWSPACINGUNITS => "ROW_SPACING_UNITS",
+            COLSPACING => "COL_SPACING",
+            COLSPACINGUNITS => "COL_SPACING_UNITS",
+            FOCALLENGTH => "FOCAL_LENGTH",
+            SENSERIAL => "SENSERIAL",
+            ABSWVER => "ABSWVER",
+            CALDATE => "CAL_DATE",
+            PATCHTOT => "PATCH_TOT",
+            MTITOT => "MTI_TOT",
+        }
+    }
+}
+
+impl Field for AcftbField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        AcftbField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum AimidbField {
+    ACQUISITIONDATE,
+    MISSIONNO,
+    MISSIONIDENTIFICATION,
+    FLIGHTNO,
+    OPNUM,
+    CURRENTSEGMENT,
+    REPRONUM,
+    REPLAY,
+    RESERVED1,
+    STARTTILECOLUMN,
+    STARTTILEROW,
+    ENDSEGMENT,
+    ENDTILECOLUMN,
+    ENDTILEROW,
+    COUNTRY,
+    RESERVED2,
+    LOCATION,
+    RESERVED3,
+}
+
+impl AimidbField {
+    pub fn values() -> &'static [usize] {
+        &[
+            14, 4, 10, 2, 3, 2, 2, 3, 1, 3, 5, 2, 3, 5, 2, 4, 11, 13,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use AimidbField::*;
+        match self {
+            ACQUISITIONDATE => "ACQUISITION_DATE",
+            MISSIONNO => "MISSION_NO",
+            MISSIONIDENTIFICATION => "MISSION_IDENTIFICATION",
+            FLIGHTNO => "FLIGHT_NO",
+            OPNUM => "OP_NUM",
+            CURRENTSEGMENT => "CURRENT_SEGMENT",
+            REPRONUM => "REPRO_NUM",
+            REPLAY => "REPLAY",
+            RESERVED1 => "RESERVED_1",
+            STARTTILECOLUMN => "START_TILE_COLUMN",
+            STARTTILEROW => "START_TILE_ROW",
+            ENDSEGMENT => "END_SEGMENT",
+            ENDTILECOLUMN => "END_TILE_COLUMN",
+            ENDTILEROW => "END_TILE_ROW",
+            COUNTRY => "COUNTRY",
+            RESERVED2 => "RESERVED_2",
+            LOCATION => "LOCATION",
+            RESERVED3 => "RESERVED_3",
+        }
+    }
+}
+
+impl Field for AimidbField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        AimidbField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum StdidcField {
+    ACQUISITIONDATE,
+    MISSION,
+    PASS,
+    OPNUM,
+    STARTSEGMENT,
+    REPRONUM,
+    REPLAYREGEN,
+    BLANKFILL,
+    STARTCOLUMN,
+    STARTROW,
+    ENDSEGMENT,
+    ENDCOLUMN,
+    ENDROW,
+    COUNTRY,
+    WAC,
+    LOCATION,
+    RESERVED1,
+    RESERVED2,
+}
+
+impl StdidcField {
+    pub fn values() -> &'static [usize] {
+        &[
+            14, 14, 2, 3, 2, 2, 3, 1, 3, 5, 2, 3, 5, 2, 4, 11, 5, 8,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use StdidcField::*;
+        match self {
+            ACQUISITIONDATE => "ACQUISITION_DATE",
+            MISSION => "MISSION",
+            PASS => "PASS",
+            OPNUM => "OP_NUM",
+            STARTSEGMENT => "START_SEGMENT",
+            REPRONUM => "REPRO_NUM",
+            REPLAYREGEN => "REPLAY_REGEN",
+            BLANKFILL => "BLANK_FILL",
+            STARTCOLUMN => "START_COLUMN",
+            STARTROW => "START_ROW",
+            ENDSEGMENT => "END_SEGMENT",
+            ENDCOLUMN => "END_COLUMN",
+            ENDROW => "END_ROW",
+            COUNTRY => "COUNTRY",
+            WAC => "WAC",
+            LOCATION => "LOCATION",
+            RESERVED1 => "RESERVED_1",
+            RESERVED2 => "RESERVED_2",
+        }
+    }
+}
+
+impl Field for StdidcField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        StdidcField::values()[*self as usize]
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum PiaimcField {
+    CLOUDCVR,
+    SRP,
+    SENSMODE,
+    SENSNAME,
+    SOURCE,
+    COMGEN,
+    SUBQUAL,
+    PIAMSNNUM,
+    CAMSPECS,
+    PROJID,
+    GENERATION,
+    ESD,
+    OTHERCOND,
+    MEANGSD,
+    IDATUM,
+    IELLIP,
+    PREPROC,
+    IPROJ,
+    SATTRACK,
+}
+
+impl PiaimcField {
+    pub fn values() -> &'static [usize] {
+        &[
+            3, 1, 12, 18, 255, 2, 1, 7, 32, 2, 1, 1, 2, 7, 3, 3, 2, 2, 8,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use PiaimcField::*;
+        match self {
+            CLOUDCVR => "CLOUDCVR",
+            SRP => "SRP",
+            SENSMODE => "SENSMODE",
+            SENSNAME => "SENSNAME",
+            SOURCE => "SOURCE",
+            COMGEN => "COMGEN",
+            SUBQUAL => "SUBQUAL",
+            PIAMSNNUM => "PIAMSNNUM",
+            CAMSPECS => "CAMSPECS",
+            PROJID => "PROJID",
+            GENERATION => "GENERATION",
+            ESD => "ESD",
+            OTHERCOND => "OTHERCOND",
+            MEANGSD => "MEANGSD",
+            IDATUM => "IDATUM",
+            IELLIP => "IELLIP",
+            PREPROC => "PREPROC",
+            IPROJ => "IPROJ",
+            SATTRACK => "SATTRACK",
+        }
+    }
+}
+
+impl Field for PiaimcField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        PiaimcField::values()[*self as usize]
+    }
+}
@@ -0,0 +1,173 @@
+use crate::modify::error::{NitfError, Result};
+use crate::modify::parser::file_ops::{Field, FieldCursor, FieldWriter};
+use chrono::NaiveDateTime;
+
+/// DESID of a DES holding an XML document (SICD, SIDD, ...).
+pub const XML_DATA_CONTENT: &str = "XML_DATA_CONTENT";
+
+/// DESCRC when no CRC was computed.
+const NO_CRC: usize = 99999;
+
+const DESSHDT_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// The user defined subheader (DESSHF) of an XML_DATA_CONTENT DES.
+///
+/// DESSHL is 0, 5 (DESCRC only), 283 (up to DESSHTN) or 773 (everything). Fields missing from
+/// a shorter DESSHF are left blank, and a DESSHF is written back as 283 bytes unless one of the
+/// location fields (DESSHLPG onwards) is set.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct XmlDataContent {
+    /// CRC of the DES data, see [`crc16`]. `None` when not used (99999).
+    pub descrc: Option<u16>,
+    /// "XML" for the documents this is meant for.
+    pub desshft: String,
+    pub desshdt: Option<NaiveDateTime>,
+    pub desshrp: String,
+    pub desshsi: String,
+    pub desshsv: String,
+    pub desshsd: String,
+    pub desshtn: String,
+    pub desshlpg: String,
+    pub desshlpt: String,
+    pub desshli: String,
+    pub desshlin: String,
+    pub desshabs: String,
+}
+
+impl XmlDataContent {
+    pub fn from_bytes(bytes: &[u8]) -> Result<XmlDataContent> {
+        use XmlDataContentField::*;
+        let mut sub = XmlDataContent::default();
+        if bytes.is_empty() {
+            return Ok(sub);
+        }
+        if ![5, 283, 773].contains(&bytes.len()) {
+            return Err(NitfError::MalformedField {
+                field: "DESSHL",
+                offset: 0,
+                raw: bytes.len().to_string(),
+            });
+        }
+        let mut c = FieldCursor::new(bytes);
+        sub.descrc = match c.read_int(DESCRC)? {
+            NO_CRC => None,
+            crc => Some(u16::try_from(crc).map_err(|_| NitfError::MalformedField {
+                field: DESCRC.name(),
+                offset: 0,
+                raw: crc.to_string(),
+            })?),
+        };
+        if c.remaining() == 0 {
+            return Ok(sub);
+        }
+        sub.desshft = c.read_string(DESSHFT)?;
+        sub.desshdt = NaiveDateTime::parse_from_str(&c.read_string(DESSHDT)?, DESSHDT_FORMAT).ok();
+        sub.desshrp = c.read_string(DESSHRP)?;
+        sub.desshsi = c.read_string(DESSHSI)?;
+        sub.desshsv = c.read_string(DESSHSV)?;
+        sub.desshsd = c.read_string(DESSHSD)?;
+        sub.desshtn = c.read_string(DESSHTN)?;
+        if c.remaining() == 0 {
+            return Ok(sub);
+        }
+        sub.desshlpg = c.read_string(DESSHLPG)?;
+        sub.desshlpt = c.read_string(DESSHLPT)?;
+        sub.desshli = c.read_string(DESSHLI)?;
+        sub.desshlin = c.read_string(DESSHLIN)?;
+        sub.desshabs = c.read_string(DESSHABS)?;
+        Ok(sub)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        use XmlDataContentField::*;
+        let mut w = FieldWriter::new();
+        w.write_int(DESCRC, self.descrc.map_or(NO_CRC, usize::from))?;
+        w.write_string(DESSHFT, &self.desshft)?;
+        let desshdt = self.desshdt.map(|v| v.format(DESSHDT_FORMAT).to_string()).unwrap_or_default();
+        w.write_string(DESSHDT, &desshdt)?;
+        w.write_string(DESSHRP, &self.desshrp)?;
+        w.write_string(DESSHSI, &self.desshsi)?;
+        w.write_string(DESSHSV, &self.desshsv)?;
+        w.write_string(DESSHSD, &self.desshsd)?;
+        w.write_string(DESSHTN, &self.desshtn)?;
+        let location = [&self.desshlpg, &self.desshlpt, &self.desshli, &self.desshlin, &self.desshabs];
+        if location.iter().any(|f| !f.is_empty()) {
+            w.write_string(DESSHLPG, &self.desshlpg)?;
+            w.write_string(DESSHLPT, &self.desshlpt)?;
+            w.write_string(DESSHLI, &self.desshli)?;
+            w.write_string(DESSHLIN, &self.desshlin)?;
+            w.write_string(DESSHABS, &self.desshabs)?;
+        }
+        Ok(w.into_bytes())
+    }
+
+    /// Whether DESCRC matches `data`. A DES without a CRC always matches.
+    pub fn crc_matches(&self, data: &[u8]) -> bool {
+        self.descrc.is_none_or(|crc| crc == crc16(data))
+    }
+}
+
+/// The CRC-16/CCITT (polynomial 0x1021, initial value 0xFFFF) used for DESCRC.
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xFFFFu16;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+    crc
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum XmlDataContentField {
+    DESCRC,
+    DESSHFT,
+    DESSHDT,
+    DESSHRP,
+    DESSHSI,
+    DESSHSV,
+    DESSHSD,
+    DESSHTN,
+    DESSHLPG,
+    DESSHLPT,
+    DESSHLI,
+    DESSHLIN,
+    DESSHABS,
+}
+
+impl XmlDataContentField {
+    pub fn values() -> &'static [usize] {
+        &[
+            5, 8, 20, 40, 60, 10, 20, 120, 125, 25, 20, 120, 200,
+        ]
+    }
+    pub fn as_str(&self) -> &'static str {
+        use XmlDataContentField::*;
+        match self {
+            DESCRC => "DESCRC",
+            DESSHFT => "DESSHFT",
+            DESSHDT => "DESSHDT",
+            DESSHRP => "DESSHRP",
+            DESSHSI => "DESSHSI",
+            DESSHSV => "DESSHSV",
+            DESSHSD => "DESSHSD",
+            DESSHTN => "DESSHTN",
+            DESSHLPG => "DESSHLPG",
+            DESSHLPT => "DESSHLPT",
+            DESSHLI => "DESSHLI",
+            DESSHLIN => "DESSHLIN",
+            DESSHABS => "DESSHABS",
+        }
+    }
+}
+
+impl Field for XmlDataContentField {
+    fn name(&self) -> &'static str {
+        self.as_str()
+    }
+
+    fn length(&self) -> usize {
+        XmlDataContentField::values()[*self as usize]
+    }
+}
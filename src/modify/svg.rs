@@ -0,0 +1,290 @@
+use crate::modify::parser::cgm::{ArcClosure, Cgm, CgmElement, Colour, InteriorStyle, Point};
+use std::f64::consts::{PI, TAU};
+use std::fmt::Write;
+
+/// Attributes in effect while walking the elements, starting from the CGM defaults.
+struct State {
+    line_type: isize,
+    line_width: isize,
+    line_colour: Colour,
+    text_colour: Colour,
+    character_height: isize,
+    interior_style: InteriorStyle,
+    fill_colour: Colour,
+    edge_type: isize,
+    edge_width: isize,
+    edge_colour: Colour,
+    edge_visible: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State {
+            line_type: 1,
+            line_width: 1,
+            line_colour: Colour::default(),
+            text_colour: Colour::default(),
+            character_height: 10,
+            interior_style: InteriorStyle::Hollow,
+            fill_colour: Colour::default(),
+            edge_type: 1,
+            edge_width: 1,
+            edge_colour: Colour::default(),
+            edge_visible: false,
+        }
+    }
+}
+
+impl State {
+    fn line(&self) -> String {
+        format!(r#"fill="none" {}"#, stroke(self.line_colour, self.line_width, self.line_type))
+    }
+
+    /// Fill and outline of a closed figure. A hollow interior is drawn as its boundary in the fill colour.
+    fn area(&self) -> String {
+        let fill = match self.interior_style {
+            InteriorStyle::Solid | InteriorStyle::Pattern | InteriorStyle::Hatch => rgb(self.fill_colour),
+            InteriorStyle::Hollow | InteriorStyle::Empty => "none".to_string(),
+        };
+        let outline = if self.edge_visible {
+            stroke(self.edge_colour, self.edge_width, self.edge_type)
+        } else if self.interior_style == InteriorStyle::Hollow {
+            stroke(self.fill_colour, 1, 1)
+        } else {
+            r#"stroke="none""#.to_string()
+        };
+        format!(r#"fill="{}" {}"#, fill, outline)
+    }
+}
+
+/// Renders a CGM to an SVG document in CCS coordinates (x a column, y a row).
+///
+/// `location` is the (row, column) of the graphic's origin in the CCS, which for a graphic
+/// segment is SLOC added to the location of whatever it is attached to (see
+/// `core::get_graphic_svg`). The view box is the VDC extent, or the bounds of the drawn
+/// points if the CGM has none.
+pub fn render_svg(cgm: &Cgm, location: (isize, isize)) -> String {
+    let to_ccs = |p: &Point| (location.1 + p.x, location.0 - p.y);
+    let points = |ps: &[Point]| {
+        ps.iter()
+            .map(|p| {
+                let (x, y) = to_ccs(p);
+                format!("{},{}", x, y)
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut state = State::default();
+    let mut bounds: Option<(isize, isize, isize, isize)> = None;
+    let mut extent = None;
+    let mut body = String::new();
+    let mut grow = |p: &Point| {
+        let (x, y) = to_ccs(p);
+        bounds = Some(match bounds {
+            None => (x, y, x, y),
+            Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+        });
+    };
+    for element in &cgm.elements {
+        use CgmElement::*;
+        match element {
+            VdcExtent(first, second) => extent = Some((to_ccs(first), to_ccs(second))),
+            Polyline(ps) => {
+                ps.iter().for_each(&mut grow);
+                let _ = writeln!(body, r#"<polyline points="{}" {}/>"#, points(ps), state.line());
+            }
+            DisjointPolyline(ps) => {
+                ps.iter().for_each(&mut grow);
+                let path = ps
+                    .chunks_exact(2)
+                    .map(|pair| {
+                        let ((x0, y0), (x1, y1)) = (to_ccs(&pair[0]), to_ccs(&pair[1]));
+                        format!("M{},{} L{},{}", x0, y0, x1, y1)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let _ = writeln!(body, r#"<path d="{}" {}/>"#, path, state.line());
+            }
+            Polygon(ps) => {
+                ps.iter().for_each(&mut grow);
+                let _ = writeln!(body, r#"<polygon points="{}" {}/>"#, points(ps), state.area());
+            }
+            Rectangle(first, second) => {
+                grow(first);
+                grow(second);
+                let ((x0, y0), (x1, y1)) = (to_ccs(first), to_ccs(second));
+                let _ = writeln!(
+                    body,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+                    x0.min(x1),
+                    y0.min(y1),
+                    (x1 - x0).abs(),
+                    (y1 - y0).abs(),
+                    state.area()
+                );
+            }
+            Circle { centre, radius } => {
+                grow(&Point { x: centre.x - radius, y: centre.y - radius });
+                grow(&Point { x: centre.x + radius, y: centre.y + radius });
+                let (cx, cy) = to_ccs(centre);
+                let _ = writeln!(body, r#"<circle cx="{}" cy="{}" r="{}" {}/>"#, cx, cy, radius, state.area());
+            }
+            Ellipse { centre, first, second } => {
+                //A unit circle mapped onto the two conjugate diameters, which covers skewed ellipses too
+                let (u, v) = ((first.x - centre.x, first.y - centre.y), (second.x - centre.x, second.y - centre.y));
+                ellipse_bounds(centre, u, v).iter().for_each(&mut grow);
+                let _ = writeln!(
+                    body,
+                    r#"<circle r="1" {} vector-effect="non-scaling-stroke" {}/>"#,
+                    matrix(u, v, to_ccs(centre)),
+                    state.area()
+                );
+            }
+            CircularArc { centre, start, end, radius, close } => {
+                let (u, v) = ((*radius, 0), (0, *radius));
+                ellipse_bounds(centre, u, v).iter().for_each(&mut grow);
+                if let Some(arc) = arc(u, v, to_ccs(centre), start, end, *close, &state) {
+                    let _ = writeln!(body, "{}", arc);
+                }
+            }
+            EllipticalArc { centre, first, second, start, end, close } => {
+                let (u, v) = ((first.x - centre.x, first.y - centre.y), (second.x - centre.x, second.y - centre.y));
+                ellipse_bounds(centre, u, v).iter().for_each(&mut grow);
+                if let Some(arc) = arc(u, v, to_ccs(centre), start, end, *close, &state) {
+                    let _ = writeln!(body, "{}", arc);
+                }
+            }
+            Text { position, text } => {
+                grow(position);
+                let (x, y) = to_ccs(position);
+                let _ = writeln!(
+                    body,
+                    r#"<text x="{}" y="{}" font-size="{}" fill="{}">{}</text>"#,
+                    x,
+                    y,
+                    state.character_height,
+                    rgb(state.text_colour),
+                    escape(text)
+                );
+            }
+            LineType(t) => state.line_type = *t,
+            LineWidth(w) => state.line_width = *w,
+            LineColour(c) => state.line_colour = *c,
+            TextColour(c) => state.text_colour = *c,
+            CharacterHeight(h) => state.character_height = *h,
+            CgmElement::InteriorStyle(s) => state.interior_style = *s,
+            FillColour(c) => state.fill_colour = *c,
+            EdgeType(t) => state.edge_type = *t,
+            EdgeWidth(w) => state.edge_width = *w,
+            EdgeColour(c) => state.edge_colour = *c,
+            EdgeVisibility(v) => state.edge_visible = *v,
+            BeginMetafile(_) | EndMetafile | BeginPicture(_) | BeginPictureBody | EndPicture
+            | BackgroundColour(_) | Unknown { .. } => {}
+        }
+    }
+    let (x0, y0, x1, y1) = match (extent, bounds) {
+        (Some(((xa, ya), (xb, yb))), _) => (xa.min(xb), ya.min(yb), xa.max(xb), ya.max(yb)),
+        (None, Some(b)) => b,
+        (None, None) => (location.1, location.0, location.1, location.0),
+    };
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n{}</svg>\n",
+        x0,
+        y0,
+        x1 - x0,
+        y1 - y0,
+        x1 - x0,
+        y1 - y0,
+        body
+    )
+}
+
+/// Corners of a box holding the ellipse with conjugate semi-diameters `u` and `v` about `centre`.
+fn ellipse_bounds(centre: &Point, u: (isize, isize), v: (isize, isize)) -> [Point; 2] {
+    let half = |a: isize, b: isize| ((a * a + b * b) as f64).sqrt().ceil() as isize;
+    let (hx, hy) = (half(u.0, v.0), half(u.1, v.1));
+    [Point { x: centre.x - hx, y: centre.y - hy }, Point { x: centre.x + hx, y: centre.y + hy }]
+}
+
+/// The transform taking the unit circle onto the ellipse with conjugate semi-diameters `u` and
+/// `v` about `(cx, cy)` in the CCS, flipping VDC y.
+fn matrix(u: (isize, isize), v: (isize, isize), (cx, cy): (isize, isize)) -> String {
+    format!(r#"transform="matrix({} {} {} {} {} {})""#, u.0, -u.1, v.0, -v.1, cx, cy)
+}
+
+/// An arc as a path around the unit circle, mapped like an ellipse by [`matrix`]. The point of
+/// the ellipse at angle t on the unit circle is centre + u cos t + v sin t, so the arc runs from
+/// `first` towards `second` by increasing t. None if the ellipse has collapsed to a line.
+fn arc(
+    u: (isize, isize),
+    v: (isize, isize),
+    centre: (isize, isize),
+    start: &Point,
+    end: &Point,
+    close: Option<ArcClosure>,
+    state: &State,
+) -> Option<String> {
+    let det = (u.0 * v.1 - u.1 * v.0) as f64;
+    if det == 0.0 {
+        return None;
+    }
+    //The angle of a ray, found by writing its direction in terms of u and v
+    let angle = |d: &Point| {
+        let a = (d.x * v.1 - d.y * v.0) as f64 / det;
+        let b = (u.0 * d.y - u.1 * d.x) as f64 / det;
+        b.atan2(a)
+    };
+    let (t0, t1) = (angle(start), angle(end));
+    let at = |t: f64| format!("{},{}", unit(t.cos()), unit(t.sin()));
+    let sweep = (t1 - t0).rem_euclid(TAU);
+    let mut path = format!("M{}", at(t0));
+    if sweep == 0.0 {
+        //Coinciding rays: the whole ellipse, as two halves since an arc cannot end where it starts
+        let _ = write!(path, " A1 1 0 0 1 {} A1 1 0 0 1 {}", at(t0 + PI), at(t0));
+    } else {
+        let _ = write!(path, " A1 1 0 {} 1 {}", (sweep > PI) as u8, at(t1));
+    }
+    let style = match close {
+        None => state.line(),
+        Some(ArcClosure::Pie) => {
+            path.push_str(" L0,0 Z");
+            state.area()
+        }
+        Some(ArcClosure::Chord) => {
+            path.push_str(" Z");
+            state.area()
+        }
+    };
+    Some(format!(
+        r#"<path d="{}" {} vector-effect="non-scaling-stroke" {}/>"#,
+        path,
+        matrix(u, v, centre),
+        style
+    ))
+}
+
+/// A unit circle coordinate to 4 decimal places, with no negative zero.
+fn unit(value: f64) -> String {
+    format!("{}", (value * 1e4).round() / 1e4 + 0.0)
+}
+
+fn rgb(c: Colour) -> String {
+    format!("rgb({},{},{})", c.r, c.g, c.b)
+}
+
+/// Stroke attributes, mapping the CGM line types (solid, dash, dot, dash-dot, dash-dot-dot) to dash arrays.
+fn stroke(colour: Colour, width: isize, line_type: isize) -> String {
+    let w = width.max(1);
+    let dashes = match line_type {
+        2 => format!(r#" stroke-dasharray="{} {}""#, 4 * w, 2 * w),
+        3 => format!(r#" stroke-dasharray="{} {}""#, w, 2 * w),
+        4 => format!(r#" stroke-dasharray="{} {} {} {}""#, 4 * w, 2 * w, w, 2 * w),
+        5 => format!(r#" stroke-dasharray="{} {} {} {} {} {}""#, 4 * w, 2 * w, w, 2 * w, w, 2 * w),
+        _ => String::new(),
+    };
+    format!(r#"stroke="{}" stroke-width="{}"{}"#, rgb(colour), width, dashes)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
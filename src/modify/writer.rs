@@ -0,0 +1,135 @@
+use crate::modify::error::Result;
+use crate::modify::parser::file_ops::read_bytes;
+use crate::modify::parser::nitf21::{
+    DesSubheader, FileHeader, ImageSubheader, SegmentLength, SegmentType, TextSubheader,
+};
+use std::io::{Read, Seek, Write};
+
+/// One segment as it is stored in the file, the subheader followed by the data.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub subheader: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    pub fn new(subheader: Vec<u8>, data: Vec<u8>) -> Segment {
+        Segment { subheader, data }
+    }
+
+    /// An image segment built from a typed subheader.
+    pub fn image(subheader: &ImageSubheader, data: Vec<u8>) -> Result<Segment> {
+        Ok(Segment::new(subheader.to_bytes()?, data))
+    }
+
+    pub fn length(&self) -> SegmentLength {
+        SegmentLength {
+            subheader: self.subheader.len(),
+            data: self.data.len(),
+        }
+    }
+
+    /// The subheader of an image segment, parsed.
+    pub fn image_subheader(&self) -> Result<ImageSubheader> {
+        ImageSubheader::from_bytes(&self.subheader)
+    }
+
+    /// The subheader of a text segment, parsed.
+    pub fn text_subheader(&self) -> Result<TextSubheader> {
+        TextSubheader::from_bytes(&self.subheader)
+    }
+
+    /// The subheader of a data extension segment, parsed.
+    pub fn des_subheader(&self) -> Result<DesSubheader> {
+        DesSubheader::from_bytes(&self.subheader)
+    }
+}
+
+/// An in-memory NITF 2.1 file that can be edited and written back out.
+///
+/// Edits are made to the segment lists rather than to byte offsets. FL, HL and the
+/// segment length tables of `file_header` are ignored and recomputed when writing.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct NitfModel {
+    pub file_header: FileHeader,
+    pub images: Vec<Segment>,
+    pub graphics: Vec<Segment>,
+    pub texts: Vec<Segment>,
+    pub des: Vec<Segment>,
+    pub res: Vec<Segment>,
+}
+
+impl NitfModel {
+    /// Loads the file header and every segment of a 2.1 (or NSIF 1.0) file.
+    pub fn read<R: Read + Seek>(reader: &mut R) -> Result<NitfModel> {
+        let file_header = FileHeader::read(reader)?;
+        Ok(NitfModel {
+            images: read_segments(reader, &file_header, SegmentType::Image)?,
+            graphics: read_segments(reader, &file_header, SegmentType::Graphic)?,
+            texts: read_segments(reader, &file_header, SegmentType::Text)?,
+            des: read_segments(reader, &file_header, SegmentType::DataExtension)?,
+            res: read_segments(reader, &file_header, SegmentType::ReservedExtension)?,
+            file_header,
+        })
+    }
+
+    /// The file header with its length tables, HL and FL filled in from the segments.
+    pub fn header(&self) -> Result<FileHeader> {
+        let lengths = |segments: &[Segment]| segments.iter().map(Segment::length).collect::<Vec<_>>();
+        let mut header = self.file_header.clone();
+        header.images = lengths(&self.images);
+        header.graphics = lengths(&self.graphics);
+        header.texts = lengths(&self.texts);
+        header.des = lengths(&self.des);
+        header.res = lengths(&self.res);
+        //HL and FL are fixed width, so their values do not change the header length
+        header.hl = header.to_bytes()?.len();
+        header.fl = header.hl + self.segments().map(|s| s.length().total()).sum::<usize>();
+        Ok(header)
+    }
+
+    /// Every segment in file order: images, graphics, text, DES then RES.
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.images
+            .iter()
+            .chain(&self.graphics)
+            .chain(&self.texts)
+            .chain(&self.des)
+            .chain(&self.res)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let header = self.header()?;
+        let mut bytes = header.to_bytes()?;
+        bytes.reserve(header.fl - header.hl);
+        for segment in self.segments() {
+            bytes.extend_from_slice(&segment.subheader);
+            bytes.extend_from_slice(&segment.data);
+        }
+        Ok(bytes)
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.header()?.to_bytes()?)?;
+        for segment in self.segments() {
+            writer.write_all(&segment.subheader)?;
+            writer.write_all(&segment.data)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads every segment of one type, using a file header already read from `reader`.
+pub fn read_segments<R: Read + Seek>(reader: &mut R, file_header: &FileHeader, segment: SegmentType) -> Result<Vec<Segment>> {
+    let lengths = file_header.lengths(segment);
+    let mut offset = file_header.segment_offsets().start(segment);
+    let mut segments = Vec::with_capacity(lengths.len());
+    for length in lengths {
+        segments.push(Segment::new(
+            read_bytes(reader, offset, length.subheader)?,
+            read_bytes(reader, offset + length.subheader, length.data)?,
+        ));
+        offset += length.total();
+    }
+    Ok(segments)
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::io::Read;
 
 //helper functions
@@ -17,8 +19,528 @@ pub fn calculate_file_crc32(file_path: &str) -> std::io::Result<u32> {
     Ok(hasher.finalize())
 }
 
-pub fn calculate_bytes_crc32(data: &Vec<u8>) -> u32 {
+pub fn calculate_bytes_crc32(data: &[u8]) -> u32 {
     let mut hasher = crc32fast::Hasher::new();
     hasher.update(data);
     hasher.finalize()
 }
+
+//NITF fixture builders, so tests do not depend on checked in .ntf files
+pub fn field(value: &str, length: usize) -> Vec<u8> {
+    let mut buf = vec![b' '; length];
+    let value = &value.as_bytes()[..value.len().min(length)];
+    buf[..value.len()].copy_from_slice(value);
+    buf
+}
+
+pub fn num(value: usize, length: usize) -> Vec<u8> {
+    format!("{:0width$}", value, width = length).into_bytes()
+}
+
+/// Unclassified security group with every other field blank.
+pub fn security_group() -> Vec<u8> {
+    let mut buf = field("U", 1);
+    buf.extend(field("", 166));
+    buf
+}
+
+/// NITF 2.0 unclassified security group. FSDEVT/ISDEVT is added when `dwng` is 999998.
+pub fn security_group_v02_00(dwng: &str, devt: &str) -> Vec<u8> {
+    let mut buf = field("U", 1);
+    buf.extend(field("", 160));
+    buf.extend(field(dwng, 6));
+    if dwng == "999998" {
+        buf.extend(field(devt, 40));
+    }
+    buf
+}
+
+#[derive(Clone)]
+pub struct ImageFixture {
+    pub iid1: String,
+    pub nrows: usize,
+    pub ncols: usize,
+    pub pvtype: String,
+    pub irep: String,
+    pub irepbands: Vec<String>,
+    pub luts: Vec<Vec<Vec<u8>>>,
+    pub icords: String,
+    pub igeolo: String,
+    pub comments: Vec<String>,
+    pub ic: String,
+    pub abpp: usize,
+    pub nbpp: usize,
+    pub imode: String,
+    pub nbpr: usize,
+    pub nbpc: usize,
+    pub nppbh: usize,
+    pub nppbv: usize,
+    pub idlvl: usize,
+    pub ialvl: usize,
+    pub iloc: String,
+    pub udid: Vec<u8>,
+    /// UDOFL, the TRE_OVERFLOW DES the UDID TREs were moved to.
+    pub udofl: usize,
+    pub ixshd: Vec<u8>,
+}
+
+impl Default for ImageFixture {
+    fn default() -> Self {
+        ImageFixture {
+            iid1: "TESTIMG".to_string(),
+            nrows: 4,
+            ncols: 4,
+            pvtype: "INT".to_string(),
+            irep: "MONO".to_string(),
+            irepbands: vec!["M".to_string()],
+            luts: Vec::new(),
+            icords: " ".to_string(),
+            igeolo: String::new(),
+            comments: Vec::new(),
+            ic: "NC".to_string(),
+            abpp: 8,
+            nbpp: 8,
+            imode: "B".to_string(),
+            nbpr: 1,
+            nbpc: 1,
+            nppbh: 4,
+            nppbv: 4,
+            idlvl: 1,
+            ialvl: 0,
+            iloc: "0000000000".to_string(),
+            udid: Vec::new(),
+            udofl: 0,
+            ixshd: Vec::new(),
+        }
+    }
+}
+
+impl ImageFixture {
+    pub fn subheader(&self) -> Vec<u8> {
+        let mut buf = field("IM", 2);
+        buf.extend(field(&self.iid1, 10));
+        buf.extend(field("20240102030405", 14));
+        buf.extend(field("", 17));
+        buf.extend(field("Test image", 80));
+        buf.extend(security_group());
+        buf.extend(field("0", 1));
+        buf.extend(field("Unit test", 42));
+        buf.extend(num(self.nrows, 8));
+        buf.extend(num(self.ncols, 8));
+        buf.extend(field(&self.pvtype, 3));
+        buf.extend(field(&self.irep, 8));
+        buf.extend(field("VIS", 8));
+        buf.extend(num(self.abpp, 2));
+        buf.extend(field("R", 1));
+        buf.extend(field(&self.icords, 1));
+        if self.icords.trim() != "" {
+            buf.extend(field(&self.igeolo, 60));
+        }
+        buf.extend(num(self.comments.len(), 1));
+        for comment in &self.comments {
+            buf.extend(field(comment, 80));
+        }
+        buf.extend(field(&self.ic, 2));
+        if self.ic != "NC" && self.ic != "NM" {
+            buf.extend(field("01.0", 4));
+        }
+        if self.irepbands.len() > 9 {
+            buf.extend(num(0, 1));
+            buf.extend(num(self.irepbands.len(), 5));
+        } else {
+            buf.extend(num(self.irepbands.len(), 1));
+        }
+        for (i, irepband) in self.irepbands.iter().enumerate() {
+            buf.extend(field(irepband, 2));
+            buf.extend(field("", 6));
+            buf.extend(field("N", 1));
+            buf.extend(field("", 3));
+            let luts = self.luts.get(i).cloned().unwrap_or_default();
+            buf.extend(num(luts.len(), 1));
+            if !luts.is_empty() {
+                buf.extend(num(luts[0].len(), 5));
+                for lut in luts {
+                    buf.extend(lut);
+                }
+            }
+        }
+        buf.extend(num(0, 1));
+        buf.extend(field(&self.imode, 1));
+        buf.extend(num(self.nbpr, 4));
+        buf.extend(num(self.nbpc, 4));
+        buf.extend(num(self.nppbh, 4));
+        buf.extend(num(self.nppbv, 4));
+        buf.extend(num(self.nbpp, 2));
+        buf.extend(num(self.idlvl, 3));
+        buf.extend(num(self.ialvl, 3));
+        buf.extend(field(&self.iloc, 10));
+        buf.extend(field("1.0", 4));
+        overflow_area(&mut buf, self.udofl, &self.udid);
+        extension_area(&mut buf, &self.ixshd);
+        buf
+    }
+
+    /// The same image as a NITF 2.0 subheader. Bands past nine are not representable.
+    pub fn subheader_v02_00(&self) -> Vec<u8> {
+        let mut buf = field("IM", 2);
+        buf.extend(field(&self.iid1, 10));
+        buf.extend(field("02030405ZJAN97", 14));
+        buf.extend(field("", 17));
+        buf.extend(field("Test image", 80));
+        buf.extend(security_group_v02_00("", ""));
+        buf.extend(field("0", 1));
+        buf.extend(field("Unit test", 42));
+        buf.extend(num(self.nrows, 8));
+        buf.extend(num(self.ncols, 8));
+        buf.extend(field(&self.pvtype, 3));
+        buf.extend(field(&self.irep, 8));
+        buf.extend(field("VIS", 8));
+        buf.extend(num(self.abpp, 2));
+        buf.extend(field("R", 1));
+        //2.0 uses N rather than a blank for no coordinates
+        let icords = if self.icords.trim().is_empty() { "N" } else { &self.icords };
+        buf.extend(field(icords, 1));
+        if icords != "N" {
+            buf.extend(field(&self.igeolo, 60));
+        }
+        buf.extend(num(self.comments.len(), 1));
+        for comment in &self.comments {
+            buf.extend(field(comment, 80));
+        }
+        buf.extend(field(&self.ic, 2));
+        if self.ic != "NC" && self.ic != "NM" {
+            buf.extend(field("01.0", 4));
+        }
+        buf.extend(num(self.irepbands.len(), 1));
+        for (i, irepband) in self.irepbands.iter().enumerate() {
+            buf.extend(field(irepband, 2));
+            buf.extend(field("", 6));
+            buf.extend(field("N", 1));
+            buf.extend(field("", 3));
+            let luts = self.luts.get(i).cloned().unwrap_or_default();
+            buf.extend(num(luts.len(), 1));
+            if !luts.is_empty() {
+                buf.extend(num(luts[0].len(), 5));
+                for lut in luts {
+                    buf.extend(lut);
+                }
+            }
+        }
+        buf.extend(num(0, 1));
+        buf.extend(field(&self.imode, 1));
+        buf.extend(num(self.nbpr, 4));
+        buf.extend(num(self.nbpc, 4));
+        buf.extend(num(self.nppbh, 4));
+        buf.extend(num(self.nppbv, 4));
+        buf.extend(num(self.nbpp, 2));
+        buf.extend(num(self.idlvl, 3));
+        buf.extend(num(self.ialvl, 3));
+        buf.extend(field(&self.iloc, 10));
+        buf.extend(field("1.0", 4));
+        extension_area(&mut buf, &self.udid);
+        extension_area(&mut buf, &self.ixshd);
+        buf
+    }
+}
+
+/// Writes a UDIDL/UDOFL/UDID style length, overflow and data triple.
+pub fn extension_area(buf: &mut Vec<u8>, data: &[u8]) {
+    overflow_area(buf, 0, data)
+}
+
+/// Like [`extension_area`] with the overflow field set to `ofl`. An area whose TREs all
+/// overflowed is only its length (3) and `ofl`.
+pub fn overflow_area(buf: &mut Vec<u8>, ofl: usize, data: &[u8]) {
+    if data.is_empty() && ofl == 0 {
+        buf.extend(num(0, 5));
+    } else {
+        buf.extend(num(data.len() + 3, 5));
+        buf.extend(num(ofl, 3));
+        buf.extend(data);
+    }
+}
+
+/// Segments as (subheader, data) pairs.
+#[derive(Default, Clone)]
+pub struct NitfFixture {
+    pub images: Vec<(Vec<u8>, Vec<u8>)>,
+    pub graphics: Vec<(Vec<u8>, Vec<u8>)>,
+    pub texts: Vec<(Vec<u8>, Vec<u8>)>,
+    pub des: Vec<(Vec<u8>, Vec<u8>)>,
+    pub res: Vec<(Vec<u8>, Vec<u8>)>,
+    pub udhd: Vec<u8>,
+    pub xhd: Vec<u8>,
+}
+
+impl NitfFixture {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = field("NITF", 4);
+        header.extend(field("02.10", 5));
+        header.extend(field("03", 2));
+        header.extend(field("BF01", 4));
+        header.extend(field("TESTSTA", 10));
+        header.extend(field("20240102030405", 14));
+        header.extend(field("Test file", 80));
+        header.extend(security_group());
+        header.extend(field("00000", 5));
+        header.extend(field("00000", 5));
+        header.extend(field("0", 1));
+        header.extend([0u8, 0, 0]);
+        header.extend(field("Tester", 24));
+        header.extend(field("555-0100", 18));
+        let fl_offset = header.len();
+        header.extend(num(0, 12));
+        let hl_offset = header.len();
+        header.extend(num(0, 6));
+        let tables = [
+            (&self.images, 6, 10),
+            (&self.graphics, 4, 6),
+            (&self.texts, 4, 5),
+            (&self.des, 4, 9),
+            (&self.res, 4, 7),
+        ];
+        for (i, (segments, sh_len, data_len)) in tables.into_iter().enumerate() {
+            //NUMX sits between the graphic and text tables
+            if i == 2 {
+                header.extend(num(0, 3));
+            }
+            header.extend(num(segments.len(), 3));
+            for (sub, data) in segments {
+                header.extend(num(sub.len(), sh_len));
+                header.extend(num(data.len(), data_len));
+            }
+        }
+        extension_area(&mut header, &self.udhd);
+        extension_area(&mut header, &self.xhd);
+        let hl = header.len();
+        let mut out = header;
+        for segments in [&self.images, &self.graphics, &self.texts, &self.des, &self.res] {
+            for (sub, data) in segments {
+                out.extend(sub);
+                out.extend(data);
+            }
+        }
+        let fl = out.len();
+        out[hl_offset..hl_offset + 6].copy_from_slice(&num(hl, 6));
+        out[fl_offset..fl_offset + 12].copy_from_slice(&num(fl, 12));
+        out
+    }
+
+    /// Writes the fixture to a scratch file and opens it for reading and writing.
+    pub fn write(&self, name: &str) -> std::fs::File {
+        write_temp(name, &self.to_bytes())
+    }
+}
+
+/// NITF 2.0 symbol subheader of type `stype` at (10, -20), rotated 90 degrees, with `lut` as DLUT.
+pub fn symbol_subheader_v02_00(stype: &str, lut: &[[u8; 3]]) -> Vec<u8> {
+    let mut buf = field("SY", 2);
+    buf.extend(field("SYM01", 10));
+    buf.extend(field("Arrow", 20));
+    buf.extend(security_group_v02_00("", ""));
+    buf.extend(field("0", 1));
+    buf.extend(field(stype, 1));
+    buf.extend(num(4, 4));
+    buf.extend(num(4, 4));
+    buf.extend(num(1, 4));
+    buf.extend(num(1, 1));
+    buf.extend(num(2, 3));
+    buf.extend(num(1, 3));
+    buf.extend(field("00010-0020", 10));
+    buf.extend(num(0, 10));
+    buf.extend(field("C", 1));
+    buf.extend(num(1, 6));
+    buf.extend(num(90, 3));
+    buf.extend(num(lut.len(), 3));
+    buf.extend(lut.iter().flatten());
+    buf.extend(num(0, 5));
+    buf
+}
+
+/// NITF 2.0 label subheader at (5, -3), red on black, with `lxshd` as LXSHD.
+pub fn label_subheader_v02_00(lid: &str, lxshd: &[u8]) -> Vec<u8> {
+    let mut buf = field("LA", 2);
+    buf.extend(field(lid, 10));
+    buf.extend(security_group_v02_00("", ""));
+    buf.extend(field("0", 1));
+    buf.extend(field("", 1));
+    buf.extend(num(8, 2));
+    buf.extend(num(12, 2));
+    buf.extend(num(3, 3));
+    buf.extend(num(1, 3));
+    buf.extend(field("00005-0003", 10));
+    buf.extend([255, 0, 0, 0, 0, 0]);
+    extension_area(&mut buf, lxshd);
+    buf
+}
+
+/// NITF 2.0 text subheader in STA format. TSDEVT is added when `dwng` is 999998.
+pub fn text_subheader_v02_00(textid: &str, dwng: &str) -> Vec<u8> {
+    let mut buf = field("TE", 2);
+    buf.extend(field(textid, 10));
+    buf.extend(field("02030405ZJAN97", 14));
+    buf.extend(field("Test text", 80));
+    buf.extend(security_group_v02_00(dwng, "On release"));
+    buf.extend(field("0", 1));
+    buf.extend(field("STA", 3));
+    buf.extend(num(0, 5));
+    buf
+}
+
+/// NITF 2.0 DES subheader, with DESOFLW and DESITEM when `overflow` is given.
+pub fn des_subheader_v02_00(destag: &str, overflow: Option<(&str, usize)>, desshf: &[u8]) -> Vec<u8> {
+    let mut buf = field("DE", 2);
+    buf.extend(field(destag, 25));
+    buf.extend(num(1, 2));
+    buf.extend(security_group_v02_00("", ""));
+    if let Some((desoflw, desitem)) = overflow {
+        buf.extend(field(desoflw, 6));
+        buf.extend(num(desitem, 3));
+    }
+    buf.extend(num(desshf.len(), 4));
+    buf.extend(desshf);
+    buf
+}
+
+/// A NITF 2.0 file, with symbol and label segments in place of graphics.
+#[derive(Default, Clone)]
+pub struct Nitf20Fixture {
+    pub fsdwng: String,
+    pub images: Vec<(Vec<u8>, Vec<u8>)>,
+    pub symbols: Vec<(Vec<u8>, Vec<u8>)>,
+    pub labels: Vec<(Vec<u8>, Vec<u8>)>,
+    pub texts: Vec<(Vec<u8>, Vec<u8>)>,
+    pub des: Vec<(Vec<u8>, Vec<u8>)>,
+    pub res: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Nitf20Fixture {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut header = field("NITF", 4);
+        header.extend(field("02.00", 5));
+        header.extend(field("03", 2));
+        header.extend(field("", 4));
+        header.extend(field("TESTSTA", 10));
+        header.extend(field("02030405ZJAN97", 14));
+        header.extend(field("Test file", 80));
+        header.extend(security_group_v02_00(&self.fsdwng, "On release"));
+        header.extend(field("00000", 5));
+        header.extend(field("00000", 5));
+        header.extend(field("0", 1));
+        header.extend(field("Tester", 27));
+        header.extend(field("555-0100", 18));
+        let fl_offset = header.len();
+        header.extend(num(0, 12));
+        let hl_offset = header.len();
+        header.extend(num(0, 6));
+        let segments = [
+            &self.images,
+            &self.symbols,
+            &self.labels,
+            &self.texts,
+            &self.des,
+            &self.res,
+        ];
+        let widths = [(6, 10), (4, 6), (4, 3), (4, 5), (4, 9), (4, 7)];
+        for (table, (sh_len, data_len)) in segments.iter().zip(widths) {
+            header.extend(num(table.len(), 3));
+            for (sub, data) in table.iter() {
+                header.extend(num(sub.len(), sh_len));
+                header.extend(num(data.len(), data_len));
+            }
+        }
+        extension_area(&mut header, &[]);
+        extension_area(&mut header, &[]);
+        let hl = header.len();
+        let mut out = header;
+        for table in segments {
+            for (sub, data) in table {
+                out.extend(sub);
+                out.extend(data);
+            }
+        }
+        let fl = out.len();
+        out[hl_offset..hl_offset + 6].copy_from_slice(&num(hl, 6));
+        out[fl_offset..fl_offset + 12].copy_from_slice(&num(fl, 12));
+        out
+    }
+}
+
+pub fn write_temp(name: &str, bytes: &[u8]) -> std::fs::File {
+    let path = temp_path(name);
+    std::fs::write(&path, bytes).expect("Failed to write fixture");
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .expect("Failed to open fixture")
+}
+
+pub fn temp_path(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join("nitf-gnr-tests");
+    std::fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+    dir.join(name)
+}
+
+/// One binary encoded CGM command, in the long form when the parameters do not fit the short one.
+pub fn cgm_element(class: u16, id: u16, params: &[u8]) -> Vec<u8> {
+    let short = params.len() < 31;
+    let length = if short { params.len() } else { 31 };
+    let mut buf = ((class << 12) | (id << 5) | length as u16).to_be_bytes().to_vec();
+    if !short {
+        buf.extend((params.len() as u16).to_be_bytes());
+    }
+    buf.extend(params);
+    if params.len() % 2 == 1 {
+        buf.push(0);
+    }
+    buf
+}
+
+/// CGM points as pairs of 16 bit VDC.
+pub fn cgm_points(points: &[(i16, i16)]) -> Vec<u8> {
+    points
+        .iter()
+        .flat_map(|(x, y)| x.to_be_bytes().into_iter().chain(y.to_be_bytes()))
+        .collect()
+}
+
+/// One JPEG 2000 marker segment, the length counting itself but not the marker.
+pub fn j2k_segment(marker: u16, params: &[u8]) -> Vec<u8> {
+    let mut buf = marker.to_be_bytes().to_vec();
+    buf.extend((params.len() as u16 + 2).to_be_bytes());
+    buf.extend(params);
+    buf
+}
+
+/// SOC to the last main header marker for one 8 bit component: SIZ, COD with 12 layers and
+/// code-blocks of 2^`cb` samples, QCD, then TLM if asked for.
+pub fn j2k_main_header(size: (u32, u32), tiles: (u32, u32), order: u8, levels: u8, cb: u8, tlm: bool) -> Vec<u8> {
+    let mut siz = 0u16.to_be_bytes().to_vec();
+    for v in [size.0, size.1, 0, 0, tiles.0, tiles.1, 0, 0] {
+        siz.extend(v.to_be_bytes());
+    }
+    siz.extend([0, 1, 7, 1, 1]);
+    let mut buf = vec![0xFF, 0x4F];
+    buf.extend(j2k_segment(0xFF51, &siz));
+    buf.extend(j2k_segment(0xFF52, &[0, order, 0, 12, 0, levels, cb - 2, cb - 2, 0, 0]));
+    buf.extend(j2k_segment(0xFF5C, &[0x42, 0x48, 0x50]));
+    if tlm {
+        buf.extend(j2k_segment(0xFF55, &[0, 0x50, 0, 0]));
+    }
+    buf
+}
+
+/// A tile-part with `data` as its packets. `psot` false writes Psot 0, as for a last tile-part.
+pub fn j2k_tile_part(tile: u16, index: u8, count: u8, plt: bool, psot: bool, data: &[u8]) -> Vec<u8> {
+    let plt = if plt { j2k_segment(0xFF58, &[0, 0x05]) } else { Vec::new() };
+    let length = if psot { 12 + plt.len() + 2 + data.len() } else { 0 };
+    let mut sot = tile.to_be_bytes().to_vec();
+    sot.extend((length as u32).to_be_bytes());
+    sot.extend([index, count]);
+    let mut buf = j2k_segment(0xFF90, &sot);
+    buf.extend(plt);
+    buf.extend([0xFF, 0x93]);
+    buf.extend(data);
+    buf
+}
@@ -57,10 +57,10 @@ pub fn get_version() {
 #[test]
 pub fn copy_des() {
     let valid_num_des = {
-        let file = fs::File::open("tests/out/copyDes.ntf").expect("Failed to open file");
-        let file2 = fs::File::open("tests/nitf/copyDes.ntf").expect("Failed to open file");
-        let num_des_pre = get_numdes(&file);
-        let num_des_add = get_numdes(&file2);
+        let mut file = fs::File::open("tests/out/copyDes.ntf").expect("Failed to open file");
+        let mut file2 = fs::File::open("tests/nitf/copyDes.ntf").expect("Failed to open file");
+        let num_des_pre = get_numdes(&mut file).unwrap();
+        let num_des_add = get_numdes(&mut file2).unwrap();
         num_des_pre + num_des_add
     };
     let output = Command::new("java")
@@ -80,18 +80,18 @@ pub fn copy_des() {
     }
     let stdout = str::from_utf8(&output.stdout).unwrap();
     println!("{}", stdout);
-    let file = fs::File::open("tests/out/copyDes.ntf").expect("Failed to open file");
-    let num_des_post = get_numdes(&file);
+    let mut file = fs::File::open("tests/out/copyDes.ntf").expect("Failed to open file");
+    let num_des_post = get_numdes(&mut file).unwrap();
     assert_eq!(num_des_post, valid_num_des);
 }
 
 #[test]
 pub fn copy_text() {
     let valid_numt = {
-        let file = fs::File::open("tests/out/copyText.ntf").expect("Failed to open file");
-        let file2 = fs::File::open("tests/nitf/copyText.ntf").expect("Failed to open file");
-        let numt_pre = get_numt(&file);
-        let numt_add = get_numt(&file2);
+        let mut file = fs::File::open("tests/out/copyText.ntf").expect("Failed to open file");
+        let mut file2 = fs::File::open("tests/nitf/copyText.ntf").expect("Failed to open file");
+        let numt_pre = get_numt(&mut file).unwrap();
+        let numt_add = get_numt(&mut file2).unwrap();
         numt_pre + numt_add
     };
     let output = Command::new("java")
@@ -111,18 +111,18 @@ pub fn copy_text() {
     }
     let stdout = str::from_utf8(&output.stdout).unwrap();
     println!("{}", stdout);
-    let file = fs::File::open("tests/out/copyText.ntf").expect("Failed to open file");
-    let numt_post = get_numt(&file);
+    let mut file = fs::File::open("tests/out/copyText.ntf").expect("Failed to open file");
+    let numt_post = get_numt(&mut file).unwrap();
     assert_eq!(numt_post, valid_numt);
 }
 
 #[test]
 pub fn copy_graphic() {
     let valid_nums = {
-        let file = fs::File::open("tests/out/copyGraphic.ntf").expect("Failed to open file");
-        let file2 = fs::File::open("tests/nitf/copyGraphic.ntf").expect("Failed to open file");
-        let num_g_pre = get_nums(&file);
-        let num_g_add = get_nums(&file2);
+        let mut file = fs::File::open("tests/out/copyGraphic.ntf").expect("Failed to open file");
+        let mut file2 = fs::File::open("tests/nitf/copyGraphic.ntf").expect("Failed to open file");
+        let num_g_pre = get_nums(&mut file).unwrap();
+        let num_g_add = get_nums(&mut file2).unwrap();
         num_g_pre + num_g_add
     };
     let output = Command::new("java")
@@ -142,8 +142,8 @@ pub fn copy_graphic() {
     }
     let stdout = str::from_utf8(&output.stdout).unwrap();
     println!("{}", stdout);
-    let file = fs::File::open("tests/out/copyGraphic.ntf").expect("Failed to open file");
-    let num_g_post = get_nums(&file);
+    let mut file = fs::File::open("tests/out/copyGraphic.ntf").expect("Failed to open file");
+    let num_g_post = get_nums(&mut file).unwrap();
     assert_eq!(num_g_post, valid_nums);
 }
 
@@ -1,11 +1,12 @@
 use nitf_gnr::modify::core;
+use nitf_gnr::modify::parser::nitf21::Nitf;
 mod helpers;
 
 #[test]
 fn get_version() {
-    let file =
+    let mut file =
         std::fs::File::open("tests/nitf/Japan_1_Uncompressed.ntf").expect("Failed to open file");
-    let (fhdr, ver) = core::get_version(&file);
+    let (fhdr, ver) = core::get_version(&mut file).unwrap();
     println!("File Header: {}", fhdr);
     println!("Version: {}", ver);
     let str = fhdr + &ver;
@@ -25,35 +26,1916 @@ fn copy_des() {
         .open("tests/out/copyDes.ntf")
         .expect("Failed to open file");
     println!("Opened files");
-    let num_des_pre = core::get_numdes(&output_file);
+    let num_des_pre = core::get_numdes(&mut output_file).unwrap();
     println!("Number of Data Extensions: {}", num_des_pre);
-    let num_des_add = core::get_numdes(&input_file);
+    let num_des_add = core::get_numdes(&mut input_file).unwrap();
     println!("Number of Data Extensions to add: {}", num_des_add);
     let valid_num_des = num_des_pre + num_des_add;
-    core::copy_des_segments(&mut input_file, &mut output_file);
-    let num_des_post = core::get_numdes(&output_file);
+    core::copy_des_segments(&mut input_file, &mut output_file).unwrap();
+    let num_des_post = core::get_numdes(&mut output_file).unwrap();
     assert_eq!(num_des_post, valid_num_des);
 }
 
 #[test]
 fn extract_all_jp2() {
-    let input_file = std::fs::OpenOptions::new()
+    let mut input_file = std::fs::OpenOptions::new()
         .read(true)
         .write(true)
         .open("tests/nitf/Japan_1_Uncompressed.ntf")
         .expect("Failed to open file");
-    core::extract_jp2(&input_file, "tests/out/extract_all_jp2");
+    core::extract_jp2(&mut input_file, "tests/out/extract_all_jp2").unwrap();
 }
 
 #[test]
 fn extract_jp2_index() {
-    let input_file = std::fs::OpenOptions::new()
+    let mut input_file = std::fs::OpenOptions::new()
         .read(true)
         .write(true)
         .open("tests/nitf/Japan_1_Uncompressed.ntf")
         .expect("Failed to open file");
-    let check_bytes = helpers::calculate_bytes_crc32(&core::extract_jp2_index(&input_file, 0).unwrap());
-    let check = helpers::calculate_file_crc32("tests/out/extract_all_jp20.jp2").unwrap();
+    let check_bytes = helpers::calculate_bytes_crc32(&core::extract_jp2_index(&mut input_file, 0).unwrap());
+    let check = helpers::calculate_file_crc32("tests/out/extract_all_jp20.raw").unwrap();
     assert_eq!(check_bytes, check);
 }
 
+
+#[test]
+fn parse_image_subheaders() {
+    let mono = helpers::ImageFixture {
+        iid1: "MONO".to_string(),
+        icords: "G".to_string(),
+        igeolo: "351200N1394500E351200N1394600E351100N1394600E351100N1394500E".to_string(),
+        comments: vec!["first comment".to_string(), "second comment".to_string()],
+        luts: vec![vec![vec![0, 1, 2, 3]]],
+        iloc: "-000200010".to_string(),
+        ixshd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let multi = helpers::ImageFixture {
+        iid1: "MULTI".to_string(),
+        nrows: 1024,
+        ncols: 2048,
+        irep: "MULTI".to_string(),
+        irepbands: (0..12).map(|_| String::new()).collect(),
+        ic: "C8".to_string(),
+        idlvl: 2,
+        ialvl: 1,
+        ..Default::default()
+    };
+    let fixture = helpers::NitfFixture {
+        images: vec![(mono.subheader(), vec![0u8; 16]), (multi.subheader(), vec![0u8; 32])],
+        ..Default::default()
+    };
+    let mut file = fixture.write("parse_image_subheaders.ntf");
+    let nitf = Nitf::new(&mut file).unwrap().into_v02_10().unwrap();
+    assert_eq!(nitf.images.len(), 2);
+
+    let first = &nitf.images[0];
+    assert_eq!(first.im, "IM");
+    assert_eq!(first.iid1, "MONO");
+    assert_eq!(first.security.clas, "U");
+    assert_eq!((first.nrows, first.ncols), (4, 4));
+    assert_eq!(first.igeolo.as_deref(), Some(&mono.igeolo[..]));
+    assert_eq!(first.icom, vec!["first comment", "second comment"]);
+    assert_eq!(first.ic, "NC");
+    assert_eq!(first.comrat, None);
+    assert_eq!(first.nbands(), 1);
+    assert_eq!(first.bands[0].irepband, "M");
+    assert_eq!(first.bands[0].lutd, vec![vec![0, 1, 2, 3]]);
+    assert_eq!(first.iloc, (-2, 10));
+    assert!(first.udid.is_empty());
+    assert_eq!(first.ixshd, b"TESTAA00003abc");
+
+    let second = &nitf.images[1];
+    assert_eq!(second.iid1, "MULTI");
+    assert_eq!((second.nrows, second.ncols), (1024, 2048));
+    assert_eq!(second.igeolo, None);
+    assert_eq!(second.comrat.as_deref(), Some("01.0"));
+    assert_eq!(second.nbands(), 12);
+    assert_eq!((second.idlvl, second.ialvl), (2, 1));
+}
+
+#[test]
+fn parse_file_header() {
+    let fixture = helpers::NitfFixture {
+        images: vec![(helpers::ImageFixture::default().subheader(), vec![7u8; 16])],
+        texts: vec![(b"TE-text-subheader".to_vec(), b"hello".to_vec())],
+        des: vec![
+            (b"DE-first".to_vec(), b"one".to_vec()),
+            (b"DE-second".to_vec(), b"two!".to_vec()),
+        ],
+        xhd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let bytes = fixture.to_bytes();
+    let mut file = helpers::write_temp("parse_file_header.ntf", &bytes);
+    let nitf = Nitf::new(&mut file).unwrap().into_v02_10().unwrap();
+    let header = &nitf.file_header;
+    assert_eq!(header.fhdr, "NITF");
+    assert_eq!(header.fver, "02.10");
+    assert_eq!(header.clevel, 3);
+    assert_eq!(header.ostaid, "TESTSTA");
+    assert_eq!(
+        header.fdt,
+        chrono::NaiveDate::from_ymd_opt(2024, 1, 2).and_then(|d| d.and_hms_opt(3, 4, 5))
+    );
+    assert_eq!(header.security.clas, "U");
+    assert_eq!(header.security.dcdt, None);
+    assert_eq!(header.oname, "Tester");
+    assert_eq!(header.fl, bytes.len());
+    assert_eq!((header.numi(), header.nums(), header.numt()), (1, 0, 1));
+    assert_eq!(header.numdes(), 2);
+    assert_eq!(header.des[1].subheader, 9);
+    assert_eq!(header.des[1].data, 4);
+    assert!(header.udhd.is_empty());
+    assert_eq!(header.xhd, b"TESTAA00003abc");
+
+    //The offset map agrees with the field by field offset lookups
+    use nitf_gnr::modify::parser::nitf21::{NitfHeader21 as N, NitfHeader21::*};
+    assert_eq!(nitf.header.numdes, N::get_offset(NUMDES, &mut file).unwrap());
+    assert_eq!(nitf.header.numres, N::get_offset(NUMRES, &mut file).unwrap());
+    assert_eq!(nitf.header.xhdl, N::get_offset(XHDL, &mut file).unwrap());
+    assert_eq!(nitf.header.xhd, header.hl - header.xhd.len());
+
+    let des = core::extract_des_index(&mut file, 1).unwrap();
+    assert_eq!(des, b"DE-secondtwo!");
+    assert_eq!(core::get_numdes(&mut file).unwrap(), 2);
+
+    //The same bytes parse identically from memory
+    let mut cursor = std::io::Cursor::new(&bytes);
+    let from_memory = Nitf::new(&mut cursor).unwrap().into_v02_10().unwrap();
+    assert_eq!(from_memory.header.xhdl, nitf.header.xhdl);
+    assert_eq!(from_memory.file_header.des, nitf.file_header.des);
+    assert_eq!(core::get_version(&mut cursor).unwrap(), ("NITF".to_string(), "02.10".to_string()));
+    assert_eq!(core::extract_des_index(&mut cursor, 1).unwrap(), des);
+}
+
+#[test]
+fn parse_errors() {
+    use nitf_gnr::modify::error::NitfError;
+    use std::io::Cursor;
+    let bytes = helpers::NitfFixture {
+        des: vec![(b"DE-only".to_vec(), b"one".to_vec())],
+        ..Default::default()
+    }
+    .to_bytes();
+
+    let mut old_version = bytes.clone();
+    old_version[4..9].copy_from_slice(b"01.10");
+    let mut reader = Cursor::new(&old_version);
+    assert!(matches!(
+        Nitf::new(&mut reader),
+        Err(NitfError::UnsupportedVersion(v)) if v == "NITF01.10"
+    ));
+
+    let mut bad_fhdr = bytes.clone();
+    bad_fhdr[..4].copy_from_slice(b"NSIX");
+    assert!(matches!(
+        core::get_version(&mut Cursor::new(&bad_fhdr)),
+        Err(NitfError::UnsupportedVersion(v)) if v == "NSIX02.10"
+    ));
+
+    let mut bad_clevel = bytes.clone();
+    bad_clevel[9..11].copy_from_slice(b"X3");
+    let mut reader = Cursor::new(&bad_clevel);
+    assert!(matches!(
+        Nitf::new(&mut reader),
+        Err(NitfError::MalformedField { field: "CLEVEL", offset: 9, ref raw }) if raw == "X3"
+    ));
+
+    let mut reader = Cursor::new(&bytes[..200]);
+    assert!(matches!(Nitf::new(&mut reader), Err(NitfError::Truncated { .. })));
+
+    let mut reader = Cursor::new(&bytes);
+    assert!(matches!(
+        core::extract_des_index(&mut reader, 1),
+        Err(NitfError::IndexOutOfRange { segment: "DES", index: 1, count: 1 })
+    ));
+}
+
+#[test]
+fn parse_nitf20() {
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf20::{LabelSubheader, NitfHeader20 as N, NitfHeader20::*, SymbolSubheader};
+    use nitf_gnr::modify::parser::nitf21::NitfVersion;
+    let image = helpers::ImageFixture {
+        iid1: "OLDIMG".to_string(),
+        icords: "G".to_string(),
+        igeolo: "351200N1394500E351200N1394600E351100N1394600E351100N1394500E".to_string(),
+        iloc: "00050-0007".to_string(),
+        ..Default::default()
+    };
+    let fixture = helpers::Nitf20Fixture {
+        fsdwng: "999998".to_string(),
+        images: vec![(image.subheader_v02_00(), vec![1u8; 16])],
+        symbols: vec![(helpers::symbol_subheader_v02_00("B", &[[0, 0, 0], [255, 255, 255]]), b"cgm".to_vec())],
+        labels: vec![(helpers::label_subheader_v02_00("LABEL1", b"ABCDEF00003xyz"), b"hi".to_vec())],
+        texts: vec![(helpers::text_subheader_v02_00("TXT01", "999998"), b"hello".to_vec())],
+        des: vec![
+            (helpers::des_subheader_v02_00("Registered Extensions", Some(("UDID", 1)), b""), b"TRE".to_vec()),
+            (helpers::des_subheader_v02_00("LOCAL", None, b"shf"), Vec::new()),
+        ],
+        ..Default::default()
+    };
+    let bytes = fixture.to_bytes();
+    let mut cursor = std::io::Cursor::new(&bytes);
+    let nitf = Nitf::new(&mut cursor).unwrap();
+    assert_eq!(nitf.version(), NitfVersion::V02_00);
+    let nitf = nitf.into_v02_00().unwrap();
+
+    let header = &nitf.file_header;
+    assert_eq!(header.fver, "02.00");
+    assert_eq!(
+        header.fdt,
+        chrono::NaiveDate::from_ymd_opt(1997, 1, 2).and_then(|d| d.and_hms_opt(3, 4, 5))
+    );
+    assert_eq!(header.security.dwng, "999998");
+    assert_eq!(header.security.devt.as_deref(), Some("On release"));
+    assert_eq!(header.oname, "Tester");
+    assert_eq!(header.fl, bytes.len());
+    assert_eq!((header.numi(), header.nums(), header.numl(), header.numt()), (1, 1, 1, 1));
+    assert_eq!(header.labels[0].data, 2);
+
+    //Labels sit between the symbols and the text
+    let label_start = nitf.segments.label_segments;
+    let label_length = header.labels[0].total();
+    assert_eq!(&bytes[label_start..label_start + 12], b"LALABEL1    ");
+    assert_eq!(&bytes[label_start + label_length - 2..label_start + label_length], b"hi");
+    assert_eq!(nitf.segments.text_segments, label_start + label_length);
+    assert_eq!(nitf.header.fsdevt, Some(N::fixed_offset(FSDEVT)));
+    assert_eq!(nitf.header.numl, N::get_offset(NUML, &mut cursor).unwrap());
+    assert_eq!(
+        nitf.header.llsh,
+        N::get_label_header_field_offset(&mut cursor, 0).unwrap()
+    );
+
+    let sub = &nitf.images[0];
+    assert_eq!(sub.iid, "OLDIMG");
+    assert_eq!(sub.ititle, "Test image");
+    assert_eq!(sub.security.clas, "U");
+    assert_eq!(sub.security.devt, None);
+    assert_eq!(sub.igeolo.as_deref(), Some(&image.igeolo[..]));
+    assert_eq!((sub.nrows, sub.ncols, sub.nbands()), (4, 4, 1));
+    assert_eq!(sub.iloc, (50, -7));
+
+    let symbol = &nitf.symbols[0];
+    assert_eq!((symbol.sy.as_str(), symbol.sid.as_str(), symbol.sname.as_str()), ("SY", "SYM01", "Arrow"));
+    assert_eq!((symbol.stype.as_str(), symbol.nlips, symbol.npixpl, symbol.nbpp), ("B", 4, 4, 1));
+    assert_eq!((symbol.sdlvl, symbol.salvl, symbol.sloc, symbol.srot), (2, 1, (10, -20), 90));
+    assert_eq!(symbol.dlut, vec![[0, 0, 0], [255, 255, 255]]);
+    let label = &nitf.labels[0];
+    assert_eq!((label.lid.as_str(), label.lcw, label.lch, label.lloc), ("LABEL1", 8, 12, (5, -3)));
+    assert_eq!((label.ltc, label.lbc), ([255, 0, 0], [0, 0, 0]));
+    assert_eq!(label.lxshd, b"ABCDEF00003xyz");
+    let text = &nitf.texts[0];
+    assert_eq!((text.textid.as_str(), text.txtitl.as_str(), text.txtfmt.as_str()), ("TXT01", "Test text", "STA"));
+    assert_eq!(text.txtdt, header.fdt);
+    assert_eq!(text.security.devt.as_deref(), Some("On release"));
+    let (overflow, local) = (&nitf.des[0], &nitf.des[1]);
+    assert!(overflow.is_overflow() && !local.is_overflow());
+    assert_eq!((overflow.desoflw.as_deref(), overflow.desitem), (Some("UDID"), Some(1)));
+    assert_eq!((local.destag.as_str(), local.desver, local.desoflw.as_ref()), ("LOCAL", 1, None));
+    assert_eq!(local.desshf, b"shf");
+    let symbol_bytes = helpers::symbol_subheader_v02_00("C", &[]);
+    assert!(matches!(
+        SymbolSubheader::from_bytes(&symbol_bytes[..symbol_bytes.len() - 1]),
+        Err(NitfError::Truncated { .. })
+    ));
+    assert_eq!(LabelSubheader::from_bytes(&helpers::label_subheader_v02_00("L", b"")).unwrap().lxshd, b"");
+
+    //The counts read the 2.0 layout, the functions that only know 2.1 refuse the file
+    let mut cursor = std::io::Cursor::new(&bytes);
+    assert_eq!(core::get_num_images(&mut cursor).unwrap(), 1);
+    assert_eq!(core::get_nums(&mut cursor).unwrap(), 1);
+    assert_eq!(core::get_numdes(&mut cursor).unwrap(), 2);
+    assert_eq!((core::get_hl(&mut cursor).unwrap(), core::get_fl(&mut cursor).unwrap()), (header.hl, bytes.len()));
+    assert!(matches!(
+        core::get_des_subheader(&mut cursor, 0),
+        Err(NitfError::UnsupportedVersion(version)) if version == "NITF02.00"
+    ));
+    assert!(matches!(
+        core::copy_des_segments(&mut cursor, &mut std::io::Cursor::new(helpers::NitfFixture::default().to_bytes())),
+        Err(NitfError::UnsupportedVersion(_))
+    ));
+
+    //Without FSDWNG 999998 there is no FSDEVT and everything after it moves up
+    let plain = helpers::Nitf20Fixture::default().to_bytes();
+    let nitf = Nitf::new(&mut std::io::Cursor::new(&plain)).unwrap().into_v02_00().unwrap();
+    assert_eq!(nitf.file_header.security.devt, None);
+    assert_eq!(nitf.header.fsdevt, None);
+    assert_eq!(nitf.header.fscop, N::fixed_offset(FSCOP) - N::get_value(FSDEVT));
+    assert_eq!(nitf.file_header.hl, plain.len());
+    assert!(Nitf::new(&mut std::io::Cursor::new(&plain)).unwrap().into_v02_10().is_err());
+}
+
+#[test]
+fn parse_nsif() {
+    use nitf_gnr::modify::parser::nitf21::NitfVersion;
+    let mut bytes = helpers::NitfFixture {
+        images: vec![(helpers::ImageFixture::default().subheader(), vec![0u8; 16])],
+        ..Default::default()
+    }
+    .to_bytes();
+    bytes[..9].copy_from_slice(b"NSIF01.00");
+    let mut cursor = std::io::Cursor::new(&bytes);
+    assert_eq!(
+        core::get_version(&mut cursor).unwrap(),
+        ("NSIF".to_string(), "01.00".to_string())
+    );
+    let nitf = Nitf::new(&mut cursor).unwrap();
+    assert_eq!(nitf.version(), NitfVersion::NSIF01_00);
+    assert!(matches!(nitf, Nitf::NSIF01_00(_)));
+    let nitf = nitf.into_v02_10().unwrap();
+    assert_eq!((nitf.file_header.fhdr.as_str(), nitf.file_header.fver.as_str()), ("NSIF", "01.00"));
+    assert_eq!(nitf.images[0].iid1, "TESTIMG");
+    assert_eq!(core::get_num_images(&mut cursor).unwrap(), 1);
+}
+
+#[test]
+fn parse_tres() {
+    use nitf_gnr::modify::error::{NitfError, Result};
+    use nitf_gnr::modify::parser::tre::{decode_tres, Tre, TreDecoder, TreIter, TreRegistry};
+
+    #[derive(Debug, PartialEq)]
+    struct Testaa {
+        value: String,
+    }
+
+    impl TreDecoder for Testaa {
+        const CETAG: &'static str = "TESTAA";
+
+        fn decode(cedata: &[u8]) -> Result<Testaa> {
+            Ok(Testaa {
+                value: String::from_utf8_lossy(cedata).to_string(),
+            })
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum TestTre {
+        Testaa(Testaa),
+        Unknown(Tre),
+    }
+
+    impl TreRegistry for TestTre {
+        fn decode(tre: Tre) -> Result<TestTre> {
+            match tre.cetag.as_str() {
+                Testaa::CETAG => Ok(TestTre::Testaa(Testaa::decode(&tre.cedata)?)),
+                _ => Ok(TestTre::Unknown(tre)),
+            }
+        }
+    }
+
+    let image = helpers::ImageFixture {
+        udid: b"TESTAA00002hi".to_vec(),
+        ixshd: b"OTHERB00000TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let fixture = helpers::NitfFixture {
+        images: vec![(image.subheader(), vec![0u8; 16])],
+        udhd: b"UDTRE 00004data".to_vec(),
+        xhd: b"TESTAA00003xyz".to_vec(),
+        ..Default::default()
+    };
+    let bytes = fixture.to_bytes();
+    let nitf = Nitf::new(&mut std::io::Cursor::new(&bytes)).unwrap().into_v02_10().unwrap();
+
+    let header_tres: Vec<Tre> = nitf.file_header.tres().collect::<Result<_>>().unwrap();
+    assert_eq!(header_tres.len(), 2);
+    assert_eq!(header_tres[0].cetag, "UDTRE");
+    assert_eq!(header_tres[0].cedata, b"data");
+    assert_eq!(header_tres[1].cel(), 3);
+    assert_eq!(header_tres[0].to_bytes(), fixture.udhd);
+
+    let image_tres: Vec<Tre> = nitf.images[0].tres().collect::<Result<_>>().unwrap();
+    let tags: Vec<&str> = image_tres.iter().map(|tre| tre.cetag.as_str()).collect();
+    assert_eq!(tags, ["TESTAA", "OTHERB", "TESTAA"]);
+    assert!(image_tres[1].cedata.is_empty());
+
+    //Known tags are decoded, the rest are kept as they are
+    let decoded = decode_tres::<TestTre>(&nitf.images[0].ixshd).unwrap();
+    assert_eq!(
+        decoded,
+        [
+            TestTre::Unknown(image_tres[1].clone()),
+            TestTre::Testaa(Testaa { value: "abc".to_string() }),
+        ]
+    );
+
+    //A CEL running past the end of the area stops the iteration with an error
+    let mut tres = TreIter::new(b"TESTAA00009abc");
+    assert!(matches!(tres.next(), Some(Err(NitfError::Truncated { offset: 11, length: 9 }))));
+    assert!(tres.next().is_none());
+    assert!(matches!(
+        decode_tres::<Tre>(b"TESTAA0000Xabc"),
+        Err(NitfError::MalformedField { field: "CEL", offset: 6, .. })
+    ));
+}
+
+#[test]
+fn decode_image_tres() {
+    use helpers::{field, num};
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::tre::Tre;
+    use nitf_gnr::modify::parser::tre_decoders::ImageTre;
+    let tre = |cetag: &str, cedata: Vec<u8>| {
+        Tre {
+            cetag: cetag.to_string(),
+            cedata,
+        }
+        .to_bytes()
+    };
+
+    let mut rpc = b"10001.000000.5000010000200+35.1234+139.1234+005000100002000+00.5000+000.5000+0500".to_vec();
+    for i in 0..80 {
+        rpc.extend(format!("+{}.000000E-1", i % 10).into_bytes());
+    }
+    let mut blocka = b"010000000004      ".to_vec();
+    blocka.extend(field("", 16));
+    for corner in ["+35.100000+139.200000", "+35.000000+139.200000", "+35.000000+139.100000", "+35.100000+139.100000"] {
+        blocka.extend(corner.as_bytes());
+    }
+    blocka.extend(b"010.0");
+    let geolob = b"000000360000000720+139.0000000000+035.5000000000".to_vec();
+    let mut acftb = field("MSN01", 20);
+    acftb.extend(field("TAIL", 10));
+    acftb.extend(field("", 170));
+    acftb.extend(num(2, 4));
+    acftb.extend(num(1, 3));
+    let mut aimidb = b"20240102030405MSN1".to_vec();
+    aimidb.extend(field("MISSIONID", 10));
+    aimidb.extend(b"01001AA00000 00100001AA00200003JA    ");
+    aimidb.extend(field("3512N13945E", 11));
+    aimidb.extend(field("", 13));
+    let mut stdidc = b"20240102030405".to_vec();
+    stdidc.extend(field("MISSIONX", 14));
+    stdidc.extend(b"01001AA00000 00100001AA00200003JA    ");
+    stdidc.extend(field("3512N13945E", 11));
+    stdidc.extend(field("", 13));
+    let mut piaimc = b"050 ".to_vec();
+    piaimc.extend(field("", 12));
+    piaimc.extend(field("SENSOR", 18));
+    piaimc.extend(field("", 255));
+    piaimc.extend(b"03 ");
+    piaimc.extend(field("", 41));
+    piaimc.extend(b"1   00012.5");
+    piaimc.extend(field("", 18));
+
+    let mut ixshd = Vec::new();
+    for (cetag, cedata) in [
+        ("RPC00B", rpc),
+        ("BLOCKA", blocka),
+        ("GEOLOB", geolob),
+        ("ACFTB", acftb),
+        ("AIMIDB", aimidb),
+        ("STDIDC", stdidc),
+        ("PIAIMC", piaimc),
+        ("MYTRE", b"raw".to_vec()),
+    ] {
+        ixshd.extend(tre(cetag, cedata));
+    }
+    let image = helpers::ImageFixture {
+        ixshd,
+        ..Default::default()
+    };
+    let bytes = helpers::NitfFixture {
+        images: vec![(image.subheader(), vec![0u8; 16])],
+        ..Default::default()
+    }
+    .to_bytes();
+    let mut cursor = std::io::Cursor::new(&bytes);
+    let tres = core::get_tres(&mut cursor, 0).unwrap();
+    assert_eq!(tres.len(), 8);
+
+    let ImageTre::Rpc00b(rpc) = &tres[0] else { panic!("{:?}", tres[0]) };
+    assert!(rpc.success);
+    assert_eq!((rpc.err_bias, rpc.err_rand), (1.0, 0.5));
+    assert_eq!((rpc.line_off, rpc.samp_off), (100.0, 200.0));
+    assert_eq!((rpc.lat_off, rpc.long_off, rpc.height_off), (35.1234, 139.1234, 50.0));
+    assert_eq!(rpc.height_scale, 500.0);
+    assert_eq!(rpc.line_num_coeff[1], 0.1);
+    assert_eq!(rpc.samp_den_coeff[19], 0.9);
+
+    let ImageTre::Blocka(blocka) = &tres[1] else { panic!("{:?}", tres[1]) };
+    assert_eq!((blocka.block_instance, blocka.l_lines), (1, 4));
+    assert_eq!(blocka.frfc_loc, "+35.100000+139.100000");
+    assert_eq!(blocka.lrlc_loc, "+35.000000+139.200000");
+
+    let ImageTre::Geolob(geolob) = &tres[2] else { panic!("{:?}", tres[2]) };
+    assert_eq!((geolob.arv, geolob.brv, geolob.lso, geolob.pso), (360, 720, 139.0, 35.5));
+
+    let ImageTre::Acftb(acftb) = &tres[3] else { panic!("{:?}", tres[3]) };
+    assert_eq!((acftb.ac_msn_id.as_str(), acftb.ac_tail_no.as_str()), ("MSN01", "TAIL"));
+    assert_eq!((acftb.patch_tot, acftb.mti_tot), (2, 1));
+
+    let ImageTre::Aimidb(aimidb) = &tres[4] else { panic!("{:?}", tres[4]) };
+    assert_eq!(aimidb.mission_identification, "MISSIONID");
+    assert_eq!((aimidb.end_tile_column, aimidb.end_tile_row), (2, 3));
+    assert_eq!(aimidb.location, "3512N13945E");
+
+    let ImageTre::Stdidc(stdidc) = &tres[5] else { panic!("{:?}", tres[5]) };
+    assert_eq!(
+        stdidc.acquisition_date,
+        chrono::NaiveDate::from_ymd_opt(2024, 1, 2).and_then(|d| d.and_hms_opt(3, 4, 5))
+    );
+    assert_eq!((stdidc.mission.as_str(), stdidc.country.as_str()), ("MISSIONX", "JA"));
+    assert_eq!((stdidc.start_column, stdidc.start_row, stdidc.end_row), (1, 1, 3));
+
+    let ImageTre::Piaimc(piaimc) = &tres[6] else { panic!("{:?}", tres[6]) };
+    assert_eq!((piaimc.cloudcvr, piaimc.comgen, piaimc.generation), (50, 3, 1));
+    assert_eq!(piaimc.sensname, "SENSOR");
+    assert_eq!(piaimc.meangsd, 12.5);
+
+    assert_eq!(
+        tres[7],
+        ImageTre::Unknown(Tre {
+            cetag: "MYTRE".to_string(),
+            cedata: b"raw".to_vec()
+        })
+    );
+
+    assert!(matches!(
+        core::get_tres(&mut cursor, 1),
+        Err(NitfError::IndexOutOfRange { segment: "Image", index: 1, count: 1 })
+    ));
+    let bad = helpers::ImageFixture {
+        ixshd: tre("GEOLOB", b"000000360000000720+139.000000000X+035.5000000000".to_vec()),
+        ..Default::default()
+    };
+    let nitf = Nitf::new(&mut std::io::Cursor::new(
+        helpers::NitfFixture {
+            images: vec![(bad.subheader(), vec![0u8; 16])],
+            ..Default::default()
+        }
+        .to_bytes(),
+    ))
+    .unwrap()
+    .into_v02_10()
+    .unwrap();
+    assert!(matches!(
+        nitf.images[0].decode_tres(),
+        Err(NitfError::MalformedField { field: "LSO", offset: 18, .. })
+    ));
+}
+
+#[test]
+fn write_model() {
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::writer::{NitfModel, Segment};
+    let mono = helpers::ImageFixture {
+        icords: "G".to_string(),
+        igeolo: "351200N1394500E351200N1394600E351100N1394600E351100N1394500E".to_string(),
+        comments: vec!["a comment".to_string()],
+        luts: vec![vec![vec![0, 1, 2, 3]]],
+        iloc: "-000200010".to_string(),
+        ixshd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let multi = helpers::ImageFixture {
+        irepbands: (0..12).map(|_| String::new()).collect(),
+        ic: "C8".to_string(),
+        ..Default::default()
+    };
+    let fixture = helpers::NitfFixture {
+        images: vec![(mono.subheader(), vec![1u8; 16]), (multi.subheader(), vec![2u8; 8])],
+        graphics: vec![(b"SY-graphic".to_vec(), b"cgm".to_vec())],
+        texts: vec![(b"TE-text".to_vec(), b"hello".to_vec())],
+        des: vec![(b"DE-first".to_vec(), b"one".to_vec()), (b"DE-second".to_vec(), b"two!".to_vec())],
+        res: vec![(b"RE-reserved".to_vec(), b"res".to_vec())],
+        udhd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let bytes = fixture.to_bytes();
+
+    //Reading and writing an untouched file gives back the same bytes
+    let model = NitfModel::read(&mut std::io::Cursor::new(&bytes)).unwrap();
+    assert_eq!(model.des[1], Segment::new(b"DE-second".to_vec(), b"two!".to_vec()));
+    assert_eq!(model.to_bytes().unwrap(), bytes);
+    for (i, image) in model.images.iter().enumerate() {
+        assert_eq!(image.image_subheader().unwrap().to_bytes().unwrap(), fixture.images[i].0);
+    }
+
+    //Edits are list operations, the length tables, HL and FL follow them
+    let mut edited = model.clone();
+    edited.des.remove(0);
+    edited.texts.push(Segment::new(b"TE-second".to_vec(), b"more text".to_vec()));
+    let mut subheader = edited.images[0].image_subheader().unwrap();
+    subheader.iid1 = "RENAMED".to_string();
+    edited.images[0] = Segment::image(&subheader, edited.images[0].data.clone()).unwrap();
+    let mut out = Vec::new();
+    edited.write(&mut out).unwrap();
+    let expected = helpers::NitfFixture {
+        des: vec![fixture.des[1].clone()],
+        texts: vec![fixture.texts[0].clone(), (b"TE-second".to_vec(), b"more text".to_vec())],
+        images: vec![
+            (helpers::ImageFixture { iid1: "RENAMED".to_string(), ..mono }.subheader(), vec![1u8; 16]),
+            fixture.images[1].clone(),
+        ],
+        ..fixture.clone()
+    };
+    assert_eq!(out, expected.to_bytes());
+    let mut cursor = std::io::Cursor::new(&out);
+    assert_eq!(core::get_numdes(&mut cursor).unwrap(), 1);
+    assert_eq!(core::get_fl(&mut cursor).unwrap(), out.len());
+    assert_eq!(core::extract_des_index(&mut cursor, 0).unwrap(), b"DE-secondtwo!");
+
+    //A segment too long for its length table entry is rejected rather than truncated
+    edited.texts[0].data = vec![b'x'; 100_000];
+    assert!(matches!(
+        edited.to_bytes(),
+        Err(NitfError::Overflow { field: "LTNNN", value: 100_000 })
+    ));
+
+    //Non-ASCII text would take more bytes than the field and shift everything after it
+    subheader.iid1 = "caf\u{e9}".to_string();
+    assert!(matches!(
+        Segment::image(&subheader, Vec::new()),
+        Err(NitfError::MalformedField { field: "IID1", offset: 2, raw }) if raw == "caf\u{e9}"
+    ));
+    let mut header = model.file_header.clone();
+    header.ftitle = "\u{e9}".repeat(80);
+    assert!(matches!(header.to_bytes(), Err(NitfError::MalformedField { field: "FTITLE", .. })));
+}
+
+#[test]
+fn build_nitf() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::writer::NitfModel;
+    let fdt = chrono::NaiveDate::from_ymd_opt(2024, 1, 2).and_then(|d| d.and_hms_opt(3, 4, 5)).unwrap();
+    let mut out = Vec::new();
+    NitfBuilder::new()
+        .title("Built in code")
+        .originating_station("TESTSTA")
+        .originator("Tester", "555-0100")
+        .date_time(fdt)
+        .raw_image(2, 3, 1, 8, vec![0, 1, 2, 3, 4, 5]).unwrap()
+        .cgm(b"not really cgm".to_vec(), (10, 20))
+        .jp2_image(9000, 4, 3, 8, "N001", b"\xff\x4f\xff\x51".to_vec())
+        .text("Hello")
+        .des("TEST_DES", b"payload".to_vec())
+        .write(&mut out)
+        .unwrap();
+
+    let mut cursor = std::io::Cursor::new(&out);
+    assert_eq!(core::get_version(&mut cursor).unwrap(), ("NITF".to_string(), "02.10".to_string()));
+    let nitf = Nitf::new(&mut cursor).unwrap().into_v02_10().unwrap();
+    let header = &nitf.file_header;
+    assert_eq!((header.ftitle.as_str(), header.ostaid.as_str()), ("Built in code", "TESTSTA"));
+    assert_eq!(header.fdt, Some(fdt));
+    assert_eq!(header.security.clas, "U");
+    assert_eq!(header.fl, out.len());
+    assert_eq!((header.numi(), header.nums(), header.numt(), header.numdes()), (2, 1, 1, 1));
+    //Fixed length subheaders: graphic 258, text 282, DES 200 with no DESSHF
+    assert_eq!((header.graphics[0].subheader, header.texts[0].subheader, header.des[0].subheader), (258, 282, 200));
+
+    let raw = &nitf.images[0];
+    assert_eq!((raw.nrows, raw.ncols, raw.irep.as_str(), raw.ic.as_str()), (2, 3, "MONO", "NC"));
+    assert_eq!((raw.nppbh, raw.nppbv, raw.idlvl), (3, 2, 1));
+    assert_eq!(raw.idatim, Some(fdt));
+    let jp2 = &nitf.images[1];
+    assert_eq!((jp2.irep.as_str(), jp2.ic.as_str(), jp2.nbands()), ("RGB", "C8", 3));
+    assert_eq!(jp2.bands.iter().map(|b| b.irepband.as_str()).collect::<Vec<_>>(), ["R", "G", "B"]);
+    //Taller than one block can be, so NPPBV is 0
+    assert_eq!((jp2.nppbh, jp2.nppbv, jp2.idlvl), (4, 0, 3));
+    assert!(jp2.is_compressed());
+    assert_eq!(jp2.comrat.as_deref(), Some("N001"));
+
+    let model = NitfModel::read(&mut cursor).unwrap();
+    assert_eq!(model.images[0].data, [0, 1, 2, 3, 4, 5]);
+    assert_eq!(model.images[1].data, b"\xff\x4f\xff\x51");
+    assert_eq!(model.graphics[0].data, b"not really cgm");
+    assert_eq!(&model.graphics[0].subheader[..2], b"SY");
+    assert_eq!(model.texts[0].data, b"Hello");
+    assert_eq!(&model.des[0].subheader[2..27], b"TEST_DES                 ");
+    assert_eq!(model.des[0].data, b"payload");
+
+    //Pixels must fill the image exactly, 12 bit pixels being packed
+    assert!(NitfBuilder::new().raw_image(1, 3, 1, 12, vec![0; 5]).is_ok());
+    match NitfBuilder::new().raw_image(2, 3, 1, 8, vec![0; 5]) {
+        Err(NitfError::MalformedField { field: "NROWS", raw, .. }) => {
+            assert_eq!(raw, "2 rows, 3 columns, 1 bands of 8 bits in 5 bytes")
+        }
+        other => panic!("{:?}", other.map(|_| ())),
+    }
+    assert!(NitfBuilder::new().raw_image(usize::MAX, 2, 1, 8, Vec::new()).is_err());
+}
+
+#[test]
+fn remove_segments() {
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::{GraphicSubheader, SegmentType, TextSubheader};
+    let graphic = GraphicSubheader { sy: "SY".to_string(), sxsofl: 2, ..Default::default() };
+    let text = TextSubheader { te: "TE".to_string(), txtfmt: "STA".to_string(), ..Default::default() };
+    let fixture = helpers::NitfFixture {
+        images: vec![
+            (helpers::ImageFixture::default().subheader(), vec![1u8; 16]),
+            (helpers::ImageFixture { iid1: "SECOND".to_string(), ..Default::default() }.subheader(), vec![2u8; 16]),
+        ],
+        graphics: vec![(graphic.to_bytes().unwrap(), b"cgm".to_vec())],
+        texts: vec![(text.to_bytes().unwrap(), b"hello".to_vec())],
+        des: vec![(b"DE-first".to_vec(), b"one".to_vec()), (b"DE-second".to_vec(), b"two!".to_vec())],
+        res: vec![(b"RE-reserved".to_vec(), b"res".to_vec())],
+        xhd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let bytes = fixture.to_bytes();
+    let remove = |segment, index| {
+        let mut out = Vec::new();
+        core::remove_segment(&mut std::io::Cursor::new(&bytes), &mut out, segment, index).map(|_| out)
+    };
+
+    //The result is the file that would have been written without the segment
+    let mut expected = fixture.clone();
+    expected.images.remove(0);
+    assert_eq!(remove(SegmentType::Image, 0).unwrap(), expected.to_bytes());
+    let mut expected = fixture.clone();
+    expected.graphics.clear();
+    assert_eq!(remove(SegmentType::Graphic, 0).unwrap(), expected.to_bytes());
+    let mut expected = fixture.clone();
+    expected.texts.clear();
+    assert_eq!(remove(SegmentType::Text, 0).unwrap(), expected.to_bytes());
+    let mut expected = fixture.clone();
+    expected.des.remove(0);
+    let moved = GraphicSubheader { sxsofl: 1, ..graphic.clone() };
+    expected.graphics[0].0 = moved.to_bytes().unwrap();
+    let mut out = Vec::new();
+    core::remove_des_segment(&mut std::io::Cursor::new(&bytes), &mut out, 0).unwrap();
+    assert_eq!(out, expected.to_bytes());
+    let mut cursor = std::io::Cursor::new(&out);
+    assert_eq!(core::get_numdes(&mut cursor).unwrap(), 1);
+    assert_eq!(core::get_fl(&mut cursor).unwrap(), out.len());
+    assert_eq!(core::extract_des_index(&mut cursor, 0).unwrap(), b"DE-secondtwo!");
+    assert_eq!(core::get_graphic_subheader(&mut cursor, 0).unwrap().sxsofl, 1);
+
+    //A DES that still holds overflowed TREs stays, as do those of the other areas
+    match remove(SegmentType::DataExtension, 1) {
+        Err(NitfError::MalformedField { field: "SXSOFL", raw, .. }) => assert_eq!(raw, "2"),
+        other => panic!("{:?}", other.map(|out| out.len())),
+    }
+    let overflowed = |images: Vec<helpers::ImageFixture>| {
+        let fixture = helpers::NitfFixture {
+            images: images.into_iter().map(|image| (image.subheader(), vec![1u8; 16])).collect(),
+            ..fixture.clone()
+        };
+        let mut out = Vec::new();
+        core::remove_des_segment(&mut std::io::Cursor::new(fixture.to_bytes()), &mut out, 0).map(|_| out)
+    };
+    let image = |udofl| helpers::ImageFixture { udofl, udid: b"TESTAA00001x".to_vec(), ..Default::default() };
+    assert!(matches!(
+        overflowed(vec![image(0), image(1)]),
+        Err(NitfError::MalformedField { field: "UDOFL", .. })
+    ));
+    let expected = helpers::NitfFixture {
+        images: [image(0), image(1)].into_iter().map(|image| (image.subheader(), vec![1u8; 16])).collect(),
+        ..expected.clone()
+    };
+    assert_eq!(overflowed(vec![image(0), image(2)]).unwrap(), expected.to_bytes());
+    let mut expected = fixture.clone();
+    expected.res.clear();
+    assert_eq!(remove(SegmentType::ReservedExtension, 0).unwrap(), expected.to_bytes());
+
+    assert!(matches!(
+        remove(SegmentType::DataExtension, 2),
+        Err(NitfError::IndexOutOfRange { segment: "DES", index: 2, count: 2 })
+    ));
+    let empty = helpers::NitfFixture::default().to_bytes();
+    assert!(matches!(
+        core::remove_text_segment(&mut std::io::Cursor::new(&empty), &mut Vec::new(), 0),
+        Err(NitfError::IndexOutOfRange { segment: "Text", index: 0, count: 0 })
+    ));
+}
+
+#[test]
+fn copy_selected_segments() {
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::{DesSubheader, SegmentType, TextSubheader};
+    let des = |desid: &str, data: &str| {
+        let subheader = DesSubheader { de: "DE".to_string(), desid: desid.to_string(), desver: 1, ..Default::default() };
+        (subheader.to_bytes().unwrap(), data.as_bytes().to_vec())
+    };
+    let text = |textid: &str, data: &str| {
+        let subheader = TextSubheader { te: "TE".to_string(), textid: textid.to_string(), txtfmt: "STA".to_string(), ..Default::default() };
+        (subheader.to_bytes().unwrap(), data.as_bytes().to_vec())
+    };
+    let input = helpers::NitfFixture {
+        texts: vec![text("INPUT", "input text"), text("SKIP", "not copied")],
+        des: vec![des("XML_DATA_CONTENT", "<a/>"), des("TEST_DES", "skip"), des("XML_DATA_CONTENT", "<b/>")],
+        ..Default::default()
+    }
+    .to_bytes();
+    let output = helpers::NitfFixture {
+        images: vec![(helpers::ImageFixture::default().subheader(), vec![1u8; 16])],
+        texts: vec![text("OUTPUT", "output text")],
+        des: vec![des("OUTPUT_DES", "first"), des("OUTPUT_DES", "last")],
+        ..Default::default()
+    };
+
+    //Chosen indices, in the order given, in front of the output's second DES
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_segments(&mut std::io::Cursor::new(&input), &mut out, SegmentType::DataExtension, &[2, 0], 1).unwrap();
+    let expected = helpers::NitfFixture {
+        des: vec![output.des[0].clone(), des("XML_DATA_CONTENT", "<b/>"), des("XML_DATA_CONTENT", "<a/>"), output.des[1].clone()],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+
+    //Matching a predicate, appended after the existing segments
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_segments_where(&mut std::io::Cursor::new(&input), &mut out, SegmentType::DataExtension, 2, |s| {
+        s.des_subheader().is_ok_and(|d| d.desid == "XML_DATA_CONTENT")
+    })
+    .unwrap();
+    let expected = helpers::NitfFixture {
+        des: [output.des.clone(), vec![des("XML_DATA_CONTENT", "<a/>"), des("XML_DATA_CONTENT", "<b/>")]].concat(),
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    assert_eq!(core::get_fl(&mut out).unwrap(), out.get_ref().len());
+
+    //The same with the subheader parsed for the predicate
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_des_segments_where(&mut std::io::Cursor::new(&input), &mut out, 2, |d: &DesSubheader| d.desid == "XML_DATA_CONTENT")
+        .unwrap();
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_text_segments_where(&mut std::io::Cursor::new(&input), &mut out, 1, |t: &TextSubheader| t.textid == "INPUT").unwrap();
+    let expected = helpers::NitfFixture {
+        texts: vec![output.texts[0].clone(), text("INPUT", "input text")],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    //A DES whose subheader does not parse fails the copy instead of being skipped
+    let broken = helpers::NitfFixture { des: vec![(b"DEshort".to_vec(), Vec::new())], ..Default::default() }.to_bytes();
+    assert!(matches!(
+        core::copy_des_segments_where(&mut std::io::Cursor::new(&broken), &mut std::io::Cursor::new(output.to_bytes()), 0, |_: &DesSubheader| true),
+        Err(NitfError::Truncated { .. })
+    ));
+
+    //Text at the front
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_segments(&mut std::io::Cursor::new(&input), &mut out, SegmentType::Text, &[0], 0).unwrap();
+    let expected = helpers::NitfFixture {
+        texts: vec![text("INPUT", "input text"), output.texts[0].clone()],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    assert!(matches!(
+        core::copy_segments(&mut std::io::Cursor::new(&input), &mut out, SegmentType::DataExtension, &[3], 0),
+        Err(NitfError::IndexOutOfRange { segment: "DES", index: 3, count: 3 })
+    ));
+    assert!(matches!(
+        core::copy_segments(&mut std::io::Cursor::new(&input), &mut out, SegmentType::DataExtension, &[0], 3),
+        Err(NitfError::IndexOutOfRange { segment: "DES", index: 3, count: 2 })
+    ));
+    assert_eq!(out.get_ref(), &output.to_bytes());
+}
+
+#[test]
+fn copy_image_segments() {
+    use nitf_gnr::modify::parser::nitf21::GraphicSubheader;
+    let image = |iid1: &str, idlvl, ialvl| helpers::ImageFixture {
+        iid1: iid1.to_string(),
+        idlvl,
+        ialvl,
+        ixshd: b"TESTAA00003abc".to_vec(),
+        ..Default::default()
+    };
+    let graphic = GraphicSubheader {
+        sy: "SY".to_string(),
+        sdlvl: 2,
+        ..Default::default()
+    };
+    let input = helpers::NitfFixture {
+        images: vec![
+            (image("A", 1, 0).subheader(), vec![1u8; 16]),
+            (image("B", 2, 1).subheader(), vec![2u8; 16]),
+            (image("C", 5, 9).subheader(), vec![3u8; 16]),
+        ],
+        ..Default::default()
+    }
+    .to_bytes();
+    let output = helpers::NitfFixture {
+        images: vec![(image("OUT", 1, 0).subheader(), vec![0u8; 16])],
+        graphics: vec![(graphic.to_bytes().unwrap(), b"cgm".to_vec())],
+        texts: vec![(b"TE-text".to_vec(), b"hello".to_vec())],
+        ..Default::default()
+    };
+
+    //Levels 1 and 2 are taken by the output's image and graphic, so A and B move above them
+    //and B stays attached to A. C's attachment (level 9) is not copied, so it is dropped.
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_image_segments(&mut std::io::Cursor::new(&input), &mut out).unwrap();
+    let expected = helpers::NitfFixture {
+        images: vec![
+            output.images[0].clone(),
+            (image("A", 3, 0).subheader(), vec![1u8; 16]),
+            (image("B", 4, 3).subheader(), vec![2u8; 16]),
+            (image("C", 5, 0).subheader(), vec![3u8; 16]),
+        ],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    let nitf = Nitf::new(&mut out).unwrap().into_v02_10().unwrap();
+    assert_eq!(nitf.images.iter().map(|i| (i.idlvl, i.ialvl)).collect::<Vec<_>>(), [(1, 0), (3, 0), (4, 3), (5, 0)]);
+    assert_eq!(nitf.file_header.fl, out.get_ref().len());
+
+    //Just B: level 2 belongs to the graphic and A is not there to attach to
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_image_segments_index(&mut std::io::Cursor::new(&input), &mut out, &[1]).unwrap();
+    let expected = helpers::NitfFixture {
+        images: vec![output.images[0].clone(), (image("B", 3, 0).subheader(), vec![2u8; 16])],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+
+    //A copied twice gets two levels and B attaches to the first copy. D's TREs all overflowed
+    //(UDIDL 003, UDOFL 001), which must not shift where its levels are written
+    let overflowed = |idlvl, ialvl| helpers::ImageFixture { udofl: 1, ..image("D", idlvl, ialvl) }.subheader();
+    let input = helpers::NitfFixture {
+        images: vec![
+            (image("A", 1, 0).subheader(), vec![1u8; 16]),
+            (image("B", 2, 1).subheader(), vec![2u8; 16]),
+            (overflowed(3, 0), vec![4u8; 16]),
+        ],
+        ..Default::default()
+    }
+    .to_bytes();
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_image_segments_index(&mut std::io::Cursor::new(&input), &mut out, &[0, 0, 1, 2]).unwrap();
+    let expected = helpers::NitfFixture {
+        images: vec![
+            output.images[0].clone(),
+            (image("A", 3, 0).subheader(), vec![1u8; 16]),
+            (image("A", 4, 0).subheader(), vec![1u8; 16]),
+            (image("B", 5, 3).subheader(), vec![2u8; 16]),
+            (overflowed(6, 0), vec![4u8; 16]),
+        ],
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    let nitf = Nitf::new(&mut out).unwrap().into_v02_10().unwrap();
+    assert_eq!((nitf.images[4].idlvl, nitf.images[4].udofl, nitf.images[4].iloc), (6, 1, (0, 0)));
+}
+
+#[test]
+fn reserved_extension_segments() {
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::ResSubheader;
+    let subheader = |resid: &str, resshf: &[u8]| {
+        let mut buf = helpers::field("RE", 2);
+        buf.extend(helpers::field(resid, 25));
+        buf.extend(helpers::num(1, 2));
+        buf.extend(helpers::security_group());
+        buf.extend(helpers::num(resshf.len(), 4));
+        buf.extend(resshf);
+        buf
+    };
+    let input = helpers::NitfFixture {
+        des: vec![(b"DE-des".to_vec(), b"des".to_vec())],
+        res: vec![(subheader("FIRST_RES", b""), b"one".to_vec()), (subheader("SECOND_RES", b"shf"), b"two!".to_vec())],
+        ..Default::default()
+    };
+    let bytes = input.to_bytes();
+    let mut cursor = std::io::Cursor::new(&bytes);
+
+    let res = core::get_res_subheader(&mut cursor, 1).unwrap();
+    assert_eq!((res.re.as_str(), res.resid.as_str(), res.resver), ("RE", "SECOND_RES", 1));
+    assert_eq!(res.security.clas, "U");
+    assert_eq!(res.resshf, b"shf");
+    assert_eq!(res.to_bytes().unwrap(), input.res[1].0);
+    assert_eq!(ResSubheader::from_bytes(&input.res[0].0).unwrap().resshf, b"");
+    assert_eq!(core::extract_res_index(&mut cursor, 1).unwrap(), [input.res[1].0.clone(), b"two!".to_vec()].concat());
+    assert_eq!(core::extract_res_header_fields_index(&mut cursor, 1).unwrap(), b"02030000004");
+    assert!(matches!(
+        core::get_res_subheader(&mut cursor, 2),
+        Err(NitfError::IndexOutOfRange { segment: "RES", index: 2, count: 2 })
+    ));
+
+    let output = helpers::NitfFixture {
+        res: vec![(subheader("OUTPUT_RES", b""), b"out".to_vec())],
+        ..Default::default()
+    };
+    let mut out = std::io::Cursor::new(output.to_bytes());
+    core::copy_res_segments(&mut cursor, &mut out).unwrap();
+    let expected = helpers::NitfFixture {
+        res: [output.res.clone(), input.res.clone()].concat(),
+        ..output.clone()
+    };
+    assert_eq!(out.get_ref(), &expected.to_bytes());
+    assert_eq!(core::get_numres(&mut out).unwrap(), 3);
+}
+
+#[test]
+fn tre_overflow() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::{DesSubheader, NitfDesSubheader21, TRE_OVERFLOW};
+    use nitf_gnr::modify::parser::tre::Tre;
+    use nitf_gnr::modify::parser::tre_decoders::ImageTre;
+    use nitf_gnr::modify::writer::Segment;
+    let tre = |cetag: &str, cedata: &[u8]| Tre { cetag: cetag.to_string(), cedata: cedata.to_vec() };
+    let overflow = |desoflw: &str, desitem, tres: &[Tre]| {
+        let subheader = DesSubheader {
+            de: "DE".to_string(),
+            desid: TRE_OVERFLOW.to_string(),
+            desver: 1,
+            desoflw: Some(desoflw.to_string()),
+            desitem: Some(desitem),
+            desshf: b"shf".to_vec(),
+            ..Default::default()
+        };
+        (subheader, tres.iter().flat_map(Tre::to_bytes).collect::<Vec<u8>>())
+    };
+    let (udhd_des, udhd_tres) = overflow("UDHD", 0, &[tre("HDRTWO", b"2")]);
+    let (ixshd_des, ixshd_tres) = overflow("IXSHD", 1, &[tre("IMGTWO", b"22"), tre("IMGTRI", b"333")]);
+    let mut model = NitfBuilder::new()
+        .raw_image(2, 2, 1, 8, vec![0u8; 4]).unwrap()
+        .des_segment(udhd_des.clone(), udhd_tres)
+        .des_segment(ixshd_des, ixshd_tres)
+        .des("TEST_DES", b"not tres".to_vec())
+        .build()
+        .unwrap();
+    model.file_header.udhofl = 1;
+    model.file_header.xhd = tre("XHDONE", b"x").to_bytes();
+    let mut image = model.images[0].image_subheader().unwrap();
+    image.ixshd = tre("IMGONE", b"1").to_bytes();
+    image.ixsofl = 2;
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let bytes = model.to_bytes().unwrap();
+    let mut cursor = std::io::Cursor::new(&bytes);
+
+    let des = core::get_des_subheader(&mut cursor, 0).unwrap();
+    assert_eq!(des, udhd_des);
+    assert!(des.is_overflow());
+    let plain = core::get_des_subheader(&mut cursor, 2).unwrap();
+    assert_eq!((plain.desid.as_str(), plain.desoflw.as_deref(), plain.desitem), ("TEST_DES", None, None));
+    assert_eq!(plain.to_bytes().unwrap().len(), model.des[2].subheader.len());
+
+    //UDHD held nothing itself, every TRE of it is in the first DES
+    assert_eq!(
+        core::get_header_tres(&mut cursor).unwrap(),
+        vec![tre("HDRTWO", b"2"), tre("XHDONE", b"x")]
+    );
+    assert_eq!(
+        core::get_tres(&mut cursor, 0).unwrap(),
+        vec![
+            ImageTre::Unknown(tre("IMGONE", b"1")),
+            ImageTre::Unknown(tre("IMGTWO", b"22")),
+            ImageTre::Unknown(tre("IMGTRI", b"333")),
+        ]
+    );
+
+    //An overflow field pointing at a DES that is not TRE_OVERFLOW
+    image.ixsofl = 3;
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let bytes = model.to_bytes().unwrap();
+    assert!(matches!(
+        core::get_tres(&mut std::io::Cursor::new(&bytes), 0),
+        Err(NitfError::MalformedField { field: "DESID", raw, .. }) if raw == "TEST_DES"
+    ));
+
+    //XHDLOFL pointing at the DES that overflowed UDHD, and IXSOFL at one for another image
+    model.file_header.xhdlofl = 1;
+    let bytes = model.to_bytes().unwrap();
+    let des_start = bytes.len() - model.des.iter().map(|d| d.subheader.len() + d.data.len()).sum::<usize>();
+    let desoflw = des_start + NitfDesSubheader21::fixed_offset(NitfDesSubheader21::DESOFLW);
+    match core::get_header_tres(&mut std::io::Cursor::new(&bytes)) {
+        Err(NitfError::MalformedField { field: "DESOFLW", offset, raw }) => assert_eq!((offset, raw.as_str()), (desoflw, "UDHD")),
+        other => panic!("{:?}", other),
+    }
+    model.file_header.xhdlofl = 0;
+    image.ixsofl = 2;
+    let mut ixshd_des = DesSubheader::from_bytes(&model.des[1].subheader).unwrap();
+    ixshd_des.desitem = Some(2);
+    model.des[1] = Segment::new(ixshd_des.to_bytes().unwrap(), model.des[1].data.clone());
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let bytes = model.to_bytes().unwrap();
+    assert!(matches!(
+        core::get_tres(&mut std::io::Cursor::new(&bytes), 0),
+        Err(NitfError::MalformedField { field: "DESITEM", raw, .. }) if raw == "2"
+    ));
+}
+
+#[test]
+fn xml_data_content() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::xml_des::{crc16, XmlDataContent};
+    assert_eq!(crc16(b"123456789"), 0x29B1);
+
+    let xml = "<SICD><CollectionInfo/></SICD>";
+    let fields = XmlDataContent {
+        desshft: "XML".to_string(),
+        desshdt: chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap().and_hms_opt(12, 30, 0),
+        desshsi: "SICD Volume 1 Design & Implementation Description Document".to_string(),
+        desshsv: "1.3.0".to_string(),
+        desshtn: "urn:SICD:1.3.0".to_string(),
+        ..Default::default()
+    };
+    let mut file = std::io::Cursor::new(Vec::new());
+    NitfBuilder::new().des("OTHER", b"data".to_vec()).write(&mut file).unwrap();
+    core::add_xml_des(&mut file, xml, &fields).unwrap();
+
+    assert_eq!(core::get_numdes(&mut file).unwrap(), 2);
+    assert_eq!(core::get_xml_des_indices(&mut file).unwrap(), vec![1]);
+    let parsed = core::get_xml_des_subheader(&mut file, 1).unwrap();
+    assert_eq!(parsed.descrc, Some(crc16(xml.as_bytes())));
+    assert_eq!(XmlDataContent { descrc: None, ..parsed.clone() }, fields);
+    assert_eq!(parsed.to_bytes().unwrap().len(), 283);
+    assert_eq!(core::extract_xml_index(&mut file, 1).unwrap(), xml);
+    let des = core::get_des_subheader(&mut file, 1).unwrap();
+    assert_eq!(des.desid, "XML_DATA_CONTENT");
+    assert_eq!(des.desshf.len(), 283);
+
+    //The location fields make it the long form
+    let long = XmlDataContent { desshli: "Locator".to_string(), descrc: None, ..fields };
+    let bytes = long.to_bytes().unwrap();
+    assert_eq!(bytes.len(), 773);
+    assert_eq!(&bytes[..5], b"99999");
+    assert_eq!(XmlDataContent::from_bytes(&bytes).unwrap(), long);
+    assert_eq!(XmlDataContent::from_bytes(b"12345").unwrap().descrc, Some(12345));
+    assert!(matches!(
+        XmlDataContent::from_bytes(b"1234"),
+        Err(NitfError::MalformedField { field: "DESSHL", .. })
+    ));
+
+    assert!(matches!(
+        core::extract_xml_index(&mut file, 0),
+        Err(NitfError::MalformedField { field: "DESID", raw, .. }) if raw == "OTHER"
+    ));
+    let mut bytes = file.into_inner();
+    let last = bytes.len() - 2;
+    bytes[last] = b'X';
+    assert!(matches!(
+        core::extract_xml_index(&mut std::io::Cursor::new(bytes), 1),
+        Err(NitfError::MalformedField { field: "DESCRC", .. })
+    ));
+}
+
+#[test]
+fn text_segments() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::{SecurityGroup, TextSubheader};
+    let subheader = |txtfmt: &str| TextSubheader {
+        te: "TE".to_string(),
+        textid: "ID".to_string(),
+        txtalvl: 2,
+        txtdt: chrono::NaiveDate::from_ymd_opt(2023, 7, 14).unwrap().and_hms_opt(8, 0, 5),
+        txtitl: "Notes".to_string(),
+        security: SecurityGroup { clas: "U".to_string(), ..Default::default() },
+        encryp: "0".to_string(),
+        txtfmt: txtfmt.to_string(),
+        txsofl: 0,
+        txshd: Vec::new(),
+    };
+    let mut file = std::io::Cursor::new(Vec::new());
+    NitfBuilder::new()
+        .text("plain text")
+        .text_segment(subheader("UT1"), b"caf\xe9 \xa9".to_vec())
+        .text_segment(subheader("U8S"), "café ©".as_bytes().to_vec())
+        .text_segment(subheader("MTF"), b"EXER/ABC//".to_vec())
+        .text_segment(subheader("U8S"), b"caf\xe9".to_vec())
+        .text_segment(subheader("XYZ"), b"text".to_vec())
+        .write(&mut file)
+        .unwrap();
+
+    assert_eq!(core::get_text_subheader(&mut file, 1).unwrap(), subheader("UT1"));
+    assert_eq!(core::get_text_subheader(&mut file, 0).unwrap().txtfmt, "STA");
+    let with_txshd = TextSubheader { txsofl: 0, txshd: b"TXTTRE00001x".to_vec(), ..subheader("STA") };
+    assert_eq!(TextSubheader::from_bytes(&with_txshd.to_bytes().unwrap()).unwrap(), with_txshd);
+
+    assert_eq!(core::extract_text_index(&mut file, 0).unwrap(), "plain text");
+    assert_eq!(core::extract_text_index(&mut file, 1).unwrap(), "café ©");
+    assert_eq!(core::extract_text_index(&mut file, 2).unwrap(), "café ©");
+    assert_eq!(core::extract_text_index(&mut file, 3).unwrap(), "EXER/ABC//");
+    assert!(matches!(
+        core::extract_text_index(&mut file, 4),
+        Err(NitfError::MalformedField { field: "TEXT", .. })
+    ));
+    assert!(matches!(
+        core::extract_text_index(&mut file, 5),
+        Err(NitfError::MalformedField { field: "TXTFMT", raw, .. }) if raw == "XYZ"
+    ));
+    assert!(matches!(
+        core::extract_text_index(&mut file, 6),
+        Err(NitfError::IndexOutOfRange { segment: "Text", index: 6, count: 6 })
+    ));
+}
+
+#[test]
+fn graphic_segments() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::GraphicSubheader;
+    let cgm = b"\x00\x22cgm".to_vec();
+    let mut file = std::io::Cursor::new(Vec::new());
+    NitfBuilder::new()
+        .raw_image(1, 1, 1, 8, vec![0]).unwrap()
+        .cgm(cgm.clone(), (40, 60))
+        .cgm(b"second".to_vec(), (1, 1))
+        .write(&mut file)
+        .unwrap();
+
+    let subheader = core::get_graphic_subheader(&mut file, 0).unwrap();
+    assert_eq!((subheader.sy.as_str(), subheader.sfmt.as_str(), subheader.scolor.as_str()), ("SY", "C", "C"));
+    assert_eq!((subheader.sdlvl, subheader.sbnd1, subheader.sbnd2), (2, (0, 0), (40, 60)));
+    assert_eq!(core::get_graphic_subheader(&mut file, 1).unwrap().sdlvl, 3);
+    assert_eq!(core::extract_graphic_index(&mut file, 0).unwrap(), cgm);
+    assert_eq!(core::extract_graphic_index(&mut file, 1).unwrap(), b"second");
+    assert!(matches!(
+        core::extract_graphic_index(&mut file, 2),
+        Err(NitfError::IndexOutOfRange { segment: "Graphic", index: 2, count: 2 })
+    ));
+
+    let full = GraphicSubheader {
+        sid: "G1".to_string(),
+        sname: "Arrow".to_string(),
+        salvl: 1,
+        sloc: (-5, 12),
+        sxshd: b"GRPTRE00003abc".to_vec(),
+        ..subheader
+    };
+    assert_eq!(GraphicSubheader::from_bytes(&full.to_bytes().unwrap()).unwrap(), full);
+
+    let dir = std::env::temp_dir().join(format!("nitf_graphics_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let outpath = format!("{}/", dir.display());
+    core::extract_graphics(&mut file, &outpath).unwrap();
+    assert_eq!(std::fs::read(dir.join("0.cgm")).unwrap(), cgm);
+    assert_eq!(std::fs::read(dir.join("1.cgm")).unwrap(), b"second");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cgm_to_svg() {
+    use helpers::{cgm_element, cgm_points};
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::cgm::{ArcClosure, Cgm, CgmElement, Colour, InteriorStyle, Point};
+    use nitf_gnr::modify::parser::nitf21::{GraphicSubheader, SecurityGroup};
+    use nitf_gnr::modify::writer::Segment;
+    let p = |x, y| Point { x, y };
+    let mut text = cgm_points(&[(5, -20)]);
+    text.extend([0, 1, 3]);
+    text.extend(b"A<B");
+    let long_line = [(0, 0), (1, -1), (2, -2), (3, -3), (4, -4), (5, -5), (6, -6), (7, -7)];
+    let mut cgm = Vec::new();
+    cgm.extend(cgm_element(0, 1, b"\x04test"));
+    cgm.extend(cgm_element(1, 1, &[0, 1]));
+    cgm.extend(cgm_element(0, 3, b"\x01p"));
+    cgm.extend(cgm_element(2, 6, &cgm_points(&[(0, -100), (200, 0)])));
+    cgm.extend(cgm_element(0, 4, &[]));
+    cgm.extend(cgm_element(5, 4, &[255, 0, 0]));
+    cgm.extend(cgm_element(5, 3, &[0, 2]));
+    cgm.extend(cgm_element(4, 1, &cgm_points(&[(0, 0), (10, -10)])));
+    cgm.extend(cgm_element(4, 1, &cgm_points(&long_line)));
+    cgm.extend(cgm_element(5, 14, &[0, 0, 255]));
+    cgm.extend(cgm_element(4, 4, &text));
+    cgm.extend(cgm_element(5, 22, &[0, 1]));
+    cgm.extend(cgm_element(5, 23, &[0, 255, 0]));
+    cgm.extend(cgm_element(4, 7, &cgm_points(&[(0, 0), (10, 0), (10, -10)])));
+    cgm.extend(cgm_element(4, 17, &cgm_points(&[(50, -50), (70, -50), (50, -60)])));
+    //A quarter circle and a pie slice of the ellipse above, counterclockwise from the x axis
+    let mut quarter = cgm_points(&[(100, -50), (1, 0), (0, 1)]);
+    quarter.extend(10i16.to_be_bytes());
+    cgm.extend(cgm_element(4, 15, &quarter));
+    let mut pie = cgm_points(&[(50, -50), (70, -50), (50, -60), (1, 0), (0, -1)]);
+    pie.extend([0, 0]);
+    cgm.extend(cgm_element(4, 19, &pie));
+    cgm.extend([0, 0]);
+    cgm.extend(cgm_element(0, 5, &[]));
+    cgm.extend(cgm_element(0, 2, &[]));
+
+    let parsed = Cgm::parse(&cgm).unwrap();
+    assert_eq!(
+        parsed.elements,
+        vec![
+            CgmElement::BeginMetafile("test".to_string()),
+            CgmElement::Unknown { class: 1, id: 1, params: vec![0, 1] },
+            CgmElement::BeginPicture("p".to_string()),
+            CgmElement::VdcExtent(p(0, -100), p(200, 0)),
+            CgmElement::BeginPictureBody,
+            CgmElement::LineColour(Colour { r: 255, g: 0, b: 0 }),
+            CgmElement::LineWidth(2),
+            CgmElement::Polyline(vec![p(0, 0), p(10, -10)]),
+            CgmElement::Polyline(long_line.iter().map(|&(x, y)| p(x as isize, y as isize)).collect()),
+            CgmElement::TextColour(Colour { r: 0, g: 0, b: 255 }),
+            CgmElement::Text { position: p(5, -20), text: "A<B".to_string() },
+            CgmElement::InteriorStyle(InteriorStyle::Solid),
+            CgmElement::FillColour(Colour { r: 0, g: 255, b: 0 }),
+            CgmElement::Polygon(vec![p(0, 0), p(10, 0), p(10, -10)]),
+            CgmElement::Ellipse { centre: p(50, -50), first: p(70, -50), second: p(50, -60) },
+            CgmElement::CircularArc { centre: p(100, -50), start: p(1, 0), end: p(0, 1), radius: 10, close: None },
+            CgmElement::EllipticalArc {
+                centre: p(50, -50),
+                first: p(70, -50),
+                second: p(50, -60),
+                start: p(1, 0),
+                end: p(0, -1),
+                close: Some(ArcClosure::Pie),
+            },
+            CgmElement::EndPicture,
+            CgmElement::EndMetafile,
+        ]
+    );
+    assert!(matches!(Cgm::parse(&cgm[..cgm.len() - 31]), Err(NitfError::Truncated { .. })));
+
+    //The graphic is attached to an image at ILOC (100, 200)
+    let graphic = |salvl| GraphicSubheader {
+        sy: "SY".to_string(),
+        security: SecurityGroup { clas: "U".to_string(), ..Default::default() },
+        encryp: "0".to_string(),
+        sfmt: "C".to_string(),
+        sdlvl: 2,
+        salvl,
+        sloc: (10, 20),
+        scolor: "C".to_string(),
+        ..Default::default()
+    };
+    let mut model = NitfBuilder::new()
+        .raw_image(1, 1, 1, 8, vec![0]).unwrap()
+        .graphic_segment(graphic(1), cgm.clone())
+        .graphic_segment(graphic(7), cgm)
+        .build()
+        .unwrap();
+    let mut image = model.images[0].image_subheader().unwrap();
+    image.iloc = (100, 200);
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let mut file = std::io::Cursor::new(model.to_bytes().unwrap());
+
+    let svg = core::get_graphic_svg(&mut file, 0).unwrap();
+    assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="220 110 200 100""#));
+    assert!(svg.contains(r#"<polyline points="220,110 230,120" fill="none" stroke="rgb(255,0,0)" stroke-width="2"/>"#));
+    assert!(svg.contains(r#"<text x="225" y="130" font-size="10" fill="rgb(0,0,255)">A&lt;B</text>"#));
+    assert!(svg.contains(r#"<polygon points="220,110 230,110 230,120" fill="rgb(0,255,0)" stroke="none"/>"#));
+    assert!(svg.contains(r#"transform="matrix(20 0 0 10 270 160)""#));
+    assert!(svg.contains(
+        r#"<path d="M1,0 A1 1 0 0 1 0,1" transform="matrix(10 0 0 -10 320 160)" vector-effect="non-scaling-stroke" fill="none""#
+    ));
+    assert!(svg.contains(r#"<path d="M1,0 A1 1 0 0 1 0,1 L0,0 Z" transform="matrix(20 0 0 10 270 160)""#));
+    let mut close = cgm_points(&[(0, 0), (1, 0), (0, 1)]);
+    close.extend([0, 10, 0, 2]);
+    assert!(matches!(
+        Cgm::parse(&cgm_element(4, 16, &close)),
+        Err(NitfError::MalformedField { field: "CLOSE TYPE", offset: 16, .. })
+    ));
+    assert!(matches!(
+        core::get_graphic_svg(&mut file, 1),
+        Err(NitfError::MalformedField { field: "SALVL", raw, .. }) if raw == "7"
+    ));
+}
+
+#[test]
+fn extract_images_by_ic() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::nitf21::ImageCompression;
+    let codestream = b"\xff\x4f\xff\x51codestream\xff\xd9".to_vec();
+    let mut file = std::io::Cursor::new(Vec::new());
+    NitfBuilder::new()
+        .raw_image(2, 3, 1, 8, vec![1, 2, 3, 4, 5, 6]).unwrap()
+        .jp2_image(64, 64, 3, 8, "N001", codestream.clone())
+        .write(&mut file)
+        .unwrap();
+
+    assert_eq!(
+        core::extract_image_index(&mut file, 0).unwrap(),
+        (ImageCompression::Uncompressed, vec![1, 2, 3, 4, 5, 6])
+    );
+    assert_eq!(
+        core::extract_image_index(&mut file, 1).unwrap(),
+        (ImageCompression::Jpeg2000, codestream.clone())
+    );
+    assert_eq!(core::extract_jp2_index(&mut file, 1).unwrap(), codestream);
+    assert!(matches!(
+        core::extract_image_index(&mut file, 2),
+        Err(NitfError::IndexOutOfRange { segment: "Image", index: 2, count: 2 })
+    ));
+
+    let outpath = helpers::temp_path("extract_images_by_ic").display().to_string();
+    core::extract_jp2(&mut file, &outpath).unwrap();
+    assert_eq!(std::fs::read(format!("{}0.raw", outpath)).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    assert_eq!(std::fs::read(format!("{}1.j2k", outpath)).unwrap(), codestream);
+
+    //2.0 files are laid out differently but extract the same way
+    let image = helpers::ImageFixture { ic: "C3".to_string(), ..Default::default() };
+    let jpeg = b"\xff\xd8jpeg\xff\xd9".to_vec();
+    let fixture = helpers::Nitf20Fixture {
+        images: vec![(image.subheader_v02_00(), jpeg.clone())],
+        symbols: vec![(helpers::symbol_subheader_v02_00("C", &[]), b"cgm".to_vec())],
+        ..Default::default()
+    };
+    assert_eq!(
+        core::extract_image_index(&mut std::io::Cursor::new(fixture.to_bytes()), 0).unwrap(),
+        (ImageCompression::Jpeg, jpeg)
+    );
+    assert_eq!(ImageCompression::from_ic("C7"), ImageCompression::Other("C7".to_string()));
+    assert_eq!(ImageCompression::from_ic("M8").extension(), "j2k");
+}
+
+#[test]
+fn decode_uncompressed_images() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::image::{decode_uncompressed, ImageLayout, PixelData};
+    let layout = |nrows, ncols, nbands, imode: &str, pvtype: &str, nbpp| ImageLayout {
+        nrows,
+        ncols,
+        nbands,
+        pvtype: pvtype.to_string(),
+        abpp: nbpp,
+        pjust: "R".to_string(),
+        ic: "NC".to_string(),
+        imode: imode.to_string(),
+        nbpr: 1,
+        nbpc: 1,
+        nppbh: ncols,
+        nppbv: nrows,
+        nbpp,
+    };
+    let pixels = |layout: &ImageLayout, data: &[u8]| decode_uncompressed(layout, data).unwrap().pixels;
+
+    //Two bands of 2x2 in every IMODE come out interleaved by pixel
+    let interleaved = PixelData::U8(vec![1, 5, 2, 6, 3, 7, 4, 8]);
+    for (imode, data) in [
+        ("B", [1, 2, 3, 4, 5, 6, 7, 8]),
+        ("P", [1, 5, 2, 6, 3, 7, 4, 8]),
+        ("R", [1, 2, 5, 6, 3, 4, 7, 8]),
+        ("S", [1, 2, 3, 4, 5, 6, 7, 8]),
+    ] {
+        assert_eq!(pixels(&layout(2, 2, 2, imode, "INT", 8), &data), interleaved, "IMODE {}", imode);
+    }
+
+    //3x3 in 2x2 blocks, the right and bottom blocks padded
+    let blocked = ImageLayout { nbpr: 2, nbpc: 2, nppbh: 2, nppbv: 2, ..layout(3, 3, 1, "B", "INT", 8) };
+    let data = [1, 2, 4, 5, 3, 0, 6, 0, 7, 8, 0, 0, 9, 0, 0, 0];
+    assert_eq!(pixels(&blocked, &data), PixelData::U8((1..=9).collect()));
+    let sequential = ImageLayout { nbands: 2, imode: "S".to_string(), ..blocked.clone() };
+    let mut data2 = data.to_vec();
+    data2.extend(data.iter().map(|v| v * 10));
+    let decoded = decode_uncompressed(&sequential, &data2).unwrap();
+    assert_eq!(decoded.pixels, PixelData::U8((1..=9).flat_map(|v| [v, v * 10]).collect()));
+    assert_eq!(decoded.index(2, 1, 1), 15);
+
+    assert_eq!(pixels(&layout(1, 2, 1, "B", "INT", 12), &[0xAB, 0xC1, 0x23]), PixelData::U16(vec![0xABC, 0x123]));
+    let left = ImageLayout { abpp: 12, pjust: "L".to_string(), ..layout(1, 1, 1, "B", "INT", 16) };
+    assert_eq!(pixels(&left, &[0xAB, 0xC0]), PixelData::U16(vec![0xABC]));
+    let right = ImageLayout { abpp: 11, ..layout(1, 1, 1, "B", "INT", 16) };
+    assert_eq!(pixels(&right, &[0xF8, 0x01]), PixelData::U16(vec![1]));
+    assert_eq!(pixels(&layout(1, 2, 1, "B", "SI", 16), &[0xFF, 0xFE, 0x00, 0x07]), PixelData::I16(vec![-2, 7]));
+    let signed = ImageLayout { abpp: 12, pjust: "L".to_string(), ..layout(1, 1, 1, "B", "SI", 16) };
+    assert_eq!(pixels(&signed, &[0xFF, 0xE0]), PixelData::I16(vec![-2]));
+    assert_eq!(pixels(&layout(1, 1, 1, "B", "R", 32), &1.5f32.to_be_bytes()), PixelData::F32(vec![1.5]));
+    let mut complex = 1.0f32.to_be_bytes().to_vec();
+    complex.extend((-2.0f32).to_be_bytes());
+    assert_eq!(pixels(&layout(1, 1, 1, "B", "C", 64), &complex), PixelData::Complex32(vec![(1.0, -2.0)]));
+    assert_eq!(pixels(&layout(1, 8, 1, "B", "B", 1), &[0b1011_0000]), PixelData::U8(vec![1, 0, 1, 1, 0, 0, 0, 0]));
+
+    let masked = ImageLayout { ic: "NM".to_string(), ..layout(1, 2, 1, "B", "INT", 8) };
+    assert_eq!(pixels(&masked, &[0, 0, 0, 10, 0, 0, 0, 0, 0, 0, 7, 9]), PixelData::U8(vec![7, 9]));
+    assert!(matches!(
+        decode_uncompressed(&layout(2, 2, 1, "B", "INT", 8), &[1, 2, 3]),
+        Err(NitfError::Truncated { offset: 3, length: 1 })
+    ));
+    assert!(matches!(
+        decode_uncompressed(&ImageLayout { ic: "C8".to_string(), ..layout(1, 1, 1, "B", "INT", 8) }, &[0]),
+        Err(NitfError::MalformedField { field: "IC", .. })
+    ));
+    //Sizes from the subheader are checked before anything is allocated from them
+    assert!(matches!(
+        decode_uncompressed(&layout(99999999, 99999999, 99999, "B", "INT", 8), &[0]),
+        Err(NitfError::MalformedField { field: "NROWS", .. })
+    ));
+    assert!(matches!(
+        decode_uncompressed(&layout(40000, 40000, 3, "P", "INT", 16), &[0; 64]),
+        Err(NitfError::Truncated { offset: 64, length: 9_599_999_936 })
+    ));
+    let huge = ImageLayout { nbpr: usize::MAX / 2, nbpc: 4, ..layout(1, 1, 1, "B", "INT", 8) };
+    assert!(matches!(decode_uncompressed(&huge, &[0]), Err(NitfError::MalformedField { field: "NBPR", .. })));
+
+    let mut file = std::io::Cursor::new(Vec::new());
+    NitfBuilder::new()
+        .raw_image(2, 3, 3, 8, (0..18).collect()).unwrap()
+        .write(&mut file)
+        .unwrap();
+    let decoded = core::decode_image_index(&mut file, 0).unwrap();
+    assert_eq!((decoded.nrows, decoded.ncols, decoded.nbands), (2, 3, 3));
+    assert_eq!(decoded.pixels, PixelData::U8((0..6).flat_map(|p| [p, p + 6, p + 12]).collect()));
+}
+
+#[test]
+fn masked_images() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::image::{BlockMask, PixelData};
+    use nitf_gnr::modify::writer::Segment;
+    //4x4 in 2x2 blocks, the lower left block not recorded and TPXCD 0xEE
+    let mut data = vec![0, 0, 0, 27, 0, 4, 0, 0, 0, 8, 0xEE];
+    for bmr in [0u32, 4, 0xFFFFFFFF, 8] {
+        data.extend(bmr.to_be_bytes());
+    }
+    data.extend([1, 2, 5, 6, 3, 4, 7, 8, 11, 12, 15, 16]);
+    let codestream = b"\xff\x4f\xff\x51j2k\xff\xd9".to_vec();
+    let mut j2k = vec![0, 0, 0, 14, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0];
+    j2k.extend(&codestream);
+
+    let mut model = NitfBuilder::new()
+        .raw_image(4, 4, 1, 8, vec![0; 16]).unwrap()
+        .jp2_image(4, 4, 1, 8, "N001", j2k)
+        .raw_image(2, 2, 1, 8, vec![1, 2, 3, 4]).unwrap()
+        .build()
+        .unwrap();
+    let mut image = model.images[0].image_subheader().unwrap();
+    image.ic = "NM".to_string();
+    (image.nbpr, image.nbpc, image.nppbh, image.nppbv) = (2, 2, 2, 2);
+    model.images[0] = Segment::image(&image, data).unwrap();
+    let mut image = model.images[1].image_subheader().unwrap();
+    image.ic = "M8".to_string();
+    model.images[1] = Segment::image(&image, model.images[1].data.clone()).unwrap();
+    let mut file = std::io::Cursor::new(model.to_bytes().unwrap());
+
+    let mask = core::get_block_mask(&mut file, 0).unwrap().unwrap();
+    assert_eq!((mask.imdatoff, mask.bmrlnth, mask.tmrlnth, mask.tpxcd), (27, 4, 0, Some(0xEE)));
+    assert_eq!(mask.bmr, vec![Some(0), Some(4), None, Some(8)]);
+    assert!(mask.tmr.is_empty());
+    assert_eq!(core::get_block_mask(&mut file, 2).unwrap(), None);
+
+    assert_eq!(core::read_image_block(&mut file, 0, 0, 1, 0).unwrap(), Some(vec![3, 4, 7, 8]));
+    assert_eq!(core::read_image_block(&mut file, 0, 1, 0, 0).unwrap(), None);
+    assert_eq!(core::read_image_block(&mut file, 0, 1, 1, 0).unwrap(), Some(vec![11, 12, 15, 16]));
+    assert_eq!(core::read_image_block(&mut file, 1, 0, 0, 0).unwrap(), Some(codestream.clone()));
+    assert_eq!(core::read_image_block(&mut file, 2, 0, 0, 0).unwrap(), Some(vec![1, 2, 3, 4]));
+    assert!(matches!(
+        core::read_image_block(&mut file, 0, 2, 0, 0),
+        Err(NitfError::IndexOutOfRange { segment: "Block", index: 4, count: 4 })
+    ));
+    assert!(matches!(
+        core::read_image_block(&mut file, 0, 0, 0, 1),
+        Err(NitfError::IndexOutOfRange { segment: "Band", index: 1, count: 1 })
+    ));
+
+    assert_eq!(
+        core::decode_image_index(&mut file, 0).unwrap().pixels,
+        PixelData::U8(vec![1, 2, 3, 4, 5, 6, 7, 8, 0xEE, 0xEE, 11, 12, 0xEE, 0xEE, 15, 16])
+    );
+
+    let outpath = helpers::temp_path("masked_images").display().to_string();
+    core::extract_jp2(&mut file, &outpath).unwrap();
+    assert_eq!(std::fs::read(format!("{}1.j2k", outpath)).unwrap(), codestream);
+    assert_eq!(std::fs::read(format!("{}0.raw", outpath)).unwrap().len(), 12);
+    assert_eq!(core::extract_jp2_index(&mut file, 1).unwrap(), codestream);
+    assert_eq!(core::extract_image_index(&mut file, 0).unwrap().1, [1, 2, 5, 6, 3, 4, 7, 8, 11, 12, 15, 16]);
+
+    //A pad pixel mask without a block mask, TPXCD over two bytes
+    let layout = nitf_gnr::modify::image::ImageLayout { nbpr: 1, nbpc: 2, imode: "B".to_string(), ..Default::default() };
+    let bytes = [0, 0, 0, 20, 0, 0, 0, 4, 0, 12, 0x0F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 6];
+    let mask = BlockMask::from_bytes(&bytes, &layout).unwrap();
+    assert_eq!((mask.tpxcd, mask.bmr.len(), mask.tmr), (Some(0xFFF), 0, vec![None, Some(6)]));
+    assert!(matches!(BlockMask::from_bytes(&bytes[..15], &layout), Err(NitfError::Truncated { offset: 12, length: 4 })));
+}
+
+#[test]
+fn export_images() {
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::export::{BandSelection, ExportOptions, ImageFormat, Stretch};
+    use nitf_gnr::modify::writer::Segment;
+    let values12: Vec<u8> = [0u16, 100, 200, 300, 4095].iter().flat_map(|v| v.to_be_bytes()).collect();
+    let mut model = NitfBuilder::new()
+        .raw_image(1, 2, 3, 8, vec![10, 20, 30, 40, 50, 60]).unwrap()
+        .raw_image(1, 2, 1, 8, vec![0, 1]).unwrap()
+        .raw_image(1, 5, 1, 16, values12).unwrap()
+        .build()
+        .unwrap();
+    //Bands stored B, G, R
+    let mut image = model.images[0].image_subheader().unwrap();
+    for (band, rep) in image.bands.iter_mut().zip(["B", "G", "R"]) {
+        band.irepband = rep.to_string();
+    }
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let mut image = model.images[1].image_subheader().unwrap();
+    image.irep = "RGB/LUT".to_string();
+    image.bands[0].irepband = "LU".to_string();
+    image.bands[0].nelut = 2;
+    image.bands[0].lutd = vec![vec![255, 0], vec![0, 128], vec![7, 9]];
+    model.images[1] = Segment::image(&image, model.images[1].data.clone()).unwrap();
+    let mut image = model.images[2].image_subheader().unwrap();
+    image.abpp = 12;
+    model.images[2] = Segment::image(&image, model.images[2].data.clone()).unwrap();
+    let mut file = std::io::Cursor::new(model.to_bytes().unwrap());
+
+    let export = |file: &mut std::io::Cursor<Vec<u8>>, i, options: &ExportOptions| {
+        let path = helpers::temp_path(&format!("export_images{}", i)).display().to_string();
+        core::export_image(file, i, options, &path)?;
+        Ok::<_, NitfError>(std::fs::read(path).unwrap())
+    };
+    let pnm = ExportOptions::new(ImageFormat::Pnm);
+    let ppm = |pixels: &[u8]| [b"P6\n2 1\n255\n".as_slice(), pixels].concat();
+
+    //IREPBAND picks the colour bands, a single band can be picked instead
+    assert_eq!(export(&mut file, 0, &pnm).unwrap(), ppm(&[50, 30, 10, 60, 40, 20]));
+    let options = ExportOptions { bands: BandSelection::Rgb([0, 0, 1]), ..pnm.clone() };
+    assert_eq!(export(&mut file, 0, &options).unwrap(), ppm(&[10, 10, 30, 20, 20, 40]));
+    let options = ExportOptions { bands: BandSelection::Mono(1), ..pnm.clone() };
+    assert_eq!(export(&mut file, 0, &options).unwrap(), b"P5\n2 1\n255\n\x1e\x28".to_vec());
+    let options = ExportOptions { bands: BandSelection::Mono(3), ..pnm.clone() };
+    assert!(matches!(
+        export(&mut file, 0, &options),
+        Err(NitfError::IndexOutOfRange { segment: "Band", index: 3, count: 3 })
+    ));
+
+    //RGB/LUT, 16 bit output scales the LUT entries
+    assert_eq!(export(&mut file, 1, &pnm).unwrap(), ppm(&[255, 0, 7, 0, 128, 9]));
+    let options = ExportOptions { sixteen_bit: true, ..pnm.clone() };
+    assert_eq!(
+        export(&mut file, 1, &options).unwrap(),
+        [b"P6\n2 1\n65535\n".as_slice(), &[255, 255, 0, 0, 7, 7, 0, 0, 128, 128, 9, 9]].concat()
+    );
+
+    //12 bit data over the ABPP range, stretched between the extremes and between percentiles
+    let pgm = |pixels: &[u8]| [b"P5\n5 1\n255\n".as_slice(), pixels].concat();
+    assert_eq!(export(&mut file, 2, &pnm).unwrap(), pgm(&[0, 6, 12, 19, 255]));
+    let options = ExportOptions { stretch: Stretch::Linear, ..pnm.clone() };
+    assert_eq!(export(&mut file, 2, &options).unwrap(), pgm(&[0, 6, 12, 19, 255]));
+    let options = ExportOptions { stretch: Stretch::Percentile { low: 25.0, high: 75.0 }, ..pnm.clone() };
+    assert_eq!(export(&mut file, 2, &options).unwrap(), pgm(&[0, 0, 128, 255, 255]));
+
+    //PNG, read back by hand: IHDR, then one stored deflate block of filter byte and scanline
+    let png = export(&mut file, 0, &ExportOptions::new(ImageFormat::Png)).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    let mut chunks = Vec::new();
+    let mut pos = 8;
+    while pos < png.len() {
+        let length = u32::from_be_bytes(png[pos..pos + 4].try_into().unwrap()) as usize;
+        let (kind, data) = (&png[pos + 4..pos + 8], &png[pos + 8..pos + 8 + length]);
+        let crc = u32::from_be_bytes(png[pos + 8 + length..pos + 12 + length].try_into().unwrap());
+        assert_eq!(crc, crc32fast::hash(&png[pos + 4..pos + 8 + length]));
+        chunks.push((kind.to_vec(), data.to_vec()));
+        pos += 12 + length;
+    }
+    assert_eq!(chunks.iter().map(|c| c.0.clone()).collect::<Vec<_>>(), [b"IHDR", b"IDAT", b"IEND"]);
+    assert_eq!(chunks[0].1, [0, 0, 0, 2, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+    assert_eq!(&chunks[1].1[..7], [0x78, 0x01, 1, 7, 0, 0xF8, 0xFF]);
+    assert_eq!(&chunks[1].1[7..14], [0, 50, 30, 10, 60, 40, 20]);
+
+    //TIFF, following the strip tags to the pixels
+    let tiff = export(&mut file, 2, &ExportOptions { sixteen_bit: true, ..ExportOptions::new(ImageFormat::Tiff) }).unwrap();
+    assert_eq!(&tiff[..8], b"II\x2a\x00\x08\x00\x00\x00");
+    let entries = u16::from_le_bytes([tiff[8], tiff[9]]) as usize;
+    let tag = |tag: u16| {
+        let entry = (0..entries).map(|e| 10 + 12 * e).find(|&e| u16::from_le_bytes([tiff[e], tiff[e + 1]]) == tag).unwrap();
+        u32::from_le_bytes(tiff[entry + 8..entry + 12].try_into().unwrap()) as usize
+    };
+    assert_eq!((tag(256), tag(257), tag(258), tag(262), tag(277)), (5, 1, 16, 1, 1));
+    let strip = &tiff[tag(273)..tag(273) + tag(279)];
+    let expected: Vec<u8> = [0u16, 1600, 3201, 4801, 65535].iter().flat_map(|v| v.to_le_bytes()).collect();
+    assert_eq!(strip, expected);
+}
+
+#[test]
+fn j2k_codestreams() {
+    use helpers::{j2k_main_header, j2k_segment, j2k_tile_part};
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::parser::j2k::{Codestream, Profile, ProgressionOrder, JP2_SIGNATURE};
+    use nitf_gnr::modify::writer::Segment;
+
+    //Two 1024x1024 tiles, LRCP, with TLM and PLT: NPJE
+    let mut npje = j2k_main_header((2048, 1024), (1024, 1024), 0, 5, 6, true);
+    npje.extend(j2k_segment(0xFF64, b"\x00\x01encoder"));
+    npje.extend(j2k_tile_part(0, 0, 1, true, true, &[1, 2, 3]));
+    npje.extend(j2k_tile_part(1, 0, 1, true, true, &[4, 5]));
+    npje.extend([0xFF, 0xD9]);
+    let codestream = Codestream::parse(&npje).unwrap();
+    assert_eq!((codestream.siz.width(), codestream.siz.height()), (2048, 1024));
+    assert_eq!((codestream.siz.tiles_across(), codestream.siz.tiles_down(), codestream.siz.tile_count()), (2, 1, 2));
+    assert_eq!(codestream.siz.components.len(), 1);
+    assert_eq!(codestream.siz.components[0].precision, 8);
+    assert_eq!((codestream.cod.layers, codestream.cod.resolution_levels()), (12, 6));
+    assert_eq!((codestream.cod.progression, codestream.cod.code_block), (ProgressionOrder::Lrcp, (64, 64)));
+    assert_eq!((codestream.qcd.style, codestream.qcd.guard_bits), (2, 2));
+    assert_eq!(codestream.comments, ["encoder"]);
+    assert_eq!(codestream.tile_parts.iter().map(|t| (t.tile, t.length, t.plt)).collect::<Vec<_>>(), [(0, 23, true), (1, 22, true)]);
+    assert!(codestream.conforms(Profile::Npje));
+    assert_eq!(
+        codestream.violations(Profile::Epje),
+        ["LRCP progression, not RLCP", "Tile 0 has 1 tile-parts, not 6", "Tile 1 has 1 tile-parts, not 6"]
+    );
+
+    //One tile split by resolution level, RLCP: EPJE. The last tile-part has Psot 0
+    let mut epje = j2k_main_header((512, 512), (512, 512), 1, 5, 6, true);
+    for r in 0..6 {
+        epje.extend(j2k_tile_part(0, r, 6, true, r < 5, &[r; 4]));
+    }
+    epje.extend([0xFF, 0xD9]);
+    let codestream = Codestream::parse(&epje).unwrap();
+    assert_eq!(codestream.tile_parts.len(), 6);
+    assert_eq!(codestream.tile_parts[5].length, 24);
+    assert!(codestream.conforms(Profile::Epje));
+    assert_eq!(codestream.violations(Profile::Npje), ["RLCP progression, not LRCP"]);
+
+    let mut other = j2k_main_header((2048, 2048), (512, 512), 2, 3, 5, false);
+    other.extend(j2k_tile_part(0, 0, 0, false, true, &[]));
+    other.extend([0xFF, 0xD9]);
+    assert_eq!(
+        Codestream::parse(&other).unwrap().violations(Profile::Npje),
+        [
+            "Tiles are 512x512, not 1024x1024",
+            "Code-blocks are 32x32, not 64x64",
+            "3 decomposition levels, not 5",
+            "No TLM marker",
+            "Tile-part without a PLT marker",
+            "RPCL progression, not LRCP",
+        ]
+    );
+
+    //4096x4096 tiles of one sample: too many for Isot to number, and too many for a u32 once
+    //the image is u32::MAX on a side
+    let tiled = |size| {
+        let mut tiled = j2k_main_header(size, (1, 1), 0, 5, 6, true);
+        tiled.extend(j2k_tile_part(0, 0, 1, true, true, &[1]));
+        tiled.extend([0xFF, 0xD9]);
+        Codestream::parse(&tiled).unwrap()
+    };
+    let codestream = tiled((4096, 4096));
+    assert_eq!(codestream.siz.tile_count(), 1 << 24);
+    assert_eq!(codestream.violations(Profile::Npje), ["Tiles are 1x1, not 1024x1024", "16777216 tiles, more than 65535"]);
+    //Only the tiles Isot can number are looked for
+    assert_eq!(codestream.violations(Profile::Epje).len(), 3 + 65535);
+    assert_eq!(tiled((u32::MAX, u32::MAX)).siz.tile_count(), (u32::MAX as u64).pow(2));
+
+    //A JP2 file is read from its codestream box
+    let mut jp2 = JP2_SIGNATURE.to_vec();
+    jp2.extend(b"\x00\x00\x00\x14ftypjp2 \x00\x00\x00\x00jp2 ");
+    jp2.extend(((epje.len() + 8) as u32).to_be_bytes());
+    jp2.extend(b"jp2c");
+    jp2.extend(&epje);
+    let codestream = Codestream::parse(&jp2).unwrap();
+    assert_eq!((codestream.offset, codestream.tile_parts[0].offset), (40, 40 + epje.len() - 6 * 24 - 2));
+
+    assert!(matches!(Codestream::parse(&epje[2..]), Err(NitfError::MalformedField { field: "SOC", offset: 0, .. })));
+    let truncated = &npje[..npje.len() - 3];
+    assert!(matches!(Codestream::parse(truncated), Err(NitfError::Truncated { length: 22, .. })));
+    //Code-blocks of 2^11 samples, past the largest a COD can give
+    let wide = j2k_main_header((64, 64), (64, 64), 0, 1, 11, false);
+    match Codestream::parse(&wide) {
+        Err(NitfError::MalformedField { field: "COD", offset, raw }) => assert_eq!((offset, raw.as_str()), (55, "9")),
+        other => panic!("{:?}", other),
+    }
+    //A Psot too short to hold the SOT and SOD markers
+    let mut short = j2k_main_header((64, 64), (64, 64), 0, 1, 6, false);
+    let sot = short.len();
+    short.extend(j2k_tile_part(0, 0, 1, false, true, &[1, 2]));
+    short[sot + 6..sot + 10].copy_from_slice(&13u32.to_be_bytes());
+    match Codestream::parse(&short) {
+        Err(NitfError::MalformedField { field: "SOT", offset, raw }) => assert_eq!((offset, raw.as_str()), (sot + 6, "13")),
+        other => panic!("{:?}", other),
+    }
+
+    //In a NITF, with the size checked against NROWS and NCOLS
+    let mut masked = vec![0, 0, 0, 14, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0];
+    masked.extend(&epje);
+    let mut model = NitfBuilder::new()
+        .jp2_image(1024, 2048, 1, 8, "N001", npje.clone())
+        .jp2_image(512, 500, 1, 8, "N001", masked)
+        .raw_image(1, 1, 1, 8, vec![0]).unwrap()
+        .build()
+        .unwrap();
+    let mut image = model.images[1].image_subheader().unwrap();
+    image.ic = "M8".to_string();
+    model.images[1] = Segment::image(&image, model.images[1].data.clone()).unwrap();
+    let bytes = model.to_bytes().unwrap();
+    let start = bytes.windows(epje.len()).position(|w| w == epje).unwrap();
+    let mut file = std::io::Cursor::new(bytes);
+
+    assert_eq!(core::inspect_j2k_index(&mut file, 0).unwrap(), Codestream::parse(&npje).unwrap());
+    assert!(core::check_j2k_index(&mut file, 0).is_ok());
+    assert_eq!(core::inspect_j2k_index(&mut file, 1).unwrap().tile_parts[0].offset, epje.len() - 6 * 24 - 2);
+    match core::check_j2k_index(&mut file, 1) {
+        Err(NitfError::MalformedField { field: "SIZ", offset, raw }) => {
+            assert_eq!((offset, raw.as_str()), (start + 8, "512 columns, NCOLS 500"));
+        }
+        other => panic!("{:?}", other),
+    }
+    assert!(matches!(core::inspect_j2k_index(&mut file, 2), Err(NitfError::MalformedField { field: "IC", .. })));
+    assert!(matches!(core::inspect_j2k_index(&mut file, 3), Err(NitfError::IndexOutOfRange { index: 3, count: 3, .. })));
+}
+
+#[test]
+fn wrap_jp2() {
+    use helpers::{j2k_main_header, j2k_tile_part};
+    use nitf_gnr::modify::builder::NitfBuilder;
+    use nitf_gnr::modify::error::NitfError;
+    use nitf_gnr::modify::jp2::{igeolo_corners, Georeference};
+    use nitf_gnr::modify::parser::j2k::{Codestream, JP2_SIGNATURE};
+    use nitf_gnr::modify::writer::Segment;
+    fn boxes(bytes: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut found = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+            let kind = String::from_utf8_lossy(&bytes[pos + 4..pos + 8]).to_string();
+            found.push((kind, bytes[pos + 8..pos + length].to_vec()));
+            pos += length;
+        }
+        found
+    }
+    let mut codestream = j2k_main_header((3, 2), (3, 2), 0, 1, 6, false);
+    codestream.extend(j2k_tile_part(0, 0, 1, false, true, &[1, 2, 3]));
+    codestream.extend([0xFF, 0xD9]);
+    let mut jp2 = JP2_SIGNATURE.to_vec();
+    jp2.extend(b"\x00\x00\x00\x08jp2c");
+
+    let mut model = NitfBuilder::new()
+        .jp2_image(2, 3, 3, 8, "N001", codestream.clone())
+        .jp2_image(2, 3, 1, 8, "N001", codestream.clone())
+        .raw_image(1, 1, 1, 8, vec![7]).unwrap()
+        .jp2_image(2, 3, 1, 8, "N001", jp2.clone())
+        .build()
+        .unwrap();
+    //Corners 10.5N 20E to 10N 20.5E, a quarter degree per column and half a degree per row
+    let mut image = model.images[0].image_subheader().unwrap();
+    image.icords = "G".to_string();
+    image.igeolo = Some("103000N0200000E103000N0203000E100000N0203000E100000N0200000E".to_string());
+    model.images[0] = Segment::image(&image, model.images[0].data.clone()).unwrap();
+    let mut image = model.images[1].image_subheader().unwrap();
+    image.icords = "U".to_string();
+    image.igeolo = Some("33UVP0000000000".repeat(4));
+    model.images[1] = Segment::image(&image, model.images[1].data.clone()).unwrap();
+    let bytes = model.to_bytes().unwrap();
+    let mut file = std::io::Cursor::new(bytes.clone());
+
+    let wrapped = core::wrap_jp2_index(&mut file, 0, Georeference::None).unwrap();
+    let found = boxes(&wrapped);
+    assert_eq!(found.iter().map(|b| b.0.as_str()).collect::<Vec<_>>(), ["jP  ", "ftyp", "jp2h", "jp2c"]);
+    assert_eq!(found[1].1, b"jp2 \x00\x00\x00\x00jp2 ");
+    let jp2h = boxes(&found[2].1);
+    assert_eq!((jp2h[0].0.as_str(), jp2h[0].1.as_slice()), ("ihdr", &[0, 0, 0, 2, 0, 0, 0, 3, 0, 3, 7, 7, 0, 0][..]));
+    assert_eq!((jp2h[1].0.as_str(), jp2h[1].1.as_slice()), ("colr", &[1, 0, 0, 0, 0, 0, 16][..]));
+    assert_eq!(found[3].1, codestream);
+    assert_eq!(Codestream::parse(&wrapped).unwrap().siz, Codestream::parse(&codestream).unwrap().siz);
+
+    //GMLJP2, an asoc box of a label and an asoc box of a label and the GML
+    let found = boxes(&core::wrap_jp2_index(&mut file, 0, Georeference::GmlJp2).unwrap());
+    assert_eq!(found.iter().map(|b| b.0.as_str()).collect::<Vec<_>>(), ["jP  ", "ftyp", "jp2h", "asoc", "jp2c"]);
+    let asoc = boxes(&found[3].1);
+    assert_eq!((asoc[0].0.as_str(), asoc[0].1.as_slice()), ("lbl ", &b"gml.data"[..]));
+    let instance = boxes(&asoc[1].1);
+    assert_eq!(instance[0].1, b"gml.root-instance");
+    let gml = String::from_utf8(instance[1].1.clone()).unwrap();
+    assert!(gml.contains("<gml:high>2 1</gml:high>"));
+    assert!(gml.contains("<gml:pos>10.5 20</gml:pos>"));
+    assert!(gml.contains(r#"<gml:offsetVector srsName="urn:ogc:def:crs:EPSG::4326">0 0.25</gml:offsetVector>"#));
+    assert!(gml.contains(r#"<gml:offsetVector srsName="urn:ogc:def:crs:EPSG::4326">-0.5 0</gml:offsetVector>"#));
+
+    //GeoJP2, a UUID and a one pixel GeoTIFF
+    let found = boxes(&core::wrap_jp2_index(&mut file, 0, Georeference::GeoJp2).unwrap());
+    assert_eq!(found[3].0, "uuid");
+    let (uuid, tiff) = found[3].1.split_at(16);
+    assert_eq!(uuid, [0xB1, 0x4B, 0xF8, 0xBD, 0x08, 0x3D, 0x4B, 0x43, 0xA5, 0xAE, 0x8C, 0xD7, 0xD5, 0xA6, 0xCE, 0x03]);
+    assert_eq!(&tiff[..4], b"II\x2a\x00");
+    let entries = u16::from_le_bytes([tiff[8], tiff[9]]) as usize;
+    let tag = |tag: u16| {
+        let entry = (0..entries).map(|e| 10 + 12 * e).find(|&e| u16::from_le_bytes([tiff[e], tiff[e + 1]]) == tag).unwrap();
+        let count = u32::from_le_bytes(tiff[entry + 4..entry + 8].try_into().unwrap()) as usize;
+        (count, u32::from_le_bytes(tiff[entry + 8..entry + 12].try_into().unwrap()) as usize)
+    };
+    let (count, offset) = tag(34264);
+    let transform: Vec<f64> = (0..count).map(|k| f64::from_le_bytes(tiff[offset + 8 * k..offset + 8 * k + 8].try_into().unwrap())).collect();
+    assert_eq!(transform[..8], [0.25, 0.0, 0.0, 20.0, 0.0, -0.5, 0.0, 10.5]);
+    let (count, offset) = tag(34735);
+    let keys: Vec<u16> = (0..count).map(|k| u16::from_le_bytes([tiff[offset + 2 * k], tiff[offset + 2 * k + 1]])).collect();
+    assert_eq!(keys, [1, 1, 0, 3, 1024, 0, 1, 2, 1025, 0, 1, 2, 2048, 0, 1, 4326]);
+
+    //UTM corners cannot go in a box and are left out, a malformed geographic IGEOLO is an error
+    assert_eq!(
+        core::wrap_jp2_index(&mut file, 1, Georeference::GmlJp2).unwrap(),
+        core::wrap_jp2_index(&mut file, 1, Georeference::None).unwrap()
+    );
+    let igeolo = bytes.windows(15).position(|w| w == b"103000N0200000E").unwrap();
+    let mut bad = bytes.clone();
+    bad[igeolo + 6] = b'X';
+    match core::wrap_jp2_index(&mut std::io::Cursor::new(bad), 0, Georeference::GeoJp2) {
+        Err(NitfError::MalformedField { field: "IGEOLO", offset, .. }) => assert_eq!(offset, igeolo + 6),
+        other => panic!("{:?}", other),
+    }
+    let jp2h = boxes(&boxes(&core::wrap_jp2_index(&mut file, 1, Georeference::None).unwrap())[2].1);
+    assert_eq!(jp2h[1].1, [1, 0, 0, 0, 0, 0, 17]);
+    assert_eq!(core::wrap_jp2_index(&mut file, 3, Georeference::GmlJp2).unwrap(), jp2);
+    assert!(matches!(core::wrap_jp2_index(&mut file, 2, Georeference::None), Err(NitfError::MalformedField { field: "IC", .. })));
+
+    let outpath = helpers::temp_path("wrap_jp2").display().to_string();
+    core::extract_jp2_wrapped(&mut file, &outpath, Georeference::None).unwrap();
+    assert_eq!(std::fs::read(format!("{}0.jp2", outpath)).unwrap(), core::wrap_jp2_index(&mut file, 0, Georeference::None).unwrap());
+    assert_eq!(std::fs::read(format!("{}2.raw", outpath)).unwrap(), [7]);
+    assert_eq!(std::fs::read(format!("{}3.jp2", outpath)).unwrap(), jp2);
+
+    //Decimal degrees, and a bad hemisphere in the second corner
+    assert_eq!(igeolo_corners("D", &"+10.500-020.250".repeat(4)).unwrap(), [(10.5, -20.25); 4]);
+    let bad = "103000N0200000E103000X0203000E100000N0203000E100000N0200000E";
+    assert!(matches!(igeolo_corners("G", bad), Err(NitfError::MalformedField { field: "IGEOLO", offset: 21, .. })));
+}